@@ -1,37 +1,96 @@
-//! Autostart module - Manage Windows startup
+//! Autostart module - Start the app at login: the Run registry key on Windows,
+//! a systemd user unit on Linux and a LaunchAgent on macOS
 
 #[cfg(windows)]
 use winreg::enums::*;
 #[cfg(windows)]
 use winreg::RegKey;
 
+#[cfg(windows)]
 const REGISTRY_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+#[cfg(windows)]
 const APP_NAME: &str = "AutoOpen";
 
-/// Enable autostart with Windows
+/// systemd user unit, under ~/.config/systemd/user
+#[cfg(target_os = "linux")]
+const UNIT_NAME: &str = "routine-runner.service";
+
+/// LaunchAgent label; the plist is ~/Library/LaunchAgents/<label>.plist
+#[cfg(target_os = "macos")]
+const AGENT_LABEL: &str = "com.autoopen.app";
+
+/// Enable autostart at login
 pub fn enable_autostart() -> Result<(), String> {
+    let exe_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to get exe path: {}", e))?;
+
     #[cfg(windows)]
     {
-        let exe_path = std::env::current_exe()
-            .map_err(|e| format!("Failed to get exe path: {}", e))?;
-        
         let hkcu = RegKey::predef(HKEY_CURRENT_USER);
         let key = hkcu
             .open_subkey_with_flags(REGISTRY_KEY, KEY_WRITE)
             .map_err(|e| format!("Failed to open registry key: {}", e))?;
-        
+
         // Add --tray flag to start minimized
         let value = format!("\"{}\" --tray", exe_path.display());
         key.set_value(APP_NAME, &value)
             .map_err(|e| format!("Failed to set registry value: {}", e))?;
-        
-        tracing::info!("Autostart enabled");
-        Ok(())
     }
-    
-    #[cfg(not(windows))]
+
+    #[cfg(target_os = "linux")]
     {
-        Err("Autostart is only supported on Windows".to_string())
+        // Started with the graphical session, so the app finds the display
+        let unit = format!(
+            "[Unit]\nDescription=Routine Runner\nPartOf=graphical-session.target\nAfter=graphical-session.target\n\n\
+             [Service]\nExecStart=\"{}\" --tray\nRestart=on-failure\n\n\
+             [Install]\nWantedBy=graphical-session.target\n",
+            exe_path.display().to_string().replace('\\', "\\\\").replace('"', "\\\""),
+        );
+        let path = config_file()?;
+        std::fs::create_dir_all(path.parent().unwrap_or(&path)).map_err(|e| e.to_string())?;
+        std::fs::write(&path, unit).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        systemctl(&["daemon-reload"])?;
+        systemctl(&["enable", UNIT_NAME])?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let escape = |text: &str| text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{}</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>{}</string>
+    <string>--tray</string>
+  </array>
+  <key>RunAtLoad</key>
+  <true/>
+</dict>
+</plist>
+"#,
+            AGENT_LABEL,
+            escape(&exe_path.to_string_lossy()),
+        );
+        let path = config_file()?;
+        std::fs::create_dir_all(path.parent().unwrap_or(&path)).map_err(|e| e.to_string())?;
+        std::fs::write(&path, plist).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+    {
+        let _ = exe_path;
+        return Err("Autostart is not supported on this system".to_string());
+    }
+
+    #[allow(unreachable_code)]
+    {
+        tracing::info!("Autostart enabled");
+        Ok(())
     }
 }
 
@@ -43,18 +102,24 @@ pub fn disable_autostart() -> Result<(), String> {
         let key = hkcu
             .open_subkey_with_flags(REGISTRY_KEY, KEY_WRITE)
             .map_err(|e| format!("Failed to open registry key: {}", e))?;
-        
+
         // Ignore error if value doesn't exist
         let _ = key.delete_value(APP_NAME);
-        
-        tracing::info!("Autostart disabled");
-        Ok(())
     }
-    
-    #[cfg(not(windows))]
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
     {
-        Err("Autostart is only supported on Windows".to_string())
+        let path = config_file()?;
+        if !path.exists() {
+            return Ok(());
+        }
+        #[cfg(target_os = "linux")]
+        systemctl(&["disable", UNIT_NAME])?;
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
     }
+
+    tracing::info!("Autostart disabled");
+    Ok(())
 }
 
 /// Check if autostart is currently enabled
@@ -68,8 +133,18 @@ pub fn is_autostart_enabled() -> bool {
         }
         false
     }
-    
-    #[cfg(not(windows))]
+
+    #[cfg(target_os = "linux")]
+    {
+        systemctl(&["is-enabled", "--quiet", UNIT_NAME]).is_ok()
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        config_file().is_ok_and(|path| path.exists())
+    }
+
+    #[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
     {
         false
     }
@@ -83,3 +158,34 @@ pub fn set_autostart(enabled: bool) -> Result<(), String> {
         disable_autostart()
     }
 }
+
+/// Where the unit file or LaunchAgent plist goes
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn config_file() -> Result<std::path::PathBuf, String> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(dirs::config_dir().ok_or("No config directory")?.join("systemd/user").join(UNIT_NAME))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Ok(dirs::home_dir()
+            .ok_or("No home directory")?
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", AGENT_LABEL)))
+    }
+}
+
+/// Run `systemctl --user`, failing with its error output
+#[cfg(target_os = "linux")]
+fn systemctl(args: &[&str]) -> Result<(), String> {
+    let output = std::process::Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to start systemctl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
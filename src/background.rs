@@ -0,0 +1,188 @@
+//! Background module - Keep schedules firing before login and after the app is closed
+//!
+//! A Task Scheduler entry starts `routine-runner engine` at boot as the current user,
+//! without a desktop (session 0). The engine runs tasks that need no window; tasks that
+//! do stay due until the app starts in the user's session, takes over and runs them.
+
+use crate::models::*;
+use std::process::Command;
+
+/// Name of the Task Scheduler entry
+pub const ENGINE_TASK_NAME: &str = "Routine Runner Engine";
+
+/// Pipe the engine listens on for the app taking over
+pub const ENGINE_PIPE_NAME: &str = r"\\.\pipe\routine-runner-engine";
+
+/// Request sent by the app, answered once the engine has stopped its scheduler
+#[cfg(windows)]
+const HANDOVER_REQUEST: &str = "handover";
+
+/// Whether a task opens something on screen, which is impossible without a desktop
+pub fn needs_desktop(task: &Task) -> bool {
+    task.target_type != TargetType::Exe || !matches!(task.run_window_style, RunWindowStyle::Hidden)
+}
+
+/// Register the engine to start at boot. Needs administrator rights, since it runs
+/// before anyone logs in (without storing a password, so it has no network credentials).
+pub fn install() -> Result<(), String> {
+    let exe_path = std::env::current_exe()
+        .map_err(|e| format!("Failed to get exe path: {}", e))?;
+    let user = match (std::env::var("USERDOMAIN"), std::env::var("USERNAME")) {
+        (Ok(domain), Ok(name)) => format!("{}\\{}", domain, name),
+        (_, Ok(name)) => name,
+        _ => return Err("Failed to get the user name".to_string()),
+    };
+
+    let xml = task_xml(&exe_path.to_string_lossy(), &user);
+    let path = std::env::temp_dir().join("routine-runner-engine.xml");
+    // Task Scheduler expects UTF-16 with a byte order mark
+    let bytes: Vec<u8> = std::iter::once(0xFEFF)
+        .chain(xml.encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+    let result = schtasks(&["/Create", "/F", "/TN", ENGINE_TASK_NAME, "/XML", &path.to_string_lossy()]);
+    let _ = std::fs::remove_file(&path);
+    result?;
+
+    tracing::info!("Background engine registered for {}", user);
+    Ok(())
+}
+
+/// Remove the Task Scheduler entry
+pub fn uninstall() -> Result<(), String> {
+    schtasks(&["/Delete", "/F", "/TN", ENGINE_TASK_NAME])?;
+    tracing::info!("Background engine unregistered");
+    Ok(())
+}
+
+pub fn is_installed() -> bool {
+    schtasks(&["/Query", "/TN", ENGINE_TASK_NAME]).is_ok()
+}
+
+/// Start the engine now, e.g. when the app exits
+pub fn start() -> Result<(), String> {
+    schtasks(&["/Run", "/TN", ENGINE_TASK_NAME]).map(|_| ())
+}
+
+/// Task definition: at boot, as `user` without a password, no time limit, never twice
+fn task_xml(exe_path: &str, user: &str) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Runs Routine Runner schedules while the app is not open</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <BootTrigger>
+      <Enabled>true</Enabled>
+    </BootTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <UserId>{}</UserId>
+      <LogonType>S4U</LogonType>
+      <RunLevel>LeastPrivilege</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <MultipleInstancesPolicy>IgnoreNew</MultipleInstancesPolicy>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+    <ExecutionTimeLimit>PT0S</ExecutionTimeLimit>
+    <Enabled>true</Enabled>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{}</Command>
+      <Arguments>engine</Arguments>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        escape(user),
+        escape(exe_path),
+    )
+}
+
+/// Run schtasks.exe, returning its output
+fn schtasks(args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("schtasks");
+    cmd.args(args);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().map_err(|e| format!("Failed to start schtasks: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Ask a running engine to stop scheduling, so the app can take over.
+/// Returns once it has stopped; false if no engine was running.
+pub fn hand_over() -> bool {
+    #[cfg(windows)]
+    {
+        use std::io::{BufRead, BufReader, Write};
+
+        let Ok(mut pipe) = std::fs::OpenOptions::new().read(true).write(true).open(ENGINE_PIPE_NAME) else {
+            return false;
+        };
+        let mut reply = String::new();
+        let result = writeln!(pipe, "{}", HANDOVER_REQUEST)
+            .and_then(|_| BufReader::new(&pipe).read_line(&mut reply));
+        if let Err(e) = result {
+            tracing::warn!("Background engine did not confirm the handover: {}", e);
+        }
+        tracing::info!("Took over from the background engine");
+        true
+    }
+
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Wait for the app to take over, then call `stop` and confirm once it returns
+pub async fn wait_for_handover(stop: impl FnOnce()) {
+    #[cfg(windows)]
+    {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let mut server = match ServerOptions::new().first_pipe_instance(true).create(ENGINE_PIPE_NAME) {
+            Ok(server) => server,
+            Err(e) => {
+                tracing::error!("Failed to create pipe {}: {}", ENGINE_PIPE_NAME, e);
+                return std::future::pending().await;
+            }
+        };
+        loop {
+            if server.connect().await.is_ok() {
+                let mut line = String::new();
+                let mut reader = BufReader::new(&mut server);
+                if reader.read_line(&mut line).await.is_ok() && line.trim() == HANDOVER_REQUEST {
+                    stop();
+                    let _ = server.write_all(b"stopped\n").await;
+                    let _ = server.flush().await;
+                    return;
+                }
+            }
+            let _ = server.disconnect();
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = stop;
+        std::future::pending().await
+    }
+}
@@ -0,0 +1,80 @@
+//! Backup module - Automatic daily database backups with rotation
+
+use crate::storage::Database;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Prefix of automatic backup files ("auto-open-YYYY-MM-DD.db")
+const AUTO_BACKUP_PREFIX: &str = "auto-open-";
+
+/// How often the backup job checks whether today's backup exists
+const CHECK_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Directory holding backups under the app data dir
+pub fn backup_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("backups")
+}
+
+/// Create today's automatic backup if it doesn't exist yet and prune old ones.
+/// Returns the path of the new backup, if one was made.
+pub fn run_daily_backup(db: &Database, dir: &Path, keep: u32) -> Result<Option<PathBuf>, String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let today = chrono::Local::now().format("%Y-%m-%d");
+    let path = dir.join(format!("{}{}.db", AUTO_BACKUP_PREFIX, today));
+    if path.exists() {
+        return Ok(None);
+    }
+
+    db.backup_to(&path).map_err(|e| e.to_string())?;
+    tracing::info!("Created automatic backup {:?}", path);
+
+    prune_backups(dir, keep)?;
+    Ok(Some(path))
+}
+
+/// Delete the oldest automatic backups so at most `keep` remain
+fn prune_backups(dir: &Path, keep: u32) -> Result<(), String> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(AUTO_BACKUP_PREFIX) && n.ends_with(".db"))
+        })
+        .collect();
+
+    // Names embed the date, so they sort oldest first
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep.max(1) as usize);
+    for path in backups.into_iter().take(excess) {
+        tracing::info!("Removing old backup {:?}", path);
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!("Failed to remove old backup {:?}: {}", path, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Background job: back up once a day while automatic backups are enabled
+pub async fn auto_backup_loop(db: Arc<Database>, data_dir: PathBuf) {
+    let dir = backup_dir(&data_dir);
+
+    loop {
+        match db.get_settings() {
+            Ok(settings) if settings.auto_backup_enabled => {
+                let dir = dir.clone();
+                let result = db.run(move |db| run_daily_backup(db, &dir, settings.auto_backup_keep)).await;
+                if let Err(e) = result {
+                    tracing::error!("Automatic backup failed: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to read settings for backup: {}", e),
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+    }
+}
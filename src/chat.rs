@@ -0,0 +1,91 @@
+//! Chat module - Post run results to Slack, Discord and Telegram
+
+use crate::models::*;
+
+/// Message used when a notifier has no template of its own
+const DEFAULT_TEMPLATE: &str = "{task_name}: {status} (exit code {exit_code}, {duration})\n{error}\n{output}";
+
+/// Most output characters quoted in a message (the end of the output is kept)
+const OUTPUT_SNIPPET_CHARS: usize = 500;
+
+/// Longest message Discord accepts
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// Notifiers a task wants this run's result sent to
+pub fn notifiers_for<'a>(log: &RunLog, task: &Task, settings: &'a Settings) -> Vec<&'a ChatNotifier> {
+    task.chat_alerts.iter()
+        .filter(|alert| match log.status {
+            RunStatus::Failed => alert.on_failure,
+            RunStatus::Success => alert.on_success,
+            RunStatus::Skipped => alert.on_skip,
+            _ => false,
+        })
+        .filter_map(|alert| settings.chat_notifiers.iter().find(|n| n.id == alert.notifier_id))
+        .collect()
+}
+
+/// Fill a message template with the run's details. Lines whose placeholders all came out
+/// empty are dropped.
+pub fn render_message(template: Option<&str>, log: &RunLog) -> String {
+    let status = match log.status {
+        RunStatus::Success => "succeeded".to_string(),
+        RunStatus::Failed => "failed".to_string(),
+        RunStatus::Skipped => match &log.skip_reason {
+            Some(reason) => format!("skipped ({:?})", reason),
+            None => "skipped".to_string(),
+        },
+        RunStatus::Started => "started".to_string(),
+        RunStatus::DryRun => "dry run".to_string(),
+    };
+    let values = [
+        ("{task_name}", log.task_name.clone()),
+        ("{status}", status),
+        ("{exit_code}", log.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string())),
+        ("{duration}", log.duration_ms.map(|ms| format!("{:.1}s", ms as f64 / 1000.0)).unwrap_or_else(|| "-".to_string())),
+        ("{error}", log.error_message.clone().unwrap_or_default()),
+        ("{output}", output_snippet(log.output.as_deref().unwrap_or_default())),
+    ];
+
+    template.unwrap_or(DEFAULT_TEMPLATE)
+        .lines()
+        .filter_map(|line| {
+            let filled = values.iter().fold(line.to_string(), |text, (key, value)| text.replace(key, value));
+            let had_placeholders = values.iter().any(|(key, _)| line.contains(key));
+            (!had_placeholders || !filled.trim().is_empty()).then_some(filled)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The last part of a run's output
+fn output_snippet(output: &str) -> String {
+    let output = output.trim();
+    let count = output.chars().count();
+    if count <= OUTPUT_SNIPPET_CHARS {
+        return output.to_string();
+    }
+    let tail: String = output.chars().skip(count - OUTPUT_SNIPPET_CHARS).collect();
+    format!("…{}", tail)
+}
+
+/// Post a message through a notifier (with its credential already decrypted). Blocks until sent.
+pub fn send(notifier: &ChatNotifier, message: &str) -> Result<(), String> {
+    let (url, body) = match &notifier.service {
+        ChatService::Slack { webhook_url } => (
+            webhook_url.clone(),
+            serde_json::json!({ "text": message }),
+        ),
+        ChatService::Discord { webhook_url } => (
+            webhook_url.clone(),
+            serde_json::json!({ "content": message.chars().take(DISCORD_MAX_CHARS).collect::<String>() }),
+        ),
+        ChatService::Telegram { bot_token, chat_id } => (
+            format!("https://api.telegram.org/bot{}/sendMessage", bot_token),
+            serde_json::json!({ "chat_id": chat_id, "text": message }),
+        ),
+    };
+    match crate::http::post_json(&url, &body.to_string())? {
+        code if (200..300).contains(&code) => Ok(()),
+        code => Err(format!("{} answered HTTP {}", notifier.name, code)),
+    }
+}
@@ -0,0 +1,247 @@
+//! CLI module - Headless subcommands that work on the database and exit without a window
+
+use crate::commands::{self, AppState};
+use crate::ipc::IpcRequest;
+use crate::models::*;
+use std::sync::Arc;
+
+/// How often a headless run is checked for completion
+const RUN_POLL_MS: u64 = 500;
+
+const USAGE: &str = "\
+Usage: routine-runner [--profile <name>] <command>
+       routine-runner [--profile <name>] --run-task <name|id>
+
+Commands:
+  list                                   List tasks with their next and last run
+  run <name|id>                          Run a task now (waits for it unless the app is open);
+                                         --run-task <name|id> does the same, e.g. for hotkey tools
+  pause [minutes]                        Pause the scheduler, for the given minutes or until resumed
+  resume                                 Resume the scheduler
+  export <path> [--settings]             Export tasks (and settings) to a JSON file
+  import <path> [--overwrite|--duplicate] [--settings]
+                                         Import tasks; existing ids are skipped by default
+  engine [install|uninstall]             Run the scheduler without a window, or register it
+                                         to start with Windows (needs administrator rights)
+  help                                   Show this help";
+
+const COMMANDS: [&str; 8] = ["list", "run", "pause", "resume", "export", "import", "engine", "help"];
+
+/// Run the subcommand on the command line, returning the process exit code.
+/// None if there is no subcommand and the app should start as usual.
+pub fn run(args: &[String]) -> Option<i32> {
+    let mut profile = None;
+    let mut positional = Vec::new();
+    let mut rest = args.iter().skip(1);
+    while let Some(arg) = rest.next() {
+        if arg == "--profile" {
+            profile = rest.next().cloned();
+        } else if arg == "--run-task" {
+            // Same as the run subcommand
+            positional.insert(0, "run".to_string());
+            if let Some(task) = rest.next() {
+                positional.insert(1, task.clone());
+            }
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+    let command = positional.first().filter(|c| COMMANDS.contains(&c.as_str()))?.clone();
+
+    attach_console();
+    if command == "help" {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+
+    // The same holder the app gives Tauri to manage, without an app around it
+    let state = Arc::new(AppState::default());
+    let result = tokio::runtime::Runtime::new()
+        .map_err(|e| e.to_string())
+        .and_then(|runtime| runtime.block_on(execute(&state, &command, &positional[1..], profile.as_deref())));
+    commands::shutdown(&state);
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            Some(1)
+        }
+    }
+}
+
+async fn execute(state: &AppState, command: &str, params: &[String], profile: Option<&str>) -> Result<(), String> {
+    // Scheduler commands go to the running app, so its scheduler sees them
+    if let Some(request) = ipc_request(command, params)? {
+        if let Some(result) = crate::ipc::send(&request) {
+            let result = result?;
+            match result["run_id"].as_str() {
+                Some(run_id) => println!("Started run {} in the running app", run_id),
+                None => println!("Done"),
+            }
+            return Ok(());
+        }
+    }
+
+    match (command, params.first().map(String::as_str)) {
+        ("engine", Some("install")) => {
+            crate::background::install()?;
+            println!("Background engine registered; it starts with Windows and when the app exits");
+            return Ok(());
+        }
+        ("engine", Some("uninstall")) => {
+            crate::background::uninstall()?;
+            println!("Background engine removed");
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    open_database(state, profile)?;
+    match command {
+        "list" => list(state).await,
+        "run" => run_and_wait(state, params[0].clone()).await,
+        "pause" => {
+            commands::init_headless_scheduler(state)?;
+            match parse_minutes(params)? {
+                Some(minutes) => {
+                    let until = chrono::Utc::now() + chrono::Duration::minutes(minutes.into());
+                    commands::pause(state, Some(until)).await?;
+                    println!("Scheduler paused until {}", until.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"));
+                }
+                None => {
+                    commands::pause(state, None).await?;
+                    println!("Scheduler paused");
+                }
+            }
+            Ok(())
+        }
+        "resume" => {
+            commands::init_headless_scheduler(state)?;
+            commands::resume(state).await?;
+            println!("Scheduler resumed");
+            Ok(())
+        }
+        "export" => {
+            let path = params.first().ok_or("Usage: routine-runner export <path> [--settings]")?;
+            let count = commands::export_to_file(state, path.clone(), params.iter().any(|p| p == "--settings")).await?;
+            println!("Exported {} task(s) to {}", count, path);
+            Ok(())
+        }
+        "import" => {
+            let path = params.first().ok_or("Usage: routine-runner import <path> [--overwrite|--duplicate] [--settings]")?;
+            let on_collision = if params.iter().any(|p| p == "--overwrite") {
+                ImportCollision::Overwrite
+            } else if params.iter().any(|p| p == "--duplicate") {
+                ImportCollision::Duplicate
+            } else {
+                ImportCollision::Skip
+            };
+            let summary = commands::import_from_file(state, path.clone(), on_collision, params.iter().any(|p| p == "--settings")).await?;
+            println!(
+                "Created {}, overwritten {}, duplicated {}, skipped {}{}",
+                summary.created,
+                summary.overwritten,
+                summary.duplicated,
+                summary.skipped,
+                if summary.settings_imported { ", settings imported" } else { "" },
+            );
+            for name in &summary.missing_secrets {
+                println!("Enter the secrets of task '{}' again, they were not exported", name);
+            }
+            if summary.settings_missing_secrets {
+                println!("Enter the notifier, email and MQTT credentials again, they were not exported");
+            }
+            Ok(())
+        }
+        "engine" => commands::run_engine(state).await,
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// The pipe request for a scheduler command, None for commands that only use the database
+fn ipc_request(command: &str, params: &[String]) -> Result<Option<IpcRequest>, String> {
+    Ok(match command {
+        "run" => {
+            let task = params.first().ok_or("Usage: routine-runner run <name|id>")?;
+            Some(IpcRequest::Run { task: task.clone() })
+        }
+        "pause" => Some(IpcRequest::Pause { minutes: parse_minutes(params)? }),
+        "resume" => Some(IpcRequest::Resume),
+        _ => None,
+    })
+}
+
+fn parse_minutes(params: &[String]) -> Result<Option<u32>, String> {
+    params.first()
+        .map(|m| m.parse().map_err(|_| format!("Not a number of minutes: {}", m)))
+        .transpose()
+}
+
+/// The app data directory: the one chosen in the app, or Tauri's app data dir
+pub fn app_data_dir() -> Option<std::path::PathBuf> {
+    crate::data_location::current()
+}
+
+fn open_database(state: &AppState, profile: Option<&str>) -> Result<(), String> {
+    let data_dir = app_data_dir().ok_or("No application data directory")?;
+    std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+    if let Some(recovered) = commands::init_database(state, &data_dir, profile)? {
+        eprintln!(
+            "The database of profile {} was corrupt and has been replaced; the old file was moved to {}",
+            recovered.profile,
+            recovered.moved_to.display(),
+        );
+    }
+    Ok(())
+}
+
+async fn list(state: &AppState) -> Result<(), String> {
+    println!("ID\tENABLED\tNAME\tNEXT RUN\tLAST STATUS");
+    for item in commands::list_tasks_with_state(state, None).await? {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            item.task.id,
+            if item.task.enabled { "yes" } else { "no" },
+            item.task.name,
+            item.next_run_at.as_deref().unwrap_or("-"),
+            if item.is_running { "running" } else { item.last_run_status.as_deref().unwrap_or("-") },
+        );
+    }
+    Ok(())
+}
+
+/// Run a task in this process and wait for it, failing if the run fails
+async fn run_and_wait(state: &AppState, id_or_name: String) -> Result<(), String> {
+    commands::init_headless_scheduler(state)?;
+    let run_id = commands::run_task_by_name(state, id_or_name).await?;
+    let log = loop {
+        tokio::time::sleep(tokio::time::Duration::from_millis(RUN_POLL_MS)).await;
+        match commands::run_status(state, &run_id).await? {
+            Some(log) if log.status != RunStatus::Started => break log,
+            _ => {}
+        }
+    };
+
+    if let Some(output) = log.output.as_deref().filter(|o| !o.is_empty()) {
+        println!("{}", output.trim_end());
+    }
+    println!(
+        "{}: {:?}{}",
+        log.task_name,
+        log.status,
+        log.exit_code.map(|c| format!(" (exit code {})", c)).unwrap_or_default(),
+    );
+    match log.status {
+        RunStatus::Failed => Err(log.error_message.unwrap_or_else(|| "Run failed".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Print to the terminal the app was started from (release builds have no console of their own)
+fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -1,245 +1,1965 @@
 //! Tauri commands - Frontend <-> Backend bridge
 
 use crate::models::*;
+use crate::scheduler_runner::SchedulerRunner;
 use crate::storage::Database;
-use std::sync::OnceLock;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
 use std::collections::HashMap;
-// use tauri::State;
+use tauri::Emitter;
 
-static DB: OnceLock<Database> = OnceLock::new();
+/// Emitted after switching or creating a profile, with a `ProfileList` payload
+pub const EVENT_PROFILES_CHANGED: &str = "profiles_changed";
 
-/// Initialize the database
-pub fn init_database(data_dir: &std::path::Path) -> Result<(), String> {
-    let db_path = data_dir.join("auto-open.db");
-    let db = Database::open(&db_path).map_err(|e| e.to_string())?;
-    DB.set(db).map_err(|_| "Database already initialized".to_string())?;
+/// Emitted after tasks were added, changed or removed, without a payload
+pub const EVENT_TASKS_CHANGED: &str = "tasks_changed";
+
+/// Emitted when a corrupt database was replaced on opening a profile, with a `DatabaseRecovered` payload
+pub const EVENT_DATABASE_RECOVERED: &str = "database_recovered";
+
+/// Seconds runs get to end after their programs were stopped on quitting
+pub const CANCEL_GRACE_SECS: u64 = 10;
+
+/// Seconds runs get to end when the app exits without asking, e.g. as Windows ends the session
+pub const EXIT_GRACE_SECS: u64 = 3;
+
+/// Everything tied to the open profile
+struct ProfileRuntime {
+    name: String,
+    dir: PathBuf,
+    db: Arc<Database>,
+    scheduler: Option<Arc<SchedulerRunner>>,
+    /// Background loops to stop when switching away
+    jobs: Vec<tauri::async_runtime::JoinHandle<()>>,
+}
+
+/// The data folder and the profile open in it. The app gives one to Tauri to manage
+/// (commands get it as `tauri::State<Arc<AppState>>`), the CLI builds its own.
+/// Switching profiles swaps the runtime inside, so the holder itself never changes.
+#[derive(Default)]
+pub struct AppState {
+    data_dir: RwLock<Option<PathBuf>>,
+    profile: RwLock<Option<ProfileRuntime>>,
+}
+
+static APP: OnceLock<tauri::AppHandle> = OnceLock::new();
+
+/// Available profiles and the one that is open
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+/// A corrupt profile database that was moved aside and replaced by an empty one
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DatabaseRecovered {
+    pub profile: String,
+    pub moved_to: PathBuf,
+}
+
+/// Initialize the database of the requested profile (or the one used last time).
+/// Returns details if its database was corrupt and had to be recreated.
+pub fn init_database(state: &AppState, data_dir: &Path, profile: Option<&str>) -> Result<Option<DatabaseRecovered>, String> {
+    {
+        let mut current = state.data_dir.write().unwrap();
+        if current.is_some() {
+            return Err("Database already initialized".to_string());
+        }
+        *current = Some(data_dir.to_path_buf());
+    }
+    
+    let name = match profile {
+        Some(name) => crate::profiles::find_profile(data_dir, name)
+            .ok_or_else(|| format!("Profile '{}' does not exist", name))?,
+        None => crate::profiles::load_active(data_dir),
+    };
+    open_profile(state, &name)
+}
+
+/// Open a profile's database and make it the active one
+fn open_profile(state: &AppState, name: &str) -> Result<Option<DatabaseRecovered>, String> {
+    let (runtime, recovered) = load_profile(state, name)?;
+    *state.profile.write().unwrap() = Some(runtime);
+    activated(state, name);
+    Ok(recovered)
+}
+
+/// Open a profile's database, without touching the active profile
+fn load_profile(state: &AppState, name: &str) -> Result<(ProfileRuntime, Option<DatabaseRecovered>), String> {
+    let dir = crate::profiles::profile_dir(&get_data_dir(state)?, name);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    
+    let db_path = dir.join(crate::profiles::DATABASE_FILE);
+    let (db, moved_to) = match Database::open_or_recover(&db_path) {
+        Ok(opened) => opened,
+        // E.g. a snapshot on read-only media: history can still be browsed
+        Err(e) if db_path.exists() => {
+            tracing::warn!("Opening database read-only after write access failed: {}", e);
+            (Database::open_read_only(&db_path).map_err(|e| e.to_string())?, None)
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+    
+    let runtime = ProfileRuntime {
+        name: name.to_string(),
+        dir,
+        db: Arc::new(db),
+        scheduler: None,
+        jobs: Vec::new(),
+    };
+    Ok((runtime, moved_to.map(|moved_to| DatabaseRecovered { profile: name.to_string(), moved_to })))
+}
+
+/// Remember the profile that was just made active for the next start
+fn activated(state: &AppState, name: &str) {
+    if let Ok(data_dir) = get_data_dir(state) {
+        if let Err(e) = crate::profiles::save_active(&data_dir, name) {
+            tracing::warn!("Failed to remember active profile: {}", e);
+        }
+    }
+    tracing::info!("Opened profile {}", name);
+}
+
+/// Stop the active profile's scheduler and background jobs
+fn close_profile(state: &AppState) {
+    let runtime = state.profile.write().unwrap().take();
+    if let Some(runtime) = runtime {
+        stop_profile(runtime);
+    }
+}
+
+/// Stop a profile's background jobs and write its pending run logs
+fn stop_profile(runtime: ProfileRuntime) {
+    for job in runtime.jobs {
+        job.abort();
+    }
+    if let Err(e) = runtime.db.flush_logs() {
+        tracing::error!("Failed to write pending run logs: {}", e);
+    }
+    tracing::info!("Closed profile {}", runtime.name);
+}
+
+/// Keep a background loop of the active profile so it stops on a switch
+fn spawn_job<F>(state: &AppState, future: F) -> Result<(), String>
+where
+    F: std::future::Future<Output = ()> + Send + 'static,
+{
+    let mut profile = state.profile.write().unwrap();
+    let runtime = profile.as_mut().ok_or_else(|| "Database not initialized".to_string())?;
+    runtime.jobs.push(tauri::async_runtime::spawn(future));
+    Ok(())
+}
+
+fn get_data_dir(state: &AppState) -> Result<PathBuf, String> {
+    state.data_dir.read().unwrap().clone().ok_or_else(|| "Database not initialized".to_string())
+}
+
+/// Directory of the active profile (database and backups)
+fn get_profile_dir(state: &AppState) -> Result<PathBuf, String> {
+    state.profile.read().unwrap()
+        .as_ref()
+        .map(|p| p.dir.clone())
+        .ok_or_else(|| "Database not initialized".to_string())
+}
+
+/// Start the automatic daily backup job
+pub fn init_auto_backup(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let profile_dir = get_profile_dir(state)?;
+    spawn_job(state, async move {
+        crate::backup::auto_backup_loop(db, profile_dir).await;
+    })
+}
+
+/// Start saving a configuration version after every change
+pub fn init_config_history(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let profile_dir = get_profile_dir(state)?;
+    spawn_job(state, async move {
+        crate::config_history::history_loop(db, profile_dir).await;
+    })
+}
+
+/// Start the monthly database maintenance job
+pub fn init_maintenance(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    spawn_job(state, async move {
+        crate::maintenance::maintenance_loop(db).await;
+    })
+}
+
+/// Start the hourly email digest job
+pub fn init_email_digest(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    spawn_job(state, async move {
+        crate::email::digest_loop(db).await;
+    })
+}
+
+/// Start the metrics endpoint, serving while enabled in settings
+pub fn init_metrics(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let scheduler = get_scheduler(state)?;
+    spawn_job(state, async move {
+        crate::metrics::serve_loop(db, scheduler).await;
+    })
+}
+
+/// Start the MQTT bridge, connected while enabled in settings
+pub fn init_mqtt(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let scheduler = get_scheduler(state)?;
+    spawn_job(state, async move {
+        crate::mqtt::bridge_loop(db, scheduler).await;
+    })
+}
+
+/// Start the webhook server, listening while enabled in settings
+pub fn init_webhook_server(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let scheduler = get_scheduler(state)?;
+    spawn_job(state, async move {
+        crate::webhook_server::serve_loop(db, scheduler).await;
+    })
+}
+
+/// Start the daily update check, installing at the quiet time if allowed
+pub fn init_auto_update(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let scheduler = get_scheduler(state)?;
+    let app = APP.get().cloned().ok_or_else(|| "Scheduler not initialized".to_string())?;
+    spawn_job(state, async move {
+        crate::updater::update_loop(db, scheduler, move || app.exit(0)).await;
+    })
+}
+
+/// Start writing buffered scheduler run logs in batches
+pub fn init_log_flush(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    spawn_job(state, async move {
+        crate::scheduler_runner::log_flush_loop(db).await;
+    })
+}
+
+/// Stop the active profile before the app exits, writing any buffered run logs
+pub fn shutdown(state: &AppState) {
+    close_profile(state);
+}
+
+fn get_db(state: &AppState) -> Result<Arc<Database>, String> {
+    state.profile.read().unwrap()
+        .as_ref()
+        .map(|p| p.db.clone())
+        .ok_or_else(|| "Database not initialized".to_string())
+}
+
+/// Run database work on the blocking pool instead of the command's async task
+async fn with_db<T, E, F>(state: &AppState, f: F) -> Result<T, String>
+where
+    F: FnOnce(&Database) -> Result<T, E> + Send + 'static,
+    T: Send + 'static,
+    E: From<crate::storage::QueryPanicked> + ToString + Send + 'static,
+{
+    get_db(state)?.run(f).await.map_err(|e| e.to_string())
+}
+
+/// Start the background scheduler loop
+pub fn init_scheduler(state: &AppState, app: tauri::AppHandle) -> Result<(), String> {
+    let _ = APP.set(app.clone());
+    let db = get_db(state)?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let scheduler = Arc::new(SchedulerRunner::new(db, settings.max_parallel_runs).with_app_handle(app));
+    
+    state.profile.write().unwrap()
+        .as_mut()
+        .ok_or_else(|| "Database not initialized".to_string())?
+        .scheduler = Some(scheduler.clone());
+    spawn_job(state, scheduler.run_supervised())
+}
+
+/// Create the scheduler without starting its loop or an app handle, for one-off CLI commands
+pub fn init_headless_scheduler(state: &AppState) -> Result<(), String> {
+    let db = get_db(state)?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let scheduler = Arc::new(SchedulerRunner::new(db, settings.max_parallel_runs));
+    
+    state.profile.write().unwrap()
+        .as_mut()
+        .ok_or_else(|| "Database not initialized".to_string())?
+        .scheduler = Some(scheduler);
+    Ok(())
+}
+
+/// Run the scheduler as the background engine until the app takes over
+pub async fn run_engine(state: &AppState) -> Result<(), String> {
+    if crate::ipc::send(&crate::ipc::IpcRequest::Status).is_some() {
+        tracing::info!("The app is running, background engine not needed");
+        return Ok(());
+    }
+    let db = get_db(state)?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let scheduler = Arc::new(SchedulerRunner::new(db, settings.max_parallel_runs).in_background());
+    
+    state.profile.write().unwrap()
+        .as_mut()
+        .ok_or_else(|| "Database not initialized".to_string())?
+        .scheduler = Some(scheduler.clone());
+    spawn_job(state, scheduler.run_supervised())?;
+    init_log_flush(state)?;
+    
+    tracing::info!("Background engine started");
+    crate::background::wait_for_handover(|| close_profile(state)).await;
+    tracing::info!("Background engine handed over to the app");
+    Ok(())
+}
+
+/// Show desktop notifications and call webhooks for finished and skipped runs,
+/// following the settings and each task's own configuration; also counts them for metrics
+pub fn init_notifications(app: &tauri::AppHandle) {
+    use crate::scheduler_runner::{EVENT_TASK_FINISHED, EVENT_TASK_SKIPPED};
+    use tauri::{Listener, Manager};
+
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    for event in [EVENT_TASK_FINISHED, EVENT_TASK_SKIPPED] {
+        let app_id = app.config().identifier.clone();
+        let state = state.clone();
+        app.listen(event, move |event| {
+            let Ok(log) = serde_json::from_str::<RunLog>(event.payload()) else { return };
+            crate::metrics::METRICS.record(&log);
+            let (app_id, state) = (app_id.clone(), state.clone());
+            tauri::async_runtime::spawn_blocking(move || {
+                if let Err(e) = notify_run(&state, &app_id, &log) {
+                    tracing::warn!("Failed to send notifications for {}: {}", log.task_name, e);
+                }
+            });
+        });
+    }
+}
+
+fn notify_run(state: &Arc<AppState>, app_id: &str, log: &RunLog) -> Result<(), String> {
+    let db = get_db(state)?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let task = db.get_task(&log.task_id).map_err(|e| e.to_string())?;
+    
+    // Deliveries retry for a while, so they run on their own
+    let body = crate::webhooks::payload(log);
+    for hook in crate::webhooks::webhooks_for(log, task.as_ref(), &settings) {
+        tauri::async_runtime::spawn(crate::webhooks::deliver(
+            db.clone(), log.run_id.clone(), log.task_id.clone(), hook.url.clone(), body.clone(),
+        ));
+    }
+    
+    if crate::eventlog::should_log(&settings, log) {
+        if let Err(e) = crate::eventlog::write_run_event(log) {
+            tracing::warn!("Failed to write event log entry: {}", e);
+        }
+    }
+    
+    let wants_email = settings.email.enabled && log.status == RunStatus::Failed;
+    let needs_credentials = wants_email || task.as_ref().is_some_and(|t| !t.chat_alerts.is_empty());
+    let revealed = if needs_credentials { crate::secrets::reveal_settings(&settings)? } else { settings.clone() };
+    
+    if wants_email {
+        let streak = db.consecutive_failures(&log.task_id).map_err(|e| e.to_string())?;
+        if crate::email::should_alert(&settings.email, log, streak) {
+            let (subject, body) = crate::email::alert_text(log, streak);
+            if settings.email.digest {
+                db.queue_email_alert(&subject, &body).map_err(|e| e.to_string())?;
+            } else {
+                let email = revealed.email.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    if let Err(e) = crate::email::send(&email, &subject, &body) {
+                        tracing::warn!("Failed to send email alert: {}", e);
+                    }
+                });
+            }
+        }
+    }
+    
+    if let Some(task) = &task {
+        for notifier in crate::chat::notifiers_for(log, task, &revealed) {
+            let message = crate::chat::render_message(notifier.template.as_deref(), log);
+            let notifier = notifier.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                if let Err(e) = crate::chat::send(&notifier, &message) {
+                    tracing::warn!("Failed to post to {}: {}", notifier.name, e);
+                }
+            });
+        }
+    }
+    
+    let policy = task.map(|task| task.notify_policy).unwrap_or_default();
+    if !crate::notifications::should_notify(log, &policy, &settings) {
+        return Ok(());
+    }
+    let (title, body) = crate::notifications::notification_text(log, settings.language);
+    let actions = crate::notifications::toast_actions(log);
+    let (task_id, run_id) = (log.task_id.clone(), log.run_id.clone());
+    let state = state.clone();
+    crate::notifications::show_toast(app_id, &title, &body, &actions, move |action| {
+        let (state, task_id, run_id) = (state.clone(), task_id.clone(), run_id.clone());
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = handle_toast_action(&state, action, task_id, run_id).await {
+                tracing::warn!("Notification action {:?} failed: {}", action, e);
+            }
+        });
+    })
+}
+
+/// Frontend event asking to show a run's log (payload: the run id)
+pub const EVENT_OPEN_RUN_LOG: &str = "open_run_log";
+
+/// Carry out a button clicked on a run notification
+async fn handle_toast_action(
+    state: &AppState,
+    action: crate::notifications::ToastAction,
+    task_id: String,
+    run_id: String,
+) -> Result<(), String> {
+    use crate::notifications::ToastAction;
+    
+    match action {
+        ToastAction::Retry => start_run(state, task_id, RunOverrides::default()).await.map(|_| ()),
+        ToastAction::Snooze => snooze(state, task_id, crate::notifications::SNOOZE_MINUTES).await.map(|_| ()),
+        ToastAction::OpenLog => open_run_log(run_id),
+    }
+}
+
+/// Bring up the main window on a run's log
+pub fn open_run_log(run_id: String) -> Result<(), String> {
+    show_main_window()?;
+    let app = APP.get().ok_or_else(|| "App not initialized".to_string())?;
+    app.emit(EVENT_OPEN_RUN_LOG, run_id).map_err(|e| e.to_string())
+}
+
+/// Show and focus the main window, e.g. when the app is started a second time
+pub fn show_main_window() -> Result<(), String> {
+    use tauri::Manager;
+    
+    let app = APP.get().ok_or_else(|| "App not initialized".to_string())?;
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+    Ok(())
+}
+
+/// Label of the quick-run palette window
+pub const QUICK_RUN_WINDOW: &str = "quick-run";
+
+/// Emitted to the palette each time it opens, so it can clear and focus its search box
+pub const EVENT_QUICK_RUN_OPENED: &str = "quick_run_opened";
+
+/// Open the quick-run palette, creating its window the first time. It hides again
+/// when it loses focus.
+pub fn show_quick_run() -> Result<(), String> {
+    use tauri::Manager;
+    
+    let app = APP.get().ok_or_else(|| "App not initialized".to_string())?;
+    let window = match app.get_webview_window(QUICK_RUN_WINDOW) {
+        Some(window) => window,
+        None => {
+            let window = tauri::WebviewWindowBuilder::new(
+                app,
+                QUICK_RUN_WINDOW,
+                tauri::WebviewUrl::App("index.html#/quick-run".into()),
+            )
+            .title("Chạy nhanh")
+            .inner_size(560.0, 360.0)
+            .decorations(false)
+            .resizable(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .visible(false)
+            .build()
+            .map_err(|e| e.to_string())?;
+            let palette = window.clone();
+            window.on_window_event(move |event| {
+                if let tauri::WindowEvent::Focused(false) = event {
+                    let _ = palette.hide();
+                }
+            });
+            window
+        }
+    };
+    let _ = window.center();
+    let _ = window.show();
+    let _ = window.set_focus();
+    app.emit_to(QUICK_RUN_WINDOW, EVENT_QUICK_RUN_OPENED, ()).map_err(|e| e.to_string())
+}
+
+/// Register the open profile's quick-run hotkey, replacing the previous one
+pub fn init_quick_run_hotkey(state: &AppState) -> Result<(), String> {
+    let settings = get_db(state)?.get_settings().map_err(|e| e.to_string())?;
+    set_quick_run_hotkey(settings.quick_run_hotkey.as_deref())
+}
+
+fn set_quick_run_hotkey(hotkey: Option<&str>) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+    
+    let app = APP.get().ok_or_else(|| "App not initialized".to_string())?;
+    let shortcuts = app.global_shortcut();
+    shortcuts.unregister_all().map_err(|e| e.to_string())?;
+    if let Some(hotkey) = hotkey.map(str::trim).filter(|h| !h.is_empty()) {
+        shortcuts.register(hotkey)
+            .map_err(|e| format!("Failed to register hotkey {}: {}", hotkey, e))?;
+        tracing::info!("Quick-run palette on {}", hotkey);
+    }
+    Ok(())
+}
+
+/// Latest runs of the open profile, newest first, for the tray's recent menu
+pub fn recent_runs(state: &AppState, limit: u32) -> Result<Vec<RunLog>, String> {
+    get_db(state)?.get_logs(limit).map_err(|e| e.to_string())
+}
+
+/// Accept commands from local scripts on the named pipe, acting on the open profile
+pub fn init_ipc(state: &Arc<AppState>) {
+    let state = state.clone();
+    tauri::async_runtime::spawn(crate::ipc::serve_loop(move |request| handle_ipc_request(state.clone(), request)));
+}
+
+async fn handle_ipc_request(state: Arc<AppState>, request: crate::ipc::IpcRequest) -> Result<serde_json::Value, String> {
+    use crate::ipc::IpcRequest;
+
+    match request {
+        IpcRequest::Run { task } => {
+            let run_id = run_task_by_name(&state, task).await?;
+            Ok(serde_json::json!({ "run_id": run_id }))
+        }
+        IpcRequest::Pause { minutes: Some(minutes) } => {
+            pause(&state, Some(chrono::Utc::now() + chrono::Duration::minutes(minutes.into()))).await?;
+            Ok(serde_json::Value::Null)
+        }
+        IpcRequest::Pause { minutes: None } => pause(&state, None).await.map(|_| serde_json::Value::Null),
+        IpcRequest::Resume => resume(&state).await.map(|_| serde_json::Value::Null),
+        IpcRequest::Status => {
+            serde_json::to_value(get_scheduler(&state)?.status().await).map_err(|e| e.to_string())
+        }
+        IpcRequest::Show => show_main_window().map(|_| serde_json::Value::Null),
+    }
+}
+
+/// Start a task now, given its id or its name; returns the run id
+pub async fn run_task_by_name(state: &AppState, id_or_name: String) -> Result<String, String> {
+    let task = with_db(state, move |db| find_task(db, &id_or_name)).await??;
+    get_scheduler(state)?.run_now(task, RunOverrides::default()).await
+}
+
+/// Carry out a routine-runner:// link the app was started with
+pub fn open_deep_link(state: &Arc<AppState>, link: crate::deeplink::DeepLink) {
+    let crate::deeplink::DeepLink::Run { task_id } = link;
+    let state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = start_run(&state, task_id.clone(), RunOverrides::default()).await {
+            tracing::error!("Failed to run task {} from deep link: {}", task_id, e);
+        }
+    });
+}
+
+/// The task with this id, or else the one task with this name (ignoring case)
+fn find_task(db: &Database, id_or_name: &str) -> rusqlite::Result<Result<Task, String>> {
+    if let Some(task) = db.get_task(id_or_name)? {
+        return Ok(Ok(task));
+    }
+    let name = id_or_name.trim().to_lowercase();
+    let mut matches: Vec<Task> = db.get_all_tasks()?
+        .into_iter()
+        .filter(|t| t.name.to_lowercase() == name)
+        .collect();
+    Ok(match matches.len() {
+        0 => Err(format!("Task not found: {}", id_or_name)),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("{} tasks are named {}; use the task id", n, id_or_name)),
+    })
+}
+
+/// Keep file-managed tasks in sync with the configured task file
+pub fn init_task_file_watcher(state: &Arc<AppState>) -> Result<(), String> {
+    let db = get_db(state)?;
+    let watcher = state.clone();
+    spawn_job(state, async move {
+        crate::task_file::watch(db, move || notify_scheduler(&watcher)).await;
+    })
+}
+
+/// Keep tasks in sync with the shared file in the configured sync folder
+pub fn init_task_sync(state: &Arc<AppState>) -> Result<(), String> {
+    let db = get_db(state)?;
+    let syncer = state.clone();
+    spawn_job(state, async move {
+        crate::task_sync::sync_loop(db, move || notify_scheduler(&syncer)).await;
+    })
+}
+
+fn get_scheduler(state: &AppState) -> Result<Arc<SchedulerRunner>, String> {
+    state.profile.read().unwrap()
+        .as_ref()
+        .and_then(|p| p.scheduler.clone())
+        .ok_or_else(|| "Scheduler not initialized".to_string())
+}
+
+/// Start the scheduler and background jobs of the freshly opened profile
+fn start_profile_jobs(state: &Arc<AppState>) -> Result<(), String> {
+    let app = APP.get().cloned().ok_or_else(|| "Scheduler not initialized".to_string())?;
+    init_scheduler(state, app)?;
+    init_log_flush(state)?;
+    init_task_file_watcher(state)?;
+    init_task_sync(state)?;
+    init_auto_backup(state)?;
+    init_config_history(state)?;
+    init_maintenance(state)?;
+    init_email_digest(state)?;
+    init_metrics(state)?;
+    init_mqtt(state)?;
+    init_webhook_server(state)?;
+    init_auto_update(state)?;
+    // A hotkey taken by another app shouldn't keep the profile from opening
+    if let Err(e) = init_quick_run_hotkey(state) {
+        tracing::warn!("Failed to register quick-run hotkey: {}", e);
+    }
+    Ok(())
+}
+
+/// Enabled tasks of the open profile by name, for the tray's run menu
+pub fn enabled_tasks(state: &AppState) -> Result<Vec<Task>, String> {
+    let mut tasks: Vec<Task> = get_db(state)?.get_all_tasks()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|t| t.enabled)
+        .map(crate::secrets::mask_task)
+        .collect();
+    tasks.sort_by_key(|t| t.name.to_lowercase());
+    Ok(tasks)
+}
+
+pub fn profile_list(state: &AppState) -> Result<ProfileList, String> {
+    let active = state.profile.read().unwrap()
+        .as_ref()
+        .map(|p| p.name.clone())
+        .ok_or_else(|| "Database not initialized".to_string())?;
+    Ok(ProfileList {
+        active,
+        profiles: crate::profiles::list_profiles(&get_data_dir(state)?),
+    })
+}
+
+fn emit_profiles_changed(state: &AppState) -> Result<ProfileList, String> {
+    let list = profile_list(state)?;
+    if let Some(app) = APP.get() {
+        if let Err(e) = app.emit(EVENT_PROFILES_CHANGED, list.clone()) {
+            tracing::warn!("Failed to emit {} event: {}", EVENT_PROFILES_CHANGED, e);
+        }
+    }
+    Ok(list)
+}
+
+#[tauri::command]
+pub async fn get_profiles(state: tauri::State<'_, Arc<AppState>>) -> Result<ProfileList, String> {
+    profile_list(&state)
+}
+
+/// Create an empty profile (it is not opened)
+#[tauri::command]
+pub async fn create_profile(state: tauri::State<'_, Arc<AppState>>, name: String) -> Result<ProfileList, String> {
+    crate::profiles::create_profile(&get_data_dir(&state)?, &name)?;
+    emit_profiles_changed(&state)
+}
+
+/// Close the active profile and open another one with its own scheduler.
+/// Runs already in progress finish and log into the profile they started in.
+#[tauri::command]
+pub async fn switch_profile(state: tauri::State<'_, Arc<AppState>>, name: String) -> Result<ProfileList, String> {
+    let name = crate::profiles::find_profile(&get_data_dir(&state)?, &name)
+        .ok_or_else(|| format!("Profile '{}' does not exist", name))?;
+    if profile_list(&state)?.active == name {
+        return profile_list(&state);
+    }
+    
+    replace_profile(&state, &name)?;
+    emit_profiles_changed(&state)
+}
+
+/// Open a profile in place of the active one and start its scheduler and jobs.
+/// The active profile keeps running if the other one can't be opened.
+fn replace_profile(state: &Arc<AppState>, name: &str) -> Result<(), String> {
+    let (runtime, recovered) = load_profile(state, name).inspect_err(|e| {
+        tracing::error!("Failed to open profile {}: {}", name, e);
+    })?;
+    // Swapped in one step, so commands in the meantime find one profile or the other
+    let previous = state.profile.write().unwrap().replace(runtime);
+    if let Some(previous) = previous {
+        stop_profile(previous);
+    }
+    activated(state, name);
+    
+    // The next profile's scheduler starts unpaused unless it has a pause of its own
+    if let Some(app) = APP.get() {
+        let _ = app.emit(
+            crate::scheduler_runner::EVENT_PAUSE_CHANGED,
+            crate::scheduler_runner::PauseChanged { paused: false, paused_until_utc: None },
+        );
+        if let Some(recovered) = recovered {
+            let _ = app.emit(EVENT_DATABASE_RECOVERED, recovered);
+        }
+    }
+    start_profile_jobs(state)
+}
+
+/// Where the app data is kept
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DataLocation {
+    pub dir: PathBuf,
+    pub default_dir: Option<PathBuf>,
+}
+
+pub fn data_location(state: &AppState) -> Result<DataLocation, String> {
+    Ok(DataLocation {
+        dir: get_data_dir(state)?,
+        default_dir: crate::data_location::default_dir(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_data_location(state: tauri::State<'_, Arc<AppState>>) -> Result<DataLocation, String> {
+    data_location(&state)
+}
+
+/// Move the app data (every profile, backups, logs and crash reports) to an empty folder,
+/// check the copy and carry on from there. The old folder is kept, to delete by hand;
+/// logs and crash reports go to the new folder from the next start.
+#[tauri::command]
+pub async fn move_data_dir(state: tauri::State<'_, Arc<AppState>>, target: String) -> Result<DataLocation, String> {
+    let target = PathBuf::from(target.trim());
+    let from = get_data_dir(&state)?;
+    crate::data_location::validate_target(&from, &target)?;
+    let scheduler = get_scheduler(&state)?;
+    if !scheduler.status().await.running.is_empty() {
+        return Err("Wait for the running tasks to finish before moving the data".to_string());
+    }
+    // Nothing should change in the old folder once it is copied
+    scheduler.stop_starting();
+    
+    let db = get_db(&state)?;
+    let db_path = get_profile_dir(&state)?.join(crate::profiles::DATABASE_FILE);
+    let (copy_from, copy_to) = (from.clone(), target.clone());
+    let moved = tauri::async_runtime::spawn_blocking(move || {
+        crate::data_location::migrate(&db, &db_path, &copy_from, &copy_to)?;
+        crate::data_location::save(&copy_to)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|moved| moved);
+    if moved.is_ok() {
+        *state.data_dir.write().unwrap() = Some(target.clone());
+        tracing::info!("Moved the data from {} to {}", from.display(), target.display());
+    }
+    
+    // A fresh scheduler, from the new folder or again from the old one
+    replace_profile(&state, &profile_list(&state)?.active)?;
+    moved?;
+    data_location(&state)
+}
+
+/// Let the scheduler re-plan after tasks, state or settings changed
+fn notify_scheduler(state: &AppState) {
+    if let Ok(scheduler) = get_scheduler(state) {
+        scheduler.wake();
+    }
+    if let Some(app) = APP.get() {
+        if let Err(e) = app.emit(EVENT_TASKS_CHANGED, ()) {
+            tracing::warn!("Failed to emit {} event: {}", EVENT_TASKS_CHANGED, e);
+        }
+    }
+}
+
+/// Tasks defined in the task file can only be changed by editing the file
+fn ensure_not_file_managed(db: &Database, id: &str) -> Result<(), String> {
+    match db.get_task(id).map_err(|e| e.to_string())? {
+        Some(task) if task.managed_by_file => {
+            Err(format!("Task '{}' is managed by the task file; edit the file instead", task.name))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// All tasks, or only those carrying `tag`
+#[tauri::command]
+pub async fn get_tasks(state: tauri::State<'_, Arc<AppState>>, tag: Option<String>) -> Result<Vec<Task>, String> {
+    let tasks = with_db(&state, |db| db.get_all_tasks()).await?;
+    Ok(filter_by_tag(tasks, tag.as_deref()))
+}
+
+/// Tasks whose name, description, path or arguments contain the query, optionally
+/// narrowed to a tag
+#[tauri::command]
+pub async fn search_tasks(state: tauri::State<'_, Arc<AppState>>, query: String, tag: Option<String>) -> Result<Vec<Task>, String> {
+    let tasks = with_db(&state, move |db| db.search_tasks(&query)).await?;
+    Ok(filter_by_tag(tasks, tag.as_deref()))
+}
+
+/// Tasks matching the tag, with secret values masked for the client
+fn filter_by_tag(tasks: Vec<Task>, tag: Option<&str>) -> Vec<Task> {
+    tasks.into_iter()
+        .filter(|t| tag.is_none_or(|tag| t.has_tag(tag)))
+        .map(crate::secrets::mask_task)
+        .collect()
+}
+
+/// Every tag in use, sorted
+#[tauri::command]
+pub async fn get_tags(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = with_db(&state, |db| db.get_all_tasks())
+        .await?
+        .into_iter()
+        .flat_map(|t| t.tags)
+        .collect();
+    tags.sort_by_key(|t| t.to_lowercase());
+    tags.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+    Ok(tags)
+}
+
+/// Enable or disable every task with the tag (file-managed tasks are left alone).
+/// Returns how many tasks changed.
+#[tauri::command]
+pub async fn set_enabled_by_tag(state: tauri::State<'_, Arc<AppState>>, tag: String, enabled: bool) -> Result<usize, String> {
+    let tag_filter = tag.clone();
+    let changed = with_db(&state, move |db| {
+        let mut changed = 0;
+        for mut task in db.get_all_tasks()? {
+            if !task.has_tag(&tag_filter) || task.managed_by_file || task.enabled == enabled {
+                continue;
+            }
+            task.enabled = enabled;
+            db.update_task(&task)?;
+            changed += 1;
+        }
+        Ok::<_, rusqlite::Error>(changed)
+    }).await?;
+    
+    tracing::info!("{} {} tasks tagged '{}'", if enabled { "Enabled" } else { "Disabled" }, changed, tag);
+    notify_scheduler(&state);
+    Ok(changed)
+}
+
+/// Enable, disable or delete several tasks in one transaction (file-managed tasks
+/// are left alone). Returns how many tasks changed.
+#[tauri::command]
+pub async fn bulk_update_tasks(state: tauri::State<'_, Arc<AppState>>, ids: Vec<String>, patch: TaskPatch) -> Result<usize, String> {
+    let changed = with_db(&state, move |db| db.bulk_update_tasks(&ids, &patch)).await?;
+    tracing::info!("Bulk update changed {} tasks", changed);
+    notify_scheduler(&state);
+    Ok(changed)
+}
+
+#[tauri::command]
+pub async fn get_groups(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<TaskGroup>, String> {
+    with_db(&state, |db| db.get_groups()).await
+}
+
+/// Create a group at the end of the list
+#[tauri::command]
+pub async fn create_group(state: tauri::State<'_, Arc<AppState>>, name: String) -> Result<TaskGroup, String> {
+    with_db(&state, move |db| {
+        let sort_order = db.get_groups()?
+            .iter()
+            .map(|g| g.sort_order + 1)
+            .max()
+            .unwrap_or(0);
+        
+        let group = TaskGroup {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            sort_order,
+            created_at_utc: chrono::Utc::now(),
+        };
+        db.insert_group(&group)?;
+        Ok::<_, rusqlite::Error>(group)
+    }).await
+}
+
+/// Rename or reorder a group
+#[tauri::command]
+pub async fn update_group(state: tauri::State<'_, Arc<AppState>>, group: TaskGroup) -> Result<(), String> {
+    with_db(&state, move |db| db.update_group(&group)).await
+}
+
+/// Delete a group, leaving its tasks ungrouped
+#[tauri::command]
+pub async fn delete_group(state: tauri::State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    with_db(&state, move |db| db.delete_group(&id)).await
+}
+
+/// Move a task into a group (None removes it from its group)
+#[tauri::command]
+pub async fn move_task_to_group(state: tauri::State<'_, Arc<AppState>>, task_id: String, group_id: Option<String>) -> Result<(), String> {
+    with_db(&state, move |db| {
+        if let Some(group_id) = &group_id {
+            if !db.get_groups().map_err(|e| e.to_string())?.iter().any(|g| &g.id == group_id) {
+                return Err("Group not found".to_string());
+            }
+        }
+        db.set_task_group(&task_id, group_id.as_deref()).map_err(|e| e.to_string())
+    }).await
+}
+
+#[tauri::command]
+pub async fn get_templates(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<TaskTemplate>, String> {
+    let templates = with_db(&state, |db| db.get_templates()).await?;
+    Ok(templates.into_iter()
+        .map(|t| TaskTemplate { task: crate::secrets::mask_task(t.task), ..t })
+        .collect())
+}
+
+/// Save an existing task's configuration as a reusable template
+#[tauri::command]
+pub async fn save_task_as_template(
+    state: tauri::State<'_, Arc<AppState>>,
+    task_id: String,
+    name: String,
+    description: Option<String>,
+) -> Result<TaskTemplate, String> {
+    with_db(&state, move |db| {
+        let task = db.get_task(&task_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Task not found".to_string())?;
+        
+        let template = TaskTemplate {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            description,
+            task,
+            created_at_utc: chrono::Utc::now(),
+        };
+        db.insert_template(&template).map_err(|e| e.to_string())?;
+        Ok::<_, String>(template)
+    }).await
+}
+
+#[tauri::command]
+pub async fn delete_template(state: tauri::State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    with_db(&state, move |db| db.delete_template(&id)).await
+}
+
+/// Create a new task from a template, optionally under a different name
+#[tauri::command]
+pub async fn create_task_from_template(state: tauri::State<'_, Arc<AppState>>, template_id: String, name: Option<String>) -> Result<Task, String> {
+    let task = with_db(&state, move |db| {
+        let template = db.get_template(&template_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Template not found".to_string())?;
+        
+        let task = template.instantiate(name);
+        db.insert_task(&task).map_err(|e| e.to_string())?;
+        Ok::<_, String>(task)
+    }).await?;
+    notify_scheduler(&state);
+    Ok(crate::secrets::mask_task(task))
+}
+
+/// Get tasks with their current state (last run, next run, is running)
+#[derive(serde::Serialize)]
+pub struct TaskWithState {
+    #[serde(flatten)]
+    pub task: Task,
+    pub last_run_at: Option<String>,
+    pub last_run_status: Option<String>,
+    pub next_run_at: Option<String>,
+    pub is_running: bool,
+    pub process_name: Option<String>,
+}
+
+#[tauri::command]
+pub async fn get_tasks_with_state(state: tauri::State<'_, Arc<AppState>>, tag: Option<String>) -> Result<Vec<TaskWithState>, String> {
+    list_tasks_with_state(&state, tag).await
+}
+
+pub async fn list_tasks_with_state(state: &AppState, tag: Option<String>) -> Result<Vec<TaskWithState>, String> {
+    // Also checks running processes, so the whole thing runs on the blocking pool
+    with_db(state, move |db| tasks_with_state(db, tag.as_deref())).await
+}
+
+fn tasks_with_state(db: &Database, tag: Option<&str>) -> Result<Vec<TaskWithState>, String> {
+    let tasks = filter_by_tag(db.get_all_tasks().map_err(|e| e.to_string())?, tag);
+    let states = db.get_task_states().map_err(|e| e.to_string())?;
+    
+    // Create a map of task_id -> TaskState
+    let state_map: HashMap<String, TaskState> = states.into_iter()
+        .map(|s| (s.task_id.clone(), s))
+        .collect();
+    
+    let mut result = Vec::with_capacity(tasks.len());
+    let running_processes = crate::executor::running_process_names();
+    
+    for task in tasks {
+        // Check if process is running (for exe targets)
+        let (is_running, process_name) = if matches!(task.target_type, TargetType::Exe) {
+            let pname = crate::executor::get_process_name_from_path(&task.path_or_url);
+            let running = crate::executor::launched_process_running(&task.id)
+                .unwrap_or_else(|| running_processes.contains(&pname.to_lowercase()));
+            (running, Some(pname))
+        } else {
+            (false, None)
+        };
+        
+        let state = state_map.get(&task.id);
+        
+        result.push(TaskWithState {
+            last_run_at: state.and_then(|s| s.last_run_at_utc.map(|t| t.to_rfc3339())),
+            last_run_status: state.and_then(|s| s.last_result.as_ref().map(|r| format!("{:?}", r))),
+            next_run_at: state.and_then(|s| s.next_run_at_utc.map(|t| t.to_rfc3339())),
+            is_running,
+            process_name,
+            task,
+        });
+    }
+    
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn get_task_states(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<TaskState>, String> {
+    with_db(&state, |db| db.get_task_states()).await
+}
+
+#[tauri::command]
+pub async fn create_task(state: tauri::State<'_, Arc<AppState>>, task: Task) -> Result<Task, String> {
+    crate::validation::check_webhook_tokens(&task)?;
+    let mut new_task = task;
+    new_task.id = uuid::Uuid::new_v4().to_string();
+    new_task.created_at_utc = chrono::Utc::now();
+    new_task.updated_at_utc = chrono::Utc::now();
+    
+    let stored = new_task.clone();
+    with_db(&state, move |db| db.insert_task(&stored)).await?;
+    notify_scheduler(&state);
+    Ok(crate::secrets::mask_task(new_task))
+}
+
+#[tauri::command]
+pub async fn update_task(state: tauri::State<'_, Arc<AppState>>, mut task: Task) -> Result<(), String> {
+    crate::validation::check_webhook_tokens(&task)?;
+    with_db(&state, move |db| {
+        ensure_not_file_managed(db, &task.id)?;
+        if let Some(stored) = db.get_task(&task.id).map_err(|e| e.to_string())? {
+            crate::secrets::keep_masked(&mut task, &stored);
+        }
+        db.update_task(&task).map_err(|e| e.to_string())?;
+        
+        // Triggers may have changed, let the scheduler plan the next run again
+        if let Some(mut state) = db.get_task_state(&task.id).map_err(|e| e.to_string())? {
+            let keys: Vec<String> = task.triggers.iter()
+                .enumerate()
+                .map(|(index, trigger)| trigger.state_key(index))
+                .collect();
+            state.trigger_states.retain(|key, _| keys.contains(key));
+            for trigger_state in state.trigger_states.values_mut() {
+                trigger_state.next_run_utc = None;
+            }
+            state.next_run_at_utc = None;
+            db.update_task_state(&state).map_err(|e| e.to_string())?;
+        }
+        Ok::<_, String>(())
+    }).await?;
+    notify_scheduler(&state);
+    Ok(())
+}
+
+/// Problems with a task as the editor has it, for flagging before save
+#[tauri::command]
+pub async fn validate_task(task: Task) -> Result<Vec<crate::validation::TaskWarning>, String> {
+    Ok(crate::validation::validate_task(&task))
+}
+
+/// Evaluate conditions right now, reporting each one's result and why
+#[tauri::command]
+pub async fn test_conditions(conditions: Vec<Condition>) -> Result<Vec<crate::conditions::ConditionResult>, String> {
+    tokio::task::spawn_blocking(move || crate::conditions::test_conditions(&conditions))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Current power, network, idle, disk and CPU readings to show beside condition fields
+#[tauri::command]
+pub async fn get_system_snapshot() -> Result<crate::conditions::SystemSnapshot, String> {
+    tokio::task::spawn_blocking(crate::conditions::system_snapshot)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_task(state: tauri::State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    with_db(&state, move |db| {
+        ensure_not_file_managed(db, &id)?;
+        db.delete_task(&id).map_err(|e| e.to_string())
+    }).await?;
+    notify_scheduler(&state);
     Ok(())
 }
 
-fn get_db() -> Result<&'static Database, String> {
-    DB.get().ok_or_else(|| "Database not initialized".to_string())
+/// Recorded changes to a task, newest first
+#[tauri::command]
+pub async fn get_task_history(state: tauri::State<'_, Arc<AppState>>, task_id: String) -> Result<Vec<TaskHistoryEntry>, String> {
+    let history = with_db(&state, move |db| db.get_task_history(&task_id)).await?;
+    Ok(history.into_iter()
+        .map(|e| TaskHistoryEntry { snapshot: crate::secrets::mask_task(e.snapshot), ..e })
+        .collect())
+}
+
+/// Put a task back to how it was after the given history entry
+#[tauri::command]
+pub async fn revert_task_to_version(state: tauri::State<'_, Arc<AppState>>, history_id: i64) -> Result<Task, String> {
+    let (entry, current) = with_db(&state, move |db| {
+        let entry = db.get_history_entry(history_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("History entry not found")?;
+        let current = db.get_task(&entry.task_id)
+            .map_err(|e| e.to_string())?
+            .ok_or("Task not found, restore it from the trash first")?;
+        Ok::<_, String>((entry, current))
+    }).await?;
+    
+    // Only the configuration is reverted
+    let task = Task {
+        created_at_utc: current.created_at_utc,
+        paused_until_utc: current.paused_until_utc,
+        managed_by_file: current.managed_by_file,
+        deleted_at_utc: None,
+        ..entry.snapshot
+    };
+    update_task(state, task.clone()).await?;
+    Ok(crate::secrets::mask_task(task))
+}
+
+/// Tasks in the trash
+#[tauri::command]
+pub async fn get_deleted_tasks(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<Task>, String> {
+    let tasks = with_db(&state, |db| db.get_deleted_tasks()).await?;
+    Ok(tasks.into_iter().map(crate::secrets::mask_task).collect())
+}
+
+#[tauri::command]
+pub async fn restore_task(state: tauri::State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    if !with_db(&state, move |db| db.restore_task(&id)).await? {
+        return Err("Task not found in trash".to_string());
+    }
+    notify_scheduler(&state);
+    Ok(())
+}
+
+/// Permanently delete everything in the trash, returns how many tasks were removed
+#[tauri::command]
+pub async fn purge_trash(state: tauri::State<'_, Arc<AppState>>) -> Result<usize, String> {
+    with_db(&state, |db| db.purge_trash()).await
+}
+
+/// Start a task now in the background; returns the run id
+#[tauri::command]
+pub async fn run_task_now(state: tauri::State<'_, Arc<AppState>>, id: String) -> Result<String, String> {
+    start_run(&state, id, RunOverrides::default()).await
+}
+
+/// Run a task once with different arguments/environment, without editing it.
+/// Returns the run id at once; completion arrives as a task_finished event.
+#[tauri::command]
+pub async fn run_task_now_with(state: tauri::State<'_, Arc<AppState>>, id: String, overrides: RunOverrides) -> Result<String, String> {
+    start_run(&state, id, overrides).await
+}
+
+async fn start_run(state: &AppState, id: String, overrides: RunOverrides) -> Result<String, String> {
+    let task = with_db(state, move |db| db.get_task(&id)).await?
+        .ok_or_else(|| "Task not found".to_string())?;
+    get_scheduler(state)?.run_now(task, overrides).await
+}
+
+/// A run's log: the started entry while it is in progress, the final one afterwards
+#[tauri::command]
+pub async fn get_run_status(state: tauri::State<'_, Arc<AppState>>, run_id: String) -> Result<Option<RunLog>, String> {
+    run_status(&state, &run_id).await
+}
+
+pub async fn run_status(state: &AppState, run_id: &str) -> Result<Option<RunLog>, String> {
+    get_scheduler(state)?.run_status(run_id).await
+}
+
+/// Watch a run in progress: its captured output lines arrive on `on_output` as they are
+/// written, starting with the latest ones so far. Ends when the run finishes.
+#[tauri::command]
+pub async fn subscribe_run_output(
+    state: tauri::State<'_, Arc<AppState>>,
+    run_id: String,
+    on_output: tauri::ipc::Channel<crate::scheduler_runner::RunOutputLine>,
+) -> Result<(), String> {
+    get_scheduler(&state)?.subscribe_output(&run_id, on_output)
+}
+
+/// Stop watching a run's output on the channel with this id
+#[tauri::command]
+pub async fn unsubscribe_run_output(state: tauri::State<'_, Arc<AppState>>, run_id: String, channel_id: u32) -> Result<(), String> {
+    get_scheduler(&state)?.unsubscribe_output(&run_id, channel_id);
+    Ok(())
+}
+
+/// Suspend a task until the given time (None resumes it immediately)
+#[tauri::command]
+pub async fn pause_task_until(state: tauri::State<'_, Arc<AppState>>, id: String, until: Option<chrono::DateTime<chrono::Utc>>) -> Result<(), String> {
+    let task = with_db(&state, move |db| {
+        let mut task = db.get_task(&id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Task not found".to_string())?;
+        
+        task.paused_until_utc = until;
+        db.update_task(&task).map_err(|e| e.to_string())?;
+        Ok::<_, String>(task)
+    }).await?;
+    
+    tracing::info!("Task {} paused until {:?}", task.name, until);
+    notify_scheduler(&state);
+    Ok(())
+}
+
+/// Push a task's due run back by the given number of minutes (0 clears the snooze)
+#[tauri::command]
+pub async fn snooze_task(state: tauri::State<'_, Arc<AppState>>, task_id: String, minutes: u32) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    snooze(&state, task_id, minutes).await
+}
+
+async fn snooze(state: &AppState, task_id: String, minutes: u32) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    let id = task_id.clone();
+    let task_state = with_db(state, move |db| {
+        let mut task_state = db.get_task_state_or_default(&id)?;
+        
+        task_state.snoozed_until_utc = if minutes > 0 {
+            Some(chrono::Utc::now() + chrono::Duration::minutes(minutes as i64))
+        } else {
+            None
+        };
+        db.update_task_state(&task_state)?;
+        Ok::<_, rusqlite::Error>(task_state)
+    }).await?;
+    
+    tracing::info!("Task {} snoozed until {:?}", task_id, task_state.snoozed_until_utc);
+    notify_scheduler(state);
+    Ok(task_state.snoozed_until_utc)
+}
+
+/// A planned run in the forecast
+#[derive(serde::Serialize)]
+pub struct UpcomingRun {
+    pub task_id: String,
+    pub task_name: String,
+    pub trigger_type: String,
+    pub scheduled_time_utc: chrono::DateTime<chrono::Utc>,
+}
+
+/// Upper bound on forecast length and on occurrences listed per trigger
+const MAX_FORECAST_DAYS: u32 = 366;
+const MAX_OCCURRENCES_PER_TRIGGER: usize = 1000;
+
+/// Expand all enabled triggers into a sorted agenda over the next N days
+#[tauri::command]
+pub async fn get_upcoming_runs(state: tauri::State<'_, Arc<AppState>>, days: u32) -> Result<Vec<UpcomingRun>, String> {
+    let (tasks, state_map) = with_db(&state, |db| {
+        let tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
+        Ok::<_, String>((tasks, task_state_map(db)?))
+    }).await?;
+    
+    let from = chrono::Local::now();
+    let until = from + chrono::Duration::days(days.min(MAX_FORECAST_DAYS) as i64);
+    
+    Ok(planned_runs(&tasks, &state_map, from, until).into_iter().map(|(_, run)| run).collect())
+}
+
+/// Write the planned runs of the next `days` days to an .ics file; returns how many were written
+#[tauri::command]
+pub async fn export_ical(state: tauri::State<'_, Arc<AppState>>, path: String, days: u32) -> Result<usize, String> {
+    let runs = get_upcoming_runs(state, days).await?;
+    std::fs::write(&path, crate::ical::calendar_text(&runs)).map_err(|e| e.to_string())?;
+    
+    tracing::info!("Exported {} planned runs to {}", runs.len(), path);
+    Ok(runs.len())
+}
+
+fn task_state_map(db: &Database) -> Result<HashMap<String, TaskState>, String> {
+    Ok(db.get_task_states()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|s| (s.task_id.clone(), s))
+        .collect())
+}
+
+/// Occurrences of every enabled task's triggers between `from` and `until`, sorted by time
+fn planned_runs<'a>(
+    tasks: &'a [Task],
+    state_map: &HashMap<String, TaskState>,
+    from: chrono::DateTime<chrono::Local>,
+    until: chrono::DateTime<chrono::Local>,
+) -> Vec<(&'a Task, UpcomingRun)> {
+    let mut runs = Vec::new();
+    for task in tasks.iter().filter(|t| t.enabled) {
+        let state = state_map.get(&task.id).cloned().unwrap_or_default();
+        
+        for (index, trigger) in task.triggers.iter().enumerate() {
+            let trigger_state = state.for_trigger(&trigger.state_key(index));
+            let occurrences = crate::scheduler::expand_occurrences(
+                trigger, &trigger_state, from, until, MAX_OCCURRENCES_PER_TRIGGER,
+            );
+            for time in occurrences {
+                // Paused tasks won't fire before their resume date
+                if task.paused_until_utc.is_some_and(|until| time < until) {
+                    continue;
+                }
+                runs.push((task, UpcomingRun {
+                    task_id: task.id.clone(),
+                    task_name: task.name.clone(),
+                    trigger_type: trigger.type_name().to_string(),
+                    scheduled_time_utc: time,
+                }));
+            }
+        }
+    }
+    
+    runs.sort_by_key(|(_, r)| r.scheduled_time_utc);
+    runs
+}
+
+/// A planned run on the schedule calendar
+#[derive(serde::Serialize)]
+pub struct CalendarRun {
+    #[serde(flatten)]
+    pub run: UpcomingRun,
+    /// When the run is expected to start, if a snooze or quiet hours hold it back
+    pub deferred_until_utc: Option<chrono::DateTime<chrono::Utc>>,
+    /// Dropped because it falls into quiet hours
+    pub skipped: bool,
+}
+
+/// The planned runs of one local day
+#[derive(serde::Serialize)]
+pub struct CalendarDay {
+    pub date: chrono::NaiveDate,
+    pub runs: Vec<CalendarRun>,
+}
+
+/// Planned runs for each local day from `from` to `to` (inclusive), after snoozes,
+/// pauses and quiet hours, for a week or month view
+#[tauri::command]
+pub async fn get_schedule_calendar(state: tauri::State<'_, Arc<AppState>>, from: chrono::NaiveDate, to: chrono::NaiveDate) -> Result<Vec<CalendarDay>, String> {
+    use chrono::TimeZone;
+    
+    if to < from {
+        return Err("The calendar ends before it starts".to_string());
+    }
+    if (to - from).num_days() >= MAX_FORECAST_DAYS as i64 {
+        return Err(format!("The calendar spans at most {} days", MAX_FORECAST_DAYS));
+    }
+    let (tasks, state_map, settings) = with_db(&state, |db| {
+        let tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
+        let settings = db.get_settings().map_err(|e| e.to_string())?;
+        Ok::<_, String>((tasks, task_state_map(db)?, settings))
+    }).await?;
+    
+    let midnight = |date: chrono::NaiveDate| {
+        chrono::Local.from_local_datetime(&date.and_time(chrono::NaiveTime::MIN)).earliest()
+    };
+    let start = midnight(from).ok_or("Invalid start date")?;
+    let end = to.succ_opt().and_then(midnight).ok_or("Invalid end date")?;
+    // Past days have no planned runs
+    let now = chrono::Local::now();
+    
+    let mut days: Vec<CalendarDay> = from.iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| CalendarDay { date, runs: Vec::new() })
+        .collect();
+    for (task, run) in planned_runs(&tasks, &state_map, start.max(now), end) {
+        let mut at = run.scheduled_time_utc;
+        let mut deferred = false;
+        let mut skipped = false;
+        
+        // Same order as the scheduler: a snooze holds the run, then quiet hours apply
+        let snoozed_until = state_map.get(&task.id).and_then(|s| s.snoozed_until_utc);
+        if let Some(until) = snoozed_until.filter(|until| *until > at) {
+            at = until;
+            deferred = true;
+        }
+        if settings.quiet_hours_enabled && !task.exempt_from_quiet_hours {
+            let quiet_end = crate::scheduler::quiet_hours_end(
+                &settings.quiet_hours_start, &settings.quiet_hours_end, at.with_timezone(&chrono::Local),
+            );
+            if let Some(quiet_end) = quiet_end {
+                match settings.quiet_hours_action {
+                    QuietHoursAction::Skip => skipped = true,
+                    QuietHoursAction::Defer => {
+                        at = quiet_end;
+                        deferred = true;
+                    }
+                }
+            }
+        }
+        
+        let date = at.with_timezone(&chrono::Local).date_naive();
+        let Some(day) = days.iter_mut().find(|d| d.date == date) else {
+            // Deferred past the end of the calendar
+            continue;
+        };
+        day.runs.push(CalendarRun {
+            run,
+            deferred_until_utc: deferred.then_some(at),
+            skipped,
+        });
+    }
+    
+    for day in &mut days {
+        day.runs.sort_by_key(|r| r.deferred_until_utc.unwrap_or(r.run.scheduled_time_utc));
+    }
+    Ok(days)
+}
+
+/// Most runs a preview lists
+const MAX_PREVIEW_RUNS: usize = 100;
+
+/// The next `count` runs of a draft task, as if it were saved now and had never run
+#[tauri::command]
+pub async fn preview_next_runs(task: Task, count: usize) -> Result<Vec<UpcomingRun>, String> {
+    let count = count.min(MAX_PREVIEW_RUNS);
+    let from = chrono::Local::now();
+    let until = from + chrono::Duration::days(MAX_FORECAST_DAYS as i64);
+    
+    let mut runs: Vec<UpcomingRun> = task.triggers.iter()
+        .flat_map(|trigger| {
+            crate::scheduler::expand_occurrences(trigger, &TaskState::default(), from, until, count)
+                .into_iter()
+                .map(move |time| (trigger, time))
+        })
+        .filter(|(_, time)| task.paused_until_utc.is_none_or(|until| *time >= until))
+        .map(|(trigger, time)| UpcomingRun {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            trigger_type: trigger.type_name().to_string(),
+            scheduled_time_utc: time,
+        })
+        .collect();
+    
+    runs.sort_by_key(|r| r.scheduled_time_utc);
+    runs.truncate(count);
+    Ok(runs)
+}
+
+/// Tasks that need attention: failed last run, missing target, no enabled
+/// trigger, or not run for longer than expected
+#[tauri::command]
+pub async fn get_health_summary(state: tauri::State<'_, Arc<AppState>>) -> Result<crate::health::HealthSummary, String> {
+    with_db(&state, |db| {
+        let tasks = db.get_all_tasks()?;
+        let states: HashMap<String, TaskState> = db.get_task_states()?
+            .into_iter()
+            .map(|s| (s.task_id.clone(), s))
+            .collect();
+        Ok::<_, rusqlite::Error>(crate::health::health_summary(&tasks, &states, chrono::Utc::now()))
+    }).await
+}
+
+/// Show a file or folder selected in Explorer (e.g. a task's target)
+#[tauri::command]
+pub async fn reveal_in_explorer(path: String) -> Result<(), String> {
+    crate::executor::reveal_in_explorer(Path::new(&path)).map_err(|e| e.to_string())
+}
+
+/// Open the active profile's data folder (database, backups) in Explorer
+#[tauri::command]
+pub async fn open_data_folder(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    crate::executor::open_folder(&get_profile_dir(&state)?).map_err(|e| e.to_string())
+}
+
+/// The last lines of the app's log files, for attaching to a bug report
+#[tauri::command]
+pub async fn get_log_tail(state: tauri::State<'_, Arc<AppState>>, lines: Option<usize>) -> Result<String, String> {
+    let dir = crate::logging::log_dir(&get_data_dir(&state)?);
+    let lines = lines.unwrap_or(crate::logging::MAX_TAIL_LINES);
+    tauri::async_runtime::spawn_blocking(move || crate::logging::tail(&dir, lines))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Latest log events held in memory, for the live log panel; filters are optional
+#[tauri::command]
+pub async fn get_diagnostics(
+    level: Option<String>,
+    module: Option<String>,
+    after_seq: Option<u64>,
+) -> Result<Vec<crate::logging::LogEntry>, String> {
+    crate::logging::recent(level.as_deref(), module.as_deref(), after_seq)
+}
+
+/// Describe a picked path (type, shortcut target, icon source, size) so the editor can fill in a task
+#[tauri::command]
+pub async fn inspect_target(path: String) -> Result<crate::inspect::TargetInfo, String> {
+    tauri::async_runtime::spawn_blocking(move || crate::inspect::inspect_target(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Whether the background engine is registered to start with Windows
+#[tauri::command]
+pub async fn get_background_engine() -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(crate::background::is_installed)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Register or remove the background engine (registering needs administrator rights)
+#[tauri::command]
+pub async fn set_background_engine(enabled: bool) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if enabled {
+            crate::background::install()
+        } else {
+            crate::background::uninstall()
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
+/// A fresh random token for a webhook trigger
 #[tauri::command]
-pub async fn get_tasks() -> Result<Vec<Task>, String> {
-    let db = get_db()?;
-    db.get_all_tasks().map_err(|e| e.to_string())
+pub async fn generate_webhook_token() -> Result<String, String> {
+    Ok(uuid::Uuid::new_v4().simple().to_string())
 }
 
-/// Get tasks with their current state (last run, next run, is running)
+/// Shortcuts in the Startup folders that can be imported as on-login tasks
+#[tauri::command]
+pub async fn scan_startup_shortcuts() -> Result<Vec<crate::startup_import::StartupShortcut>, String> {
+    tauri::async_runtime::spawn_blocking(crate::startup_import::scan)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Create an on-login task for each picked Startup shortcut, optionally moving the
+/// originals into the profile's "startup-disabled" folder so they don't start twice
+#[tauri::command]
+pub async fn import_startup_shortcuts(
+    state: tauri::State<'_, Arc<AppState>>,
+    paths: Vec<String>,
+    disable_originals: bool,
+) -> Result<crate::startup_import::StartupImportSummary, String> {
+    use crate::startup_import as startup;
+
+    let backup_dir = get_profile_dir(&state)?.join("startup-disabled");
+    let summary = get_db(&state)?.run(move |db| {
+        let mut tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
+        let mut summary = startup::StartupImportSummary::default();
+        // Only shortcuts that are really in a Startup folder can be moved
+        for shortcut in startup::scan().into_iter().filter(|s| paths.contains(&s.path)) {
+            if startup::is_imported(&shortcut, &tasks) {
+                summary.skipped += 1;
+            } else if let Some(task) = startup::shortcut_task(&shortcut) {
+                db.insert_task(&task).map_err(|e| e.to_string())?;
+                tasks.push(task);
+                summary.imported += 1;
+            } else {
+                summary.errors.push(format!("{}: target could not be resolved", shortcut.name));
+                continue;
+            }
+
+            if disable_originals {
+                match startup::disable(&shortcut, &backup_dir) {
+                    Ok(moved_to) => {
+                        tracing::info!("Moved startup shortcut {} to {}", shortcut.path, moved_to.display());
+                        summary.disabled += 1;
+                    }
+                    Err(e) => summary.errors.push(format!("{}: failed to disable shortcut: {}", shortcut.name, e)),
+                }
+            }
+        }
+        Ok::<_, String>(summary)
+    }).await?;
+
+    if summary.imported > 0 {
+        notify_scheduler(&state);
+    }
+    tracing::info!("Imported startup shortcuts: {:?}", summary);
+    Ok(summary)
+}
+
+/// Get running processes for all tasks
 #[derive(serde::Serialize)]
-pub struct TaskWithState {
-    #[serde(flatten)]
-    pub task: Task,
-    pub last_run_at: Option<String>,
-    pub last_run_status: Option<String>,
-    pub next_run_at: Option<String>,
+pub struct RunningProcess {
+    pub task_id: String,
+    pub task_name: String,
+    pub process_name: String,
     pub is_running: bool,
-    pub process_name: Option<String>,
 }
 
 #[tauri::command]
-pub async fn get_tasks_with_state() -> Result<Vec<TaskWithState>, String> {
-    let db = get_db()?;
-    let tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
-    let states = db.get_task_states().map_err(|e| e.to_string())?;
-    
-    // Create a map of task_id -> TaskState
-    let state_map: HashMap<String, TaskState> = states.into_iter()
-        .map(|s| (s.task_id.clone(), s))
-        .collect();
+pub async fn get_running_processes(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<RunningProcess>, String> {
+    let tasks = with_db(&state, |db| db.get_all_tasks()).await?;
     
-    let mut result = Vec::with_capacity(tasks.len());
-    
-    for task in tasks {
-        // Check if process is running (for exe targets)
-        let (is_running, process_name) = if matches!(task.target_type, TargetType::Exe) {
-            let pname = crate::executor::get_process_name_from_path(&task.path_or_url);
-            let running = crate::executor::check_process_running(&pname);
-            (running, Some(pname))
-        } else {
-            (false, None)
-        };
+    // Listing processes walks the whole process table, keep it off the async runtime
+    tokio::task::spawn_blocking(move || {
+        let mut processes = Vec::new();
+        let running_processes = crate::executor::running_process_names();
         
-        let state = state_map.get(&task.id);
+        for task in tasks {
+            if matches!(task.target_type, TargetType::Exe) {
+                let process_name = crate::executor::get_process_name_from_path(&task.path_or_url);
+                let is_running = crate::executor::launched_process_running(&task.id)
+                    .unwrap_or_else(|| running_processes.contains(&process_name.to_lowercase()));
+                
+                processes.push(RunningProcess {
+                    task_id: task.id,
+                    task_name: task.name,
+                    process_name,
+                    is_running,
+                });
+            }
+        }
         
-        result.push(TaskWithState {
-            last_run_at: state.and_then(|s| s.last_run_at_utc.map(|t| t.to_rfc3339())),
-            last_run_status: state.and_then(|s| s.last_result.as_ref().map(|r| format!("{:?}", r))),
-            next_run_at: state.and_then(|s| s.next_run_at_utc.map(|t| t.to_rfc3339())),
-            is_running,
-            process_name,
-            task,
-        });
+        processes
+    }).await.map_err(|e| e.to_string())
+}
+
+/// Stop the running process of an Exe task, returns false if none was running
+#[tauri::command]
+pub async fn kill_task_process(state: tauri::State<'_, Arc<AppState>>, task_id: String) -> Result<bool, String> {
+    let task = with_db(&state, move |db| db.get_task(&task_id)).await?
+        .ok_or_else(|| "Task not found".to_string())?;
+    if task.target_type != TargetType::Exe {
+        return Err(format!("Task '{}' does not run a program", task.name));
+    }
+    let killed = tokio::task::spawn_blocking(move || crate::executor::kill_task_process(&task))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(killed)
+}
+
+/// Most webhook delivery attempts returned at once
+const MAX_WEBHOOK_DELIVERIES: u32 = 500;
+
+/// Recent webhook delivery attempts, newest first, optionally for one task
+#[tauri::command]
+pub async fn get_webhook_deliveries(state: tauri::State<'_, Arc<AppState>>, task_id: Option<String>, limit: u32) -> Result<Vec<WebhookDelivery>, String> {
+    let limit = limit.min(MAX_WEBHOOK_DELIVERIES);
+    with_db(&state, move |db| db.get_webhook_deliveries(task_id.as_deref(), limit)).await
+}
+
+/// A made-up failed run for testing notification settings
+fn sample_failed_run(task_name: &str) -> RunLog {
+    let now = chrono::Utc::now();
+    RunLog {
+        run_id: uuid::Uuid::new_v4().to_string(),
+        task_id: "test".to_string(),
+        task_name: task_name.to_string(),
+        trigger_type: "Manual".to_string(),
+        scheduled_time_utc: Some(now),
+        started_at_utc: now,
+        finished_at_utc: Some(now),
+        status: RunStatus::Failed,
+        skip_reason: None,
+        exit_code: Some(1),
+        error_message: Some("This is a test".to_string()),
+        error: None,
+        output: Some("Sample output".to_string()),
+        overrides: None,
+        duration_ms: Some(1500),
     }
-    
-    Ok(result)
 }
 
+/// Send a sample failed-run payload to a webhook once, returns the HTTP status
 #[tauri::command]
-pub async fn get_task_states() -> Result<Vec<TaskState>, String> {
-    let db = get_db()?;
-    db.get_task_states().map_err(|e| e.to_string())
+pub async fn test_webhook(url: String) -> Result<u16, String> {
+    let sample = sample_failed_run("Webhook test");
+    let body = crate::webhooks::payload(&sample);
+    tokio::task::spawn_blocking(move || crate::http::post_json(&url, &body))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[tauri::command]
-pub async fn create_task(task: Task) -> Result<Task, String> {
-    let db = get_db()?;
-    let mut new_task = task;
-    new_task.id = uuid::Uuid::new_v4().to_string();
-    new_task.created_at_utc = chrono::Utc::now();
-    new_task.updated_at_utc = chrono::Utc::now();
-    
-    db.insert_task(&new_task).map_err(|e| e.to_string())?;
-    Ok(new_task)
+pub async fn get_logs(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<RunLog>, String> {
+    with_db(&state, |db| db.get_logs(100)).await
+}
+
+/// Most logs returned per page of a task's history
+const MAX_TASK_LOGS_PAGE: u32 = 500;
+
+/// A page of one task's run logs, newest first
+#[tauri::command]
+pub async fn get_logs_for_task(state: tauri::State<'_, Arc<AppState>>, task_id: String, limit: u32, offset: u32) -> Result<Vec<RunLog>, String> {
+    let limit = limit.min(MAX_TASK_LOGS_PAGE);
+    with_db(&state, move |db| db.get_logs_for_task(&task_id, limit, offset)).await
+}
+
+/// Run health of a task over the last `days` days
+#[tauri::command]
+pub async fn get_task_stats(state: tauri::State<'_, Arc<AppState>>, task_id: String, days: u32) -> Result<TaskStats, String> {
+    with_db(&state, move |db| db.get_task_stats(&task_id, days)).await
 }
 
+/// Find runs whose task name, error or output contains the given words
 #[tauri::command]
-pub async fn update_task(task: Task) -> Result<(), String> {
-    let db = get_db()?;
-    db.update_task(&task).map_err(|e| e.to_string())
+pub async fn search_logs(state: tauri::State<'_, Arc<AppState>>, query: String) -> Result<Vec<RunLog>, String> {
+    with_db(&state, move |db| db.search_logs(&query, 200)).await
 }
 
+/// Export the run logs matching `filter` to a CSV file, returns how many were written
 #[tauri::command]
-pub async fn delete_task(id: String) -> Result<(), String> {
-    let db = get_db()?;
-    db.delete_task(&id).map_err(|e| e.to_string())
+pub async fn export_logs_csv(state: tauri::State<'_, Arc<AppState>>, path: String, filter: LogFilter) -> Result<usize, String> {
+    let count = get_db(&state)?
+        .run(move |db| crate::log_export::export_logs_csv(db, Path::new(&path), &filter))
+        .await
+        .map_err(|e| e.to_string())?;
+    tracing::info!("Exported {} run logs to CSV", count);
+    Ok(count)
 }
 
 #[tauri::command]
-pub async fn run_task_now(id: String) -> Result<(), String> {
-    let db = get_db()?;
-    let tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
+pub async fn get_log_detail(state: tauri::State<'_, Arc<AppState>>, run_id: String) -> Result<Option<RunLog>, String> {
+    with_db(&state, move |db| db.get_log(&run_id)).await
+}
+
+/// Settings of the open profile as stored, secrets still sealed
+pub fn load_settings(state: &AppState) -> Result<Settings, String> {
+    get_db(state)?.get_settings().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_settings(state: tauri::State<'_, Arc<AppState>>) -> Result<Settings, String> {
+    let mut settings = with_db(&state, |db| db.get_settings()).await?;
     
-    let task = tasks.into_iter()
-        .find(|t| t.id == id)
-        .ok_or_else(|| "Task not found".to_string())?;
+    // Check actual autostart status from registry
+    settings.start_with_windows = crate::autostart::is_autostart_enabled();
     
-    tracing::info!("Running task now: {}", task.name);
+    Ok(crate::secrets::mask_settings(settings))
+}
+
+#[tauri::command]
+pub async fn update_settings(state: tauri::State<'_, Arc<AppState>>, mut settings: Settings) -> Result<(), String> {
+    // Everything is checked before anything is changed
+    if let Some(hotkey) = settings.quick_run_hotkey.as_deref().map(str::trim).filter(|h| !h.is_empty()) {
+        hotkey.parse::<tauri_plugin_global_shortcut::Shortcut>()
+            .map_err(|e| format!("Invalid hotkey {}: {}", hotkey, e))?;
+    }
+    if chrono::NaiveTime::parse_from_str(&settings.updates.install_at, "%H:%M").is_err() {
+        return Err(format!("Invalid update time {}, expected HH:MM", settings.updates.install_at));
+    }
+    if !settings.updates.manifest_url.starts_with("https://") {
+        return Err("The update manifest URL must start with https://".to_string());
+    }
     
-    // Execute the task
-    let result = crate::executor::execute_task(&task);
+    let stored = with_db(&state, |db| db.get_settings()).await?;
+    crate::secrets::keep_masked_settings(&mut settings, &stored);
+    let settings = crate::secrets::seal_settings(&settings)?;
     
-    // Log the execution
-    let (status, error_message, exit_code, output) = match &result {
-        Ok(r) => {
-            if r.success {
-                (RunStatus::Success, None, r.exit_code, r.output.clone())
-            } else {
-                (RunStatus::Failed, r.error_message.clone(), r.exit_code, r.output.clone())
-            }
+    // Then the changes outside the settings, undone if a later step fails so an error
+    // leaves things as they were
+    let hotkey_changed = settings.quick_run_hotkey != stored.quick_run_hotkey;
+    if hotkey_changed {
+        set_quick_run_hotkey(settings.quick_run_hotkey.as_deref())?;
+    }
+    let restore_hotkey = || {
+        if hotkey_changed {
+            let _ = set_quick_run_hotkey(stored.quick_run_hotkey.as_deref());
         }
-        Err(e) => (RunStatus::Failed, Some(e.to_string()), None, None),
-    };
-    
-    let now = chrono::Utc::now();
-    let log = RunLog {
-        run_id: uuid::Uuid::new_v4().to_string(),
-        task_id: task.id.clone(),
-        task_name: task.name.clone(),
-        trigger_type: "Manual".to_string(),
-        scheduled_time_utc: None,
-        started_at_utc: now,
-        finished_at_utc: Some(now),
-        status: status.clone(),
-        skip_reason: None,
-        exit_code,
-        error_message: error_message.clone(),
-        output,
-    };
-    
-    let _ = db.insert_log(&log);
-    
-    // Update task state
-    let last_result = match status {
-        RunStatus::Success => Some(RunResult::Success),
-        RunStatus::Failed => Some(RunResult::Failed),
-        RunStatus::Skipped => Some(RunResult::Skipped),
-        _ => None,
     };
+    let was_autostart = crate::autostart::is_autostart_enabled();
+    if let Err(e) = crate::autostart::set_autostart(settings.start_with_windows) {
+        restore_hotkey();
+        return Err(e);
+    }
     
-    let state = TaskState {
-        task_id: task.id.clone(),
-        last_run_date_local: Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
-        last_run_at_utc: Some(now),
-        last_result,
-        last_error: error_message.clone(),
-        next_run_at_utc: None, // Will be calculated by scheduler
-    };
-    let _ = db.update_task_state(&state);
-    
-    match result {
-        Ok(r) if r.success => Ok(()),
-        Ok(r) => Err(r.error_message.unwrap_or_else(|| "Task failed".to_string())),
-        Err(e) => Err(e.to_string()),
+    let saved = settings.clone();
+    if let Err(e) = with_db(&state, move |db| db.save_settings(&saved)).await {
+        restore_hotkey();
+        let _ = crate::autostart::set_autostart(was_autostart);
+        return Err(e);
     }
+    notify_scheduler(&state);
+    Ok(())
 }
 
-/// Get running processes for all tasks
-#[derive(serde::Serialize)]
-pub struct RunningProcess {
-    pub task_id: String,
-    pub task_name: String,
-    pub process_name: String,
-    pub is_running: bool,
+/// Message templates in a language (the configured one by default), keyed by message id,
+/// for rendering run errors and skip reasons
+#[tauri::command]
+pub async fn get_messages(state: tauri::State<'_, Arc<AppState>>, locale: Option<Locale>) -> Result<HashMap<MessageId, &'static str>, String> {
+    let locale = match locale {
+        Some(locale) => locale,
+        None => with_db(&state, |db| db.get_settings()).await?.language,
+    };
+    Ok(crate::messages::catalog(locale))
+}
+
+/// Look for a new version now
+#[tauri::command]
+pub async fn check_for_updates(state: tauri::State<'_, Arc<AppState>>) -> Result<crate::updater::UpdateInfo, String> {
+    with_db(&state, |db| {
+        let url = db.get_settings().map_err(|e| e.to_string())?.updates.manifest_url;
+        crate::updater::check_and_record(db, &url)
+    }).await
+}
+
+/// Download and start the new version's installer, then exit so it can replace the app
+#[tauri::command]
+pub async fn install_update(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    let info = check_for_updates(state).await?;
+    tauri::async_runtime::spawn_blocking(move || crate::updater::install(&info))
+        .await
+        .map_err(|e| e.to_string())??;
+    if let Some(app) = APP.get() {
+        app.exit(0);
+    }
+    Ok(())
 }
 
+/// Post a sample message through a chat notifier as edited (a masked credential
+/// means the saved one)
 #[tauri::command]
-pub async fn get_running_processes() -> Result<Vec<RunningProcess>, String> {
-    let db = get_db()?;
-    let tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
+pub async fn test_chat_notifier(state: tauri::State<'_, Arc<AppState>>, notifier: ChatNotifier) -> Result<(), String> {
+    let stored = with_db(&state, |db| db.get_settings()).await?;
+    let mut settings = Settings { chat_notifiers: vec![notifier], ..Settings::default() };
+    crate::secrets::keep_masked_settings(&mut settings, &stored);
+    let settings = crate::secrets::reveal_settings(&settings)?;
+    let notifier = settings.chat_notifiers.into_iter().next().ok_or("No notifier")?;
     
-    let mut processes = Vec::new();
+    let sample = sample_failed_run("Notifier test");
+    let message = crate::chat::render_message(notifier.template.as_deref(), &sample);
+    tokio::task::spawn_blocking(move || crate::chat::send(&notifier, &message))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Send a test email with the email settings as edited (a masked password means the saved one)
+#[tauri::command]
+pub async fn test_email(state: tauri::State<'_, Arc<AppState>>, email: EmailSettings) -> Result<(), String> {
+    let stored = with_db(&state, |db| db.get_settings()).await?;
+    let mut settings = Settings { email, ..Settings::default() };
+    crate::secrets::keep_masked_settings(&mut settings, &stored);
+    let email = crate::secrets::reveal_settings(&settings)?.email;
     
-    for task in tasks {
-        if matches!(task.target_type, TargetType::Exe) {
-            let process_name = crate::executor::get_process_name_from_path(&task.path_or_url);
-            let is_running = crate::executor::check_process_running(&process_name);
-            
-            processes.push(RunningProcess {
-                task_id: task.id,
-                task_name: task.name,
-                process_name,
-                is_running,
-            });
+    let (subject, body) = crate::email::alert_text(&sample_failed_run("Email test"), 1);
+    tokio::task::spawn_blocking(move || crate::email::send(&email, &subject, &body))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Whether the event log source is registered on this machine
+#[tauri::command]
+pub async fn is_event_log_source_registered() -> Result<bool, String> {
+    Ok(crate::eventlog::is_source_registered())
+}
+
+/// Register the event log source (needs administrator rights, once per machine)
+#[tauri::command]
+pub async fn register_event_log_source() -> Result<(), String> {
+    crate::eventlog::register_source()
+}
+
+/// What the scheduler engine is doing right now
+#[tauri::command]
+pub async fn get_scheduler_status(state: tauri::State<'_, Arc<AppState>>) -> Result<crate::scheduler_runner::SchedulerStatus, String> {
+    let scheduler = get_scheduler(&state)?;
+    Ok(scheduler.status().await)
+}
+
+/// Where the scheduler spends its time: ticks, per-task planning, database calls and queue waits
+#[tauri::command]
+pub async fn get_engine_metrics() -> Result<crate::metrics::EngineMetricsSnapshot, String> {
+    Ok(crate::metrics::ENGINE_METRICS.snapshot())
+}
+
+/// Runs in progress in the open profile, none without a scheduler
+pub async fn running_runs(state: &AppState) -> Vec<crate::scheduler_runner::RunningTask> {
+    match get_scheduler(state) {
+        Ok(scheduler) => scheduler.status().await.running,
+        Err(_) => vec![],
+    }
+}
+
+/// Let the open profile's runs finish before exiting: start no new ones, optionally
+/// kill the programs still running, then wait for every run to end, or at most
+/// `grace_secs` if given, after which the runs left are logged as interrupted
+pub async fn drain_runs(state: &AppState, cancel: bool, grace_secs: Option<u64>) -> Result<(), String> {
+    let Ok(scheduler) = get_scheduler(state) else { return Ok(()) };
+    scheduler.stop_starting();
+    if cancel {
+        for run in scheduler.status().await.running {
+            let task_id = run.task_id.clone();
+            let task = with_db(state, move |db| db.get_task(&task_id)).await?;
+            let Some(task) = task.filter(|t| t.target_type == TargetType::Exe) else { continue };
+            let killed = tokio::task::spawn_blocking(move || crate::executor::kill_task_process(&task))
+                .await
+                .map_err(|e| e.to_string())?;
+            if let Err(e) = killed {
+                tracing::warn!("Failed to stop task {}: {}", run.task_name, e);
+            }
         }
     }
-    
-    Ok(processes)
+    if !scheduler.wait_idle(grace_secs).await {
+        let interrupted = scheduler.interrupt_running().await;
+        tracing::warn!("Exiting with {} run(s) still in progress", interrupted);
+    }
+    Ok(())
 }
 
+/// Pause the whole scheduler until resumed (also across restarts)
 #[tauri::command]
-pub async fn get_logs() -> Result<Vec<RunLog>, String> {
-    let db = get_db()?;
-    db.get_logs(100).map_err(|e| e.to_string())
+pub async fn pause_scheduler(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    pause(&state, None).await
 }
 
 #[tauri::command]
-pub async fn get_log_detail(run_id: String) -> Result<Option<RunLog>, String> {
-    let db = get_db()?;
-    let logs = db.get_logs(500).map_err(|e| e.to_string())?;
-    Ok(logs.into_iter().find(|l| l.run_id == run_id))
+pub async fn is_scheduler_paused(state: tauri::State<'_, Arc<AppState>>) -> Result<bool, String> {
+    Ok(get_scheduler(&state)?.is_paused())
 }
 
+/// Pause the scheduler if it is running, resume it if paused; returns whether it is now paused
 #[tauri::command]
-pub async fn get_settings() -> Result<Settings, String> {
-    let db = get_db()?;
-    let mut settings = db.get_settings().map_err(|e| e.to_string())?;
-    
-    // Check actual autostart status from registry
-    settings.start_with_windows = crate::autostart::is_autostart_enabled();
-    
-    Ok(settings)
+pub async fn toggle_scheduler_pause(state: tauri::State<'_, Arc<AppState>>) -> Result<bool, String> {
+    Ok(get_scheduler(&state)?.toggle_pause().await)
 }
 
+/// Pause the whole scheduler until the given time; it resumes on its own afterwards
 #[tauri::command]
-pub async fn update_settings(settings: Settings) -> Result<(), String> {
-    let db = get_db()?;
-    
-    // Handle autostart separately
-    crate::autostart::set_autostart(settings.start_with_windows)?;
-    
-    db.save_settings(&settings).map_err(|e| e.to_string())
+pub async fn pause_scheduler_until(state: tauri::State<'_, Arc<AppState>>, until: chrono::DateTime<chrono::Utc>) -> Result<(), String> {
+    pause(&state, Some(until)).await
+}
+
+/// Pause the open profile's scheduler until the given time, or until resumed without one
+pub async fn pause(state: &AppState, until: Option<chrono::DateTime<chrono::Utc>>) -> Result<(), String> {
+    let scheduler = get_scheduler(state)?;
+    match until {
+        Some(until) if until <= chrono::Utc::now() => return Err("Resume time must be in the future".to_string()),
+        Some(until) => scheduler.pause_until(until).await,
+        None => scheduler.pause().await,
+    }
+    Ok(())
+}
+
+/// Resume the scheduler, ending any pause (timed or not)
+#[tauri::command]
+pub async fn resume_scheduler(state: tauri::State<'_, Arc<AppState>>) -> Result<(), String> {
+    resume(&state).await
+}
+
+pub async fn resume(state: &AppState) -> Result<(), String> {
+    get_scheduler(state)?.resume().await;
+    Ok(())
+}
+
+/// Toggle dry-run mode: the scheduler logs would-have-run entries instead of executing
+#[tauri::command]
+pub async fn set_dry_run(state: tauri::State<'_, Arc<AppState>>, enabled: bool) -> Result<(), String> {
+    with_db(&state, move |db| {
+        let mut settings = db.get_settings()?;
+        settings.dry_run = enabled;
+        db.save_settings(&settings)
+    }).await?;
+    tracing::info!("Dry-run mode {}", if enabled { "enabled" } else { "disabled" });
+    notify_scheduler(&state);
+    Ok(())
 }
 
 #[tauri::command]
@@ -252,8 +1972,264 @@ pub async fn set_autostart(enabled: bool) -> Result<(), String> {
     crate::autostart::set_autostart(enabled)
 }
 
+/// Export all tasks (and optionally settings) to a JSON file, returns the task count.
+/// Secrets are masked: they are encrypted for this PC only, so the import asks for them again.
+#[tauri::command]
+pub async fn export_tasks(state: tauri::State<'_, Arc<AppState>>, path: String, include_settings: bool) -> Result<usize, String> {
+    export_to_file(&state, path, include_settings).await
+}
+
+pub async fn export_to_file(state: &AppState, path: String, include_settings: bool) -> Result<usize, String> {
+    with_db(state, move |db| {
+        let bundle = ExportBundle {
+            format_version: ExportBundle::FORMAT_VERSION,
+            exported_at_utc: chrono::Utc::now(),
+            tasks: db.get_all_tasks().map_err(|e| e.to_string())?
+                .into_iter()
+                .map(crate::secrets::mask_task)
+                .collect(),
+            settings: if include_settings {
+                Some(crate::secrets::mask_settings(db.get_settings().map_err(|e| e.to_string())?))
+            } else {
+                None
+            },
+        };
+        
+        let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| e.to_string())?;
+        
+        tracing::info!("Exported {} tasks to {}", bundle.tasks.len(), path);
+        Ok::<_, String>(bundle.tasks.len())
+    }).await
+}
+
+/// Sync tasks with the shared file in the sync folder right away
+#[tauri::command]
+pub async fn sync_tasks_now(state: tauri::State<'_, Arc<AppState>>) -> Result<crate::task_sync::SyncSummary, String> {
+    let folder = with_db(&state, |db| db.get_settings()).await?
+        .sync_folder
+        .filter(|f| !f.trim().is_empty())
+        .ok_or_else(|| "No sync folder configured".to_string())?;
+    let summary = with_db(&state, move |db| crate::task_sync::sync_folder(db, Path::new(&folder))).await?;
+    if summary.pulled > 0 {
+        notify_scheduler(&state);
+    }
+    Ok(summary)
+}
+
+/// Import tasks (and settings, if present and requested) from an exported JSON file
+#[tauri::command]
+pub async fn import_tasks(
+    state: tauri::State<'_, Arc<AppState>>,
+    path: String,
+    on_collision: ImportCollision,
+    include_settings: bool,
+) -> Result<ImportSummary, String> {
+    import_from_file(&state, path, on_collision, include_settings).await
+}
+
+pub async fn import_from_file(
+    state: &AppState,
+    path: String,
+    on_collision: ImportCollision,
+    include_settings: bool,
+) -> Result<ImportSummary, String> {
+    let file = path.clone();
+    let summary = with_db(state, move |db| {
+        let json = std::fs::read_to_string(&file).map_err(|e| e.to_string())?;
+        let bundle: ExportBundle = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        if bundle.format_version > ExportBundle::FORMAT_VERSION {
+            return Err(format!("Unsupported export format version {}", bundle.format_version));
+        }
+        
+        let mut summary = db.import_tasks(&bundle.tasks, on_collision).map_err(|e| e.to_string())?;
+        
+        if let (true, Some(mut settings)) = (include_settings, bundle.settings) {
+            // Masked credentials keep the ones set up here, if any
+            let stored = db.get_settings().map_err(|e| e.to_string())?;
+            crate::secrets::keep_masked_settings(&mut settings, &stored);
+            summary.settings_missing_secrets = crate::secrets::clear_unusable_settings(&mut settings);
+            let settings = crate::secrets::seal_settings(&settings)?;
+            db.save_settings(&settings).map_err(|e| e.to_string())?;
+            summary.settings_imported = true;
+        }
+        Ok(summary)
+    }).await?;
+    
+    tracing::info!("Imported tasks from {}: {:?}", path, summary);
+    notify_scheduler(state);
+    Ok(summary)
+}
+
+/// Use a YAML/TOML file as the source of truth for its tasks (None turns it off and
+/// makes those tasks editable again). Returns what the initial sync changed.
+#[tauri::command]
+pub async fn set_task_file(
+    state: tauri::State<'_, Arc<AppState>>,
+    path: Option<String>,
+) -> Result<Option<crate::task_file::ReconcileSummary>, String> {
+    let summary = with_db(&state, move |db| {
+        let summary = match &path {
+            Some(p) => Some(crate::task_file::sync_task_file(db, std::path::Path::new(p)).map_err(|e| e.to_string())?),
+            None => {
+                db.release_file_managed_tasks().map_err(|e| e.to_string())?;
+                None
+            }
+        };
+        
+        let mut settings = db.get_settings().map_err(|e| e.to_string())?;
+        settings.task_file_path = path;
+        db.save_settings(&settings).map_err(|e| e.to_string())?;
+        Ok::<_, String>(summary)
+    }).await?;
+    
+    notify_scheduler(&state);
+    Ok(summary)
+}
+
+/// Re-read the task file right away instead of waiting for the watcher
+#[tauri::command]
+pub async fn reload_task_file(state: tauri::State<'_, Arc<AppState>>) -> Result<crate::task_file::ReconcileSummary, String> {
+    let path = with_db(&state, |db| db.get_settings())
+        .await?
+        .task_file_path
+        .ok_or_else(|| "No task file configured".to_string())?;
+    
+    let summary = with_db(&state, move |db| crate::task_file::sync_task_file(db, std::path::Path::new(&path))).await?;
+    notify_scheduler(&state);
+    Ok(summary)
+}
+
+/// Saved configuration versions, newest first
+#[tauri::command]
+pub async fn get_config_versions(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<crate::config_history::ConfigVersion>, String> {
+    let mode = with_db(&state, |db| db.get_settings()).await?.config_history_mode;
+    let dir = crate::config_history::history_dir(&get_profile_dir(&state)?);
+    tauri::async_runtime::spawn_blocking(move || crate::config_history::list_versions(&dir, mode))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// What changed in the tasks and settings between two saved versions
+#[tauri::command]
+pub async fn diff_config(state: tauri::State<'_, Arc<AppState>>, from: String, to: String) -> Result<crate::config_history::ConfigDiff, String> {
+    use crate::config_history::{diff, history_dir, load_version};
+    
+    let mode = with_db(&state, |db| db.get_settings()).await?.config_history_mode;
+    let dir = history_dir(&get_profile_dir(&state)?);
+    tauri::async_runtime::spawn_blocking(move || {
+        Ok(diff(&load_version(&dir, mode, &from)?, &load_version(&dir, mode, &to)?))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Purge run logs past the retention period now, returns how many were removed
+#[tauri::command]
+pub async fn cleanup_logs(state: tauri::State<'_, Arc<AppState>>) -> Result<usize, String> {
+    get_db(&state)?.run(crate::scheduler_runner::purge_old_logs).await
+}
+
+/// Outcome of clear_logs
+#[derive(serde::Serialize)]
+pub struct ClearLogsResult {
+    /// Logs matching the filter before anything was deleted
+    pub matching: usize,
+    pub deleted: usize,
+    /// Pass this back to actually delete, when nothing was deleted
+    pub confirm_token: Option<String>,
+}
+
+/// Token tying a confirmation to the filter and the number of logs the user was shown
+fn clear_logs_token(filter: &LogFilter, matching: usize) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    serde_json::to_string(filter).unwrap_or_default().hash(&mut hasher);
+    matching.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Delete the logs matching `filter` (a task, a date range, a status). Without a
+/// valid `confirm_token` nothing is deleted: the result reports how many logs match
+/// and the token to confirm with. The token goes stale if the matching logs change.
+#[tauri::command]
+pub async fn clear_logs(state: tauri::State<'_, Arc<AppState>>, filter: LogFilter, confirm_token: Option<String>) -> Result<ClearLogsResult, String> {
+    let query = filter.clone();
+    let matching = with_db(&state, move |db| db.count_logs(&query)).await?;
+    let token = clear_logs_token(&filter, matching);
+    
+    if confirm_token.as_deref() != Some(token.as_str()) {
+        return Ok(ClearLogsResult { matching, deleted: 0, confirm_token: Some(token) });
+    }
+    
+    let deleted = with_db(&state, move |db| db.delete_logs(&filter)).await?;
+    tracing::info!("Cleared {} run logs", deleted);
+    Ok(ClearLogsResult { matching, deleted, confirm_token: None })
+}
+
+/// VACUUM/ANALYZE the database now and report how much space was freed
+#[tauri::command]
+pub async fn compact_database(state: tauri::State<'_, Arc<AppState>>) -> Result<CompactSummary, String> {
+    get_db(&state)?.run(crate::maintenance::run_maintenance).await
+}
+
+/// Write a consistent copy of the database to `path`, even while tasks are running
+#[tauri::command]
+pub async fn backup_database(state: tauri::State<'_, Arc<AppState>>, path: String) -> Result<(), String> {
+    let target = std::path::PathBuf::from(&path);
+    with_db(&state, move |db| db.backup_to(&target)).await?;
+    tracing::info!("Database backed up to {}", path);
+    Ok(())
+}
+
+/// Replace the database with a backup. The current data is saved under the
+/// backups folder first, in case the wrong file was picked.
+#[tauri::command]
+pub async fn restore_database(state: tauri::State<'_, Arc<AppState>>, path: String) -> Result<(), String> {
+    let source = std::path::PathBuf::from(&path);
+    let profile_dir = get_profile_dir(&state);
+    get_db(&state)?.run(move |db| {
+        if let Ok(profile_dir) = profile_dir {
+            let dir = crate::backup::backup_dir(&profile_dir);
+            std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            let safety = dir.join(format!("pre-restore-{}.db", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+            db.backup_to(&safety).map_err(|e| e.to_string())?;
+        }
+        db.restore_from(&source).map_err(|e| e.to_string())
+    }).await?;
+    tracing::info!("Database restored from {}", path);
+    notify_scheduler(&state);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn save_config_file(path: String, content: String) -> Result<(), String> {
     std::fs::write(&path, content).map_err(|e| e.to_string())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_holders_keep_their_own_profile() {
+        let root = std::env::temp_dir().join(format!("routine-runner-state-{}", uuid::Uuid::new_v4()));
+        let (first, second) = (AppState::default(), AppState::default());
+        init_database(&first, &root.join("first"), None).unwrap();
+        init_database(&second, &root.join("second"), None).unwrap();
+        assert!(init_database(&first, &root.join("first"), None).is_err());
+        get_db(&first).unwrap()
+            .insert_task(&Task { id: "task".to_string(), name: "Backup".to_string(), ..Task::default() })
+            .unwrap();
+
+        let names = |tasks: Vec<TaskWithState>| tasks.into_iter().map(|t| t.task.name).collect::<Vec<_>>();
+        assert_eq!(names(list_tasks_with_state(&first, None).await.unwrap()), ["Backup"]);
+        assert!(list_tasks_with_state(&second, None).await.unwrap().is_empty());
+
+        shutdown(&first);
+        shutdown(&second);
+        assert!(get_db(&first).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
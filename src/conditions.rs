@@ -1,18 +1,108 @@
 //! Conditions module - Evaluate pre-run conditions
 
 use crate::models::Condition;
+use serde::Serialize;
 use std::process::Command;
 
-/// Evaluate all conditions for a task
-pub fn evaluate_conditions(conditions: &[Condition]) -> Result<bool, String> {
+/// Outcome of one condition with an explanation, for debugging skipped runs
+#[derive(Debug, Clone, Serialize)]
+pub struct ConditionResult {
+    pub condition: Condition,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Live readings of everything conditions can check, for the condition editor
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemSnapshot {
+    pub on_ac_power: Option<bool>,
+    pub battery_percent: Option<u8>,
+    /// Connected Wi-Fi network, if any
+    pub wifi_ssid: Option<String>,
+    /// Seconds since the last keyboard or mouse input
+    pub idle_seconds: Option<u64>,
+    pub drives: Vec<DriveSpace>,
+    pub cpu_load_percent: Option<f32>,
+}
+
+/// Space on one local drive
+#[derive(Debug, Clone, Serialize)]
+pub struct DriveSpace {
+    /// Root path, e.g. "C:\", or mount point, e.g. "/home"
+    pub drive: String,
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Longest one condition check may take; one that takes longer counts as not met
+const CONDITION_TIMEOUT_SECS: u64 = 5;
+
+/// Evaluate all conditions for a task. Checks run on the blocking pool with a timeout,
+/// so a slow one can't stall the scheduler.
+pub async fn evaluate_conditions(conditions: &[Condition]) -> Result<bool, String> {
     for condition in conditions {
-        if !evaluate_single_condition(condition)? {
+        let check = condition.clone();
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(CONDITION_TIMEOUT_SECS),
+            tokio::task::spawn_blocking(move || evaluate_single_condition(&check)),
+        )
+        .await;
+        let passed = match result {
+            Ok(joined) => joined.map_err(|e| e.to_string())??,
+            Err(_) => {
+                tracing::warn!("Condition {:?} not checked within {} seconds", condition, CONDITION_TIMEOUT_SECS);
+                false
+            }
+        };
+        if !passed {
             return Ok(false);
         }
     }
     Ok(true)
 }
 
+/// Evaluate every condition right now (without stopping at the first failure)
+pub fn test_conditions(conditions: &[Condition]) -> Vec<ConditionResult> {
+    conditions.iter()
+        .map(|condition| {
+            let (passed, detail) = explain_condition(condition)
+                .unwrap_or_else(|e| (false, format!("Check failed: {}", e)));
+            ConditionResult { condition: condition.clone(), passed, detail }
+        })
+        .collect()
+}
+
+/// Read the current system state. Takes a fraction of a second to sample CPU load.
+pub fn system_snapshot() -> SystemSnapshot {
+    SystemSnapshot {
+        on_ac_power: check_on_ac_power().ok(),
+        battery_percent: battery_percent(),
+        wifi_ssid: wifi_ssid(),
+        idle_seconds: idle_seconds(),
+        drives: drive_space(),
+        cpu_load_percent: cpu_load_percent(),
+    }
+}
+
+/// Evaluate a condition and describe what was found
+fn explain_condition(condition: &Condition) -> Result<(bool, String), String> {
+    let passed = evaluate_single_condition(condition)?;
+    let detail = match condition {
+        Condition::NetworkAvailable if passed => "Network is reachable".to_string(),
+        Condition::NetworkAvailable => "No internet connection".to_string(),
+        Condition::OnAcPower => match (passed, battery_percent()) {
+            (true, _) => "On AC power".to_string(),
+            (false, Some(percent)) => format!("On battery, at {}%", percent),
+            (false, None) => "On battery".to_string(),
+        },
+        Condition::ProcessNotRunning { process_name } if passed => format!("{} is not running", process_name),
+        Condition::ProcessNotRunning { process_name } => format!("{} is running", process_name),
+        Condition::OnlyIfPathExists => "Checked against the task's path when it runs".to_string(),
+        Condition::IdleForSeconds { .. } => "Idle time is not checked yet, so this always passes".to_string(),
+    };
+    Ok((passed, detail))
+}
+
 /// Evaluate a single condition
 fn evaluate_single_condition(condition: &Condition) -> Result<bool, String> {
     match condition {
@@ -24,22 +114,38 @@ fn evaluate_single_condition(condition: &Condition) -> Result<bool, String> {
     }
 }
 
-/// Check if network is available
+/// Check if the internet is reachable, as Windows' Network List Manager sees it (no traffic is sent);
+/// elsewhere by connecting to a public DNS server
 fn check_network_available() -> Result<bool, String> {
     #[cfg(windows)]
     {
-        // Try to check network connectivity using Windows API
-        // Simple approach: check if we can resolve DNS
-        use std::net::ToSocketAddrs;
-        match "www.google.com:80".to_socket_addrs() {
-            Ok(mut addrs) => Ok(addrs.next().is_some()),
-            Err(_) => Ok(false),
+        use windows::Win32::Networking::NetworkListManager::{INetworkListManager, NetworkListManager};
+        use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED};
+        
+        unsafe {
+            // Fails harmlessly on threads where COM is already set up differently
+            let initialized = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+            let connected = CoCreateInstance::<_, INetworkListManager>(&NetworkListManager, None, CLSCTX_ALL)
+                .and_then(|manager| manager.IsConnectedToInternet())
+                .map(|connected| connected.as_bool())
+                .map_err(|e| format!("Network List Manager unavailable: {}", e));
+            if initialized {
+                CoUninitialize();
+            }
+            connected
         }
     }
     
     #[cfg(not(windows))]
     {
-        Ok(true)
+        use std::net::{SocketAddr, TcpStream};
+        
+        // No traffic-free equivalent everywhere; a handshake with a public DNS server tells the same
+        let reachable = |address: &str| {
+            address.parse::<SocketAddr>()
+                .is_ok_and(|address| TcpStream::connect_timeout(&address, std::time::Duration::from_secs(2)).is_ok())
+        };
+        Ok(reachable("1.1.1.1:53") || reachable("8.8.8.8:53"))
     }
 }
 
@@ -64,37 +170,240 @@ fn check_on_ac_power() -> Result<bool, String> {
     
     #[cfg(not(windows))]
     {
-        Ok(true)
+        if cfg!(target_os = "macos") {
+            // First line is "Now drawing from 'AC Power'" or "... 'Battery Power'"
+            let output = Command::new("pmset").args(["-g", "batt"]).output().map_err(|e| e.to_string())?;
+            return Ok(!String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"));
+        }
+        // Machines without a mains supply listed (desktops) count as on AC unless a battery discharges
+        let online = power_supply_values("Mains", "online");
+        Ok(online.iter().any(|v| v == "1")
+            || (online.is_empty() && !power_supply_values("Battery", "status").iter().any(|s| s == "Discharging")))
     }
 }
 
-/// Check if a process is NOT running
-fn check_process_not_running(process_name: &str) -> Result<bool, String> {
+/// Remaining battery charge, if the machine reports one
+fn battery_percent() -> Option<u8> {
     #[cfg(windows)]
     {
-        // Use tasklist command to check
-        let output = Command::new("tasklist")
-            .args(["/FI", &format!("IMAGENAME eq {}", process_name)])
-            .output();
+        use windows::Win32::System::Power::GetSystemPowerStatus;
+        use windows::Win32::System::Power::SYSTEM_POWER_STATUS;
         
-        match output {
-            Ok(out) => {
-                let stdout = String::from_utf8_lossy(&out.stdout);
-                // If process is found, tasklist will show it in output
-                // If not found, it shows "INFO: No tasks are running..."
-                let is_running = stdout.to_lowercase().contains(&process_name.to_lowercase());
-                Ok(!is_running)
-            }
-            Err(_) => Ok(true), // Assume not running if we can't check
-        }
+        let mut status = SYSTEM_POWER_STATUS::default();
+        unsafe { GetSystemPowerStatus(&mut status) }.ok()?;
+        // 255 means the charge is unknown
+        (status.BatteryLifePercent <= 100).then_some(status.BatteryLifePercent)
     }
     
     #[cfg(not(windows))]
     {
-        Ok(true)
+        if cfg!(target_os = "macos") {
+            // " -InternalBattery-0 (id=1234)	85%; discharging; 4:12 remaining"
+            let output = Command::new("pmset").args(["-g", "batt"]).output().ok()?;
+            return String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .find_map(|word| word.strip_suffix("%;")?.parse().ok());
+        }
+        power_supply_values("Battery", "capacity").first()?.parse().ok()
+    }
+}
+
+/// SSID of the connected Wi-Fi network
+fn wifi_ssid() -> Option<String> {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+
+        let output = Command::new("netsh")
+            .args(["wlan", "show", "interfaces"])
+            .creation_flags(0x08000000) // CREATE_NO_WINDOW
+            .output()
+            .ok()?;
+        // Lines look like "    SSID                   : HomeWifi" (BSSID is a separate line)
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim() == "SSID")
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|ssid| !ssid.is_empty())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let ssid = if cfg!(target_os = "macos") {
+            // "Current Wi-Fi Network: HomeWifi"; en0 is the Wi-Fi port on Macs that have one
+            let output = Command::new("networksetup").args(["-getairportnetwork", "en0"]).output().ok()?;
+            String::from_utf8_lossy(&output.stdout).split_once(": ")?.1.trim().to_string()
+        } else {
+            // NetworkManager lists "yes:HomeWifi" for the connected network
+            let output = Command::new("nmcli").args(["-t", "-f", "active,ssid", "dev", "wifi"]).output().ok()?;
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .find_map(|line| line.strip_prefix("yes:"))?
+                .to_string()
+        };
+        Some(ssid).filter(|ssid| !ssid.is_empty())
+    }
+}
+
+/// Seconds since the last keyboard or mouse input in this session
+fn idle_seconds() -> Option<u64> {
+    #[cfg(windows)]
+    {
+        use windows::Win32::System::SystemInformation::GetTickCount;
+        use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+        let mut info = LASTINPUTINFO {
+            cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        if !unsafe { GetLastInputInfo(&mut info) }.as_bool() {
+            return None;
+        }
+        // Both are milliseconds since boot and wrap together every ~49 days
+        let idle_ms = unsafe { GetTickCount() }.wrapping_sub(info.dwTime);
+        Some(idle_ms as u64 / 1000)
+    }
+
+    #[cfg(not(windows))]
+    {
+        if cfg!(target_os = "macos") {
+            // `"HIDIdleTime" = 1234567890` in nanoseconds
+            let output = Command::new("ioreg").args(["-c", "IOHIDSystem"]).output().ok()?;
+            let text = String::from_utf8_lossy(&output.stdout);
+            let line = text.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+            let nanos: u64 = line.rsplit('=').next()?.trim().parse().ok()?;
+            return Some(nanos / 1_000_000_000);
+        }
+        // Only X11 sessions report it, through xprintidle (milliseconds)
+        let output = Command::new("xprintidle").output().ok()?;
+        let millis: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+        Some(millis / 1000)
+    }
+}
+
+/// Free and total space of every fixed drive
+fn drive_space() -> Vec<DriveSpace> {
+    #[cfg(windows)]
+    {
+        use windows::core::HSTRING;
+        use windows::Win32::Storage::FileSystem::{GetDiskFreeSpaceExW, GetDriveTypeW, GetLogicalDrives};
+        const DRIVE_FIXED: u32 = 3;
+
+        let mask = unsafe { GetLogicalDrives() };
+        (0..26u8)
+            .filter(|i| mask & (1 << i) != 0)
+            .map(|i| format!("{}:\\", (b'A' + i) as char))
+            .filter(|root| unsafe { GetDriveTypeW(&HSTRING::from(root.as_str())) } == DRIVE_FIXED)
+            .filter_map(|root| {
+                let (mut free, mut total) = (0u64, 0u64);
+                unsafe { GetDiskFreeSpaceExW(&HSTRING::from(root.as_str()), Some(&mut free), Some(&mut total), None) }.ok()?;
+                Some(DriveSpace { drive: root, free_bytes: free, total_bytes: total })
+            })
+            .collect()
+    }
+
+    #[cfg(not(windows))]
+    {
+        // POSIX output: "Filesystem 1024-blocks Used Available Capacity Mounted on"
+        let Ok(output) = Command::new("df").arg("-Pk").output() else { return Vec::new() };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                // Only disks, not tmpfs, network shares and the like
+                if fields.len() < 6 || !fields[0].starts_with("/dev/") {
+                    return None;
+                }
+                Some(DriveSpace {
+                    drive: fields[5..].join(" "),
+                    free_bytes: fields[3].parse::<u64>().ok()? * 1024,
+                    total_bytes: fields[1].parse::<u64>().ok()? * 1024,
+                })
+            })
+            .collect()
     }
 }
 
+/// Share of CPU time spent busy across all cores, sampled over a short window
+fn cpu_load_percent() -> Option<f32> {
+    #[cfg(windows)]
+    {
+        use windows::Win32::Foundation::FILETIME;
+        use windows::Win32::System::Threading::GetSystemTimes;
+        const SAMPLE: std::time::Duration = std::time::Duration::from_millis(250);
+
+        // (idle, kernel + user); kernel time includes idle time
+        let sample = || -> Option<(u64, u64)> {
+            let (mut idle, mut kernel, mut user) = (FILETIME::default(), FILETIME::default(), FILETIME::default());
+            unsafe { GetSystemTimes(Some(&mut idle), Some(&mut kernel), Some(&mut user)) }.ok()?;
+            let ticks = |t: FILETIME| ((t.dwHighDateTime as u64) << 32) | t.dwLowDateTime as u64;
+            Some((ticks(idle), ticks(kernel) + ticks(user)))
+        };
+        let (idle_before, total_before) = sample()?;
+        std::thread::sleep(SAMPLE);
+        let (idle_after, total_after) = sample()?;
+        let total = total_after.saturating_sub(total_before);
+        if total == 0 {
+            return None;
+        }
+        let idle = idle_after.saturating_sub(idle_before);
+        Some(100.0 * (1.0 - idle as f32 / total as f32))
+    }
+
+    #[cfg(not(windows))]
+    {
+        if cfg!(target_os = "macos") {
+            // The second sample covers the second between them: "CPU usage: 5.1% user, 10.2% sys, 84.6% idle"
+            let output = Command::new("top").args(["-l", "2", "-n", "0", "-s", "1"]).output().ok()?;
+            let text = String::from_utf8_lossy(&output.stdout);
+            let line = text.lines().rfind(|line| line.starts_with("CPU usage:"))?;
+            let idle: f32 = line.split(", ").find_map(|part| part.strip_suffix("% idle"))?.trim().parse().ok()?;
+            return Some(100.0 - idle);
+        }
+        const SAMPLE: std::time::Duration = std::time::Duration::from_millis(250);
+
+        // (idle + iowait, total) from the "cpu" line of /proc/stat, in clock ticks
+        let sample = || -> Option<(u64, u64)> {
+            let stat = std::fs::read_to_string("/proc/stat").ok()?;
+            let ticks: Vec<u64> = stat.lines().next()?
+                .strip_prefix("cpu ")?
+                .split_whitespace()
+                .filter_map(|t| t.parse().ok())
+                .collect();
+            Some((ticks.get(3)? + ticks.get(4).unwrap_or(&0), ticks.iter().sum()))
+        };
+        let (idle_before, total_before) = sample()?;
+        std::thread::sleep(SAMPLE);
+        let (idle_after, total_after) = sample()?;
+        let total = total_after.saturating_sub(total_before);
+        if total == 0 {
+            return None;
+        }
+        let idle = idle_after.saturating_sub(idle_before);
+        Some(100.0 * (1.0 - idle as f32 / total as f32))
+    }
+}
+
+/// `field` of every Linux power supply of the given type ("Mains" or "Battery")
+#[cfg(not(windows))]
+fn power_supply_values(kind: &str, field: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else { return Vec::new() };
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|dir| std::fs::read_to_string(dir.join("type")).is_ok_and(|t| t.trim() == kind))
+        .filter_map(|dir| std::fs::read_to_string(dir.join(field)).ok())
+        .map(|value| value.trim().to_string())
+        .collect()
+}
+
+/// Check if a process is NOT running
+fn check_process_not_running(process_name: &str) -> Result<bool, String> {
+    Ok(!crate::executor::check_process_running(process_name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
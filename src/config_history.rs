@@ -0,0 +1,277 @@
+//! Config history module - Keep every version of the tasks and settings, in git or as snapshots
+
+use crate::models::*;
+use crate::storage::Database;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// File committed to the git repository
+const CONFIG_FILE: &str = "config.json";
+
+/// Prefix of snapshot files ("config-YYYYMMDD-HHMMSS.json")
+const SNAPSHOT_PREFIX: &str = "config-";
+
+/// How often the configuration is checked for changes
+const CHECK_INTERVAL_SECS: u64 = 10;
+
+/// Directory holding the history under the profile dir
+pub fn history_dir(profile_dir: &Path) -> PathBuf {
+    profile_dir.join("config-history")
+}
+
+/// What is versioned; secrets are masked
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ConfigSnapshot {
+    pub tasks: Vec<Task>,
+    pub settings: Settings,
+}
+
+/// One saved version
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConfigVersion {
+    /// Commit hash or snapshot file name
+    pub id: String,
+    pub saved_at_utc: DateTime<Utc>,
+    pub message: String,
+}
+
+/// A task that differs between two versions
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConfigTaskChange {
+    pub task_id: String,
+    pub task_name: String,
+    /// Changed fields as `{ "field": { "old": ..., "new": ... } }`
+    pub diff: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Differences between two versions
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ConfigDiff {
+    pub added: Vec<ConfigTaskChange>,
+    pub removed: Vec<ConfigTaskChange>,
+    pub changed: Vec<ConfigTaskChange>,
+    /// Changed settings as `{ "field": { "old": ..., "new": ... } }`
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ConfigDiff {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && self.settings.is_empty()
+    }
+
+    /// One-line description, used as the commit message
+    fn summary(&self) -> String {
+        let names = |changes: &[ConfigTaskChange]| {
+            changes.iter().map(|c| c.task_name.as_str()).collect::<Vec<_>>().join(", ")
+        };
+        let mut parts = Vec::new();
+        if !self.added.is_empty() {
+            parts.push(format!("added {}", names(&self.added)));
+        }
+        if !self.changed.is_empty() {
+            parts.push(format!("changed {}", names(&self.changed)));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("removed {}", names(&self.removed)));
+        }
+        if !self.settings.is_empty() {
+            parts.push("changed settings".to_string());
+        }
+        if parts.is_empty() { "Initial configuration".to_string() } else { parts.join("; ") }
+    }
+}
+
+fn current_snapshot(db: &Database) -> Result<ConfigSnapshot, String> {
+    let tasks = db.get_all_tasks().map_err(|e| e.to_string())?;
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    Ok(ConfigSnapshot {
+        tasks: tasks.into_iter().map(crate::secrets::mask_task).collect(),
+        settings: crate::secrets::mask_settings(settings),
+    })
+}
+
+/// Field-level differences between two versions
+pub fn diff(from: &ConfigSnapshot, to: &ConfigSnapshot) -> ConfigDiff {
+    let old: HashMap<&str, &Task> = from.tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+    let new: HashMap<&str, &Task> = to.tasks.iter().map(|t| (t.id.as_str(), t)).collect();
+    let change = |task: &Task, diff| ConfigTaskChange {
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        diff,
+    };
+
+    let mut result = ConfigDiff::default();
+    for task in &to.tasks {
+        match old.get(task.id.as_str()) {
+            None => result.added.push(change(task, task_diff(None, task))),
+            Some(before) => {
+                let fields = task_diff(Some(before), task);
+                if !fields.is_empty() {
+                    result.changed.push(change(task, fields));
+                }
+            }
+        }
+    }
+    for task in from.tasks.iter().filter(|t| !new.contains_key(t.id.as_str())) {
+        result.removed.push(change(task, serde_json::Map::new()));
+    }
+
+    let old_settings = serde_json::to_value(&from.settings).unwrap_or_default();
+    if let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(&to.settings) {
+        for (field, value) in fields {
+            let previous = old_settings.get(&field).cloned().unwrap_or(serde_json::Value::Null);
+            if previous != value {
+                result.settings.insert(field, serde_json::json!({ "old": previous, "new": value }));
+            }
+        }
+    }
+    result
+}
+
+/// Save a new version if the configuration changed since the last one.
+/// Returns the new version's id.
+pub fn save_if_changed(db: &Database, dir: &Path, mode: ConfigHistoryMode) -> Result<Option<String>, String> {
+    let snapshot = current_snapshot(db)?;
+    let previous = latest(dir, mode)?;
+    let changes = match &previous {
+        Some(previous) => {
+            let changes = diff(previous, &snapshot);
+            if changes.is_empty() {
+                return Ok(None);
+            }
+            changes
+        }
+        None => ConfigDiff::default(),
+    };
+
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+    let id = match mode {
+        ConfigHistoryMode::Off => return Ok(None),
+        ConfigHistoryMode::Git => {
+            if !dir.join(".git").exists() {
+                git(dir, &["init", "--quiet"])?;
+            }
+            std::fs::write(dir.join(CONFIG_FILE), json).map_err(|e| e.to_string())?;
+            git(dir, &["add", CONFIG_FILE])?;
+            git(dir, &[
+                "-c", "user.name=Routine Runner",
+                "-c", "user.email=routine-runner@localhost",
+                "commit", "--quiet", "-m", &changes.summary(),
+            ])?;
+            git(dir, &["rev-parse", "HEAD"])?.trim().to_string()
+        }
+        ConfigHistoryMode::Snapshots => {
+            let name = format!("{}{}.json", SNAPSHOT_PREFIX, Utc::now().format("%Y%m%d-%H%M%S"));
+            std::fs::write(dir.join(&name), json).map_err(|e| e.to_string())?;
+            name
+        }
+    };
+    tracing::info!("Saved configuration version {}: {}", id, changes.summary());
+    Ok(Some(id))
+}
+
+/// Saved versions, newest first
+pub fn list_versions(dir: &Path, mode: ConfigHistoryMode) -> Result<Vec<ConfigVersion>, String> {
+    match mode {
+        ConfigHistoryMode::Off => Ok(vec![]),
+        ConfigHistoryMode::Git => {
+            if !dir.join(".git").exists() {
+                return Ok(vec![]);
+            }
+            let log = git(dir, &["log", "--format=%H%x09%cI%x09%s", "--", CONFIG_FILE])?;
+            Ok(log.lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(3, '\t');
+                    Some(ConfigVersion {
+                        id: parts.next()?.to_string(),
+                        saved_at_utc: DateTime::parse_from_rfc3339(parts.next()?).ok()?.with_timezone(&Utc),
+                        message: parts.next().unwrap_or_default().to_string(),
+                    })
+                })
+                .collect())
+        }
+        ConfigHistoryMode::Snapshots => {
+            let Ok(entries) = std::fs::read_dir(dir) else { return Ok(vec![]) };
+            let mut versions: Vec<ConfigVersion> = entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter_map(|name| {
+                    let stamp = name.strip_prefix(SNAPSHOT_PREFIX)?.strip_suffix(".json")?;
+                    let saved = chrono::NaiveDateTime::parse_from_str(stamp, "%Y%m%d-%H%M%S").ok()?;
+                    Some(ConfigVersion { saved_at_utc: saved.and_utc(), message: String::new(), id: name })
+                })
+                .collect();
+            // Names embed the time, so they sort oldest first
+            versions.sort_by(|a, b| b.id.cmp(&a.id));
+            Ok(versions)
+        }
+    }
+}
+
+/// Read a saved version by id
+pub fn load_version(dir: &Path, mode: ConfigHistoryMode, id: &str) -> Result<ConfigSnapshot, String> {
+    let json = match mode {
+        ConfigHistoryMode::Off => return Err("Configuration history is off".to_string()),
+        ConfigHistoryMode::Git => {
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Not a commit id: {}", id));
+            }
+            git(dir, &["show", &format!("{}:{}", id, CONFIG_FILE)])?
+        }
+        ConfigHistoryMode::Snapshots => {
+            if !id.starts_with(SNAPSHOT_PREFIX) || id.contains(['/', '\\']) {
+                return Err(format!("Not a snapshot: {}", id));
+            }
+            std::fs::read_to_string(dir.join(id)).map_err(|e| e.to_string())?
+        }
+    };
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+fn latest(dir: &Path, mode: ConfigHistoryMode) -> Result<Option<ConfigSnapshot>, String> {
+    match list_versions(dir, mode)?.first() {
+        Some(version) => load_version(dir, mode, &version.id).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Run git in the history directory, returning its output
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(args);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let output = cmd.output().map_err(|e| format!("Failed to start git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Background job: save a version whenever the configuration changes, while history is on
+pub async fn history_loop(db: Arc<Database>, profile_dir: PathBuf) {
+    let dir = history_dir(&profile_dir);
+
+    loop {
+        match db.get_settings() {
+            Ok(settings) if settings.config_history_mode != ConfigHistoryMode::Off => {
+                let dir = dir.clone();
+                let mode = settings.config_history_mode;
+                if let Err(e) = db.run(move |db| save_if_changed(db, &dir, mode)).await {
+                    tracing::error!("Failed to save configuration history: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to read settings for configuration history: {}", e),
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+    }
+}
@@ -0,0 +1,92 @@
+//! Crash module - Write a report for every panic, so a dead engine leaves a trace to send in
+
+use chrono::Local;
+use std::path::{Path, PathBuf};
+
+/// Prefix of report files ("crash-YYYYMMDD-HHMMSS.txt")
+const REPORT_PREFIX: &str = "crash-";
+
+/// Reports kept; the oldest are deleted as new ones are written
+const REPORTS_KEPT: usize = 20;
+
+/// Log events included as the last actions before the panic
+const LAST_EVENTS: usize = 50;
+
+/// Directory holding the crash reports under the app data dir
+pub fn crash_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("crashes")
+}
+
+/// Write a crash report for each panic, then carry on with the default hook
+pub fn install_panic_hook(data_dir: Option<PathBuf>) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current().name().unwrap_or("unnamed").to_string();
+        let message = info.payload()
+            .downcast_ref::<&str>()
+            .map(|m| m.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+        tracing::error!("Panic in thread {} at {}: {}", thread, location, message);
+
+        if let Some(dir) = data_dir.as_deref().map(crash_dir) {
+            match write_report(&dir, &thread, &message, &location) {
+                Ok(path) => tracing::error!("Crash report written to {}", path.display()),
+                Err(e) => tracing::error!("Failed to write crash report: {}", e),
+            }
+        }
+        previous(info);
+    }));
+}
+
+fn write_report(dir: &Path, thread: &str, message: &str, location: &str) -> std::io::Result<PathBuf> {
+    let now = Local::now();
+    let mut report = format!(
+        "Routine Runner {} crashed at {}\nOS: {} {}\nThread: {}\nPanic: {}\nLocation: {}\n\nBacktrace:\n{}\n\nLast actions:\n",
+        env!("CARGO_PKG_VERSION"),
+        now.to_rfc3339(),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        thread,
+        message,
+        location,
+        std::backtrace::Backtrace::force_capture(),
+    );
+    for event in crate::logging::last_events(LAST_EVENTS) {
+        report.push_str(&format!(
+            "{} {:5} {}: {}\n",
+            event.time_utc.to_rfc3339(),
+            event.level,
+            event.target,
+            event.message,
+        ));
+    }
+
+    std::fs::create_dir_all(dir)?;
+    // Several threads can panic in the same second
+    let mut path = dir.join(format!("{}{}.txt", REPORT_PREFIX, now.format("%Y%m%d-%H%M%S")));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("{}{}-{}.txt", REPORT_PREFIX, now.format("%Y%m%d-%H%M%S"), n));
+    }
+    std::fs::write(&path, report)?;
+    prune(dir);
+    Ok(path)
+}
+
+/// Delete all but the newest reports
+fn prune(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut reports: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with(REPORT_PREFIX)))
+        .collect();
+    // Names embed the time, so they sort oldest first
+    reports.sort();
+    for path in reports.iter().take(reports.len().saturating_sub(REPORTS_KEPT)) {
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,201 @@
+//! Data location module - Where databases, backups, logs and crash reports are kept,
+//! and moving them to another folder, e.g. off a small system drive
+
+use crate::storage::Database;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The default data directory is named after the Tauri identifier (see tauri.conf.json)
+const APP_IDENTIFIER: &str = "com.autoopen.app";
+
+/// File in the default directory naming the chosen data directory
+const LOCATION_FILE: &str = "data_location";
+
+/// Bytes compared at a time when checking a copy
+const COMPARE_CHUNK: usize = 64 * 1024;
+
+/// Directory the data is kept in unless another one was chosen
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+/// The chosen data directory, if any
+fn saved_dir() -> Option<PathBuf> {
+    let text = std::fs::read_to_string(default_dir()?.join(LOCATION_FILE)).ok()?;
+    let dir = PathBuf::from(text.trim());
+    dir.is_absolute().then_some(dir)
+}
+
+/// The data directory in use: the chosen one, or the default
+pub fn current() -> Option<PathBuf> {
+    match saved_dir() {
+        Some(dir) if dir.is_dir() => Some(dir),
+        // E.g. a drive that isn't plugged in; runs before logging is set up
+        Some(dir) => {
+            eprintln!("Data directory {} is missing, using the default one", dir.display());
+            default_dir()
+        }
+        None => default_dir(),
+    }
+}
+
+/// Whether the location was settled before: chosen, or already holding a database
+pub fn is_settled() -> bool {
+    saved_dir().is_some()
+        || default_dir().is_some_and(|dir| dir.join(crate::profiles::DATABASE_FILE).exists())
+}
+
+/// Use `dir` for the data from the next start on
+pub fn save(dir: &Path) -> Result<(), String> {
+    let default = default_dir().ok_or("No application data directory")?;
+    std::fs::create_dir_all(&default).map_err(|e| e.to_string())?;
+    std::fs::write(default.join(LOCATION_FILE), dir.to_string_lossy().as_bytes()).map_err(|e| e.to_string())
+}
+
+/// Check that the data in `from` can move to `target`: a full path outside it, empty if it exists
+pub fn validate_target(from: &Path, target: &Path) -> Result<(), String> {
+    if !target.is_absolute() {
+        return Err("Choose a full folder path for the data".to_string());
+    }
+    if target.starts_with(from) || from.starts_with(target) {
+        return Err("The new data folder can't be inside the current one or contain it".to_string());
+    }
+    if target.exists() {
+        let mut entries = std::fs::read_dir(target).map_err(|e| format!("Can't use {}: {}", target.display(), e))?;
+        if entries.next().is_some() {
+            return Err(format!("{} is not empty", target.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Copy everything in `from` to `to`, then check each copy. The open database at `db_path`
+/// is copied with SQLite's backup API, so it stays consistent. Returns how many files were
+/// copied; on failure `to` is left empty again.
+pub fn migrate(db: &Database, db_path: &Path, from: &Path, to: &Path) -> Result<usize, String> {
+    let existed = to.exists();
+    let result = copy_and_verify(db, db_path, from, to);
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(to);
+        if existed {
+            let _ = std::fs::create_dir_all(to);
+        }
+    }
+    result
+}
+
+fn copy_and_verify(db: &Database, db_path: &Path, from: &Path, to: &Path) -> Result<usize, String> {
+    // The open database and its journal files are copied through the backup API instead
+    let skip: Vec<PathBuf> = ["", "-wal", "-shm", "-journal"]
+        .iter()
+        .map(|suffix| PathBuf::from(format!("{}{}", db_path.display(), suffix)))
+        .collect();
+    let mut copied = Vec::new();
+    copy_dir(from, to, &skip, &mut copied)?;
+
+    let relative = db_path.strip_prefix(from).map_err(|_| "The database is outside the data folder".to_string())?;
+    let db_copy = to.join(relative);
+    if let Some(parent) = db_copy.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    db.backup_to(&db_copy).map_err(|e| format!("Failed to copy the database: {}", e))?;
+
+    for (source, copy, len) in &copied {
+        verify_copy(source, copy, *len)?;
+    }
+    // Checking the search index needs write access
+    let problems = Database::open(&db_copy)
+        .and_then(|copy| copy.integrity_check())
+        .map_err(|e| format!("Failed to check the copied database: {}", e))?;
+    if !problems.is_empty() {
+        return Err(format!("The copied database is damaged: {}", problems.join("; ")));
+    }
+    tracing::info!("Copied {} files from {} to {}", copied.len() + 1, from.display(), to.display());
+    Ok(copied.len() + 1)
+}
+
+/// Copy a directory tree, collecting each file's source, copy and length
+fn copy_dir(from: &Path, to: &Path, skip: &[PathBuf], copied: &mut Vec<(PathBuf, PathBuf, u64)>) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+    let entries = std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let source = entry.path();
+        if skip.contains(&source) || entry.file_name() == LOCATION_FILE {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            copy_dir(&source, &target, skip, copied)?;
+        } else {
+            let len = std::fs::copy(&source, &target)
+                .map_err(|e| format!("Failed to copy {}: {}", source.display(), e))?;
+            copied.push((source, target, len));
+        }
+    }
+    Ok(())
+}
+
+/// Check a copy is `len` bytes matching the start of its source, which may have grown
+/// since (the current log file)
+fn verify_copy(source: &Path, copy: &Path, len: u64) -> Result<(), String> {
+    let mismatch = || format!("The copy of {} doesn't match the original", source.display());
+    let open = |path: &Path| std::fs::File::open(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+    if std::fs::metadata(copy).map_err(|e| e.to_string())?.len() != len {
+        return Err(mismatch());
+    }
+
+    let mut original = open(source)?.take(len);
+    let mut copy = open(copy)?;
+    let (mut expected, mut actual) = (vec![0u8; COMPARE_CHUNK], vec![0u8; COMPARE_CHUNK]);
+    loop {
+        let n = read_chunk(&mut original, &mut expected).map_err(|e| e.to_string())?;
+        let m = read_chunk(&mut copy, &mut actual).map_err(|e| e.to_string())?;
+        if expected[..n] != actual[..m] {
+            return Err(mismatch());
+        }
+        if n == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Fill `buf` as far as the reader goes, returning how much was read
+fn read_chunk(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_copies_and_verifies() {
+        let root = std::env::temp_dir().join(format!("routine-runner-move-{}", uuid::Uuid::new_v4()));
+        let (from, to) = (root.join("old"), root.join("new"));
+        std::fs::create_dir_all(from.join("logs")).unwrap();
+        std::fs::write(from.join("logs").join("routine-runner.2026-01-01.log"), "started\n").unwrap();
+        let db_path = from.join(crate::profiles::DATABASE_FILE);
+        let db = Database::open(&db_path).unwrap();
+
+        assert!(validate_target(&from, &from.join("nested")).is_err());
+        assert!(validate_target(&from, Path::new("relative")).is_err());
+        validate_target(&from, &to).unwrap();
+
+        assert_eq!(migrate(&db, &db_path, &from, &to).unwrap(), 2);
+        assert_eq!(std::fs::read_to_string(to.join("logs").join("routine-runner.2026-01-01.log")).unwrap(), "started\n");
+        let copy = Database::open(&to.join(crate::profiles::DATABASE_FILE)).unwrap();
+        assert!(copy.integrity_check().unwrap().is_empty());
+        assert!(validate_target(&from, &to).is_err());
+
+        drop((db, copy));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,81 @@
+//! Deep link module - routine-runner:// URLs that fire tasks from other apps
+
+#[cfg(windows)]
+use winreg::enums::*;
+#[cfg(windows)]
+use winreg::RegKey;
+
+/// URL scheme handled by the app
+pub const SCHEME: &str = "routine-runner";
+
+#[cfg(windows)]
+const REGISTRY_KEY: &str = r"Software\Classes\routine-runner";
+
+/// What a deep link asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeepLink {
+    /// `routine-runner://run/<task-id>`
+    Run { task_id: String },
+}
+
+/// Parse a deep link URL. Tasks are only addressed by id, so a web page cannot guess a target by name.
+pub fn parse(url: &str) -> Option<DeepLink> {
+    let prefix = format!("{}://", SCHEME);
+    let rest = url.get(..prefix.len())
+        .filter(|p| p.eq_ignore_ascii_case(&prefix))
+        .map(|_| &url[prefix.len()..])?;
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+
+    match parts.as_slice() {
+        [action, id] if action.eq_ignore_ascii_case("run") && uuid::Uuid::parse_str(id).is_ok() => {
+            Some(DeepLink::Run { task_id: id.to_string() })
+        }
+        _ => None,
+    }
+}
+
+/// Hand a deep link to the running instance. Returns false if no instance is running.
+pub fn forward(link: &DeepLink) -> bool {
+    let DeepLink::Run { task_id } = link;
+    let request = crate::ipc::IpcRequest::Run { task: task_id.clone() };
+    match crate::ipc::send(&request) {
+        Some(Ok(_)) => {
+            tracing::info!("Forwarded deep link run of {} to the running instance", task_id);
+            true
+        }
+        Some(Err(e)) => {
+            tracing::error!("Running instance failed to run {}: {}", task_id, e);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Register the scheme for the current user, pointing at this executable
+pub fn register() -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        let exe_path = std::env::current_exe()
+            .map_err(|e| format!("Failed to get exe path: {}", e))?;
+
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let (key, _) = hkcu
+            .create_subkey_with_flags(REGISTRY_KEY, KEY_WRITE)
+            .map_err(|e| format!("Failed to create registry key: {}", e))?;
+        let (command, _) = key
+            .create_subkey_with_flags(r"shell\open\command", KEY_WRITE)
+            .map_err(|e| format!("Failed to create registry key: {}", e))?;
+
+        key.set_value("", &"URL:Routine Runner")
+            .and_then(|_| key.set_value("URL Protocol", &""))
+            .and_then(|_| command.set_value("", &format!("\"{}\" \"%1\"", exe_path.display())))
+            .map_err(|e| format!("Failed to set registry value: {}", e))?;
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    {
+        Err("URL schemes are only registered on Windows".to_string())
+    }
+}
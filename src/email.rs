@@ -0,0 +1,136 @@
+//! Email module - SMTP alerts for failing tasks, sent right away or as an hourly digest
+
+use crate::http::MailServer;
+use crate::models::*;
+use crate::storage::Database;
+use base64::Engine;
+use chrono::Local;
+use std::sync::Arc;
+
+/// How often queued alerts are sent in digest mode
+pub const DIGEST_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Most output characters quoted in an alert (the end of the output is kept)
+const OUTPUT_SNIPPET_CHARS: usize = 2000;
+
+/// Whether a failed run with `streak` failures in a row (itself included) should alert
+pub fn should_alert(email: &EmailSettings, log: &RunLog, streak: u32) -> bool {
+    if !email.enabled || email.to.is_empty() || log.status != RunStatus::Failed {
+        return false;
+    }
+    // Alert once when the streak reaches the threshold, not on every failure after it
+    match email.after_consecutive_failures {
+        0 | 1 => true,
+        threshold => streak == threshold,
+    }
+}
+
+/// Subject and body of the alert for a failed run
+pub fn alert_text(log: &RunLog, streak: u32) -> (String, String) {
+    let subject = if streak > 1 {
+        format!("[Routine Runner] {} failed {} times in a row", log.task_name, streak)
+    } else {
+        format!("[Routine Runner] {} failed", log.task_name)
+    };
+
+    let mut body = format!(
+        "Task: {}\nStarted: {}\nExit code: {}\n",
+        log.task_name,
+        log.started_at_utc.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        log.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string()),
+    );
+    if let Some(error) = log.error_message.as_deref().filter(|e| !e.is_empty()) {
+        body.push_str(&format!("Error: {}\n", error));
+    }
+    if let Some(output) = log.output.as_deref().map(str::trim).filter(|o| !o.is_empty()) {
+        let count = output.chars().count();
+        let tail: String = output.chars().skip(count.saturating_sub(OUTPUT_SNIPPET_CHARS)).collect();
+        body.push_str(&format!("\nOutput:\n{}\n", tail));
+    }
+    (subject, body)
+}
+
+/// One email combining the queued alerts
+pub fn digest_text(alerts: &[QueuedEmailAlert]) -> (String, String) {
+    let subject = format!("[Routine Runner] {} alert(s)", alerts.len());
+    let body = alerts.iter()
+        .map(|alert| format!(
+            "== {} ({}) ==\n{}",
+            alert.subject,
+            alert.queued_at_utc.with_timezone(&Local).format("%H:%M"),
+            alert.body,
+        ))
+        .collect::<Vec<_>>()
+        .join("\n");
+    (subject, body)
+}
+
+/// Send an email with the configured server (password already decrypted). Blocks until sent.
+pub fn send(email: &EmailSettings, subject: &str, body: &str) -> Result<(), String> {
+    if email.smtp_host.trim().is_empty() || email.to.is_empty() {
+        return Err("SMTP server or recipients are not configured".to_string());
+    }
+    let scheme = if email.security == SmtpSecurity::Tls { "smtps" } else { "smtp" };
+    let server = MailServer {
+        url: format!("{}://{}:{}", scheme, email.smtp_host.trim(), email.smtp_port),
+        require_tls: email.security == SmtpSecurity::StartTls,
+        username: &email.username,
+        password: &email.password,
+    };
+    crate::http::send_mail(&server, &email.from, &email.to, &format_message(email, subject, body))
+}
+
+/// RFC 5322 message with a UTF-8 subject and body
+fn format_message(email: &EmailSettings, subject: &str, body: &str) -> String {
+    let encoder = base64::engine::general_purpose::STANDARD;
+    let encoded_body = encoder.encode(body.replace('\n', "\r\n"));
+    // Base64 lines must not exceed 76 characters
+    let wrapped: Vec<&str> = encoded_body.as_bytes()
+        .chunks(76)
+        .map(|line| std::str::from_utf8(line).unwrap_or_default())
+        .collect();
+
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        email.from,
+        email.to.join(", "),
+        encoder.encode(subject),
+        chrono::Utc::now().to_rfc2822(),
+        wrapped.join("\r\n"),
+    )
+}
+
+/// Send the queued alerts as one email every hour while email alerts are enabled
+pub async fn digest_loop(db: Arc<Database>) {
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(DIGEST_INTERVAL_SECS)).await;
+
+        let settings = db.get_settings()
+            .map_err(|e| e.to_string())
+            .and_then(|settings| crate::secrets::reveal_settings(&settings));
+        match settings {
+            Ok(settings) if settings.email.enabled => {
+                if let Err(e) = send_digest(&db, settings.email).await {
+                    tracing::error!("Failed to send email digest: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to read settings for email digest: {}", e),
+        }
+    }
+}
+
+/// Send and clear the queued alerts; they stay queued if sending fails
+async fn send_digest(db: &Arc<Database>, email: EmailSettings) -> Result<(), String> {
+    let alerts = db.run(|db| db.get_queued_email_alerts()).await.map_err(|e| e.to_string())?;
+    let Some(last_id) = alerts.last().map(|a| a.id) else { return Ok(()) };
+
+    let (subject, body) = digest_text(&alerts);
+    tokio::task::spawn_blocking(move || send(&email, &subject, &body))
+        .await
+        .map_err(|e| e.to_string())??;
+    db.run(move |db| db.delete_queued_email_alerts(last_id)).await.map_err(|e| e.to_string())?;
+    tracing::info!("Sent email digest with {} alert(s)", alerts.len());
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Event log module - Record run results in the Windows Application event log
+
+use crate::models::*;
+
+/// Event source the entries are written under
+pub const EVENT_SOURCE: &str = "Routine Runner";
+
+/// Event ids; the registered message file maps ids 1-1000 to the entry's text
+const EVENT_ID_SUCCESS: u32 = 1;
+const EVENT_ID_FAILURE: u32 = 2;
+
+#[cfg(windows)]
+const SOURCE_KEY: &str = r"SYSTEM\CurrentControlSet\Services\EventLog\Application\Routine Runner";
+
+/// Whether this run should be written to the event log
+pub fn should_log(settings: &Settings, log: &RunLog) -> bool {
+    settings.event_log_enabled
+        && match log.status {
+            RunStatus::Failed => true,
+            RunStatus::Success => settings.event_log_successes,
+            _ => false,
+        }
+}
+
+/// Text of the event for a run
+pub fn event_text(log: &RunLog) -> String {
+    let mut text = format!(
+        "Task: {}\r\nTask id: {}\r\nRun id: {}\r\nStatus: {:?}\r\nExit code: {}",
+        log.task_name,
+        log.task_id,
+        log.run_id,
+        log.status,
+        log.exit_code.map(|c| c.to_string()).unwrap_or_else(|| "-".to_string()),
+    );
+    if let Some(error) = log.error_message.as_deref().filter(|e| !e.is_empty()) {
+        text.push_str(&format!("\r\nError: {}", error));
+    }
+    text
+}
+
+/// Write a run's result as an error (failure) or information (success) event
+pub fn write_run_event(log: &RunLog) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use windows::core::{HSTRING, PCWSTR};
+        use windows::Win32::Foundation::PSID;
+        use windows::Win32::System::EventLog::{
+            DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+            EVENTLOG_INFORMATION_TYPE,
+        };
+
+        let (kind, event_id) = if log.status == RunStatus::Failed {
+            (EVENTLOG_ERROR_TYPE, EVENT_ID_FAILURE)
+        } else {
+            (EVENTLOG_INFORMATION_TYPE, EVENT_ID_SUCCESS)
+        };
+        let text = HSTRING::from(event_text(log));
+        unsafe {
+            let source = RegisterEventSourceW(PCWSTR::null(), &HSTRING::from(EVENT_SOURCE))
+                .map_err(|e| e.to_string())?;
+            let result = ReportEventW(
+                source,
+                kind,
+                0,
+                event_id,
+                PSID::default(),
+                0,
+                Some(&[PCWSTR(text.as_ptr())]),
+                None,
+            );
+            let _ = DeregisterEventSource(source);
+            result.map_err(|e| e.to_string())
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (log, EVENT_ID_SUCCESS, EVENT_ID_FAILURE);
+        Err("The event log is only available on Windows".to_string())
+    }
+}
+
+/// Whether the event source is registered, so Event Viewer can show the entries' text
+pub fn is_source_registered() -> bool {
+    #[cfg(windows)]
+    {
+        use winreg::enums::HKEY_LOCAL_MACHINE;
+        winreg::RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(SOURCE_KEY).is_ok()
+    }
+
+    #[cfg(not(windows))]
+    {
+        false
+    }
+}
+
+/// Register the event source under HKLM. Needs administrator rights, once per machine.
+pub fn register_source() -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_WRITE};
+
+        let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| r"C:\Windows".to_string());
+        let (key, _) = winreg::RegKey::predef(HKEY_LOCAL_MACHINE)
+            .create_subkey_with_flags(SOURCE_KEY, KEY_WRITE)
+            .map_err(|e| format!("Failed to register event source (run as administrator once): {}", e))?;
+        // EventCreate.exe's message table prints the entry's text as-is
+        key.set_value("EventMessageFile", &format!(r"{}\System32\EventCreate.exe", system_root))
+            .and_then(|_| key.set_value("TypesSupported", &7u32))
+            .map_err(|e| format!("Failed to register event source: {}", e))?;
+        tracing::info!("Registered event log source {}", EVENT_SOURCE);
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    {
+        Err("The event log is only available on Windows".to_string())
+    }
+}
@@ -1,36 +1,125 @@
 //! Executor module - Execute tasks (open files, run apps, etc.)
 
 use crate::models::*;
-use std::process::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
 use thiserror::Error;
 
+/// Process last launched for each Exe task, by task id
+static LAUNCHED: Mutex<BTreeMap<String, Launch>> = Mutex::new(BTreeMap::new());
+
+/// A launched process, identified by PID and creation time since PIDs get reused
+#[derive(Debug, Clone, Copy)]
+struct Launch {
+    pid: u32,
+    /// Creation time as a FILETIME value, None if it couldn't be read
+    started: Option<u64>,
+}
+
+impl Launch {
+    /// Whether the very process we launched is still running
+    fn is_alive(&self) -> bool {
+        self.started.is_some() && process_started_at(self.pid) == self.started
+    }
+}
+
+/// Displays in English; `message()` gives the id for other languages
 #[derive(Error, Debug)]
 pub enum ExecutorError {
-    #[error("Path không tồn tại: {0}")]
+    #[error("{}", self.message())]
     PathNotFound(String),
     
-    #[error("Không thể mở: {0}")]
+    #[error("{}", self.message())]
     OpenFailed(String),
     
-    #[error("Process timeout sau {0} giây")]
+    #[error("{}", self.message())]
     Timeout(u32),
     
-    #[error("Exit code {0} không nằm trong danh sách success")]
+    #[error("{}", self.message())]
     ExitCodeFailed(i32),
     
-    #[error("IO error: {0}")]
+    #[error("{}", self.message())]
+    KillFailed(String),
+    
+    #[error("{}", self.message())]
+    Secret(String),
+    
+    #[error("{}", self.message())]
     IoError(#[from] std::io::Error),
 }
 
+impl ExecutorError {
+    pub fn message(&self) -> Message {
+        match self {
+            ExecutorError::PathNotFound(path) => Message::new(MessageId::PathNotFound, &[path]),
+            ExecutorError::OpenFailed(detail) => Message::new(MessageId::OpenFailed, &[detail]),
+            ExecutorError::Timeout(secs) => Message::new(MessageId::Timeout, &[secs]),
+            ExecutorError::ExitCodeFailed(code) => Message::new(MessageId::ExitCodeFailed, &[code]),
+            ExecutorError::KillFailed(detail) => Message::new(MessageId::KillFailed, &[detail]),
+            ExecutorError::Secret(detail) => Message::new(MessageId::SecretFailed, &[detail]),
+            ExecutorError::IoError(e) => Message::new(MessageId::IoError, &[e]),
+        }
+    }
+}
+
+/// Which pipe a line of captured output came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Receives each line of captured output as the process writes it
+pub type OutputSink<'a> = dyn Fn(OutputStream, &str) + Sync + 'a;
+
 pub struct ExecutionResult {
     pub success: bool,
     pub exit_code: Option<i32>,
-    pub error_message: Option<String>,
+    pub error: Option<Message>,
     pub output: Option<String>,
+    /// Handle of a launched process we did not wait for
+    pub child: Option<std::process::Child>,
 }
 
 /// Execute a task
 pub fn execute_task(task: &Task) -> Result<ExecutionResult, ExecutorError> {
+    execute_task_with(task, &RunOverrides::default())
+}
+
+/// Execute a task with one-off argument/environment overrides
+pub fn execute_task_with(task: &Task, overrides: &RunOverrides) -> Result<ExecutionResult, ExecutorError> {
+    execute_task_streamed(task, overrides, &|_, _| {})
+}
+
+/// Execute a task, passing captured output lines to `on_output` while it runs
+pub fn execute_task_streamed(
+    task: &Task,
+    overrides: &RunOverrides,
+    on_output: &OutputSink<'_>,
+) -> Result<ExecutionResult, ExecutorError> {
+    // Secrets are only decrypted here, and scrubbed from whatever the run reports
+    let task = crate::secrets::reveal_task(task).map_err(ExecutorError::Secret)?;
+    let secrets = crate::secrets::secret_values(&task);
+    let task = &Task {
+        args: overrides.args.clone().or_else(|| task.args.clone()),
+        ..task
+    };
+    
+    let on_output = |stream: OutputStream, line: &str| on_output(stream, &crate::secrets::redact(line, &secrets));
+    let mut result = run_target(task, overrides, &on_output)?;
+    if let Some(error) = &mut result.error {
+        for param in &mut error.params {
+            *param = crate::secrets::redact(param, &secrets);
+        }
+    }
+    result.output = result.output.map(|o| crate::secrets::redact(&o, &secrets));
+    Ok(result)
+}
+
+/// Launch the task's target
+fn run_target(task: &Task, overrides: &RunOverrides, on_output: &OutputSink<'_>) -> Result<ExecutionResult, ExecutorError> {
     tracing::info!("Executing task: {} (type: {:?}, path: {})", task.name, task.target_type, task.path_or_url);
     
     // Check if path exists (for file-based targets)
@@ -43,7 +132,7 @@ pub fn execute_task(task: &Task) -> Result<ExecutionResult, ExecutorError> {
     // Handle if_running_action for EXE type
     if matches!(task.target_type, TargetType::Exe) {
         let process_name = get_process_name(&task.path_or_url);
-        let is_running = is_process_running(&process_name);
+        let is_running = is_task_process_running(task);
         
         if is_running {
             match task.if_running_action {
@@ -52,13 +141,16 @@ pub fn execute_task(task: &Task) -> Result<ExecutionResult, ExecutorError> {
                     return Ok(ExecutionResult {
                         success: true,
                         exit_code: None,
-                        error_message: Some(format!("Skipped - {} already running", process_name)),
+                        error: Some(Message::new(MessageId::AlreadyRunning, &[&process_name])),
                         output: None,
+                        child: None,
                     });
                 }
                 IfRunningAction::Restart => {
                     tracing::info!("Task {} - killing existing {} before restart", task.name, process_name);
-                    kill_process(&process_name);
+                    if let Err(e) = kill_task_process(task) {
+                        tracing::warn!("Failed to stop {} for task {}: {}", process_name, task.name, e);
+                    }
                     // Wait a bit for process to fully close
                     std::thread::sleep(std::time::Duration::from_millis(500));
                 }
@@ -71,13 +163,53 @@ pub fn execute_task(task: &Task) -> Result<ExecutionResult, ExecutorError> {
     }
 
     match task.target_type {
-        TargetType::Exe => execute_exe(task),
+        TargetType::Exe => {
+            let mut env: HashMap<String, String> = task.env.iter()
+                .map(|v| (v.name.clone(), v.value.clone()))
+                .collect();
+            env.extend(overrides.env.clone());
+            execute_exe(task, &env, on_output)
+        }
         TargetType::File | TargetType::Folder | TargetType::Shortcut | TargetType::Url => {
             execute_shell_open(task)
         }
     }
 }
 
+/// Longest path, in UTF-16 units, the plain Win32 file APIs accept
+const MAX_PATH: usize = 260;
+
+/// The `\\?\` form of an absolute path that is too long for the plain Win32 APIs; other paths
+/// are returned as they are. That form is taken literally, so separators and `.`/`..` are resolved.
+fn extended_path(path: &str) -> String {
+    if path.encode_utf16().count() < MAX_PATH || path.starts_with(r"\\?\") {
+        return path.to_string();
+    }
+    let bytes = path.as_bytes();
+    // Segments that `..` can't climb above: the drive, or the server and share
+    let (prefix, rest, root_len) = if let Some(unc) = path.strip_prefix(r"\\").or_else(|| path.strip_prefix("//")) {
+        (r"\\?\UNC\", unc, 2)
+    } else if bytes.len() > 2 && bytes[1] == b':' && matches!(bytes[2], b'\\' | b'/') {
+        (r"\\?\", path, 1)
+    } else {
+        return path.to_string();
+    };
+    
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in rest.split(['\\', '/']) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.len() > root_len {
+                    segments.pop();
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("{}{}", prefix, segments.join("\\"))
+}
+
 /// Get process name from path (e.g., "C:\\Program Files\\app.exe" -> "app.exe")
 fn get_process_name(path: &str) -> String {
     std::path::Path::new(path)
@@ -92,47 +224,289 @@ pub fn get_process_name_from_path(path: &str) -> String {
     get_process_name(path)
 }
 
+/// A running process, as listed by a Toolhelp snapshot (Windows), /proc (Linux) or ps
+struct ProcessEntry {
+    pid: u32,
+    parent_pid: u32,
+    name: String,
+}
+
+/// How long a process snapshot answers lookups, so a scheduler tick or UI refresh lists processes once
+const SNAPSHOT_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Latest process snapshot and when it was taken
+static SNAPSHOT: Mutex<Option<(std::time::Instant, std::sync::Arc<Vec<ProcessEntry>>)>> = Mutex::new(None);
+
+/// Running processes, from a snapshot at most `SNAPSHOT_MAX_AGE` old
+fn process_snapshot() -> std::sync::Arc<Vec<ProcessEntry>> {
+    let mut cached = SNAPSHOT.lock().unwrap();
+    if let Some((_, processes)) = cached.as_ref().filter(|(taken, _)| taken.elapsed() < SNAPSHOT_MAX_AGE) {
+        return processes.clone();
+    }
+    let processes = std::sync::Arc::new(take_process_snapshot());
+    *cached = Some((std::time::Instant::now(), processes.clone()));
+    processes
+}
+
+/// Drop the cached snapshot after starting or killing processes
+fn forget_process_snapshot() {
+    *SNAPSHOT.lock().unwrap() = None;
+}
+
+/// Every running process, listed now
+#[cfg(windows)]
+fn take_process_snapshot() -> Vec<ProcessEntry> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+    
+    let mut processes = Vec::new();
+    unsafe {
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+            return processes;
+        };
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut found = Process32FirstW(snapshot, &mut entry).is_ok();
+        while found {
+            let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+            processes.push(ProcessEntry {
+                pid: entry.th32ProcessID,
+                parent_pid: entry.th32ParentProcessID,
+                name: String::from_utf16_lossy(&entry.szExeFile[..len]),
+            });
+            found = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    processes
+}
+
+/// Every running process, listed now
+#[cfg(target_os = "linux")]
+fn take_process_snapshot() -> Vec<ProcessEntry> {
+    let Ok(entries) = std::fs::read_dir("/proc") else { return Vec::new() };
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+            let (comm, fields) = parse_proc_stat(&stat)?;
+            // comm is cut at 15 characters, the executable's file name is whole
+            let name = std::fs::read_link(entry.path().join("exe"))
+                .ok()
+                .and_then(|exe| exe.file_name().map(|n| n.to_string_lossy().trim_end_matches(" (deleted)").to_string()))
+                .unwrap_or(comm);
+            Some(ProcessEntry { pid, parent_pid: fields.get(1)?.parse().ok()?, name })
+        })
+        .collect()
+}
+
+/// Every running process, listed now
+#[cfg(not(any(windows, target_os = "linux")))]
+fn take_process_snapshot() -> Vec<ProcessEntry> {
+    let Ok(output) = Command::new("ps").args(["-axo", "pid=,ppid=,comm="]).output() else {
+        return Vec::new();
+    };
+    // Lines look like "  412     1 /usr/sbin/cfprefsd"; the command may contain spaces
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pid, rest) = line.trim_start().split_once(char::is_whitespace)?;
+            let (parent_pid, command) = rest.trim_start().split_once(char::is_whitespace)?;
+            Some(ProcessEntry {
+                pid: pid.parse().ok()?,
+                parent_pid: parent_pid.parse().ok()?,
+                name: get_process_name(command.trim()),
+            })
+        })
+        .collect()
+}
+
+/// Command name and the fields after it (state, parent pid, ...) of a /proc/<pid>/stat line.
+/// The name is in parentheses and may itself contain spaces and parentheses.
+#[cfg(target_os = "linux")]
+fn parse_proc_stat(stat: &str) -> Option<(String, Vec<&str>)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    Some((stat.get(open + 1..close)?.to_string(), stat[close + 1..].split_whitespace().collect()))
+}
+
+/// Executable names of all running processes, lowercased, from a single snapshot
+pub fn running_process_names() -> std::collections::HashSet<String> {
+    process_snapshot().iter().map(|p| p.name.to_lowercase()).collect()
+}
+
 /// Check if a process is running by name
 fn is_process_running(process_name: &str) -> bool {
+    process_snapshot().iter().any(|p| p.name.eq_ignore_ascii_case(process_name))
+}
+
+/// Public version for use from commands
+pub fn check_process_running(process_name: &str) -> bool {
+    is_process_running(process_name)
+}
+
+/// Kill every process with the given executable name
+fn kill_process(process_name: &str) {
+    for process in take_process_snapshot().iter().filter(|p| p.name.eq_ignore_ascii_case(process_name)) {
+        if let Err(e) = terminate(process.pid) {
+            tracing::warn!("Failed to kill {} (pid {}): {}", process_name, process.pid, e);
+        }
+    }
+}
+
+/// Terminate one process by PID
+#[cfg(windows)]
+fn terminate(pid: u32) -> windows::core::Result<()> {
+    use windows::Win32::Foundation::{CloseHandle, BOOL};
+    use windows::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+    
+    unsafe {
+        let process = OpenProcess(PROCESS_TERMINATE, BOOL::from(false), pid)?;
+        let result = TerminateProcess(process, 1);
+        let _ = CloseHandle(process);
+        forget_process_snapshot();
+        result
+    }
+}
+
+/// Terminate one process by PID, without giving it a chance to clean up (like TerminateProcess)
+#[cfg(not(windows))]
+fn terminate(pid: u32) -> std::io::Result<()> {
+    let status = Command::new("kill")
+        .args(["-KILL", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()?;
+    forget_process_snapshot();
+    if !status.success() {
+        return Err(std::io::Error::other(format!("kill exited with {}", status)));
+    }
+    Ok(())
+}
+
+/// Remember the process launched for a task. The entry stays after the process exits,
+/// so later checks know the task's own process is gone.
+fn track_launch(task_id: &str, pid: u32) {
+    let launch = Launch { pid, started: process_started_at(pid) };
+    LAUNCHED.lock().unwrap().insert(task_id.to_string(), launch);
+    forget_process_snapshot();
+}
+
+/// Creation time of a process that is still running, as an opaque value to compare
+fn process_started_at(pid: u32) -> Option<u64> {
     #[cfg(windows)]
     {
-        let output = Command::new("tasklist")
-            .args(["/FI", &format!("IMAGENAME eq {}", process_name)])
-            .output();
+        use windows::Win32::Foundation::{CloseHandle, BOOL, FILETIME, STILL_ACTIVE};
+        use windows::Win32::System::Threading::{
+            GetExitCodeProcess, GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        };
         
-        match output {
-            Ok(out) => {
-                let stdout = String::from_utf8_lossy(&out.stdout);
-                stdout.to_lowercase().contains(&process_name.to_lowercase())
-            }
-            Err(_) => false,
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, BOOL::from(false), pid).ok()?;
+            let (mut created, mut exited, mut kernel, mut user) =
+                (FILETIME::default(), FILETIME::default(), FILETIME::default(), FILETIME::default());
+            let mut exit_code = 0u32;
+            // An exited process can still be opened while someone holds a handle to it
+            let running = GetExitCodeProcess(process, &mut exit_code).is_ok() && exit_code == STILL_ACTIVE.0 as u32;
+            let times = GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user);
+            let _ = CloseHandle(process);
+            (running && times.is_ok())
+                .then(|| ((created.dwHighDateTime as u64) << 32) | created.dwLowDateTime as u64)
         }
     }
     
-    #[cfg(not(windows))]
+    #[cfg(target_os = "linux")]
     {
-        false
+        // Start time in clock ticks since boot is field 22; the state (field 3) is Z once it exited
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        let (_, fields) = parse_proc_stat(&stat)?;
+        if *fields.first()? == "Z" {
+            return None;
+        }
+        fields.get(19)?.parse().ok()
+    }
+    
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+        use std::hash::{Hash, Hasher};
+        
+        let output = Command::new("ps").args(["-o", "stat=,lstart=", "-p", &pid.to_string()]).output().ok()?;
+        let line = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let (state, started) = line.split_once(char::is_whitespace)?;
+        if state.starts_with('Z') {
+            return None;
+        }
+        // Only ever compared for equality, so a hash of the start time will do
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        started.split_whitespace().collect::<Vec<_>>().hash(&mut hasher);
+        Some(hasher.finish())
     }
 }
 
-/// Public version for use from commands
-pub fn check_process_running(process_name: &str) -> bool {
-    is_process_running(process_name)
+/// Whether the process last launched for the task is still running, None if we never launched one
+pub fn launched_process_running(task_id: &str) -> Option<bool> {
+    LAUNCHED.lock().unwrap().get(task_id).map(Launch::is_alive)
 }
 
-/// Kill a process by name
-fn kill_process(process_name: &str) {
-    #[cfg(windows)]
-    {
-        let _ = Command::new("taskkill")
-            .args(["/F", "/IM", process_name])
-            .output();
+/// Whether an Exe task's program is running: the process we launched if there is one,
+/// otherwise any process with the same executable name
+pub fn is_task_process_running(task: &Task) -> bool {
+    launched_process_running(&task.id)
+        .unwrap_or_else(|| is_process_running(&get_process_name(&task.path_or_url)))
+}
+
+/// Stop the process of an Exe task: the one we launched if there is one, otherwise
+/// any process with the same executable name. Returns whether anything was killed.
+pub fn kill_task_process(task: &Task) -> Result<bool, ExecutorError> {
+    let process_name = get_process_name(&task.path_or_url);
+
+    let tracked = LAUNCHED.lock().unwrap().get(&task.id).copied();
+    if let Some(launch) = tracked {
+        if !launch.is_alive() {
+            return Ok(false);
+        }
+        tracing::info!("Killing {} (pid {}) for task {}", process_name, launch.pid, task.name);
+        kill_pid(launch.pid)?;
+        return Ok(true);
+    }
+
+    if !is_process_running(&process_name) {
+        return Ok(false);
     }
+    tracing::info!("Killing {} by name for task {}", process_name, task.name);
+    kill_process(&process_name);
+    Ok(true)
+}
+
+/// Kill a process and the processes it started
+fn kill_pid(pid: u32) -> Result<(), ExecutorError> {
+    // Children first, so none is left behind re-parented
+    let processes = take_process_snapshot();
+    let mut tree = vec![pid];
+    let mut i = 0;
+    while i < tree.len() {
+        let parent = tree[i];
+        let children: Vec<u32> = processes.iter()
+            .filter(|p| p.parent_pid == parent && !tree.contains(&p.pid))
+            .map(|p| p.pid)
+            .collect();
+        tree.extend(children);
+        i += 1;
+    }
+    for &child in tree[1..].iter().rev() {
+        let _ = terminate(child);
+    }
+    terminate(pid).map_err(|e| ExecutorError::KillFailed(e.to_string()))
 }
 
 /// Execute an exe with arguments
-fn execute_exe(task: &Task) -> Result<ExecutionResult, ExecutorError> {
-    let mut cmd = Command::new(&task.path_or_url);
+fn execute_exe(task: &Task, env: &HashMap<String, String>, on_output: &OutputSink<'_>) -> Result<ExecutionResult, ExecutorError> {
+    let mut cmd = Command::new(extended_path(&task.path_or_url));
+    cmd.envs(env);
     
     // Add arguments
     if let Some(args) = &task.args {
@@ -170,137 +544,271 @@ fn execute_exe(task: &Task) -> Result<ExecutionResult, ExecutorError> {
     match &task.wait_policy {
         WaitPolicy::DontWait => {
             // Spawn and don't wait
-            let _child = cmd.spawn()?;
+            let child = cmd.spawn()?;
+            track_launch(&task.id, child.id());
             Ok(ExecutionResult {
                 success: true,
                 exit_code: None,
-                error_message: None,
+                error: None,
                 output: None,
+                child: Some(child),
             })
         }
         WaitPolicy::WaitForExit { timeout_seconds } => {
-            if let Some(timeout) = timeout_seconds {
-                // Wait with timeout
-                let mut child = cmd.spawn()?;
-                let start = std::time::Instant::now();
-                let timeout_duration = std::time::Duration::from_secs(*timeout as u64);
-                
-                // Check process status with timeout
-                loop {
-                    // Check if timeout exceeded first
-                    if start.elapsed() >= timeout_duration {
-                        tracing::warn!("Process timeout after {} seconds, killing process", timeout);
-                        let _ = child.kill();
-                        let _ = child.wait(); // Clean up zombie process
-                        return Err(ExecutorError::Timeout(*timeout));
-                    }
-                    
-                    // Try to get process status
+            // The max runtime caps the wait timeout when both are set
+            let timeout_seconds = match (*timeout_seconds, task.max_runtime_seconds) {
+                (Some(wait), Some(max)) => Some(wait.min(max)),
+                (wait, max) => wait.or(max),
+            };
+            
+            // Spawned (rather than output()) so the process can be stopped while we wait.
+            // Important: On Windows, for GUI apps, output might be empty. For CLI, it works.
+            let mut child = cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+            track_launch(&task.id, child.id());
+            let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+            let deadline = timeout_seconds.map(|t| std::time::Instant::now() + std::time::Duration::from_secs(t as u64));
+            // Both pipes are read at once, so neither fills up and blocks the process
+            let (mut out_str, err_str, status) = std::thread::scope(|scope| {
+                let stderr = scope.spawn(|| read_lines(stderr, OutputStream::Stderr, on_output));
+                let stdout = scope.spawn(|| read_lines(stdout, OutputStream::Stdout, on_output));
+                let status = loop {
+                    let Some(deadline) = deadline else { break child.wait().map(Some) };
                     match child.try_wait() {
-                        Ok(Some(status)) => {
-                            let code = status.code().unwrap_or(-1);
-                            let success = check_exit_code(code, &task.success_exit_codes);
-                            tracing::info!("Process exited with code: {}", code);
-                            return Ok(ExecutionResult {
-                                success,
-                                exit_code: Some(code),
-                                error_message: if success { None } else { Some(format!("Exit code: {}", code)) },
-                                output: None, 
-                            });
-                        }
-                        Ok(None) => {
-                            // Process still running, sleep and check again
-                            std::thread::sleep(std::time::Duration::from_millis(200));
-                        }
-                        Err(e) => {
-                            tracing::error!("Error checking process status: {}", e);
-                            return Err(ExecutorError::IoError(e));
+                        Ok(None) if std::time::Instant::now() >= deadline => {
+                            // The whole tree, so nothing left behind keeps the pipes open
+                            let _ = kill_pid(child.id());
+                            let _ = child.kill();
+                            let _ = child.wait(); // Clean up zombie process
+                            break Ok(None);
                         }
+                        Ok(None) => std::thread::sleep(std::time::Duration::from_millis(200)),
+                        status => break status,
                     }
-                }
-            } else {
-                // Wait indefinitely - Use output() to capture stdout/stderr
-                // Note: output() handles waiting
-                // However, we need to be careful if we wanted to hide window but output() might show console? 
-                // No, output() just captures.
-                
-                // Important: On Windows, for GUI apps, output might be empty. For CLI, it works.
-                // We MUST set creation_flags again if needed? command structure keeps it.
-                
-                let output = cmd.output()?;
-                let code = output.status.code().unwrap_or(-1);
-                let success = check_exit_code(code, &task.success_exit_codes);
-                
-                // Combine stdout and stderr
-                let mut out_str = String::from_utf8_lossy(&output.stdout).to_string();
-                let err_str = String::from_utf8_lossy(&output.stderr).to_string();
-                if !err_str.is_empty() {
-                    out_str.push_str("\n--- STDERR ---\n");
-                    out_str.push_str(&err_str);
-                }
-                
-                Ok(ExecutionResult {
-                    success,
-                    exit_code: Some(code),
-                    error_message: if success { None } else { Some(format!("Exit code: {}", code)) },
+                };
+                (stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default(), status)
+            });
+            
+            // Combine stdout and stderr
+            if !err_str.is_empty() {
+                out_str.push_str("\n--- STDERR ---\n");
+                out_str.push_str(&err_str);
+            }
+            
+            let Some(status) = status? else {
+                let timeout = timeout_seconds.unwrap_or_default();
+                tracing::warn!("Process timeout after {} seconds, killed process", timeout);
+                return Ok(ExecutionResult {
+                    success: false,
+                    exit_code: None,
+                    error: Some(ExecutorError::Timeout(timeout).message()),
                     output: Some(out_str),
-                })
+                    child: None,
+                });
+            };
+            let code = status.code().unwrap_or(-1);
+            let success = check_exit_code(code, &task.success_exit_codes);
+            tracing::info!("Process exited with code: {}", code);
+            
+            Ok(ExecutionResult {
+                success,
+                exit_code: Some(code),
+                error: (!success).then(|| ExecutorError::ExitCodeFailed(code).message()),
+                output: Some(out_str),
+                child: None,
+            })
+        }
+    }
+}
+
+/// Read a pipe to the end, passing each line on as it arrives; returns everything read
+fn read_lines(pipe: Option<impl std::io::Read>, stream: OutputStream, on_output: &OutputSink<'_>) -> String {
+    use std::io::BufRead;
+    let Some(pipe) = pipe else { return String::new() };
+    let mut reader = std::io::BufReader::new(pipe);
+    let (mut all, mut line) = (String::new(), Vec::new());
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                // Not every program writes UTF-8
+                let text = String::from_utf8_lossy(&line);
+                on_output(stream, text.trim_end_matches(['\r', '\n']));
+                all.push_str(&text);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read process output: {}", e);
+                break;
             }
         }
     }
+    all
 }
 
 /// Open file/folder/shortcut/url using shell
 fn execute_shell_open(task: &Task) -> Result<ExecutionResult, ExecutorError> {
     #[cfg(windows)]
     {
-        use std::os::windows::process::CommandExt;
-        
-        let mut cmd = Command::new("cmd");
-        cmd.args(["/C", "start", "", &task.path_or_url]);
-        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        use windows::core::{HSTRING, PCWSTR};
+        use windows::Win32::Foundation::HWND;
+        use windows::Win32::UI::Shell::ShellExecuteW;
+        use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
         
-        let status = cmd.status()?;
+        // URLs go to the shell as they are, file system paths may need the long form
+        let target = match task.target_type {
+            TargetType::Url => task.path_or_url.clone(),
+            _ => extended_path(&task.path_or_url),
+        };
+        // Default verb, like a double click
+        let result = unsafe {
+            ShellExecuteW(HWND::default(), PCWSTR::null(), &HSTRING::from(target), PCWSTR::null(), PCWSTR::null(), SW_SHOWNORMAL)
+        };
+        // Values up to 32 are error codes
+        if result.0 <= 32 {
+            return Err(ExecutorError::OpenFailed(format!("{} (ShellExecute error {})", task.path_or_url, result.0)));
+        }
         
         Ok(ExecutionResult {
-            success: status.success(),
-            exit_code: status.code(),
-            error_message: if status.success() { None } else { Some("Failed to open".to_string()) },
+            success: true,
+            exit_code: None,
+            error: None,
+            output: None,
+            child: None,
+        })
+    }
+    
+    #[cfg(not(windows))]
+    {
+        shell_open(&[task.path_or_url.as_ref()])?;
+        Ok(ExecutionResult {
+            success: true,
+            exit_code: None,
+            error: None,
             output: None,
+            child: None,
         })
     }
+}
+
+/// The desktop's "open with the default app" command
+#[cfg(not(windows))]
+const OPENER: &str = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+
+/// Open a path or URL with its default app, like a double click; `args` go to the opener
+#[cfg(not(windows))]
+fn shell_open(args: &[&std::ffi::OsStr]) -> Result<(), ExecutorError> {
+    let status = Command::new(OPENER)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        let target = args.last().map(|a| a.to_string_lossy()).unwrap_or_default();
+        return Err(ExecutorError::OpenFailed(format!("{} ({} exited with {})", target, OPENER, status)));
+    }
+    Ok(())
+}
+
+/// Show the file or folder selected in Explorer (Finder on macOS)
+pub fn reveal_in_explorer(path: &std::path::Path) -> Result<(), ExecutorError> {
+    if !path.exists() {
+        return Err(ExecutorError::PathNotFound(path.display().to_string()));
+    }
+    
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        
+        // Explorer wants `/select,"<path>"` as one raw argument, and its exit code is meaningless
+        Command::new("explorer")
+            .raw_arg(format!("/select,\"{}\"", path.display()))
+            .spawn()?;
+        Ok(())
+    }
     
     #[cfg(not(windows))]
     {
-        Err(ExecutorError::OpenFailed("Only Windows is supported".to_string()))
+        // Finder can select the item; other file managers just get its folder
+        if cfg!(target_os = "macos") {
+            shell_open(&["-R".as_ref(), path.as_os_str()])
+        } else {
+            shell_open(&[path.parent().unwrap_or(path).as_os_str()])
+        }
     }
 }
 
-/// Parse command line arguments (handle quoted strings)
+/// Open a folder in Explorer or the desktop's file manager
+pub fn open_folder(path: &std::path::Path) -> Result<(), ExecutorError> {
+    if !path.is_dir() {
+        return Err(ExecutorError::PathNotFound(path.display().to_string()));
+    }
+    
+    #[cfg(windows)]
+    {
+        Command::new("explorer").arg(path).spawn()?;
+        Ok(())
+    }
+    
+    #[cfg(not(windows))]
+    {
+        shell_open(&[path.as_os_str()])
+    }
+}
+
+/// Split a command line into arguments the way Windows programs do (CommandLineToArgvW rules):
+/// whitespace separates arguments outside quotes, `""` is an empty argument, `\"` is a literal
+/// quote, backslashes are only special right before a quote, and `""` inside quotes is a quote
 fn parse_args(args: &str) -> Vec<String> {
     let mut result = Vec::new();
     let mut current = String::new();
+    // Quotes alone make an argument, even an empty one
+    let mut in_arg = false;
     let mut in_quotes = false;
     let mut chars = args.chars().peekable();
     
     while let Some(c) = chars.next() {
         match c {
+            '\\' => {
+                let mut backslashes = 1;
+                while chars.next_if_eq(&'\\').is_some() {
+                    backslashes += 1;
+                }
+                if chars.peek() == Some(&'"') {
+                    // 2n backslashes: n of them, then the quote opens or closes;
+                    // 2n+1: n of them and a literal quote
+                    current.push_str(&"\\".repeat(backslashes / 2));
+                    if backslashes % 2 == 1 {
+                        chars.next();
+                        current.push('"');
+                    }
+                } else {
+                    current.push_str(&"\\".repeat(backslashes));
+                }
+                in_arg = true;
+            }
             '"' => {
-                in_quotes = !in_quotes;
+                if in_quotes && chars.next_if_eq(&'"').is_some() {
+                    current.push('"');
+                } else {
+                    in_quotes = !in_quotes;
+                }
+                in_arg = true;
             }
             ' ' | '\t' if !in_quotes => {
-                if !current.is_empty() {
-                    result.push(current.clone());
-                    current.clear();
+                if in_arg {
+                    result.push(std::mem::take(&mut current));
+                    in_arg = false;
                 }
             }
             _ => {
                 current.push(c);
+                in_arg = true;
             }
         }
     }
     
-    if !current.is_empty() {
+    if in_arg {
         result.push(current);
     }
     
@@ -314,3 +822,103 @@ fn check_exit_code(code: i32, success_codes: &Option<Vec<i32>>) -> bool {
         None => code == 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_extended_path_only_for_long_paths() {
+        assert_eq!(extended_path(r"C:\Tools\app.exe"), r"C:\Tools\app.exe");
+        
+        let dir = "a".repeat(130);
+        let long = format!(r"C:\{}\.\{}\x\..\app.exe", dir, dir);
+        assert_eq!(extended_path(&long), format!(r"\\?\C:\{}\{}\app.exe", dir, dir));
+        assert_eq!(extended_path(&format!(r"\\?\{}", long)), format!(r"\\?\{}", long));
+        assert_eq!(extended_path(&format!("relative/{}", long)), format!("relative/{}", long));
+    }
+    
+    #[test]
+    fn test_extended_path_unc_and_forward_slashes() {
+        let dir = "b".repeat(250);
+        assert_eq!(
+            extended_path(&format!("//server/share/../../{}/file.txt", dir)),
+            format!(r"\\?\UNC\server\share\{}\file.txt", dir),
+        );
+        // Counted in UTF-16 units, not bytes
+        let unicode = format!(r"C:\{}", "ư".repeat(200));
+        assert_eq!(extended_path(&unicode), unicode);
+    }
+    
+    #[test]
+    fn test_parse_args_splits_on_whitespace() {
+        assert_eq!(parse_args("a b\t c  "), ["a", "b", "c"]);
+        assert!(parse_args("   ").is_empty());
+    }
+    
+    #[test]
+    fn test_parse_args_quoted_paths() {
+        assert_eq!(
+            parse_args(r#"--config "C:\Program Files\App\app.ini" -v"#),
+            ["--config", r"C:\Program Files\App\app.ini", "-v"],
+        );
+        assert_eq!(parse_args(r#"--name="John Smith""#), ["--name=John Smith"]);
+        // A trailing backslash before the closing quote has to be doubled
+        assert_eq!(parse_args(r#""C:\Program Files\\" next"#), [r"C:\Program Files\", "next"]);
+    }
+    
+    #[test]
+    fn test_parse_args_empty_quoted_args() {
+        assert_eq!(parse_args(r#""" x """#), ["", "x", ""]);
+        assert_eq!(parse_args(r#"--title """#), ["--title", ""]);
+    }
+    
+    #[test]
+    fn test_parse_args_escaped_quotes() {
+        assert_eq!(parse_args(r#"a\"b"#), [r#"a"b"#]);
+        assert_eq!(parse_args(r#"a\\\"b"#), [r#"a\"b"#]);
+        assert_eq!(parse_args(r#""say ""hi""""#), [r#"say "hi""#]);
+        assert_eq!(parse_args(r#""a\"b c""#), [r#"a"b c"#]);
+    }
+    
+    #[test]
+    fn test_parse_args_backslashes_are_literal_elsewhere() {
+        assert_eq!(parse_args(r"\\server\share C:\dir\"), [r"\\server\share", r"C:\dir\"]);
+        assert_eq!(parse_args(r#"a\\"b c""#), [r"a\b c"]);
+    }
+    
+    #[test]
+    fn test_read_lines_passes_each_line_on() {
+        let lines = Mutex::new(Vec::new());
+        let on_output = |stream: OutputStream, line: &str| lines.lock().unwrap().push((stream, line.to_string()));
+        let all = read_lines(Some(&b"one\r\ntwo\n\xffend"[..]), OutputStream::Stderr, &on_output);
+        assert_eq!(all, "one\r\ntwo\n\u{fffd}end");
+        assert_eq!(
+            *lines.lock().unwrap(),
+            [(OutputStream::Stderr, "one".to_string()), (OutputStream::Stderr, "two".to_string()), (OutputStream::Stderr, "\u{fffd}end".to_string())],
+        );
+        assert_eq!(read_lines(None::<&[u8]>, OutputStream::Stdout, &on_output), "");
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_max_runtime_keeps_captured_output() {
+        let task = Task {
+            id: "max-runtime".to_string(),
+            target_type: TargetType::Exe,
+            path_or_url: "/bin/sh".to_string(),
+            args: Some(r#"-c "echo started; exec sleep 30""#.to_string()),
+            wait_policy: WaitPolicy::WaitForExit { timeout_seconds: None },
+            if_running_action: IfRunningAction::RunAnyway,
+            max_runtime_seconds: Some(1),
+            ..Task::default()
+        };
+        let lines = Mutex::new(Vec::new());
+        let on_output = |_: OutputStream, line: &str| lines.lock().unwrap().push(line.to_string());
+        let result = execute_task_streamed(&task, &RunOverrides::default(), &on_output).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.map(|e| e.id), Some(MessageId::Timeout));
+        assert_eq!(result.output.as_deref(), Some("started\n"));
+        assert_eq!(*lines.lock().unwrap(), ["started"]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Health module - Find tasks that need attention for the dashboard
+
+use crate::models::*;
+use crate::validation::{validate_task, ValidationLevel};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// A task is overdue once it has gone this many expected periods without running
+const OVERDUE_PERIODS: i32 = 2;
+
+/// A task that needs attention, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthItem {
+    pub task_id: String,
+    pub task_name: String,
+    pub detail: String,
+}
+
+/// Tasks that need attention, grouped by problem
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct HealthSummary {
+    /// The last run failed
+    pub last_run_failed: Vec<HealthItem>,
+    /// The file, folder or program to open does not exist
+    pub missing_target: Vec<HealthItem>,
+    /// Enabled, but nothing will ever start it automatically
+    pub no_enabled_triggers: Vec<HealthItem>,
+    /// Has not run for much longer than its triggers suggest
+    pub overdue: Vec<HealthItem>,
+}
+
+/// Check every task against its state as of `now`
+pub fn health_summary(tasks: &[Task], states: &HashMap<String, TaskState>, now: DateTime<Utc>) -> HealthSummary {
+    let mut summary = HealthSummary::default();
+
+    for task in tasks {
+        let item = |detail: String| HealthItem {
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            detail,
+        };
+        let state = states.get(&task.id);
+
+        if let Some(state) = state.filter(|s| s.last_result == Some(RunResult::Failed)) {
+            summary.last_run_failed.push(item(state.last_error.clone().unwrap_or_else(|| "Failed".to_string())));
+        }
+
+        let missing = validate_task(task)
+            .into_iter()
+            .find(|w| w.field == "path_or_url" && w.level == ValidationLevel::Error);
+        if let Some(warning) = missing {
+            summary.missing_target.push(item(warning.message));
+        }
+
+        // Disabled and paused tasks are expected not to run
+        if !task.enabled || task.paused_until_utc.is_some_and(|until| until > now) {
+            continue;
+        }
+        if !task.triggers.iter().any(Trigger::is_enabled) {
+            summary.no_enabled_triggers.push(item("No enabled trigger".to_string()));
+            continue;
+        }
+
+        if let Some(period) = expected_period(task) {
+            let last = state.and_then(|s| s.last_run_at_utc).unwrap_or(task.created_at_utc);
+            if now - last > period * OVERDUE_PERIODS {
+                let detail = match state.and_then(|s| s.last_run_at_utc) {
+                    Some(last) => format!("Last ran {}", last.to_rfc3339()),
+                    None => "Never ran".to_string(),
+                };
+                summary.overdue.push(item(detail));
+            }
+        }
+    }
+
+    summary
+}
+
+/// Longest a task should go between runs, from its most frequent enabled trigger.
+/// None when only login or webhook triggers start it.
+fn expected_period(task: &Task) -> Option<Duration> {
+    task.triggers.iter()
+        .filter(|t| t.is_enabled())
+        .filter_map(|trigger| match trigger {
+            Trigger::OnLogin { .. } | Trigger::Webhook { .. } => None,
+            Trigger::Interval { every_seconds, .. } => Some(Duration::seconds(*every_seconds as i64)),
+            // Restricted to some weekdays, a week may pass between runs
+            Trigger::OncePerDay { days_of_week: Some(_), .. }
+            | Trigger::DailyAt { days_of_week: Some(_), .. } => Some(Duration::days(7)),
+            Trigger::OncePerDay { .. } | Trigger::DailyAt { .. } => Some(Duration::days(1)),
+        })
+        .min()
+}
@@ -0,0 +1,121 @@
+//! HTTP module - Minimal HTTPS (and SMTP) requests through the curl that ships with Windows
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Longest a single request may take
+const REQUEST_TIMEOUT_SECS: u32 = 15;
+
+/// Longest downloading a file (an update) may take
+const DOWNLOAD_TIMEOUT_SECS: u32 = 10 * 60;
+
+/// Longest sending one email may take
+const MAIL_TIMEOUT_SECS: u32 = 60;
+
+/// POST a JSON body and return the response status code. Blocks until the request is done.
+pub fn post_json(url: &str, body: &str) -> Result<u16, String> {
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return Err("Not an http(s) URL".to_string());
+    }
+
+    let config = format!("url = {}\ndata-binary = {}\n", config_string(url), config_string(body));
+    let stdout = run_curl(
+        &[
+            "--max-time", &REQUEST_TIMEOUT_SECS.to_string(),
+            "--output", if cfg!(windows) { "NUL" } else { "/dev/null" },
+            "--write-out", "%{http_code}",
+            "--header", "Content-Type: application/json",
+        ],
+        &config,
+    )?;
+    stdout.trim().parse().map_err(|_| "No HTTP status in curl output".to_string())
+}
+
+/// GET a URL, following redirects, and return the body. Blocks until the request is done.
+pub fn get(url: &str) -> Result<String, String> {
+    if !url.starts_with("https://") {
+        return Err("Not an https URL".to_string());
+    }
+    let config = format!("url = {}\n", config_string(url));
+    run_curl(&["--max-time", &REQUEST_TIMEOUT_SECS.to_string(), "--fail", "--location"], &config)
+}
+
+/// Download a URL to a file, following redirects. Blocks until the download is done.
+pub fn download(url: &str, path: &std::path::Path) -> Result<(), String> {
+    if !url.starts_with("https://") {
+        return Err("Not an https URL".to_string());
+    }
+    let config = format!("url = {}\noutput = {}\n", config_string(url), config_string(&path.to_string_lossy()));
+    run_curl(&["--max-time", &DOWNLOAD_TIMEOUT_SECS.to_string(), "--fail", "--location"], &config).map(|_| ())
+}
+
+/// Where and how to deliver an email
+pub struct MailServer<'a> {
+    /// smtp:// or smtps:// URL with host and port
+    pub url: String,
+    /// Refuse to send unless STARTTLS succeeds
+    pub require_tls: bool,
+    pub username: &'a str,
+    pub password: &'a str,
+}
+
+/// Send an already formatted message (headers and body) over SMTP. Blocks until sent.
+pub fn send_mail(server: &MailServer, from: &str, to: &[String], message: &str) -> Result<(), String> {
+    // curl reads the message from a file, as stdin carries the config with the password
+    let path = std::env::temp_dir().join(format!("routine-runner-{}.eml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, message).map_err(|e| e.to_string())?;
+
+    let mut config = format!("url = {}\nmail-from = {}\n", config_string(&server.url), config_string(from));
+    for rcpt in to {
+        config.push_str(&format!("mail-rcpt = {}\n", config_string(rcpt)));
+    }
+    if !server.username.is_empty() {
+        config.push_str(&format!("user = {}\n", config_string(&format!("{}:{}", server.username, server.password))));
+    }
+    if server.require_tls {
+        config.push_str("ssl-reqd\n");
+    }
+    config.push_str(&format!("upload-file = {}\n", config_string(&path.to_string_lossy())));
+
+    let result = run_curl(&["--max-time", &MAIL_TIMEOUT_SECS.to_string()], &config);
+    let _ = std::fs::remove_file(&path);
+    result.map(|_| ())
+}
+
+/// Run curl with the given arguments and a config file on stdin, returning its output
+fn run_curl(args: &[&str], config: &str) -> Result<String, String> {
+    let mut cmd = Command::new("curl");
+    cmd.args(["--silent", "--show-error"])
+        .args(args)
+        // URLs, bodies and passwords are read from stdin, keeping them out of the process list
+        .args(["--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to start curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(config.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Quote a value for a curl config file
+fn config_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
@@ -0,0 +1,70 @@
+//! iCal module - Write planned runs to an .ics file for calendar apps
+
+use crate::commands::UpcomingRun;
+use chrono::{DateTime, Utc};
+
+/// Length of each event; runs have no planned end
+const EVENT_MINUTES: i64 = 15;
+
+/// Longest content line in octets (RFC 5545), longer ones are folded
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Calendar with one event per planned run
+pub fn calendar_text(runs: &[UpcomingRun]) -> String {
+    let stamp = ical_time(Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Routine Runner//Schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Routine Runner".to_string(),
+    ];
+    for run in runs {
+        let start = run.scheduled_time_utc;
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            // Stable per occurrence, so importing again updates instead of duplicating
+            format!("UID:{}-{}@routine-runner", run.task_id, start.timestamp()),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", ical_time(start)),
+            format!("DTEND:{}", ical_time(start + chrono::Duration::minutes(EVENT_MINUTES))),
+            format!("SUMMARY:{}", escape(&run.task_name)),
+            format!("DESCRIPTION:{}", escape(&format!("Trigger: {}", run.trigger_type))),
+            // Planned runs should not show as busy next to meetings
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+fn ical_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Split a long line into continuation lines, never inside a UTF-8 character
+fn fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            // The leading space counts towards the continuation line
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out
+}
@@ -0,0 +1,195 @@
+//! Inspect module - Describe a picked path so the task editor can fill in its fields
+
+use crate::models::TargetType;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+/// Extensions launched as programs rather than opened with their associated app
+const EXE_EXTENSIONS: [&str; 4] = ["exe", "com", "bat", "cmd"];
+
+/// Extensions treated as shortcuts
+const SHORTCUT_EXTENSIONS: [&str; 2] = ["lnk", "url"];
+
+/// What the editor needs to know about a picked target
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetInfo {
+    pub path: String,
+    pub exists: bool,
+    pub target_type: TargetType,
+    /// File name without extension, as a default task name
+    pub suggested_name: String,
+    pub size_bytes: Option<u64>,
+    pub modified_utc: Option<DateTime<Utc>>,
+    /// Where a .lnk shortcut points, when it can be read
+    pub shortcut_target: Option<String>,
+    /// File to take the task's icon from (the program itself, or a shortcut's target)
+    pub icon_path: Option<String>,
+}
+
+/// Stat a path (or URL) and infer how a task should open it
+pub fn inspect_target(path: &str) -> TargetInfo {
+    let path = path.trim();
+    if path.contains("://") {
+        return TargetInfo {
+            path: path.to_string(),
+            exists: true,
+            target_type: TargetType::Url,
+            // The host, e.g. "example.com"
+            suggested_name: path.split("://")
+                .nth(1)
+                .and_then(|rest| rest.split('/').next())
+                .unwrap_or(path)
+                .to_string(),
+            size_bytes: None,
+            modified_utc: None,
+            shortcut_target: None,
+            icon_path: None,
+        };
+    }
+
+    let file = Path::new(path);
+    let metadata = std::fs::metadata(file).ok();
+    let extension = file.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let target_type = match &metadata {
+        Some(m) if m.is_dir() => TargetType::Folder,
+        _ if SHORTCUT_EXTENSIONS.contains(&extension.as_str()) => TargetType::Shortcut,
+        _ if EXE_EXTENSIONS.contains(&extension.as_str()) => TargetType::Exe,
+        _ => TargetType::File,
+    };
+    let shortcut_target = (extension == "lnk")
+        .then(|| std::fs::read(file).ok())
+        .flatten()
+        .and_then(|bytes| read_shortcut(&bytes))
+        .and_then(|shortcut| shortcut.target);
+    let icon_path = match target_type {
+        TargetType::Exe => Some(path.to_string()),
+        TargetType::Shortcut => shortcut_target.clone(),
+        _ => None,
+    };
+
+    TargetInfo {
+        path: path.to_string(),
+        exists: metadata.is_some(),
+        target_type,
+        suggested_name: file.file_stem()
+            .or_else(|| file.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or(path)
+            .to_string(),
+        size_bytes: metadata.as_ref().filter(|m| m.is_file()).map(|m| m.len()),
+        modified_utc: metadata.as_ref().and_then(|m| m.modified().ok()).map(DateTime::<Utc>::from),
+        shortcut_target,
+        icon_path,
+    }
+}
+
+/// What a .lnk shortcut launches
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShortcutInfo {
+    /// Local target path, from the LinkInfo block. Shortcuts to network shares or shell items have none.
+    pub target: Option<String>,
+    /// Target relative to the shortcut's folder
+    pub relative_path: Option<String>,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    /// Opens the target minimized
+    pub minimized: bool,
+}
+
+/// Parse a .lnk file (MS-SHLLINK)
+pub fn read_shortcut(bytes: &[u8]) -> Option<ShortcutInfo> {
+    const HEADER_SIZE: usize = 0x4C;
+    const HAS_TARGET_ID_LIST: u32 = 0x01;
+    const HAS_LINK_INFO: u32 = 0x02;
+    const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x01;
+    const SW_SHOWMINNOACTIVE: u32 = 7;
+
+    if read_u32(bytes, 0)? as usize != HEADER_SIZE {
+        return None;
+    }
+    let flags = read_u32(bytes, 0x14)?;
+    let mut shortcut = ShortcutInfo {
+        minimized: read_u32(bytes, 0x3C)? == SW_SHOWMINNOACTIVE,
+        ..Default::default()
+    };
+    let mut offset = HEADER_SIZE;
+    if flags & HAS_TARGET_ID_LIST != 0 {
+        offset += 2 + read_u16(bytes, offset)? as usize;
+    }
+
+    if flags & HAS_LINK_INFO != 0 {
+        let info = bytes.get(offset..)?;
+        let header_size = read_u32(info, 4)?;
+        if read_u32(info, 8)? & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+            // Newer shortcuts also carry the paths as UTF-16
+            let (base, suffix) = if header_size >= 0x24 {
+                (
+                    read_utf16(info, read_u32(info, 28)? as usize)?,
+                    read_utf16(info, read_u32(info, 32)? as usize).unwrap_or_default(),
+                )
+            } else {
+                (
+                    read_ansi(info, read_u32(info, 16)? as usize)?,
+                    read_ansi(info, read_u32(info, 24)? as usize).unwrap_or_default(),
+                )
+            };
+            shortcut.target = Some(base + &suffix);
+        }
+        offset += read_u32(info, 0)? as usize;
+    }
+
+    // StringData: name, relative path, working dir, arguments, icon location, each if flagged
+    let unicode = flags & 0x80 != 0;
+    let mut fields = [None, None, None, None];
+    for (bit, field) in [0x04, 0x08, 0x10, 0x20].into_iter().zip(fields.iter_mut()) {
+        if flags & bit == 0 {
+            continue;
+        }
+        let count = read_u16(bytes, offset)? as usize;
+        let width = if unicode { 2 } else { 1 };
+        let data = bytes.get(offset + 2..offset + 2 + count * width)?;
+        *field = Some(if unicode {
+            let units: Vec<u16> = data.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        } else {
+            String::from_utf8_lossy(data).into_owned()
+        });
+        offset += 2 + count * width;
+    }
+    let [_, relative_path, working_dir, arguments] = fields;
+    let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+    shortcut.relative_path = non_empty(relative_path);
+    shortcut.working_dir = non_empty(working_dir);
+    shortcut.arguments = non_empty(arguments);
+    Some(shortcut)
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// NUL-terminated single-byte string
+fn read_ansi(bytes: &[u8], at: usize) -> Option<String> {
+    let rest = bytes.get(at..)?;
+    let end = rest.iter().position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+/// NUL-terminated UTF-16LE string
+fn read_utf16(bytes: &[u8], at: usize) -> Option<String> {
+    let units: Vec<u16> = bytes.get(at..)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
@@ -0,0 +1,36 @@
+//! Instance module - Keep a single copy of the app running per user session
+
+/// Mutex held by the running app; "Local" scopes it to the user's session
+#[cfg(windows)]
+const MUTEX_NAME: &str = r"Local\RoutineRunner.Instance";
+
+/// Marks this process as the running app for as long as it is kept
+pub struct InstanceGuard {
+    #[cfg(windows)]
+    _mutex: Option<windows::Win32::Foundation::HANDLE>,
+}
+
+/// Claim the single instance. None if another copy of the app already holds it.
+pub fn acquire() -> Option<InstanceGuard> {
+    #[cfg(windows)]
+    {
+        use windows::core::HSTRING;
+        use windows::Win32::Foundation::{GetLastError, BOOL, ERROR_ALREADY_EXISTS};
+        use windows::Win32::System::Threading::CreateMutexW;
+
+        match unsafe { CreateMutexW(None, BOOL::from(false), &HSTRING::from(MUTEX_NAME)) } {
+            Ok(_) if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS => None,
+            Ok(mutex) => Some(InstanceGuard { _mutex: Some(mutex) }),
+            // Better two copies than none
+            Err(e) => {
+                tracing::warn!("Failed to create instance mutex: {}", e);
+                Some(InstanceGuard { _mutex: None })
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        Some(InstanceGuard {})
+    }
+}
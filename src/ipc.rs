@@ -0,0 +1,146 @@
+//! IPC module - JSON commands over a named pipe for local scripts
+//!
+//! Each request is one line of JSON and gets one line of JSON back, e.g. from PowerShell:
+//! `{"command":"run","task":"Backup"}`, `{"command":"pause","minutes":30}`,
+//! `{"command":"resume"}`, `{"command":"status"}`, `{"command":"show"}`.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+/// Pipe the app listens on
+pub const PIPE_NAME: &str = r"\\.\pipe\routine-runner";
+
+/// A command sent by a script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum IpcRequest {
+    /// Start a task now, by id or by (case-insensitive) name
+    Run { task: String },
+    /// Pause the scheduler, for the given minutes or until resumed
+    Pause {
+        #[serde(default)]
+        minutes: Option<u32>,
+    },
+    Resume,
+    Status,
+    /// Bring the main window to the front
+    Show,
+}
+
+/// Reply line for a request's result
+pub fn response_line(result: Result<serde_json::Value, String>) -> String {
+    let response = match result {
+        Ok(result) => serde_json::json!({ "ok": true, "result": result }),
+        Err(error) => serde_json::json!({ "ok": false, "error": error }),
+    };
+    format!("{}\n", response)
+}
+
+/// Send a request to the running app. None if no instance is listening on the pipe.
+pub fn send(request: &IpcRequest) -> Option<Result<serde_json::Value, String>> {
+    #[cfg(windows)]
+    {
+        let pipe = std::fs::OpenOptions::new().read(true).write(true).open(PIPE_NAME).ok()?;
+        Some(exchange(pipe, request))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = request;
+        None
+    }
+}
+
+#[cfg(windows)]
+fn exchange(mut pipe: std::fs::File, request: &IpcRequest) -> Result<serde_json::Value, String> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+    writeln!(pipe, "{}", line).map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    BufReader::new(&pipe).read_line(&mut reply).map_err(|e| e.to_string())?;
+    let mut reply: serde_json::Value = serde_json::from_str(&reply).map_err(|e| e.to_string())?;
+    if reply["ok"].as_bool() == Some(true) {
+        Ok(reply["result"].take())
+    } else {
+        Err(reply["error"].as_str().unwrap_or("Unknown error").to_string())
+    }
+}
+
+/// Accept clients on the pipe until the app exits, answering each request with `handle`
+pub async fn serve_loop<F, Fut>(handle: F)
+where
+    F: Fn(IpcRequest) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result<serde_json::Value, String>> + Send,
+{
+    #[cfg(windows)]
+    {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        // Owning the first instance keeps a second copy of the app from answering too
+        let mut server = match ServerOptions::new().first_pipe_instance(true).create(PIPE_NAME) {
+            Ok(server) => server,
+            Err(e) => {
+                tracing::error!("Failed to create pipe {}: {}", PIPE_NAME, e);
+                return;
+            }
+        };
+        tracing::info!("Listening for commands on {}", PIPE_NAME);
+
+        loop {
+            if let Err(e) = server.connect().await {
+                tracing::warn!("Failed to accept pipe client: {}", e);
+                continue;
+            }
+            let client = server;
+            server = match ServerOptions::new().create(PIPE_NAME) {
+                Ok(server) => server,
+                Err(e) => {
+                    tracing::error!("Failed to create pipe {}: {}", PIPE_NAME, e);
+                    return;
+                }
+            };
+
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve_client(client, handle).await {
+                    tracing::debug!("Pipe client failed: {}", e);
+                }
+            });
+        }
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = handle;
+        tracing::info!("Named pipe commands are only available on Windows");
+    }
+}
+
+/// Answer the requests of one client, line by line, until it disconnects
+#[cfg(windows)]
+async fn serve_client<F, Fut>(
+    pipe: tokio::net::windows::named_pipe::NamedPipeServer,
+    handle: F,
+) -> std::io::Result<()>
+where
+    F: Fn(IpcRequest) -> Fut,
+    Fut: Future<Output = Result<serde_json::Value, String>>,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = tokio::io::split(pipe);
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(request) => handle(request).await,
+            Err(e) => Err(format!("Invalid request: {}", e)),
+        };
+        writer.write_all(response_line(result).as_bytes()).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
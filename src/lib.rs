@@ -8,7 +8,39 @@ pub mod scheduler;
 pub mod scheduler_runner;
 pub mod executor;
 pub mod conditions;
+pub mod config_history;
+pub mod email;
+pub mod eventlog;
 pub mod autostart;
+pub mod background;
+pub mod backup;
+pub mod chat;
+pub mod cli;
+pub mod crash;
+pub mod data_location;
+pub mod deeplink;
+pub mod health;
+pub mod http;
+pub mod ical;
+pub mod inspect;
+pub mod instance;
+pub mod ipc;
+pub mod log_export;
+pub mod logging;
+pub mod maintenance;
+pub mod messages;
+pub mod metrics;
+pub mod mqtt;
+pub mod notifications;
+pub mod profiles;
+pub mod secrets;
+pub mod startup_import;
+pub mod task_file;
+pub mod task_sync;
+pub mod updater;
+pub mod validation;
+pub mod webhooks;
+pub mod webhook_server;
 pub mod commands;
 
 pub use models::*;
@@ -0,0 +1,82 @@
+//! Log export module - Write run logs to CSV for spreadsheets
+
+use crate::models::*;
+use crate::storage::Database;
+use std::io::Write;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum LogExportError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+impl From<crate::storage::QueryPanicked> for LogExportError {
+    fn from(e: crate::storage::QueryPanicked) -> Self {
+        LogExportError::Database(e.into())
+    }
+}
+
+/// Column headers; output is left out since it can exceed a spreadsheet cell
+const HEADER: [&str; 12] = [
+    "run_id", "task_id", "task_name", "trigger_type", "status", "skip_reason",
+    "scheduled_time", "started_at", "finished_at", "duration_ms", "exit_code", "error_message",
+];
+
+/// Write the logs matching `filter` to a CSV file, returns how many were written
+pub fn export_logs_csv(db: &Database, path: &Path, filter: &LogFilter) -> Result<usize, LogExportError> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    // BOM so Excel reads the file as UTF-8 (task names are often Vietnamese)
+    out.write_all("\u{feff}".as_bytes())?;
+    write_csv(db, filter, &mut out)
+}
+
+/// Write the header and the logs matching `filter` as CSV rows
+fn write_csv(db: &Database, filter: &LogFilter, out: &mut impl Write) -> Result<usize, LogExportError> {
+    write_row(out, HEADER.iter().map(|h| h.to_string()))?;
+    let count = db.for_each_log(filter, |log| write_row(out, log_fields(&log)))?;
+    out.flush()?;
+    Ok(count)
+}
+
+/// A log's values in HEADER order; times are local, as they'd appear in the UI
+fn log_fields(log: &RunLog) -> [String; 12] {
+    let local = |t: chrono::DateTime<chrono::Utc>| {
+        t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string()
+    };
+    let name = |value: serde_json::Value| value.as_str().unwrap_or_default().to_string();
+    [
+        log.run_id.clone(),
+        log.task_id.clone(),
+        log.task_name.clone(),
+        log.trigger_type.clone(),
+        name(serde_json::to_value(&log.status).unwrap_or_default()),
+        log.skip_reason.as_ref().map(|r| name(serde_json::to_value(r).unwrap_or_default())).unwrap_or_default(),
+        log.scheduled_time_utc.map(local).unwrap_or_default(),
+        local(log.started_at_utc),
+        log.finished_at_utc.map(local).unwrap_or_default(),
+        log.duration_ms.map(|d| d.to_string()).unwrap_or_default(),
+        log.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+        log.error_message.clone().unwrap_or_default(),
+    ]
+}
+
+fn write_row(out: &mut impl Write, fields: impl IntoIterator<Item = String>) -> Result<(), LogExportError> {
+    let line: Vec<String> = fields.into_iter().map(|f| escape(&f)).collect();
+    out.write_all(line.join(",").as_bytes())?;
+    out.write_all(b"\r\n")?;
+    Ok(())
+}
+
+/// Quote a field if it contains a separator, quote or line break
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
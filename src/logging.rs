@@ -0,0 +1,210 @@
+//! Logging module - Console output, daily log files users can attach to bug reports,
+//! and the latest events in memory for the app's log panel
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{layer::Context, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Log files are named "routine-runner.YYYY-MM-DD.log"
+const LOG_FILE_PREFIX: &str = "routine-runner";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Days of log files kept; older ones are deleted as new ones start
+const LOG_FILES_KEPT: usize = 14;
+
+/// What goes to the files when RUST_LOG is not set: enough to tell why a task did or didn't run
+const DEFAULT_FILE_FILTER: &str = "warn,auto_open=info,auto_open_lib=info";
+
+/// Most lines `tail` returns
+pub const MAX_TAIL_LINES: usize = 5000;
+
+/// Events kept in memory; the oldest is dropped for each new one beyond this
+const RECENT_EVENTS: usize = 3000;
+
+/// Latest events, oldest first
+static RECENT: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+/// One logged event
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LogEntry {
+    /// Increases by one per event, so a poll can ask for what came after the last one it saw
+    pub seq: u64,
+    pub time_utc: DateTime<Utc>,
+    /// "ERROR", "WARN", "INFO", "DEBUG" or "TRACE"
+    pub level: String,
+    /// Module that logged it, e.g. "auto_open_lib::scheduler_runner"
+    pub target: String,
+    /// Message followed by any other fields as `name=value`
+    pub message: String,
+}
+
+/// Collects an event's fields into one line
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            self.0.push_str(&format!("{:?}", value));
+        } else {
+            self.0.push_str(&format!("{}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Keeps events in [`RECENT`]
+struct RecentLayer;
+
+impl<S: tracing::Subscriber> Layer<S> for RecentLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+
+        let mut recent = RECENT.lock().unwrap();
+        let seq = recent.back().map_or(1, |last| last.seq + 1);
+        if recent.len() >= RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(LogEntry {
+            seq,
+            time_utc: Utc::now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message: visitor.0,
+        });
+    }
+}
+
+/// Filter for the files and memory: RUST_LOG if set, the default otherwise
+fn kept_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILE_FILTER))
+}
+
+/// Directory holding the log files under the app data dir
+pub fn log_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("logs")
+}
+
+/// Log to the console, to memory and, when the data dir is known, to rotating files.
+/// Returns the guard that flushes the files; keep it until exit.
+pub fn init(data_dir: Option<&Path>) -> Option<WorkerGuard> {
+    let console = tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env());
+    let recent = RecentLayer.with_filter(kept_filter());
+
+    let appender = data_dir.map(log_dir).and_then(|dir| {
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix(LOG_FILE_SUFFIX)
+            .max_log_files(LOG_FILES_KEPT)
+            .build(&dir)
+            .map_err(|e| eprintln!("Failed to open log files in {}: {}", dir.display(), e))
+            .ok()
+    });
+    let Some(appender) = appender else {
+        tracing_subscriber::registry().with(console).with(recent).init();
+        return None;
+    };
+
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    let file = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_filter(kept_filter());
+    tracing_subscriber::registry().with(console).with(recent).with(file).init();
+    Some(guard)
+}
+
+/// Log files, newest first
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![] };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(LOG_FILE_PREFIX) && n.ends_with(LOG_FILE_SUFFIX))
+        })
+        .collect();
+    // Names embed the date, so they sort oldest first
+    files.sort();
+    files.reverse();
+    files
+}
+
+/// The last `lines` lines logged, reaching into earlier files if today's is short
+pub fn tail(dir: &Path, lines: usize) -> Result<String, String> {
+    let lines = lines.min(MAX_TAIL_LINES);
+    let mut collected: Vec<String> = Vec::new();
+    for path in log_files(dir) {
+        if collected.len() >= lines {
+            break;
+        }
+        let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let text = String::from_utf8_lossy(&bytes);
+        let wanted = lines - collected.len();
+        let file_lines: Vec<&str> = text.lines().collect();
+        let start = file_lines.len().saturating_sub(wanted);
+        // Earlier files go in front of what was already collected
+        collected.splice(0..0, file_lines[start..].iter().map(|l| l.to_string()));
+    }
+    Ok(collected.join("\n"))
+}
+
+/// Events kept in memory after `after_seq`, at least as severe as `level` and from modules
+/// starting with `module`, oldest first
+pub fn recent(level: Option<&str>, module: Option<&str>, after_seq: Option<u64>) -> Result<Vec<LogEntry>, String> {
+    let level = level
+        .map(|l| l.parse::<tracing::Level>().map_err(|_| format!("Unknown log level: {}", l)))
+        .transpose()?;
+    let recent = RECENT.lock().unwrap();
+    Ok(recent
+        .iter()
+        .filter(|entry| after_seq.is_none_or(|seq| entry.seq > seq))
+        // More verbose levels compare greater
+        .filter(|entry| level.is_none_or(|max| entry.level.parse::<tracing::Level>().is_ok_and(|l| l <= max)))
+        .filter(|entry| module.is_none_or(|m| entry.target.starts_with(m)))
+        .cloned()
+        .collect())
+}
+
+/// The last `count` events kept in memory, oldest first; empty if they are being written,
+/// since this runs from the panic hook, which must not block
+pub fn last_events(count: usize) -> Vec<LogEntry> {
+    let recent = match RECENT.try_lock() {
+        Ok(recent) => recent,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => return vec![],
+    };
+    recent.iter().skip(recent.len().saturating_sub(count)).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_reaches_into_earlier_files() {
+        let dir = std::env::temp_dir().join(format!("routine-runner-logs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("routine-runner.2026-01-01.log"), "a\nb\nc\n").unwrap();
+        std::fs::write(dir.join("routine-runner.2026-01-02.log"), "d\ne\n").unwrap();
+        std::fs::write(dir.join("other.txt"), "x\n").unwrap();
+
+        assert_eq!(tail(&dir, 1).unwrap(), "e");
+        assert_eq!(tail(&dir, 3).unwrap(), "c\nd\ne");
+        assert_eq!(tail(&dir, 10).unwrap(), "a\nb\nc\nd\ne");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,62 +2,257 @@
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use auto_open_lib::commands;
-use tauri::{Manager, menu::{Menu, MenuItem}, tray::TrayIconBuilder};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use auto_open_lib::commands::{self, AppState, DatabaseRecovered, ProfileList, EVENT_DATABASE_RECOVERED, EVENT_PROFILES_CHANGED, EVENT_TASKS_CHANGED};
+use auto_open_lib::background;
+use auto_open_lib::deeplink;
+use auto_open_lib::instance;
+use auto_open_lib::ipc;
+use auto_open_lib::models::{RunLog, RunStatus};
+use auto_open_lib::scheduler_runner::{
+    NextRun, PauseChanged, QueueChanged, EVENT_NEXT_RUN_CHANGED, EVENT_PAUSE_CHANGED, EVENT_QUEUE_CHANGED, EVENT_TASK_FINISHED,
+    EVENT_TASK_SKIPPED,
+};
+use chrono::{Duration, Local, TimeZone, Utc};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Listener, Manager, Runtime, image::Image, menu::{CheckMenuItem, Menu, MenuItem, Submenu}, tray::TrayIconBuilder};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult};
+use tauri_plugin_global_shortcut::ShortcutState;
+
+/// Id of the app's tray icon ("main" is taken by the icon from tauri.conf.json)
+const TRAY_ID: &str = "routine-runner";
+
+/// Runs listed in the tray's recent menu
+const RECENT_RUNS: u32 = 5;
+
+/// How often the tray tooltip's countdown is refreshed
+const TOOLTIP_REFRESH_SECS: u64 = 30;
 
 fn main() {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    // Initialize logging; the guard flushes the log file on exit
+    let log_guard = auto_open_lib::logging::init(auto_open_lib::cli::app_data_dir().as_deref());
+    auto_open_lib::crash::install_panic_hook(auto_open_lib::cli::app_data_dir());
+
+    // Subcommands (`routine-runner list`, `run <task>`, ...) run headless and exit
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(code) = auto_open_lib::cli::run(&args) {
+        drop(log_guard);
+        std::process::exit(code);
+    }
+    // A routine-runner:// link goes to the running instance, or starts one that runs it
+    let deep_link = args.iter().find_map(|arg| deeplink::parse(arg));
+    if deep_link.as_ref().is_some_and(deeplink::forward) {
+        std::process::exit(0);
+    }
+    // A second copy would run every task twice; bring the running one forward instead
+    let Some(_instance) = instance::acquire() else {
+        tracing::info!("Routine Runner is already running");
+        if let Some(Err(e)) = ipc::send(&ipc::IpcRequest::Show) {
+            tracing::warn!("Failed to show the running instance: {}", e);
+        }
+        std::process::exit(0);
+    };
 
     tracing::info!("Starting Routine Runner...");
 
+    // The background engine stops scheduling once the app runs in the user's session
+    background::hand_over();
+
+    // Open the database before Tauri creates the window, so the frontend's first commands find it
+    // (`--profile <name>` picks the profile, otherwise the last one)
+    let profile = args.iter()
+        .position(|arg| arg == "--profile")
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str);
+    let first_run = !auto_open_lib::data_location::is_settled();
+    let state = Arc::new(AppState::default());
+    let opened = auto_open_lib::cli::app_data_dir()
+        .ok_or_else(|| "No application data directory".to_string())
+        .and_then(|app_data_dir| {
+            std::fs::create_dir_all(&app_data_dir).map_err(|e| e.to_string())?;
+            tracing::info!("Data directory: {:?}", app_data_dir);
+            commands::init_database(&state, &app_data_dir, profile)
+        });
+
     tauri::Builder::default()
+        .manage(state.clone())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
-        .setup(|app| {
-            // Single instance check - prevent multiple instances
-            let app_data_dir = app.path().app_data_dir()?;
-            std::fs::create_dir_all(&app_data_dir)?;
-            
-            let lock_file = app_data_dir.join(".lock");
-            
-            // Try to create lock file
-            if lock_file.exists() {
-                // Check if the lock is stale (older than 5 seconds)
-                if let Ok(metadata) = std::fs::metadata(&lock_file) {
-                    if let Ok(modified) = metadata.modified() {
-                        if let Ok(elapsed) = modified.elapsed() {
-                            if elapsed.as_secs() < 5 {
-                                tracing::warn!("Another instance is already running. Exiting...");
-                                std::process::exit(0);
-                            }
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|_app, _shortcut, event| {
+                    if event.state == ShortcutState::Pressed {
+                        if let Err(e) = commands::show_quick_run() {
+                            tracing::error!("Failed to open quick-run palette: {}", e);
                         }
                     }
+                })
+                .build(),
+        )
+        .setup(move |app| {
+            let db_ready = match opened {
+                Ok(recovered) => {
+                    if let Some(recovered) = recovered {
+                        show_database_recovered(app.handle(), &recovered);
+                    }
+                    true
                 }
-            }
-            
-            // Create/update lock file
-            std::fs::write(&lock_file, std::process::id().to_string())?;
-            tracing::info!("Data directory: {:?}", app_data_dir);
-
-            // Initialize database
-            if let Err(e) = commands::init_database(&app_data_dir) {
-                tracing::error!("Failed to initialize database: {}", e);
-            }
+                Err(e) => {
+                    tracing::error!("Failed to initialize database: {}", e);
+                    false
+                }
+            };
 
             // Setup tray menu
             let show_item = MenuItem::with_id(app, "show", "Mở Routine Runner", true, None::<&str>)?;
-            let pause_item = MenuItem::with_id(app, "pause", "Tạm dừng", true, None::<&str>)?;
+            let pause_item = CheckMenuItem::with_id(app, "pause", "Tạm dừng", true, false, None::<&str>)?;
+            let pause_hour_item = MenuItem::with_id(app, "pause_1h", "Tạm dừng 1 giờ", true, None::<&str>)?;
+            let pause_tomorrow_item = MenuItem::with_id(app, "pause_tomorrow", "Tạm dừng đến 08:00 sáng mai", true, None::<&str>)?;
+            let paused_until_item = MenuItem::with_id(app, "resume_timed", "Đang chạy", false, None::<&str>)?;
+            let profiles_menu = Submenu::with_id(app, "profiles", "Hồ sơ", db_ready)?;
+            if let Ok(list) = commands::profile_list(&state) {
+                fill_profiles_menu(app.handle(), &profiles_menu, &list)?;
+            }
+            let run_menu = Submenu::with_id(app, "run", "Chạy tác vụ", db_ready)?;
+            fill_run_menu(app.handle(), &run_menu)?;
+            let recent_menu = Submenu::with_id(app, "recent", "Gần đây", db_ready)?;
+            fill_recent_menu(app.handle(), &recent_menu)?;
             let quit_item = MenuItem::with_id(app, "quit", "Thoát", true, None::<&str>)?;
             
-            let menu = Menu::with_items(app, &[&show_item, &pause_item, &quit_item])?;
+            let menu = Menu::with_items(app, &[
+                &show_item,
+                &run_menu,
+                &recent_menu,
+                &pause_item,
+                &pause_hour_item,
+                &pause_tomorrow_item,
+                &paused_until_item,
+                &profiles_menu,
+                &quit_item,
+            ])?;
+            
+            // Keep the profile list and its check mark current
+            let handle = app.handle().clone();
+            let tasks_menu = run_menu.clone();
+            app.listen(EVENT_PROFILES_CHANGED, move |event| {
+                if let Ok(list) = serde_json::from_str::<ProfileList>(event.payload()) {
+                    if let Err(e) = fill_profiles_menu(&handle, &profiles_menu, &list) {
+                        tracing::warn!("Failed to update profile menu: {}", e);
+                    }
+                }
+                if let Err(e) = fill_run_menu(&handle, &tasks_menu) {
+                    tracing::warn!("Failed to update run menu: {}", e);
+                }
+            });
+            
+            // List the current tasks in the run menu
+            let handle = app.handle().clone();
+            app.listen(EVENT_TASKS_CHANGED, move |_| {
+                if let Err(e) = fill_run_menu(&handle, &run_menu) {
+                    tracing::warn!("Failed to update run menu: {}", e);
+                }
+            });
+            
+            // Tell the user when a profile opened later had to start from an empty database
+            let handle = app.handle().clone();
+            app.listen(EVENT_DATABASE_RECOVERED, move |event| {
+                if let Ok(recovered) = serde_json::from_str::<DatabaseRecovered>(event.payload()) {
+                    show_database_recovered(&handle, &recovered);
+                }
+            });
+            
+            commands::init_notifications(app.handle());
+            commands::init_ipc(&state);
+            
+            // Keep the tray icon in step with the scheduler
+            let status = Arc::new(TrayStatus {
+                app: app.handle().clone(),
+                icon: app.default_window_icon().unwrap().clone(),
+                state: Mutex::new(TrayState::default()),
+            });
+            let tray_status = status.clone();
+            app.listen(EVENT_QUEUE_CHANGED, move |event| {
+                if let Ok(queue) = serde_json::from_str::<QueueChanged>(event.payload()) {
+                    tray_status.update(|state| state.running = !queue.running_task_ids.is_empty());
+                }
+            });
+            let tray_status = status.clone();
+            app.listen(EVENT_TASK_FINISHED, move |event| {
+                if let Ok(log) = serde_json::from_str::<RunLog>(event.payload()) {
+                    tray_status.update(|state| match log.status {
+                        RunStatus::Failed => {
+                            state.failed.insert(log.task_id);
+                        }
+                        RunStatus::Success => {
+                            state.failed.remove(&log.task_id);
+                        }
+                        _ => {}
+                    });
+                }
+            });
+            let tray_status = status.clone();
+            let runs_menu = recent_menu.clone();
+            let handle = app.handle().clone();
+            app.listen(EVENT_PROFILES_CHANGED, move |_| {
+                tray_status.update(|state| state.failed.clear());
+                if let Err(e) = fill_recent_menu(&handle, &runs_menu) {
+                    tracing::warn!("Failed to update recent runs menu: {}", e);
+                }
+            });
+            
+            // List the latest results in the recent menu
+            for event in [EVENT_TASK_FINISHED, EVENT_TASK_SKIPPED] {
+                let handle = app.handle().clone();
+                let recent_menu = recent_menu.clone();
+                app.listen(event, move |_| {
+                    if let Err(e) = fill_recent_menu(&handle, &recent_menu) {
+                        tracing::warn!("Failed to update recent runs menu: {}", e);
+                    }
+                });
+            }
+            
+            // Check the pause item, grey out the tray icon and show the resume deadline
+            // of a timed pause; clicking the deadline resumes early
+            let toggle_item = pause_item.clone();
+            app.listen(EVENT_PAUSE_CHANGED, move |event| {
+                let Ok(change) = serde_json::from_str::<PauseChanged>(event.payload()) else {
+                    return;
+                };
+                let _ = toggle_item.set_checked(change.paused);
+                status.update(|state| state.paused = change.paused);
+                let (text, enabled) = match change.paused_until_utc {
+                    Some(until) => (
+                        format!("Tiếp tục (tự chạy lại lúc {})", until.with_timezone(&Local).format("%H:%M %d/%m")),
+                        true,
+                    ),
+                    None if change.paused => ("Đã tạm dừng".to_string(), false),
+                    None => ("Đang chạy".to_string(), false),
+                };
+                let _ = paused_until_item.set_text(text);
+                let _ = paused_until_item.set_enabled(enabled);
+            });
+
+            // Show the next planned run in the tray tooltip, counting down between changes
+            let next_run = Arc::new(Mutex::new(None::<NextRun>));
+            let handle = app.handle().clone();
+            let latest = next_run.clone();
+            app.listen(EVENT_NEXT_RUN_CHANGED, move |event| {
+                if let Ok(next) = serde_json::from_str::<Option<NextRun>>(event.payload()) {
+                    *latest.lock().unwrap() = next;
+                    update_tray_tooltip(&handle, latest.lock().unwrap().as_ref());
+                }
+            });
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    tokio::time::sleep(tokio::time::Duration::from_secs(TOOLTIP_REFRESH_SECS)).await;
+                    update_tray_tooltip(&handle, next_run.lock().unwrap().as_ref());
+                }
+            });
 
-            let _tray = TrayIconBuilder::new()
+            let _tray = TrayIconBuilder::with_id(TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
+                .tooltip("Routine Runner")
                 .menu(&menu)
                 .show_menu_on_left_click(false)
                 .on_menu_event(|app, event| {
@@ -70,13 +265,56 @@ fn main() {
                         }
                         "pause" => {
                             tracing::info!("Pause/Resume clicked");
-                            // TODO: Toggle scheduler pause
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = commands::toggle_scheduler_pause(app.state()).await {
+                                    tracing::error!("Failed to toggle scheduler pause: {}", e);
+                                }
+                            });
+                        }
+                        "pause_1h" => {
+                            pause_scheduler_until(app, Utc::now() + Duration::hours(1));
+                        }
+                        "pause_tomorrow" => {
+                            let tomorrow = Local::now().date_naive() + Duration::days(1);
+                            let eight = tomorrow.and_hms_opt(8, 0, 0).unwrap();
+                            if let Some(until) = Local.from_local_datetime(&eight).earliest() {
+                                pause_scheduler_until(app, until.with_timezone(&Utc));
+                            }
+                        }
+                        "resume_timed" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = commands::resume_scheduler(app.state()).await {
+                                    tracing::error!("Failed to resume scheduler: {}", e);
+                                }
+                            });
                         }
                         "quit" => {
                             tracing::info!("Quit clicked");
-                            app.exit(0);
+                            quit(app);
+                        }
+                        id => {
+                            if let Some(run_id) = id.strip_prefix("recent:") {
+                                if let Err(e) = commands::open_run_log(run_id.to_string()) {
+                                    tracing::error!("Failed to open run log: {}", e);
+                                }
+                            } else if let Some(task_id) = id.strip_prefix("run:") {
+                                let (app, task_id) = (app.clone(), task_id.to_string());
+                                tauri::async_runtime::spawn(async move {
+                                    if let Err(e) = commands::run_task_now(app.state(), task_id).await {
+                                        tracing::error!("Failed to run task from tray: {}", e);
+                                    }
+                                });
+                            } else if let Some(name) = id.strip_prefix("profile:") {
+                                let (app, name) = (app.clone(), name.to_string());
+                                tauri::async_runtime::spawn(async move {
+                                    if let Err(e) = commands::switch_profile(app.state(), name).await {
+                                        tracing::error!("Failed to switch profile: {}", e);
+                                    }
+                                });
+                            }
                         }
-                        _ => {}
                     }
                 })
                 .on_tray_icon_event(|tray, event| {
@@ -89,17 +327,75 @@ fn main() {
                     }
                 })
                 .build(app)?;
+            
+            // Start the scheduler once the tray listens to its events
+            if db_ready {
+                if let Err(e) = commands::init_scheduler(&state, app.handle().clone()) {
+                    tracing::error!("Failed to start scheduler: {}", e);
+                }
+                if let Err(e) = commands::init_log_flush(&state) {
+                    tracing::error!("Failed to start run log writer: {}", e);
+                }
+                if let Err(e) = commands::init_task_file_watcher(&state) {
+                    tracing::error!("Failed to watch task file: {}", e);
+                }
+                if let Err(e) = commands::init_task_sync(&state) {
+                    tracing::error!("Failed to start task sync: {}", e);
+                }
+                if let Err(e) = commands::init_auto_backup(&state) {
+                    tracing::error!("Failed to start automatic backups: {}", e);
+                }
+                if let Err(e) = commands::init_config_history(&state) {
+                    tracing::error!("Failed to start configuration history: {}", e);
+                }
+                if let Err(e) = commands::init_maintenance(&state) {
+                    tracing::error!("Failed to start database maintenance: {}", e);
+                }
+                if let Err(e) = commands::init_email_digest(&state) {
+                    tracing::error!("Failed to start email digest: {}", e);
+                }
+                if let Err(e) = commands::init_metrics(&state) {
+                    tracing::error!("Failed to start metrics endpoint: {}", e);
+                }
+                if let Err(e) = commands::init_mqtt(&state) {
+                    tracing::error!("Failed to start MQTT bridge: {}", e);
+                }
+                if let Err(e) = commands::init_webhook_server(&state) {
+                    tracing::error!("Failed to start webhook server: {}", e);
+                }
+                if let Err(e) = commands::init_auto_update(&state) {
+                    tracing::error!("Failed to start update check: {}", e);
+                }
+                if let Err(e) = commands::init_quick_run_hotkey(&state) {
+                    tracing::error!("Failed to register quick-run hotkey: {}", e);
+                }
+                if let Some(link) = deep_link.clone() {
+                    commands::open_deep_link(&state, link);
+                }
+                if first_run {
+                    ask_data_location(app.handle());
+                }
+            }
+            #[cfg(windows)]
+            if let Err(e) = deeplink::register() {
+                tracing::warn!("Failed to register {}:// links: {}", deeplink::SCHEME, e);
+            }
 
             // Handle window close -> hide to tray
             let main_window = app.get_webview_window("main").unwrap();
             
-            // Check if started with --tray flag (from autostart)
-            let args: Vec<String> = std::env::args().collect();
-            let start_in_tray = args.iter().any(|arg| arg == "--tray");
+            // The window starts hidden (see tauri.conf.json) so a start in the tray doesn't flash it.
+            // Stay in the tray with --tray (written by autostart), for a deep link, or when
+            // the settings ask for it
+            let start_in_tray = args.iter().any(|arg| arg == "--tray")
+                || deep_link.is_some()
+                || commands::load_settings(&state).is_ok_and(|s| s.start_minimized_to_tray);
             
             if start_in_tray {
-                tracing::info!("Starting in tray mode (autostart)");
-                let _ = main_window.hide();
+                tracing::info!("Starting in tray mode");
+            } else {
+                let _ = main_window.show();
+                let _ = main_window.set_focus();
             }
             
             let window_clone = main_window.clone();
@@ -115,21 +411,388 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_tasks,
+            commands::search_tasks,
             commands::get_tasks_with_state,
+            commands::get_tags,
+            commands::set_enabled_by_tag,
+            commands::bulk_update_tasks,
+            commands::get_groups,
+            commands::create_group,
+            commands::update_group,
+            commands::delete_group,
+            commands::move_task_to_group,
+            commands::get_templates,
+            commands::save_task_as_template,
+            commands::delete_template,
+            commands::create_task_from_template,
             commands::get_task_states,
             commands::get_running_processes,
+            commands::kill_task_process,
+            commands::get_webhook_deliveries,
+            commands::test_webhook,
+            commands::test_chat_notifier,
+            commands::test_email,
+            commands::is_event_log_source_registered,
+            commands::register_event_log_source,
+            commands::get_upcoming_runs,
+            commands::export_ical,
+            commands::get_health_summary,
+            commands::reveal_in_explorer,
+            commands::open_data_folder,
+            commands::get_log_tail,
+            commands::get_diagnostics,
+            commands::get_messages,
+            commands::check_for_updates,
+            commands::install_update,
+            commands::inspect_target,
+            commands::generate_webhook_token,
+            commands::get_background_engine,
+            commands::set_background_engine,
+            commands::scan_startup_shortcuts,
+            commands::import_startup_shortcuts,
+            commands::preview_next_runs,
+            commands::get_schedule_calendar,
             commands::create_task,
             commands::update_task,
+            commands::validate_task,
+            commands::test_conditions,
+            commands::get_system_snapshot,
             commands::delete_task,
+            commands::get_task_history,
+            commands::revert_task_to_version,
+            commands::get_deleted_tasks,
+            commands::restore_task,
+            commands::purge_trash,
             commands::run_task_now,
+            commands::run_task_now_with,
+            commands::get_run_status,
+            commands::subscribe_run_output,
+            commands::unsubscribe_run_output,
+            commands::snooze_task,
+            commands::pause_task_until,
             commands::get_logs,
+            commands::get_logs_for_task,
             commands::get_log_detail,
+            commands::search_logs,
+            commands::export_logs_csv,
+            commands::get_task_stats,
+            commands::get_config_versions,
+            commands::diff_config,
+            commands::cleanup_logs,
+            commands::clear_logs,
             commands::get_settings,
             commands::update_settings,
+            commands::set_dry_run,
+            commands::get_scheduler_status,
+            commands::get_engine_metrics,
+            commands::pause_scheduler,
+            commands::pause_scheduler_until,
+            commands::is_scheduler_paused,
+            commands::toggle_scheduler_pause,
+            commands::resume_scheduler,
             commands::get_autostart_status,
             commands::set_autostart,
             commands::save_config_file,
+            commands::export_tasks,
+            commands::import_tasks,
+            commands::sync_tasks_now,
+            commands::set_task_file,
+            commands::reload_task_file,
+            commands::compact_database,
+            commands::get_profiles,
+            commands::create_profile,
+            commands::switch_profile,
+            commands::get_data_location,
+            commands::move_data_dir,
+            commands::backup_database,
+            commands::restore_database,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                let state = app.state::<Arc<AppState>>();
+                // Also reached without the quit dialog: when Windows ends the session or an update installs
+                let drained = tauri::async_runtime::block_on(commands::drain_runs(&state, false, Some(commands::EXIT_GRACE_SECS)));
+                if let Err(e) = drained {
+                    tracing::error!("Failed to wait for running tasks: {}", e);
+                }
+                commands::shutdown(&state);
+                // Keep the schedules going after the app is closed
+                if background::is_installed() {
+                    if let Err(e) = background::start() {
+                        tracing::error!("Failed to start background engine: {}", e);
+                    }
+                }
+            }
+        });
+}
+
+/// Set the tray tooltip to the next planned run, e.g. "Tiếp theo: Backup sau 23 phút"
+fn update_tray_tooltip<R: Runtime>(app: &AppHandle<R>, next: Option<&NextRun>) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else { return };
+    let text = match next {
+        Some(next) => {
+            let at = next.next_run_at_utc.with_timezone(&Local);
+            let minutes = ((next.next_run_at_utc - Utc::now()).num_seconds().max(0) + 59) / 60;
+            let when = if minutes == 0 {
+                "ngay bây giờ".to_string()
+            } else if minutes < 60 {
+                format!("sau {} phút", minutes)
+            } else if at.date_naive() == Local::now().date_naive() {
+                format!("lúc {}", at.format("%H:%M"))
+            } else {
+                format!("lúc {}", at.format("%H:%M %d/%m"))
+            };
+            // Windows cuts tooltips at 127 characters
+            let name: String = next.task_name.chars().take(60).collect();
+            format!("Routine Runner\nTiếp theo: {} {}", name, when)
+        }
+        None => "Routine Runner\nKhông có lịch chạy nào".to_string(),
+    };
+    let _ = tray.set_tooltip(Some(text));
+}
+
+/// What the tray icon shows
+#[derive(Default)]
+struct TrayState {
+    paused: bool,
+    running: bool,
+    /// Tasks whose last run failed
+    failed: HashSet<String>,
+}
+
+/// The tray icon: greyed out while paused, with a green dot while a task runs
+/// and a red badge while a task's last run failed
+struct TrayStatus {
+    app: AppHandle,
+    icon: Image<'static>,
+    state: Mutex<TrayState>,
+}
+
+impl TrayStatus {
+    fn update(&self, change: impl FnOnce(&mut TrayState)) {
+        let mut state = self.state.lock().unwrap();
+        let shown = |s: &TrayState| (s.paused, s.running, !s.failed.is_empty());
+        let before = shown(&state);
+        change(&mut state);
+        if shown(&state) == before {
+            return;
+        }
+        if let Some(tray) = self.app.tray_by_id(TRAY_ID) {
+            let _ = tray.set_icon(Some(tray_icon(&self.icon, &state)));
+        }
+    }
+}
+
+/// Draw the icon for a state
+fn tray_icon(icon: &Image<'_>, state: &TrayState) -> Image<'static> {
+    let (width, height) = (icon.width() as usize, icon.height() as usize);
+    let mut rgba = icon.rgba().to_vec();
+    if state.paused {
+        for pixel in rgba.chunks_exact_mut(4) {
+            let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]].map(u32::from);
+            let grey = ((r * 30 + g * 59 + b * 11) / 100) as u8;
+            pixel.copy_from_slice(&[grey, grey, grey, (a / 2) as u8]);
+        }
+    }
+
+    // Dots of 3/8 the icon size in the bottom-right (running) and top-right (failure) corners
+    let radius = width.min(height) * 3 / 16;
+    let mut dot = |center_y: usize, color: [u8; 3]| {
+        let center_x = width - radius - 1;
+        for y in center_y.saturating_sub(radius)..(center_y + radius + 1).min(height) {
+            for x in center_x.saturating_sub(radius)..(center_x + radius + 1).min(width) {
+                let (dx, dy) = (x.abs_diff(center_x), y.abs_diff(center_y));
+                if dx * dx + dy * dy <= radius * radius {
+                    let at = (y * width + x) * 4;
+                    rgba[at..at + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+                }
+            }
+        }
+    };
+    if state.running {
+        dot(height - radius - 1, [46, 204, 113]);
+    }
+    if !state.failed.is_empty() {
+        dot(radius, [231, 76, 60]);
+    }
+    Image::new_owned(rgba, icon.width(), icon.height())
+}
+
+/// Rebuild the tray's profile submenu, checking the open profile
+fn fill_profiles_menu<R: Runtime>(app: &AppHandle<R>, menu: &Submenu<R>, list: &ProfileList) -> tauri::Result<()> {
+    for item in menu.items()? {
+        menu.remove(&item)?;
+    }
+    for name in &list.profiles {
+        let item = CheckMenuItem::with_id(
+            app, format!("profile:{}", name), name, true, *name == list.active, None::<&str>,
+        )?;
+        menu.append(&item)?;
+    }
+    Ok(())
+}
+
+/// Rebuild the tray's run submenu from the enabled tasks
+fn fill_run_menu<R: Runtime>(app: &AppHandle<R>, menu: &Submenu<R>) -> tauri::Result<()> {
+    for item in menu.items()? {
+        menu.remove(&item)?;
+    }
+    let tasks = commands::enabled_tasks(&app.state::<Arc<AppState>>()).unwrap_or_default();
+    if tasks.is_empty() {
+        menu.append(&MenuItem::with_id(app, "run_none", "(Không có tác vụ)", false, None::<&str>)?)?;
+    }
+    for task in tasks {
+        menu.append(&MenuItem::with_id(app, format!("run:{}", task.id), &task.name, true, None::<&str>)?)?;
+    }
+    Ok(())
+}
+
+/// Rebuild the tray's recent submenu from the last runs, e.g. "✓ Backup  14:30"
+fn fill_recent_menu<R: Runtime>(app: &AppHandle<R>, menu: &Submenu<R>) -> tauri::Result<()> {
+    for item in menu.items()? {
+        menu.remove(&item)?;
+    }
+    let runs = commands::recent_runs(&app.state::<Arc<AppState>>(), RECENT_RUNS).unwrap_or_default();
+    if runs.is_empty() {
+        menu.append(&MenuItem::with_id(app, "recent_none", "(Chưa có lần chạy nào)", false, None::<&str>)?)?;
+    }
+    for run in runs {
+        let glyph = match run.status {
+            RunStatus::Success => "✓",
+            RunStatus::Failed => "✗",
+            RunStatus::Skipped => "⤼",
+            RunStatus::Started => "▶",
+            RunStatus::DryRun => "○",
+        };
+        let started = run.started_at_utc.with_timezone(&Local);
+        let when = if started.date_naive() == Local::now().date_naive() {
+            started.format("%H:%M").to_string()
+        } else {
+            started.format("%H:%M %d/%m").to_string()
+        };
+        let text = format!("{} {}  {}", glyph, run.task_name, when);
+        menu.append(&MenuItem::with_id(app, format!("recent:{}", run.run_id), text, true, None::<&str>)?)?;
+    }
+    Ok(())
+}
+
+/// Warn that a corrupt database was set aside and a new one created
+fn show_database_recovered<R: Runtime>(app: &AppHandle<R>, recovered: &DatabaseRecovered) {
+    app.dialog()
+        .message(format!(
+            "Cơ sở dữ liệu của hồ sơ \"{}\" bị hỏng và đã được tạo lại từ đầu.\nBản cũ được giữ tại: {}\nBạn có thể khôi phục từ bản sao lưu.",
+            recovered.profile,
+            recovered.moved_to.display(),
+        ))
+        .title("Routine Runner")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+}
+
+/// On the first start, offer to keep the data somewhere other than the default folder,
+/// e.g. on D: rather than a small system drive
+fn ask_data_location<R: Runtime>(app: &AppHandle<R>) {
+    const KEEP: &str = "Giữ nguyên";
+    const CHOOSE: &str = "Chọn thư mục khác…";
+    
+    let Ok(location) = commands::data_location(&app.state::<Arc<AppState>>()) else { return };
+    let app = app.clone();
+    app.dialog()
+        .message(format!(
+            "Routine Runner lưu tác vụ, nhật ký và bản sao lưu tại:\n{}\nBạn có muốn lưu chúng ở thư mục khác không?",
+            location.dir.display(),
+        ))
+        .title("Routine Runner")
+        .kind(MessageDialogKind::Info)
+        .buttons(MessageDialogButtons::OkCancelCustom(KEEP.to_string(), CHOOSE.to_string()))
+        .show_with_result(move |result| {
+            if !matches!(result, MessageDialogResult::Custom(ref choice) if choice == CHOOSE) {
+                return;
+            }
+            let dialog = app.dialog().clone();
+            let handle = app.clone();
+            app.dialog()
+                .file()
+                .set_title("Chọn thư mục lưu dữ liệu")
+                .pick_folder(move |folder| {
+                    let Some(folder) = folder.and_then(|f| f.into_path().ok()) else { return };
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = commands::move_data_dir(handle.state(), folder.to_string_lossy().into_owned()).await {
+                            tracing::error!("Failed to move the data to {}: {}", folder.display(), e);
+                            dialog
+                                .message(format!("Không thể chuyển dữ liệu sang {}:\n{}", folder.display(), e))
+                                .title("Routine Runner")
+                                .kind(MessageDialogKind::Error)
+                                .show(|_| {});
+                        }
+                    });
+                });
+        });
+}
+
+/// Exit once the runs in progress are dealt with: if there are any, ask whether to
+/// wait for them, stop them, or leave them running and exit right away
+fn quit<R: Runtime>(app: &AppHandle<R>) {
+    const WAIT: &str = "Chờ chạy xong";
+    const CANCEL: &str = "Dừng tác vụ";
+    const ABANDON: &str = "Thoát ngay";
+    
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let running = commands::running_runs(&app.state::<Arc<AppState>>()).await;
+        if running.is_empty() {
+            drain_and_exit(app, false, None).await;
+            return;
+        }
+        
+        let names: Vec<&str> = running.iter().map(|r| r.task_name.as_str()).collect();
+        app.dialog()
+            .message(format!(
+                "Đang có {} tác vụ chạy: {}.\nChờ chúng chạy xong, dừng chúng, hay thoát ngay và bỏ mặc chúng?",
+                running.len(),
+                names.join(", "),
+            ))
+            .title("Thoát Routine Runner")
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::YesNoCancelCustom(WAIT.to_string(), CANCEL.to_string(), ABANDON.to_string()))
+            .show_with_result(move |result| match result {
+                MessageDialogResult::Custom(choice) if choice == WAIT => {
+                    tauri::async_runtime::spawn(drain_and_exit(app, false, None));
+                }
+                MessageDialogResult::Custom(choice) if choice == CANCEL => {
+                    tauri::async_runtime::spawn(drain_and_exit(app, true, Some(commands::CANCEL_GRACE_SECS)));
+                }
+                MessageDialogResult::Custom(choice) if choice == ABANDON => {
+                    // The programs keep running, but their runs are logged as interrupted
+                    tauri::async_runtime::spawn(drain_and_exit(app, false, Some(0)));
+                }
+                _ => tracing::info!("Quit cancelled"),
+            });
+    });
+}
+
+/// Wait for the runs in progress (stopping them first if `cancel`) for up to `grace_secs`, then exit
+async fn drain_and_exit<R: Runtime>(app: AppHandle<R>, cancel: bool, grace_secs: Option<u64>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some("Routine Runner\nĐang chờ tác vụ chạy xong để thoát…"));
+    }
+    if let Err(e) = commands::drain_runs(&app.state::<Arc<AppState>>(), cancel, grace_secs).await {
+        tracing::error!("Failed to wait for running tasks: {}", e);
+    }
+    app.exit(0);
+}
+
+/// Pause the scheduler from the tray until the given time
+fn pause_scheduler_until(app: &AppHandle, until: chrono::DateTime<Utc>) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = commands::pause_scheduler_until(app.state(), until).await {
+            tracing::error!("Failed to pause scheduler: {}", e);
+        }
+    });
 }
@@ -0,0 +1,52 @@
+//! Maintenance module - Periodic database compaction
+
+use crate::models::CompactSummary;
+use crate::storage::Database;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/// Settings key recording when maintenance last ran
+const LAST_MAINTENANCE_KEY: &str = "last_maintenance_utc";
+
+/// Days between automatic maintenance runs
+const MAINTENANCE_INTERVAL_DAYS: i64 = 30;
+
+/// How often the job checks whether maintenance is due
+const CHECK_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+/// Compact the database now and remember when it happened
+pub fn run_maintenance(db: &Database) -> Result<CompactSummary, String> {
+    let summary = db.compact().map_err(|e| e.to_string())?;
+    db.set_setting(LAST_MAINTENANCE_KEY, Some(&Utc::now())).map_err(|e| e.to_string())?;
+    tracing::info!(
+        "Database compacted: {} -> {} bytes ({} freed)",
+        summary.size_before_bytes, summary.size_after_bytes, summary.freed_bytes
+    );
+    Ok(summary)
+}
+
+/// Compact the database if the last run was over a month ago
+fn run_maintenance_if_due(db: &Database) -> Result<Option<CompactSummary>, String> {
+    let last: Option<DateTime<Utc>> = db.get_setting(LAST_MAINTENANCE_KEY).map_err(|e| e.to_string())?;
+    if last.is_some_and(|last| Utc::now() - last < chrono::Duration::days(MAINTENANCE_INTERVAL_DAYS)) {
+        return Ok(None);
+    }
+    run_maintenance(db).map(Some)
+}
+
+/// Background job: monthly maintenance while it is enabled in settings
+pub async fn maintenance_loop(db: Arc<Database>) {
+    loop {
+        match db.get_settings() {
+            Ok(settings) if settings.auto_maintenance_enabled => {
+                if let Err(e) = db.run(run_maintenance_if_due).await {
+                    tracing::error!("Database maintenance failed: {}", e);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to read settings for maintenance: {}", e),
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+    }
+}
@@ -0,0 +1,190 @@
+//! Messages module - User-facing errors and skip reasons as ids with parameters,
+//! rendered from a per-language table here or by the frontend
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Language messages are shown in
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    Vi,
+    En,
+}
+
+/// What a message says; its parameters fill `{0}`, `{1}`, ... in the template
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageId {
+    /// {0}: path
+    PathNotFound,
+    /// {0}: what could not be opened and why
+    OpenFailed,
+    /// {0}: seconds
+    Timeout,
+    /// {0}: exit code
+    ExitCodeFailed,
+    /// {0}: reason
+    KillFailed,
+    /// {0}: reason
+    SecretFailed,
+    /// {0}: reason
+    IoError,
+    /// {0}: process name
+    AlreadyRunning,
+    SkipDisabled,
+    SkipConditionFail,
+    SkipSingleton,
+    SkipMisfire,
+    SkipPathMissing,
+    SkipAlreadyRanToday,
+    SkipDayNotAllowed,
+    SkipPaused,
+    SkipManualOverride,
+    SkipQuietHours,
+    SkipUnknown,
+    RunFailed,
+    /// {0}: exit code
+    RunFailedWithCode,
+    RunSucceeded,
+    /// {0}: seconds, one decimal
+    RunSucceededAfter,
+    /// {0}: skip reason
+    RunSkipped,
+    RunInterrupted,
+}
+
+impl MessageId {
+    pub const ALL: [MessageId; 25] = [
+        MessageId::PathNotFound,
+        MessageId::OpenFailed,
+        MessageId::Timeout,
+        MessageId::ExitCodeFailed,
+        MessageId::KillFailed,
+        MessageId::SecretFailed,
+        MessageId::IoError,
+        MessageId::AlreadyRunning,
+        MessageId::SkipDisabled,
+        MessageId::SkipConditionFail,
+        MessageId::SkipSingleton,
+        MessageId::SkipMisfire,
+        MessageId::SkipPathMissing,
+        MessageId::SkipAlreadyRanToday,
+        MessageId::SkipDayNotAllowed,
+        MessageId::SkipPaused,
+        MessageId::SkipManualOverride,
+        MessageId::SkipQuietHours,
+        MessageId::SkipUnknown,
+        MessageId::RunFailed,
+        MessageId::RunFailedWithCode,
+        MessageId::RunSucceeded,
+        MessageId::RunSucceededAfter,
+        MessageId::RunSkipped,
+        MessageId::RunInterrupted,
+    ];
+
+    /// Template in the given language
+    pub fn template(self, locale: Locale) -> &'static str {
+        let (vi, en) = match self {
+            MessageId::PathNotFound => ("Đường dẫn không tồn tại: {0}", "Path not found: {0}"),
+            MessageId::OpenFailed => ("Không thể mở: {0}", "Failed to open: {0}"),
+            MessageId::Timeout => ("Quá thời gian sau {0} giây", "Timed out after {0} seconds"),
+            MessageId::ExitCodeFailed => (
+                "Mã thoát {0} không nằm trong danh sách thành công",
+                "Exit code {0} is not a success code",
+            ),
+            MessageId::KillFailed => ("Không thể dừng tiến trình: {0}", "Failed to stop the process: {0}"),
+            MessageId::SecretFailed => ("Không thể giải mã bí mật: {0}", "Failed to decrypt a secret: {0}"),
+            MessageId::IoError => ("Lỗi vào/ra: {0}", "I/O error: {0}"),
+            MessageId::AlreadyRunning => ("Bỏ qua - {0} đang chạy", "Skipped - {0} already running"),
+            MessageId::SkipDisabled => ("tác vụ đang tắt", "the task is disabled"),
+            MessageId::SkipConditionFail => ("điều kiện chạy không thỏa", "its conditions were not met"),
+            MessageId::SkipSingleton => ("lần chạy trước vẫn chưa xong", "the previous run has not finished"),
+            MessageId::SkipMisfire => ("đã quá giờ chạy", "it missed its time by too much"),
+            MessageId::SkipPathMissing => ("không tìm thấy đường dẫn", "the path was not found"),
+            MessageId::SkipAlreadyRanToday => ("đã chạy hôm nay", "it already ran today"),
+            MessageId::SkipDayNotAllowed => ("không chạy vào hôm nay", "it doesn't run on this day"),
+            MessageId::SkipPaused => ("đang tạm dừng", "the scheduler is paused"),
+            MessageId::SkipManualOverride => ("bị bỏ qua thủ công", "it was skipped by hand"),
+            MessageId::SkipQuietHours => ("đang trong giờ yên lặng", "it fell in quiet hours"),
+            MessageId::SkipUnknown => ("không rõ lý do", "unknown reason"),
+            MessageId::RunFailed => ("Thất bại", "Failed"),
+            MessageId::RunFailedWithCode => ("Thất bại (mã thoát {0})", "Failed (exit code {0})"),
+            MessageId::RunSucceeded => ("Hoàn thành", "Done"),
+            MessageId::RunSucceededAfter => ("Hoàn thành sau {0} giây", "Done in {0} seconds"),
+            MessageId::RunSkipped => ("Đã bỏ qua: {0}", "Skipped: {0}"),
+            MessageId::RunInterrupted => (
+                "Bị ngắt vì ứng dụng thoát trước khi chạy xong",
+                "Interrupted because the app exited before the run ended",
+            ),
+        };
+        match locale {
+            Locale::Vi => vi,
+            Locale::En => en,
+        }
+    }
+}
+
+/// A message with its parameters
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Message {
+    pub id: MessageId,
+    #[serde(default)]
+    pub params: Vec<String>,
+}
+
+impl Message {
+    pub fn new(id: MessageId, params: &[&dyn std::fmt::Display]) -> Self {
+        Self { id, params: params.iter().map(|p| p.to_string()).collect() }
+    }
+
+    /// The message in the given language
+    pub fn render(&self, locale: Locale) -> String {
+        self.params
+            .iter()
+            .enumerate()
+            .fold(self.id.template(locale).to_string(), |text, (i, param)| {
+                text.replace(&format!("{{{}}}", i), param)
+            })
+    }
+}
+
+impl From<MessageId> for Message {
+    fn from(id: MessageId) -> Self {
+        Self { id, params: vec![] }
+    }
+}
+
+/// English, as written to logs and returned as error text
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(Locale::En))
+    }
+}
+
+/// Every template in a language, for the frontend to render message ids with
+pub fn catalog(locale: Locale) -> HashMap<MessageId, &'static str> {
+    MessageId::ALL.iter().map(|id| (*id, id.template(locale))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_params() {
+        let message = Message::new(MessageId::Timeout, &[&30]);
+        assert_eq!(message.render(Locale::En), "Timed out after 30 seconds");
+        assert_eq!(message.render(Locale::Vi), "Quá thời gian sau 30 giây");
+        assert_eq!(Message::from(MessageId::RunFailed).to_string(), "Failed");
+    }
+
+    #[test]
+    fn test_message_round_trips_as_json() {
+        let message = Message::new(MessageId::PathNotFound, &[&"C:\\missing.exe"]);
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, r#"{"id":"path_not_found","params":["C:\\missing.exe"]}"#);
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+    }
+}
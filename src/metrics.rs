@@ -0,0 +1,378 @@
+//! Metrics module - Prometheus metrics served on localhost
+
+use crate::models::*;
+use crate::scheduler_runner::SchedulerRunner;
+use crate::storage::Database;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Upper bounds (seconds) of the run duration histogram buckets
+const DURATION_BUCKETS: [f64; 8] = [1.0, 5.0, 15.0, 60.0, 300.0, 900.0, 3600.0, 14400.0];
+
+/// How often the server checks whether it was switched off or moved
+const SETTINGS_CHECK_SECS: u64 = 15;
+
+/// Largest request head read from a client
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Tasks listed as the slowest to evaluate
+const SLOWEST_TASKS: usize = 20;
+
+/// Run counters since the app started
+pub static METRICS: Metrics = Metrics::new();
+
+/// Scheduler timings since the app started
+pub static ENGINE_METRICS: EngineMetrics = EngineMetrics::new();
+
+pub struct Metrics {
+    /// Finished and skipped runs by status
+    runs: Mutex<BTreeMap<String, u64>>,
+    /// Cumulative counts per duration bucket, then the +Inf bucket
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len() + 1],
+    duration_sum_ms: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            runs: Mutex::new(BTreeMap::new()),
+            duration_buckets: [const { AtomicU64::new(0) }; DURATION_BUCKETS.len() + 1],
+            duration_sum_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a finished or skipped run
+    pub fn record(&self, log: &RunLog) {
+        let status = serde_json::to_value(&log.status)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        *self.runs.lock().unwrap().entry(status).or_default() += 1;
+
+        if let Some(ms) = log.duration_ms.filter(|ms| *ms >= 0) {
+            let seconds = ms as f64 / 1000.0;
+            for (bucket, le) in self.duration_buckets.iter().zip(DURATION_BUCKETS.iter().map(Some).chain([None])) {
+                if le.is_none_or(|le| seconds <= *le) {
+                    bucket.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            self.duration_sum_ms.fetch_add(ms as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// The metrics in the Prometheus text format
+    pub fn render(&self, paused: bool, queue_depth: usize, running: usize) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP routine_runner_runs_total Runs finished or skipped since the app started.\n");
+        out.push_str("# TYPE routine_runner_runs_total counter\n");
+        for (status, count) in self.runs.lock().unwrap().iter() {
+            out.push_str(&format!("routine_runner_runs_total{{status=\"{}\"}} {}\n", status, count));
+        }
+
+        out.push_str("# HELP routine_runner_run_duration_seconds How long runs took.\n");
+        out.push_str("# TYPE routine_runner_run_duration_seconds histogram\n");
+        for (bucket, le) in self.duration_buckets.iter().zip(DURATION_BUCKETS.iter()) {
+            out.push_str(&format!(
+                "routine_runner_run_duration_seconds_bucket{{le=\"{}\"}} {}\n",
+                le,
+                bucket.load(Ordering::Relaxed),
+            ));
+        }
+        let count = self.duration_buckets[DURATION_BUCKETS.len()].load(Ordering::Relaxed);
+        out.push_str(&format!("routine_runner_run_duration_seconds_bucket{{le=\"+Inf\"}} {}\n", count));
+        out.push_str(&format!(
+            "routine_runner_run_duration_seconds_sum {}\n",
+            self.duration_sum_ms.load(Ordering::Relaxed) as f64 / 1000.0,
+        ));
+        out.push_str(&format!("routine_runner_run_duration_seconds_count {}\n", count));
+
+        out.push_str("# HELP routine_runner_queue_depth Due runs waiting for a free slot.\n");
+        out.push_str("# TYPE routine_runner_queue_depth gauge\n");
+        out.push_str(&format!("routine_runner_queue_depth {}\n", queue_depth));
+        out.push_str("# HELP routine_runner_running_runs Runs in progress.\n");
+        out.push_str("# TYPE routine_runner_running_runs gauge\n");
+        out.push_str(&format!("routine_runner_running_runs {}\n", running));
+        out.push_str("# HELP routine_runner_scheduler_paused Whether the scheduler is paused (1) or running (0).\n");
+        out.push_str("# TYPE routine_runner_scheduler_paused gauge\n");
+        out.push_str(&format!("routine_runner_scheduler_paused {}\n", paused as u8));
+        ENGINE_METRICS.render(&mut out);
+        out
+    }
+}
+
+/// Count, total, worst and latest of one kind of timing
+pub struct Timing {
+    count: AtomicU64,
+    total_us: AtomicU64,
+    max_us: AtomicU64,
+    last_us: AtomicU64,
+}
+
+/// A `Timing` in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct TimingSummary {
+    pub count: u64,
+    pub last_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Timing {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            total_us: AtomicU64::new(0),
+            max_us: AtomicU64::new(0),
+            last_us: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+        self.last_us.store(us, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> TimingSummary {
+        let count = self.count.load(Ordering::Relaxed);
+        let total_us = self.total_us.load(Ordering::Relaxed);
+        TimingSummary {
+            count,
+            last_ms: self.last_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            avg_ms: if count == 0 { 0.0 } else { total_us as f64 / count as f64 / 1000.0 },
+            max_ms: self.max_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        }
+    }
+
+    fn total_seconds(&self) -> f64 {
+        self.total_us.load(Ordering::Relaxed) as f64 / 1_000_000.0
+    }
+}
+
+/// How long one task takes to evaluate
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TaskTiming {
+    pub task_id: String,
+    pub task_name: String,
+    #[serde(flatten)]
+    pub timing: TimingSummary,
+}
+
+/// What the scheduler spends its time on, leaving out the runs themselves
+pub struct EngineMetrics {
+    /// Planning all tasks once
+    pub tick: Timing,
+    /// Planning one task: its state, triggers and next runs
+    pub task_evaluation: Timing,
+    /// A database call from an async task, including the wait for the connection
+    pub db_query: Timing,
+    /// From when a run was due until it started
+    pub queue_wait: Timing,
+    /// Tasks planned by the latest tick
+    tasks_per_tick: AtomicU64,
+    /// Name and evaluation time per task id
+    per_task: Mutex<BTreeMap<String, (String, Timing)>>,
+}
+
+/// Engine timings as returned to the frontend
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EngineMetricsSnapshot {
+    pub tick: TimingSummary,
+    pub task_evaluation: TimingSummary,
+    pub db_query: TimingSummary,
+    pub queue_wait: TimingSummary,
+    pub tasks_per_tick: u64,
+    /// Tasks with the slowest average evaluation, slowest first
+    pub slowest_tasks: Vec<TaskTiming>,
+}
+
+impl EngineMetrics {
+    const fn new() -> Self {
+        Self {
+            tick: Timing::new(),
+            task_evaluation: Timing::new(),
+            db_query: Timing::new(),
+            queue_wait: Timing::new(),
+            tasks_per_tick: AtomicU64::new(0),
+            per_task: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record a whole tick over `tasks` tasks
+    pub fn record_tick(&self, elapsed: Duration, tasks: usize) {
+        self.tick.record(elapsed);
+        self.tasks_per_tick.store(tasks as u64, Ordering::Relaxed);
+    }
+
+    /// Record how long one task took to evaluate
+    pub fn record_task(&self, task_id: &str, task_name: &str, elapsed: Duration) {
+        self.task_evaluation.record(elapsed);
+        let mut per_task = self.per_task.lock().unwrap();
+        let (name, timing) = per_task.entry(task_id.to_string()).or_insert_with(|| (String::new(), Timing::new()));
+        if name != task_name {
+            *name = task_name.to_string();
+        }
+        timing.record(elapsed);
+    }
+
+    pub fn snapshot(&self) -> EngineMetricsSnapshot {
+        let mut slowest: Vec<TaskTiming> = self.per_task.lock().unwrap()
+            .iter()
+            .map(|(task_id, (task_name, timing))| TaskTiming {
+                task_id: task_id.clone(),
+                task_name: task_name.clone(),
+                timing: timing.summary(),
+            })
+            .collect();
+        slowest.sort_by(|a, b| b.timing.avg_ms.total_cmp(&a.timing.avg_ms));
+        slowest.truncate(SLOWEST_TASKS);
+
+        EngineMetricsSnapshot {
+            tick: self.tick.summary(),
+            task_evaluation: self.task_evaluation.summary(),
+            db_query: self.db_query.summary(),
+            queue_wait: self.queue_wait.summary(),
+            tasks_per_tick: self.tasks_per_tick.load(Ordering::Relaxed),
+            slowest_tasks: slowest,
+        }
+    }
+
+    /// The timings as Prometheus summaries (sum and count only)
+    fn render(&self, out: &mut String) {
+        let timings = [
+            ("tick", "Time spent planning all tasks once, leaving out runs.", &self.tick),
+            ("task_evaluation", "Time spent planning one task.", &self.task_evaluation),
+            ("db_query", "Database calls from the scheduler and commands.", &self.db_query),
+            ("queue_wait", "Time from when a run was due until it started.", &self.queue_wait),
+        ];
+        for (name, help, timing) in timings {
+            out.push_str(&format!("# HELP routine_runner_{}_seconds {}\n", name, help));
+            out.push_str(&format!("# TYPE routine_runner_{}_seconds summary\n", name));
+            out.push_str(&format!("routine_runner_{}_seconds_sum {}\n", name, timing.total_seconds()));
+            out.push_str(&format!("routine_runner_{}_seconds_count {}\n", name, timing.summary().count));
+        }
+        out.push_str("# HELP routine_runner_tasks_per_tick Tasks planned by the latest tick.\n");
+        out.push_str("# TYPE routine_runner_tasks_per_tick gauge\n");
+        out.push_str(&format!("routine_runner_tasks_per_tick {}\n", self.tasks_per_tick.load(Ordering::Relaxed)));
+    }
+}
+
+/// Serve /metrics on 127.0.0.1 while enabled in settings, following changes to the port
+pub async fn serve_loop(db: Arc<Database>, scheduler: Arc<SchedulerRunner>) {
+    let wanted_port = |db: &Database| match db.get_settings() {
+        Ok(settings) => settings.metrics_enabled.then_some(settings.metrics_port),
+        Err(e) => {
+            tracing::error!("Failed to read settings for metrics: {}", e);
+            None
+        }
+    };
+
+    loop {
+        let Some(port) = wanted_port(&db) else {
+            tokio::time::sleep(tokio::time::Duration::from_secs(SETTINGS_CHECK_SECS)).await;
+            continue;
+        };
+        let listener = match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Failed to serve metrics on port {}: {}", port, e);
+                tokio::time::sleep(tokio::time::Duration::from_secs(SETTINGS_CHECK_SECS)).await;
+                continue;
+            }
+        };
+        tracing::info!("Serving metrics on http://127.0.0.1:{}/metrics", port);
+
+        let settings_changed = async {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(SETTINGS_CHECK_SECS)).await;
+                if wanted_port(&db) != Some(port) {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            _ = accept_loop(&listener, &scheduler) => {}
+            _ = settings_changed => tracing::info!("Stopped serving metrics on port {}", port),
+        }
+    }
+}
+
+async fn accept_loop(listener: &TcpListener, scheduler: &Arc<SchedulerRunner>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let scheduler = scheduler.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_request(stream, &scheduler).await {
+                        tracing::debug!("Metrics request failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                tracing::warn!("Failed to accept metrics connection: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Answer one request: GET /metrics, anything else is a 404
+async fn handle_request(mut stream: TcpStream, scheduler: &SchedulerRunner) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_BYTES {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let request_line = String::from_utf8_lossy(&head).lines().next().unwrap_or_default().to_string();
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+
+    let (status, body) = if method == "GET" && path.split('?').next() == Some("/metrics") {
+        let status = scheduler.status().await;
+        ("200 OK", METRICS.render(status.paused, status.queued_task_ids.len(), status.running.len()))
+    } else {
+        ("404 Not Found", "Not found\n".to_string())
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_metrics_list_slowest_tasks_first() {
+        let metrics = EngineMetrics::new();
+        metrics.record_task("a", "Fast", Duration::from_millis(1));
+        metrics.record_task("b", "Slow", Duration::from_millis(9));
+        metrics.record_task("b", "Slow", Duration::from_millis(3));
+        metrics.record_tick(Duration::from_millis(20), 2);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.tasks_per_tick, 2);
+        assert_eq!(snapshot.task_evaluation.count, 3);
+        assert_eq!(snapshot.task_evaluation.max_ms, 9.0);
+        assert_eq!(snapshot.slowest_tasks[0].task_name, "Slow");
+        assert_eq!(snapshot.slowest_tasks[0].timing, TimingSummary { count: 2, last_ms: 3.0, avg_ms: 6.0, max_ms: 9.0 });
+        assert_eq!(snapshot.slowest_tasks[1].task_id, "a");
+    }
+}
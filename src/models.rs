@@ -1,7 +1,10 @@
 //! Data models for Routine Runner
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub use crate::messages::{Locale, Message, MessageId};
 
 /// Target type - what to open
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -60,9 +63,56 @@ pub enum Trigger {
         enabled: bool,
         every_seconds: u32,
         jitter_seconds: Option<u32>,
+        #[serde(default)]
+        jitter_mode: JitterMode,
+    },
+    /// Fired by a POST to the webhook server carrying the token
+    Webhook {
+        enabled: bool,
+        token: String,
     },
 }
 
+/// How an interval's jitter is applied around the nominal run time
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterMode {
+    /// Delay the run by 0..=jitter seconds
+    #[default]
+    Delay,
+    /// Move the run by -jitter..=+jitter seconds
+    Spread,
+}
+
+impl Trigger {
+    /// Short snake_case name of the trigger type
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Trigger::OnLogin { .. } => "on_login",
+            Trigger::OncePerDay { .. } => "once_per_day",
+            Trigger::DailyAt { .. } => "daily_at",
+            Trigger::Interval { .. } => "interval",
+            Trigger::Webhook { .. } => "webhook",
+        }
+    }
+    
+    /// Whether the trigger is switched on
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Trigger::OnLogin { enabled, .. }
+            | Trigger::OncePerDay { enabled, .. }
+            | Trigger::DailyAt { enabled, .. }
+            | Trigger::Interval { enabled, .. }
+            | Trigger::Webhook { enabled, .. } => *enabled,
+        }
+    }
+    
+    /// Key of this trigger's entry in TaskState::trigger_states
+    pub fn state_key(&self, index: usize) -> String {
+        format!("{}:{}", index, self.type_name())
+    }
+}
+
 /// Condition types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -96,6 +146,92 @@ pub enum IfRunningAction {
     RunAnyway,
 }
 
+/// Which run outcomes show a desktop notification for a task
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyPolicy {
+    /// Failures and notable skips, plus successes if enabled in settings
+    #[default]
+    Default,
+    /// Every finished or skipped run, including successes
+    All,
+    /// Only failed runs
+    FailuresOnly,
+    /// Never notify for this task
+    Never,
+}
+
+/// A URL that run results are POSTed to as JSON
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Webhook {
+    pub url: String,
+    /// Send when a run fails
+    #[serde(default = "default_true")]
+    pub on_failure: bool,
+    /// Send when a run succeeds
+    #[serde(default)]
+    pub on_success: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Chat service a notifier posts to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "service", rename_all = "snake_case")]
+pub enum ChatService {
+    /// Slack incoming webhook
+    Slack { webhook_url: String },
+    /// Discord channel webhook
+    Discord { webhook_url: String },
+    /// Telegram bot sending to one chat
+    Telegram { bot_token: String, chat_id: String },
+}
+
+impl ChatService {
+    /// The credential of the service: the webhook URL or the bot token
+    pub fn secret(&self) -> &str {
+        match self {
+            ChatService::Slack { webhook_url } | ChatService::Discord { webhook_url } => webhook_url,
+            ChatService::Telegram { bot_token, .. } => bot_token,
+        }
+    }
+
+    /// Mutable access to the credential
+    pub fn secret_mut(&mut self) -> &mut String {
+        match self {
+            ChatService::Slack { webhook_url } | ChatService::Discord { webhook_url } => webhook_url,
+            ChatService::Telegram { bot_token, .. } => bot_token,
+        }
+    }
+}
+
+/// A configured chat destination that tasks can send run results to
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatNotifier {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub service: ChatService,
+    /// Message with {task_name}, {status}, {exit_code}, {duration}, {error} and {output}
+    /// placeholders; None uses the built-in message
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+/// Which of a task's run results go to a chat notifier
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatAlert {
+    pub notifier_id: String,
+    #[serde(default = "default_true")]
+    pub on_failure: bool,
+    #[serde(default)]
+    pub on_success: bool,
+    #[serde(default)]
+    pub on_skip: bool,
+}
+
 /// Main Task struct
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -103,12 +239,24 @@ pub struct Task {
     pub enabled: bool,
     pub name: String,
     pub description: Option<String>,
+    /// Free-form labels for grouping/filtering ("work", "gaming", ...)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Sidebar folder the task is filed under
+    #[serde(default)]
+    pub group_id: Option<String>,
     
     // Target
     pub target_type: TargetType,
     pub path_or_url: String,
     pub args: Option<String>,
+    /// Args are encrypted at rest and masked in responses
+    #[serde(default)]
+    pub args_secret: bool,
     pub working_dir: Option<String>,
+    /// Extra environment variables for exe targets
+    #[serde(default)]
+    pub env: Vec<EnvVar>,
     
     // Stdin input for terminal/console apps that require input
     #[serde(default)]
@@ -125,6 +273,27 @@ pub struct Task {
     pub success_exit_codes: Option<Vec<i32>>,
     pub misfire_policy: MisfirePolicy,
     pub if_running_action: IfRunningAction,
+    /// Kill the run if it is still going after this long, whatever the wait policy
+    #[serde(default)]
+    pub max_runtime_seconds: Option<u32>,
+    /// Kill the run once this local time passes ("HH:MM")
+    #[serde(default)]
+    pub must_finish_by_local: Option<String>,
+    /// Runs even during global quiet hours
+    #[serde(default)]
+    pub exempt_from_quiet_hours: bool,
+    /// Days to keep this task's run logs, overriding the global setting (0 keeps everything)
+    #[serde(default)]
+    pub log_retention_days: Option<u32>,
+    /// Which runs show a desktop notification
+    #[serde(default)]
+    pub notify_policy: NotifyPolicy,
+    /// Webhooks for this task, in addition to the global ones in settings
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Chat notifiers this task reports to
+    #[serde(default)]
+    pub chat_alerts: Vec<ChatAlert>,
     
     // Triggers and conditions
     pub triggers: Vec<Trigger>,
@@ -133,6 +302,118 @@ pub struct Task {
     // Timestamps
     pub created_at_utc: DateTime<Utc>,
     pub updated_at_utc: DateTime<Utc>,
+    
+    /// Suspended until this time, without touching `enabled`
+    #[serde(default)]
+    pub paused_until_utc: Option<DateTime<Utc>>,
+    
+    /// Defined in the watched task file; read-only through the API
+    #[serde(default)]
+    pub managed_by_file: bool,
+    
+    /// Set while the task sits in the trash
+    #[serde(default)]
+    pub deleted_at_utc: Option<DateTime<Utc>>,
+}
+
+impl Task {
+    /// Whether the task carries this tag (case-insensitive)
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// Environment variable passed to a task's process
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    /// Encrypted at rest and masked in responses
+    #[serde(default)]
+    pub secret: bool,
+}
+
+/// User-defined folder of tasks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskGroup {
+    pub id: String,
+    pub name: String,
+    pub sort_order: i32,
+    pub created_at_utc: DateTime<Utc>,
+}
+
+/// A saved task configuration to create new tasks from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTemplate {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub task: Task,
+    pub created_at_utc: DateTime<Utc>,
+}
+
+impl TaskTemplate {
+    /// A fresh task from this template, with its own id and timestamps
+    pub fn instantiate(&self, name: Option<String>) -> Task {
+        let now = Utc::now();
+        Task {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.unwrap_or_else(|| self.task.name.clone()),
+            created_at_utc: now,
+            updated_at_utc: now,
+            paused_until_utc: None,
+            managed_by_file: false,
+            deleted_at_utc: None,
+            ..self.task.clone()
+        }
+    }
+}
+
+/// Kind of change recorded in a task's history
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskChange {
+    Created,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+/// One recorded change to a task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskHistoryEntry {
+    pub id: i64,
+    pub task_id: String,
+    pub changed_at_utc: DateTime<Utc>,
+    pub change: TaskChange,
+    /// Changed fields as `{ "field": { "old": ..., "new": ... } }`
+    pub diff: serde_json::Value,
+    /// The task as it was right after the change
+    pub snapshot: Task,
+}
+
+/// Field-level diff between two versions of a task (`updated_at_utc` is ignored)
+pub fn task_diff(before: Option<&Task>, after: &Task) -> serde_json::Map<String, serde_json::Value> {
+    let old = before.and_then(|t| serde_json::to_value(t).ok());
+    let new = serde_json::to_value(after).unwrap_or_default();
+    let mut diff = serde_json::Map::new();
+    
+    if let serde_json::Value::Object(fields) = new {
+        for (field, value) in fields {
+            if field == "updated_at_utc" {
+                continue;
+            }
+            let previous = old.as_ref()
+                .and_then(|o| o.get(&field))
+                .cloned()
+                .unwrap_or(serde_json::Value::Null);
+            if previous != value {
+                diff.insert(field, serde_json::json!({ "old": previous, "new": value }));
+            }
+        }
+    }
+    
+    diff
 }
 
 /// Process info for tracking running processes
@@ -153,10 +434,14 @@ impl Default for Task {
             enabled: true,
             name: String::new(),
             description: None,
+            tags: vec![],
+            group_id: None,
             target_type: TargetType::File,
             path_or_url: String::new(),
             args: None,
+            args_secret: false,
             working_dir: None,
+            env: vec![],
             stdin_input: None,
             start_delay_seconds: 0,
             run_window_style: RunWindowStyle::default(),
@@ -168,16 +453,36 @@ impl Default for Task {
             success_exit_codes: Some(vec![0]),
             misfire_policy: MisfirePolicy::default(),
             if_running_action: IfRunningAction::default(),
+            max_runtime_seconds: None,
+            must_finish_by_local: None,
+            exempt_from_quiet_hours: false,
+            log_retention_days: None,
+            notify_policy: NotifyPolicy::default(),
+            webhooks: vec![],
+            chat_alerts: vec![],
             triggers: vec![],
             conditions: vec![],
             created_at_utc: Utc::now(),
             updated_at_utc: Utc::now(),
+            paused_until_utc: None,
+            managed_by_file: false,
+            deleted_at_utc: None,
         }
     }
 }
 
+/// Per-trigger runtime state
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct TriggerState {
+    pub last_fired_utc: Option<DateTime<Utc>>,
+    pub next_run_utc: Option<DateTime<Utc>>,
+    /// Scheduled time of the last occurrence that was run or skipped
+    #[serde(default)]
+    pub consumed_slot_utc: Option<DateTime<Utc>>,
+}
+
 /// Task state (runtime)
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct TaskState {
     pub task_id: String,
     pub last_run_date_local: Option<String>, // "YYYY-MM-DD"
@@ -185,6 +490,34 @@ pub struct TaskState {
     pub last_result: Option<RunResult>,
     pub last_error: Option<String>,
     pub next_run_at_utc: Option<DateTime<Utc>>,
+    /// Due runs are held back until this time
+    #[serde(default)]
+    pub snoozed_until_utc: Option<DateTime<Utc>>,
+    /// Last fired / planned times per trigger, keyed by Trigger::state_key
+    #[serde(default)]
+    pub trigger_states: HashMap<String, TriggerState>,
+}
+
+impl TaskState {
+    /// View of this state as seen by a single trigger, so one trigger's runs
+    /// don't shift another's schedule. Falls back to the task-wide values for
+    /// triggers that have no entry yet.
+    pub fn for_trigger(&self, key: &str) -> TaskState {
+        let mut view = self.clone();
+        if let Some(ts) = self.trigger_states.get(key) {
+            view.last_run_at_utc = ts.last_fired_utc;
+            view.last_run_date_local = ts.last_fired_utc
+                .map(|t| t.with_timezone(&Local).format("%Y-%m-%d").to_string());
+            view.next_run_at_utc = ts.next_run_utc;
+        }
+        view
+    }
+    
+    /// Whether the occurrence scheduled at `slot` was already consumed by this trigger
+    pub fn slot_consumed(&self, key: &str, slot: DateTime<Utc>) -> bool {
+        self.trigger_states.get(key)
+            .is_some_and(|ts| ts.consumed_slot_utc == Some(slot))
+    }
 }
 
 /// Run result
@@ -209,6 +542,25 @@ pub enum SkipReason {
     DayNotAllowed,
     Paused,
     ManualOverride,
+    QuietHours,
+}
+
+impl SkipReason {
+    /// What the reason says, for showing in the user's language
+    pub fn message_id(&self) -> MessageId {
+        match self {
+            SkipReason::Disabled => MessageId::SkipDisabled,
+            SkipReason::ConditionFail => MessageId::SkipConditionFail,
+            SkipReason::Singleton => MessageId::SkipSingleton,
+            SkipReason::MisfireSkip => MessageId::SkipMisfire,
+            SkipReason::PathMissing => MessageId::SkipPathMissing,
+            SkipReason::AlreadyRanToday => MessageId::SkipAlreadyRanToday,
+            SkipReason::DayNotAllowed => MessageId::SkipDayNotAllowed,
+            SkipReason::Paused => MessageId::SkipPaused,
+            SkipReason::ManualOverride => MessageId::SkipManualOverride,
+            SkipReason::QuietHours => MessageId::SkipQuietHours,
+        }
+    }
 }
 
 /// Run log entry
@@ -224,8 +576,125 @@ pub struct RunLog {
     pub status: RunStatus,
     pub skip_reason: Option<SkipReason>,
     pub exit_code: Option<i32>,
+    /// The error in English
     pub error_message: Option<String>,
+    /// The error as a message id, for showing it in the user's language; None for older runs
+    #[serde(default)]
+    pub error: Option<Message>,
     pub output: Option<String>,
+    /// Overrides applied to a manual run
+    #[serde(default)]
+    pub overrides: Option<RunOverrides>,
+    /// How long the run took, for runs that actually executed
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+}
+
+/// Which run logs to export or clear; unset fields match everything
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct LogFilter {
+    pub task_id: Option<String>,
+    pub status: Option<RunStatus>,
+    /// Runs started at or after this time
+    pub since_utc: Option<DateTime<Utc>>,
+    /// Runs started before this time
+    pub until_utc: Option<DateTime<Utc>>,
+}
+
+/// One-off changes to a task for a single manual run
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct RunOverrides {
+    /// Replaces the task's arguments
+    #[serde(default)]
+    pub args: Option<String>,
+    /// Extra environment variables for the launched process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+}
+
+/// One attempt to deliver a run result to a webhook
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub run_id: String,
+    pub task_id: String,
+    pub url: String,
+    /// 1 for the first try, counting up with each retry
+    pub attempt: u32,
+    pub attempted_at_utc: DateTime<Utc>,
+    /// HTTP status of the response, if one came back
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub delivered: bool,
+}
+
+/// Run health of a task over a period
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TaskStats {
+    pub task_id: String,
+    pub days: u32,
+    pub total_runs: u32,
+    pub success_count: u32,
+    pub failure_count: u32,
+    pub skipped_count: u32,
+    /// Successes out of finished runs (skips excluded), None without any
+    pub success_rate: Option<f64>,
+    pub avg_duration_ms: Option<i64>,
+    pub p95_duration_ms: Option<i64>,
+    pub last_failure: Option<RunLog>,
+}
+
+impl TaskStats {
+    /// Aggregate a task's logs (any order)
+    pub fn from_logs(task_id: &str, days: u32, logs: &[RunLog]) -> Self {
+        let mut stats = TaskStats {
+            task_id: task_id.to_string(),
+            days,
+            ..Default::default()
+        };
+        let mut durations = Vec::new();
+        
+        for log in logs {
+            match log.status {
+                RunStatus::Success => stats.success_count += 1,
+                RunStatus::Failed => stats.failure_count += 1,
+                RunStatus::Skipped => stats.skipped_count += 1,
+                RunStatus::Started | RunStatus::DryRun => continue,
+            }
+            stats.total_runs += 1;
+            
+            if log.status == RunStatus::Failed
+                && stats.last_failure.as_ref().is_none_or(|f| f.started_at_utc < log.started_at_utc)
+            {
+                stats.last_failure = Some(log.clone());
+            }
+            
+            if log.status != RunStatus::Skipped {
+                let duration = log.duration_ms.or_else(|| {
+                    log.finished_at_utc.map(|finished| (finished - log.started_at_utc).num_milliseconds())
+                });
+                if let Some(duration) = duration {
+                    durations.push(duration.max(0));
+                }
+            }
+        }
+        
+        let finished = stats.success_count + stats.failure_count;
+        if finished > 0 {
+            stats.success_rate = Some(stats.success_count as f64 / finished as f64);
+        }
+        
+        if !durations.is_empty() {
+            durations.sort_unstable();
+            stats.avg_duration_ms = Some(durations.iter().sum::<i64>() / durations.len() as i64);
+            // Nearest-rank percentile
+            let rank = ((durations.len() as f64) * 0.95).ceil() as usize;
+            stats.p95_duration_ms = Some(durations[rank.clamp(1, durations.len()) - 1]);
+        }
+        
+        stats
+    }
 }
 
 /// Run status
@@ -236,6 +705,144 @@ pub enum RunStatus {
     Success,
     Failed,
     Skipped,
+    /// Would have run, but the scheduler is in dry-run mode
+    DryRun,
+}
+
+/// What the scheduler does with runs that fall into quiet hours
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuietHoursAction {
+    /// Hold the run until quiet hours end
+    #[default]
+    Defer,
+    /// Drop the run and log it as skipped
+    Skip,
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    #[default]
+    StartTls,
+    /// TLS from the start (usually port 465)
+    Tls,
+    /// No encryption, for local relays only
+    None,
+}
+
+/// Email alerts on failing tasks
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct EmailSettings {
+    pub enabled: bool,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub security: SmtpSecurity,
+    pub username: String,
+    /// Stored encrypted
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Alert once a task has failed this many times in a row (1 alerts on every failure)
+    pub after_consecutive_failures: u32,
+    /// Collect alerts and send them together once an hour
+    pub digest: bool,
+}
+
+impl Default for EmailSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            smtp_host: String::new(),
+            smtp_port: 587,
+            security: SmtpSecurity::default(),
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            to: vec![],
+            after_consecutive_failures: 1,
+            digest: false,
+        }
+    }
+}
+
+/// MQTT broker connection, announcing tasks to Home Assistant
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    /// Stored encrypted
+    pub password: String,
+    /// Topics are published under `<base_topic>/<computer name>/`
+    pub base_topic: String,
+    /// Home Assistant's discovery prefix
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: String::new(),
+            port: 1883,
+            username: String::new(),
+            password: String::new(),
+            base_topic: "routine_runner".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// Checking for and installing new versions of the app
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UpdateSettings {
+    /// Look for a new version once a day
+    pub check_enabled: bool,
+    /// HTTPS URL of the release manifest (latest.json)
+    pub manifest_url: String,
+    /// Install a new version on its own at `install_at`, when no task is running
+    pub auto_install: bool,
+    /// "HH:MM" local time to install at
+    pub install_at: String,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            check_enabled: true,
+            manifest_url: "https://github.com/manhha5842/Routine-Runner/releases/latest/download/latest.json".to_string(),
+            auto_install: false,
+            install_at: "03:00".to_string(),
+        }
+    }
+}
+
+/// How versions of the configuration are kept
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigHistoryMode {
+    #[default]
+    Off,
+    /// Commit each version to a local git repository (needs git installed)
+    Git,
+    /// Write each version to its own timestamped file
+    Snapshots,
+}
+
+/// An email alert waiting for the next digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedEmailAlert {
+    pub id: i64,
+    pub queued_at_utc: DateTime<Utc>,
+    pub subject: String,
+    pub body: String,
 }
 
 /// Application settings
@@ -247,6 +854,99 @@ pub struct Settings {
     pub timezone_id: String,
     pub log_retention_days: u32,
     pub max_parallel_runs: u8,
+    /// Evaluate triggers and conditions but never execute anything
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub quiet_hours_enabled: bool,
+    #[serde(default = "default_quiet_hours_start")]
+    pub quiet_hours_start: String, // "HH:MM"
+    #[serde(default = "default_quiet_hours_end")]
+    pub quiet_hours_end: String, // "HH:MM"
+    #[serde(default)]
+    pub quiet_hours_action: QuietHoursAction,
+    /// YAML/TOML file whose tasks are kept in sync with the database
+    #[serde(default)]
+    pub task_file_path: Option<String>,
+    /// Back up the database once a day under the app data dir
+    #[serde(default)]
+    pub auto_backup_enabled: bool,
+    /// Number of automatic backups to keep
+    #[serde(default = "default_auto_backup_keep")]
+    pub auto_backup_keep: u32,
+    /// Compact the database (VACUUM/ANALYZE) once a month
+    #[serde(default)]
+    pub auto_maintenance_enabled: bool,
+    /// Also notify when a run succeeds (failures and skips always notify)
+    #[serde(default)]
+    pub notify_on_success: bool,
+    /// Webhooks that receive every task's run results
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Slack/Discord/Telegram destinations tasks can pick; credentials are encrypted
+    #[serde(default)]
+    pub chat_notifiers: Vec<ChatNotifier>,
+    #[serde(default)]
+    pub email: EmailSettings,
+    /// Write failed runs to the Windows Application event log
+    #[serde(default)]
+    pub event_log_enabled: bool,
+    /// Also write successful runs to the event log
+    #[serde(default)]
+    pub event_log_successes: bool,
+    /// Serve Prometheus metrics on 127.0.0.1
+    #[serde(default)]
+    pub metrics_enabled: bool,
+    #[serde(default = "default_metrics_port")]
+    pub metrics_port: u16,
+    #[serde(default)]
+    pub mqtt: MqttSettings,
+    /// Accept webhook triggers over HTTP
+    #[serde(default)]
+    pub webhook_server_enabled: bool,
+    /// Address to listen on; 0.0.0.0 lets other machines on the network reach it
+    #[serde(default = "default_webhook_server_address")]
+    pub webhook_server_address: String,
+    #[serde(default = "default_webhook_server_port")]
+    pub webhook_server_port: u16,
+    /// Folder (e.g. in OneDrive) whose shared task file keeps machines in sync
+    #[serde(default)]
+    pub sync_folder: Option<String>,
+    /// Keep a version of the tasks and settings after every change
+    #[serde(default)]
+    pub config_history_mode: ConfigHistoryMode,
+    /// Global shortcut opening the quick-run palette, e.g. "Ctrl+Alt+Space"; None turns it off
+    #[serde(default)]
+    pub quick_run_hotkey: Option<String>,
+    #[serde(default)]
+    pub updates: UpdateSettings,
+    /// Language of notifications and messages
+    #[serde(default)]
+    pub language: Locale,
+}
+
+fn default_quiet_hours_start() -> String {
+    "22:00".to_string()
+}
+
+fn default_quiet_hours_end() -> String {
+    "07:00".to_string()
+}
+
+fn default_auto_backup_keep() -> u32 {
+    7
+}
+
+fn default_metrics_port() -> u16 {
+    9470
+}
+
+fn default_webhook_server_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_webhook_server_port() -> u16 {
+    9471
 }
 
 impl Default for Settings {
@@ -258,6 +958,93 @@ impl Default for Settings {
             timezone_id: "system".to_string(),
             log_retention_days: 30,
             max_parallel_runs: 3,
+            dry_run: false,
+            quiet_hours_enabled: false,
+            quiet_hours_start: default_quiet_hours_start(),
+            quiet_hours_end: default_quiet_hours_end(),
+            quiet_hours_action: QuietHoursAction::default(),
+            task_file_path: None,
+            auto_backup_enabled: false,
+            auto_backup_keep: default_auto_backup_keep(),
+            auto_maintenance_enabled: false,
+            notify_on_success: false,
+            webhooks: vec![],
+            chat_notifiers: vec![],
+            email: EmailSettings::default(),
+            event_log_enabled: false,
+            event_log_successes: false,
+            metrics_enabled: false,
+            metrics_port: default_metrics_port(),
+            mqtt: MqttSettings::default(),
+            webhook_server_enabled: false,
+            webhook_server_address: default_webhook_server_address(),
+            webhook_server_port: default_webhook_server_port(),
+            sync_folder: None,
+            config_history_mode: ConfigHistoryMode::Off,
+            quick_run_hotkey: None,
+            updates: UpdateSettings::default(),
+            language: Locale::default(),
         }
     }
 }
+
+/// Outcome of compacting the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactSummary {
+    pub size_before_bytes: u64,
+    pub size_after_bytes: u64,
+    pub freed_bytes: u64,
+}
+
+/// Portable snapshot of tasks (and optionally settings) for moving to another PC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBundle {
+    pub format_version: u32,
+    pub exported_at_utc: DateTime<Utc>,
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub settings: Option<Settings>,
+}
+
+impl ExportBundle {
+    pub const FORMAT_VERSION: u32 = 1;
+}
+
+/// Change applied to many tasks at once by bulk_update_tasks
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TaskPatch {
+    /// Enable or disable the tasks
+    pub enabled: Option<bool>,
+    /// Move the tasks to the trash (takes precedence over other fields)
+    pub delete: bool,
+}
+
+/// What to do when an imported task has the same id as an existing one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportCollision {
+    /// Keep the existing task
+    #[default]
+    Skip,
+    /// Replace the existing task with the imported one
+    Overwrite,
+    /// Import the task as a copy with a new id
+    Duplicate,
+}
+
+/// Outcome of an import
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ImportSummary {
+    pub created: u32,
+    pub overwritten: u32,
+    pub duplicated: u32,
+    pub skipped: u32,
+    pub settings_imported: bool,
+    /// Imported tasks whose secrets were left out of the export, by name; they need entering again
+    #[serde(default)]
+    pub missing_secrets: Vec<String>,
+    /// Imported settings whose credentials were left out of the export
+    #[serde(default)]
+    pub settings_missing_secrets: bool,
+}
@@ -0,0 +1,441 @@
+//! MQTT module - Announce tasks to Home Assistant over MQTT and take commands from it
+//!
+//! Speaks the small part of MQTT 3.1.1 needed here (QoS 0 over plain TCP). Each task
+//! becomes a "run" button and an "enabled" switch through Home Assistant discovery,
+//! with its last and next run as attributes.
+
+use crate::models::*;
+use crate::scheduler_runner::SchedulerRunner;
+use crate::storage::Database;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+
+/// Keep-alive agreed with the broker; pings go out at half of it
+const KEEP_ALIVE_SECS: u16 = 60;
+
+/// How often task entities and attributes are refreshed (only changes are published)
+const SYNC_SECS: u64 = 10;
+
+/// Wait before connecting again after the connection failed
+const RECONNECT_SECS: u64 = 30;
+
+const CONNECT_TIMEOUT_SECS: u64 = 10;
+
+const ONLINE: &[u8] = b"online";
+const OFFLINE: &[u8] = b"offline";
+
+// Packet types (first byte of the fixed header)
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+/// Stay connected to the broker while MQTT is enabled, reconnecting after failures
+pub async fn bridge_loop(db: Arc<Database>, scheduler: Arc<SchedulerRunner>) {
+    loop {
+        match current_settings(&db).await {
+            Ok(mqtt) if mqtt.enabled && !mqtt.host.trim().is_empty() => {
+                match session(&db, &scheduler, &mqtt).await {
+                    // The settings changed, connect again with the new ones
+                    Ok(()) => continue,
+                    Err(e) => tracing::warn!("MQTT connection to {} failed: {}", mqtt.host, e),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Failed to read MQTT settings: {}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(RECONNECT_SECS)).await;
+    }
+}
+
+/// MQTT settings with the password decrypted
+async fn current_settings(db: &Arc<Database>) -> Result<MqttSettings, String> {
+    db.run(|db| {
+        let settings = db.get_settings().map_err(|e| e.to_string())?;
+        Ok(crate::secrets::reveal_settings(&settings)?.mqtt)
+    }).await
+}
+
+/// Name of this computer as used in topics and entity ids
+fn node_id() -> String {
+    let name = std::env::var("COMPUTERNAME").unwrap_or_else(|_| "pc".to_string());
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// One connection: returns Ok when the settings changed, Err when the connection broke
+async fn session(db: &Arc<Database>, scheduler: &Arc<SchedulerRunner>, mqtt: &MqttSettings) -> Result<(), String> {
+    let node = node_id();
+    let root = format!("{}/{}", mqtt.base_topic.trim_matches('/'), node);
+    let status_topic = format!("{}/status", root);
+
+    let connect = TcpStream::connect((mqtt.host.trim(), mqtt.port));
+    let stream = tokio::time::timeout(tokio::time::Duration::from_secs(CONNECT_TIMEOUT_SECS), connect)
+        .await
+        .map_err(|_| "Timed out connecting".to_string())?
+        .map_err(|e| e.to_string())?;
+    let (mut reader, mut writer) = stream.into_split();
+
+    writer.write_all(&connect_packet(&format!("routine-runner-{}", node), &status_topic, mqtt))
+        .await
+        .map_err(|e| e.to_string())?;
+    let connack = read_packet(&mut reader);
+    let (header, body) = tokio::time::timeout(tokio::time::Duration::from_secs(CONNECT_TIMEOUT_SECS), connack)
+        .await
+        .map_err(|_| "Timed out waiting for the broker".to_string())?
+        .map_err(|e| e.to_string())?;
+    if header != CONNACK {
+        return Err("Broker did not acknowledge the connection".to_string());
+    }
+    check_connack(&body)?;
+    tracing::info!("Connected to MQTT broker {}:{}", mqtt.host, mqtt.port);
+
+    // Reading in its own task keeps partially read packets safe from the select below
+    let (packets_tx, mut packets) = tokio::sync::mpsc::channel(16);
+    let read_task = tokio::spawn(async move {
+        loop {
+            let packet = read_packet(&mut reader).await;
+            let failed = packet.is_err();
+            if packets_tx.send(packet).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        send(&mut writer, &subscribe_packet(&[
+            &format!("{}/task/+/run", root),
+            &format!("{}/task/+/enabled/set", root),
+        ])).await?;
+        send(&mut writer, &publish_packet(&status_topic, ONLINE, true)).await?;
+
+        let mut published: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut sync = tokio::time::interval(tokio::time::Duration::from_secs(SYNC_SECS));
+        let mut ping = tokio::time::interval(tokio::time::Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2));
+        loop {
+            tokio::select! {
+                packet = packets.recv() => {
+                    let (header, body) = packet
+                        .ok_or("Connection closed")?
+                        .map_err(|e| e.to_string())?;
+                    if header & 0xF0 == PUBLISH {
+                        if let Some((topic, payload)) = parse_publish(header, &body) {
+                            handle_command(db, scheduler, &root, &topic, &payload).await;
+                            // Show the outcome (e.g. a switched task) right away
+                            sync.reset_immediately();
+                        }
+                    }
+                }
+                _ = sync.tick() => {
+                    if current_settings(db).await.ok().as_ref() != Some(mqtt) {
+                        let _ = send(&mut writer, &publish_packet(&status_topic, OFFLINE, true)).await;
+                        let _ = send(&mut writer, &[DISCONNECT, 0]).await;
+                        return Ok(());
+                    }
+                    let wanted = entity_messages(db, scheduler, mqtt, &node, &root).await?;
+                    sync_retained(&mut writer, &mut published, wanted).await?;
+                }
+                _ = ping.tick() => send(&mut writer, &[PINGREQ, 0]).await?,
+            }
+        }
+    }.await;
+
+    read_task.abort();
+    result
+}
+
+async fn send(writer: &mut OwnedWriteHalf, packet: &[u8]) -> Result<(), String> {
+    writer.write_all(packet).await.map_err(|e| e.to_string())
+}
+
+/// Publish retained messages that changed, and clear the ones no longer wanted (deleted tasks)
+async fn sync_retained(
+    writer: &mut OwnedWriteHalf,
+    published: &mut HashMap<String, Vec<u8>>,
+    wanted: Vec<(String, Vec<u8>)>,
+) -> Result<(), String> {
+    let wanted_topics: HashSet<&String> = wanted.iter().map(|(topic, _)| topic).collect();
+    let gone: Vec<String> = published.keys().filter(|t| !wanted_topics.contains(t)).cloned().collect();
+    for topic in gone {
+        // An empty retained message removes the entity from Home Assistant
+        send(writer, &publish_packet(&topic, b"", true)).await?;
+        published.remove(&topic);
+    }
+    for (topic, payload) in wanted {
+        if published.get(&topic) != Some(&payload) {
+            send(writer, &publish_packet(&topic, &payload, true)).await?;
+            published.insert(topic, payload);
+        }
+    }
+    Ok(())
+}
+
+/// Discovery configs, switch states and attributes for every task
+async fn entity_messages(
+    db: &Arc<Database>,
+    scheduler: &SchedulerRunner,
+    mqtt: &MqttSettings,
+    node: &str,
+    root: &str,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let (tasks, states) = db.run(|db| Ok::<_, rusqlite::Error>((db.get_all_tasks()?, db.get_task_states()?)))
+        .await
+        .map_err(|e| e.to_string())?;
+    let states: HashMap<String, TaskState> = states.into_iter().map(|s| (s.task_id.clone(), s)).collect();
+    let running: HashSet<String> = scheduler.status().await.running.into_iter().map(|r| r.task_id).collect();
+
+    let prefix = mqtt.discovery_prefix.trim_matches('/');
+    let device = serde_json::json!({
+        "identifiers": [format!("routine_runner_{}", node)],
+        "name": format!("Routine Runner ({})", node),
+        "manufacturer": "Routine Runner",
+    });
+
+    let mut messages = Vec::new();
+    for task in &tasks {
+        let task_root = format!("{}/task/{}", root, task.id);
+        let attributes_topic = format!("{}/attributes", task_root);
+        let state = states.get(&task.id).cloned().unwrap_or_default();
+
+        let button = serde_json::json!({
+            "name": task.name,
+            "unique_id": format!("routine_runner_{}_{}_run", node, task.id),
+            "command_topic": format!("{}/run", task_root),
+            "availability_topic": format!("{}/status", root),
+            "json_attributes_topic": attributes_topic,
+            "icon": "mdi:play",
+            "device": device,
+        });
+        let switch = serde_json::json!({
+            "name": format!("{} enabled", task.name),
+            "unique_id": format!("routine_runner_{}_{}_enabled", node, task.id),
+            "command_topic": format!("{}/enabled/set", task_root),
+            "state_topic": format!("{}/enabled", task_root),
+            "availability_topic": format!("{}/status", root),
+            "json_attributes_topic": attributes_topic,
+            "icon": "mdi:calendar-check",
+            "device": device,
+        });
+        let attributes = serde_json::json!({
+            "running": running.contains(&task.id),
+            "last_run_at": state.last_run_at_utc,
+            "last_result": state.last_result,
+            "last_error": state.last_error,
+            "next_run_at": state.next_run_at_utc,
+        });
+
+        messages.extend([
+            (format!("{}/button/{}/{}_run/config", prefix, node, task.id), button.to_string().into_bytes()),
+            (format!("{}/switch/{}/{}_enabled/config", prefix, node, task.id), switch.to_string().into_bytes()),
+            (format!("{}/enabled", task_root), if task.enabled { b"ON".to_vec() } else { b"OFF".to_vec() }),
+            (attributes_topic, attributes.to_string().into_bytes()),
+        ]);
+    }
+    Ok(messages)
+}
+
+/// Carry out a button press or switch change from Home Assistant
+async fn handle_command(db: &Arc<Database>, scheduler: &Arc<SchedulerRunner>, root: &str, topic: &str, payload: &[u8]) {
+    let Some(rest) = topic.strip_prefix(&format!("{}/task/", root)) else { return };
+    let (task_id, action) = rest.split_once('/').unwrap_or((rest, ""));
+    let id = task_id.to_string();
+    let task = match db.run(move |db| db.get_task(&id)).await {
+        Ok(Some(task)) => task,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!("Failed to load task {} for MQTT command: {}", task_id, e);
+            return;
+        }
+    };
+
+    match action {
+        // Like a webhook, a pause holds back runs requested from outside
+        "run" if scheduler.is_paused() => tracing::warn!("Not running task {} from MQTT: scheduler is paused", task_id),
+        "run" => match scheduler.run_now(task, RunOverrides::default()).await {
+            Ok(_) => tracing::info!("Started task {} from MQTT", task_id),
+            Err(e) => tracing::warn!("Failed to run task {} from MQTT: {}", task_id, e),
+        },
+        "enabled/set" => {
+            let enabled = payload.eq_ignore_ascii_case(b"ON");
+            if task.managed_by_file || task.enabled == enabled {
+                return;
+            }
+            let task = Task { enabled, ..task };
+            let updated = task.clone();
+            match db.run(move |db| db.update_task(&updated)).await {
+                Ok(()) => {
+                    tracing::info!("{} task {} from MQTT", if enabled { "Enabled" } else { "Disabled" }, task.name);
+                    scheduler.wake();
+                }
+                Err(e) => tracing::error!("Failed to update task {} from MQTT: {}", task.name, e),
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Append a length-prefixed string
+fn push_str(buf: &mut Vec<u8>, value: &[u8]) {
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+}
+
+/// Fixed header (with the variable-length remaining length) followed by the body
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// CONNECT with a clean session and a retained "offline" will on the status topic
+fn connect_packet(client_id: &str, will_topic: &str, mqtt: &MqttSettings) -> Vec<u8> {
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if !mqtt.username.is_empty() {
+        flags |= 0x80;
+        if !mqtt.password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+
+    let mut body = Vec::new();
+    push_str(&mut body, b"MQTT");
+    body.push(4); // protocol level 3.1.1
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    push_str(&mut body, client_id.as_bytes());
+    push_str(&mut body, will_topic.as_bytes());
+    push_str(&mut body, OFFLINE);
+    if !mqtt.username.is_empty() {
+        push_str(&mut body, mqtt.username.as_bytes());
+        if !mqtt.password.is_empty() {
+            push_str(&mut body, mqtt.password.as_bytes());
+        }
+    }
+    packet(CONNECT, &body)
+}
+
+fn check_connack(body: &[u8]) -> Result<(), String> {
+    match body.get(1) {
+        Some(0) => Ok(()),
+        Some(4) => Err("Bad user name or password".to_string()),
+        Some(5) => Err("Not authorized".to_string()),
+        Some(code) => Err(format!("Connection refused (code {})", code)),
+        None => Err("Malformed CONNACK".to_string()),
+    }
+}
+
+fn publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    push_str(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    packet(PUBLISH | retain as u8, &body)
+}
+
+fn subscribe_packet(filters: &[&str]) -> Vec<u8> {
+    let mut body = 1u16.to_be_bytes().to_vec(); // packet id
+    for filter in filters {
+        push_str(&mut body, filter.as_bytes());
+        body.push(0); // QoS 0
+    }
+    packet(SUBSCRIBE, &body)
+}
+
+/// Topic and payload of an incoming PUBLISH
+fn parse_publish(header: u8, body: &[u8]) -> Option<(String, Vec<u8>)> {
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = String::from_utf8(body.get(2..2 + topic_len)?.to_vec()).ok()?;
+    // QoS 1 and 2 messages carry a packet id before the payload
+    let payload_start = 2 + topic_len + if header & 0x06 != 0 { 2 } else { 0 };
+    Some((topic, body.get(payload_start..)?.to_vec()))
+}
+
+async fn read_packet(reader: &mut (impl AsyncRead + Unpin)) -> std::io::Result<(u8, Vec<u8>)> {
+    let header = reader.read_u8().await?;
+    let mut len = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = reader.read_u8().await?;
+        len |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+        if shift > 21 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Malformed packet length"));
+        }
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packet_remaining_length_encoding() {
+        assert_eq!(packet(PINGREQ, &[]), [PINGREQ, 0]);
+        assert_eq!(packet(PUBLISH, &[0; 127])[..2], [PUBLISH, 0x7F]);
+        assert_eq!(packet(PUBLISH, &[0; 128])[..3], [PUBLISH, 0x80, 0x01]);
+        assert_eq!(packet(PUBLISH, &[0; 16_383])[..3], [PUBLISH, 0xFF, 0x7F]);
+        assert_eq!(packet(PUBLISH, &[0; 16_384])[..4], [PUBLISH, 0x80, 0x80, 0x01]);
+    }
+
+    #[tokio::test]
+    async fn test_read_packet_round_trip() {
+        for len in [0, 1, 127, 128, 16_383, 16_384, 300_000] {
+            let body: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let bytes = packet(PUBLISH, &body);
+            let (header, read) = read_packet(&mut bytes.as_slice()).await.unwrap();
+            assert_eq!(header, PUBLISH);
+            assert_eq!(read, body);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_packet_rejects_bad_input() {
+        // More than four length bytes
+        let mut too_long: &[u8] = &[PUBLISH, 0x80, 0x80, 0x80, 0x80, 0x01];
+        assert_eq!(read_packet(&mut too_long).await.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        // Cut off before the announced body ends
+        let mut truncated: &[u8] = &[PUBLISH, 0x05, 1, 2];
+        assert!(read_packet(&mut truncated).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_publish() {
+        let bytes = publish_packet("rr/pc/task/a/run", b"PRESS", false);
+        let (header, body) = (bytes[0], &bytes[2..]);
+        assert_eq!(parse_publish(header, body), Some(("rr/pc/task/a/run".to_string(), b"PRESS".to_vec())));
+
+        // QoS 1 puts a packet id between topic and payload
+        let mut body = Vec::new();
+        push_str(&mut body, b"t");
+        body.extend_from_slice(&[0, 7]);
+        body.extend_from_slice(b"ON");
+        assert_eq!(parse_publish(PUBLISH | 0x02, &body), Some(("t".to_string(), b"ON".to_vec())));
+
+        assert_eq!(parse_publish(PUBLISH, &[0, 9, b'a']), None);
+        assert_eq!(parse_publish(PUBLISH, &[]), None);
+    }
+}
@@ -0,0 +1,168 @@
+//! Notifications module - Desktop toasts for finished and skipped runs
+
+use crate::models::*;
+
+/// Minutes the Snooze button holds a task back
+pub const SNOOZE_MINUTES: u32 = 15;
+
+/// A button on a run notification
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToastAction {
+    /// Run the task again now
+    Retry,
+    /// Hold the task's runs back for SNOOZE_MINUTES
+    Snooze,
+    /// Show the run's log in the app
+    OpenLog,
+}
+
+#[cfg(windows)]
+impl ToastAction {
+    fn label(self) -> &'static str {
+        match self {
+            ToastAction::Retry => "Chạy lại",
+            ToastAction::Snooze => "Hoãn 15 phút",
+            ToastAction::OpenLog => "Xem log",
+        }
+    }
+
+    /// Identifier passed back when the button is clicked
+    fn key(self) -> &'static str {
+        match self {
+            ToastAction::Retry => "retry",
+            ToastAction::Snooze => "snooze",
+            ToastAction::OpenLog => "open_log",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        [ToastAction::Retry, ToastAction::Snooze, ToastAction::OpenLog].into_iter().find(|a| a.key() == key)
+    }
+}
+
+/// Buttons offered on the notification for a run
+pub fn toast_actions(log: &RunLog) -> Vec<ToastAction> {
+    match log.status {
+        RunStatus::Failed => vec![ToastAction::Retry, ToastAction::Snooze, ToastAction::OpenLog],
+        RunStatus::Skipped => vec![ToastAction::Retry, ToastAction::OpenLog],
+        _ => vec![ToastAction::OpenLog],
+    }
+}
+
+/// Whether a finished or skipped run should show a notification
+pub fn should_notify(log: &RunLog, policy: &NotifyPolicy, settings: &Settings) -> bool {
+    if !settings.show_notifications {
+        return false;
+    }
+    match (policy, &log.status) {
+        (NotifyPolicy::Never, _) => false,
+        (_, RunStatus::Failed) => true,
+        (NotifyPolicy::FailuresOnly, _) => false,
+        (_, RunStatus::Skipped) => log.skip_reason.as_ref().is_some_and(is_notable_skip),
+        (NotifyPolicy::All, RunStatus::Success) => true,
+        (NotifyPolicy::Default, RunStatus::Success) => settings.notify_on_success,
+        _ => false,
+    }
+}
+
+/// Skips the user did not ask for. Pauses, quiet hours and day restrictions are expected.
+fn is_notable_skip(reason: &SkipReason) -> bool {
+    matches!(
+        reason,
+        SkipReason::ConditionFail | SkipReason::Singleton | SkipReason::MisfireSkip | SkipReason::PathMissing
+    )
+}
+
+/// Title and body of the notification for a run
+pub fn notification_text(log: &RunLog, locale: Locale) -> (String, String) {
+    let body = match &log.status {
+        RunStatus::Failed => {
+            let mut body = match log.exit_code {
+                Some(code) => Message::new(MessageId::RunFailedWithCode, &[&code]).render(locale),
+                None => MessageId::RunFailed.template(locale).to_string(),
+            };
+            // Runs logged before errors had ids only have the text
+            let error = match &log.error {
+                Some(error) => Some(error.render(locale)),
+                None => log.error_message.clone(),
+            };
+            if let Some(error) = error.filter(|e| !e.is_empty()) {
+                body.push_str(": ");
+                body.push_str(&error);
+            }
+            body
+        }
+        RunStatus::Skipped => {
+            let reason = log.skip_reason.as_ref().map_or(MessageId::SkipUnknown, SkipReason::message_id);
+            Message::new(MessageId::RunSkipped, &[&reason.template(locale)]).render(locale)
+        }
+        _ => match log.duration_ms {
+            Some(ms) => Message::new(MessageId::RunSucceededAfter, &[&format!("{:.1}", ms as f64 / 1000.0)]).render(locale),
+            None => MessageId::RunSucceeded.template(locale).to_string(),
+        },
+    };
+    (log.task_name.clone(), body)
+}
+
+/// Show a Windows toast under the given AppUserModelID. `on_action` is called with the
+/// button the user clicks, as long as the app is still running.
+pub fn show_toast(
+    app_id: &str,
+    title: &str,
+    body: &str,
+    actions: &[ToastAction],
+    on_action: impl Fn(ToastAction) + Send + 'static,
+) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+        use windows::core::{IInspectable, Interface, HSTRING};
+        use windows::Data::Xml::Dom::XmlDocument;
+        use windows::Foundation::TypedEventHandler;
+        use windows::UI::Notifications::{ToastActivatedEventArgs, ToastNotification, ToastNotificationManager};
+
+        let buttons: String = actions.iter()
+            .map(|action| format!(
+                "<action content=\"{}\" arguments=\"{}\" activationType=\"foreground\"/>",
+                escape_xml(action.label()),
+                action.key(),
+            ))
+            .collect();
+        let xml = format!(
+            "<toast><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text></binding></visual><actions>{}</actions></toast>",
+            escape_xml(title),
+            escape_xml(body),
+            buttons,
+        );
+        let show = || -> windows::core::Result<()> {
+            let document = XmlDocument::new()?;
+            document.LoadXml(&HSTRING::from(xml.as_str()))?;
+            let toast = ToastNotification::CreateToastNotification(&document)?;
+            toast.Activated(&TypedEventHandler::new(
+                move |_: &Option<ToastNotification>, args: &Option<IInspectable>| {
+                    let Some(args) = args else { return Ok(()) };
+                    let key = args.cast::<ToastActivatedEventArgs>()?.Arguments()?.to_string();
+                    if let Some(action) = ToastAction::from_key(&key) {
+                        on_action(action);
+                    }
+                    Ok(())
+                },
+            ))?;
+            ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(app_id))?.Show(&toast)
+        };
+        show().map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(windows))]
+    {
+        let _ = (app_id, title, body, actions, on_action);
+        Err("Notifications are only supported on Windows".to_string())
+    }
+}
+
+#[cfg(windows)]
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
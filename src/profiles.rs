@@ -0,0 +1,81 @@
+//! Profiles module - Separate named databases ("Work", "Personal") under the app data dir
+
+use std::path::{Path, PathBuf};
+
+/// The profile living at the original database location
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Database file name inside a profile directory
+pub const DATABASE_FILE: &str = "auto-open.db";
+
+/// File remembering which profile to open at startup
+const ACTIVE_PROFILE_FILE: &str = "active_profile";
+
+/// Longest accepted profile name
+const MAX_NAME_LEN: usize = 40;
+
+/// Directory holding a profile's database and backups
+pub fn profile_dir(data_dir: &Path, name: &str) -> PathBuf {
+    if name == DEFAULT_PROFILE {
+        data_dir.to_path_buf()
+    } else {
+        data_dir.join("profiles").join(name)
+    }
+}
+
+/// All profiles, Default first
+pub fn list_profiles(data_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(data_dir.join("profiles"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .filter(|name| validate_name(name).is_ok())
+        .collect();
+    names.sort_by_key(|n| n.to_lowercase());
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    names
+}
+
+/// The existing profile matching `name`, ignoring case
+pub fn find_profile(data_dir: &Path, name: &str) -> Option<String> {
+    list_profiles(data_dir).into_iter().find(|p| p.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Profile names double as directory names, so keep them plain
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let name_ok = !name.trim().is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name == name.trim()
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if !name_ok {
+        return Err(format!(
+            "Profile names use letters, digits, spaces, '-' or '_' (at most {} characters)",
+            MAX_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Create a new empty profile directory
+pub fn create_profile(data_dir: &Path, name: &str) -> Result<(), String> {
+    validate_name(name)?;
+    if find_profile(data_dir, name).is_some() {
+        return Err(format!("Profile '{}' already exists", name));
+    }
+    std::fs::create_dir_all(profile_dir(data_dir, name)).map_err(|e| e.to_string())
+}
+
+/// Profile opened last time, falling back to Default if it is gone
+pub fn load_active(data_dir: &Path) -> String {
+    std::fs::read_to_string(data_dir.join(ACTIVE_PROFILE_FILE))
+        .ok()
+        .and_then(|name| find_profile(data_dir, &name))
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Remember the profile to open at the next start
+pub fn save_active(data_dir: &Path, name: &str) -> Result<(), String> {
+    std::fs::write(data_dir.join(ACTIVE_PROFILE_FILE), name).map_err(|e| e.to_string())
+}
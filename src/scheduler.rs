@@ -2,6 +2,7 @@
 
 use crate::models::*;
 use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone, Utc, Weekday};
+use rand::Rng;
 
 /// Compute the next run time for a trigger
 pub fn compute_next_run(
@@ -18,6 +19,9 @@ pub fn compute_next_run(
             None
         }
         
+        // Fired from outside, never scheduled
+        Trigger::Webhook { .. } => None,
+        
         Trigger::OncePerDay { enabled, earliest_time_local, days_of_week } => {
             if !enabled {
                 return None;
@@ -30,11 +34,8 @@ pub fn compute_next_run(
             }
             
             // Check day of week restriction
-            if let Some(days) = days_of_week {
-                let today_weekday = weekday_to_string(now_local.weekday());
-                if !days.iter().any(|d| d.eq_ignore_ascii_case(&today_weekday)) {
-                    return None; // Not the right day
-                }
+            if !day_allowed(days_of_week, now_local.weekday()) {
+                return None; // Not the right day
             }
             
             // Check earliest time
@@ -63,26 +64,33 @@ pub fn compute_next_run(
                 Err(_) => return None,
             };
             
+            // The next run is the first occurrence after the last run (looking back at
+            // most a day). A task that never ran keeps its planned slot until it passes.
+            let now_utc = now_local.with_timezone(&Utc);
+            let after = match (state.last_run_at_utc, state.next_run_at_utc) {
+                (Some(last), _) => last.max(now_utc - chrono::Duration::days(1)),
+                (None, Some(planned)) if planned <= now_utc => return Some(planned),
+                _ => now_utc,
+            };
+            let after_local = after.with_timezone(&Local);
+            
             // Find next occurrence
-            for day_offset in 0..8 {
-                let target_date = (now_local + chrono::Duration::days(day_offset)).date_naive();
+            for day_offset in 0..9 {
+                let target_date = (after_local + chrono::Duration::days(day_offset)).date_naive();
                 let target_datetime = target_date.and_time(target_time);
                 let target_local = match Local.from_local_datetime(&target_datetime).latest() {
                     Some(t) => t,
                     None => continue, // DST gap, skip
                 };
                 
-                // Skip if in the past
-                if target_local <= now_local {
+                // Skip occurrences that were already taken
+                if target_local <= after_local {
                     continue;
                 }
                 
                 // Check day of week restriction
-                if let Some(days) = days_of_week {
-                    let weekday = weekday_to_string(target_local.weekday());
-                    if !days.iter().any(|d| d.eq_ignore_ascii_case(&weekday)) {
-                        continue;
-                    }
+                if !day_allowed(days_of_week, target_local.weekday()) {
+                    continue;
                 }
                 
                 return Some(target_local.with_timezone(&Utc));
@@ -91,30 +99,189 @@ pub fn compute_next_run(
             None
         }
         
-        Trigger::Interval { enabled, every_seconds, jitter_seconds } => {
+        Trigger::Interval { enabled, every_seconds, jitter_seconds, jitter_mode } => {
             if !enabled || *every_seconds < 60 {
                 return None;
             }
             
-            let base = state.last_run_at_utc.unwrap_or(now_local.with_timezone(&Utc));
-            let next = base + chrono::Duration::seconds(*every_seconds as i64);
+            let next = match (state.last_run_at_utc, state.next_run_at_utc) {
+                // Already planned after the last run: keep it, so the jitter drawn
+                // for it isn't redrawn on every tick
+                (Some(last), Some(planned)) if planned > last => planned,
+                (Some(base), _) => {
+                    let next = base + chrono::Duration::seconds(*every_seconds as i64);
+                    // A spread as wide as the interval could land before the last run
+                    let spread = jitter_seconds.unwrap_or(0).min(every_seconds - 1);
+                    let jitter = jitter_offset(spread, *jitter_mode);
+                    next + chrono::Duration::seconds(jitter)
+                }
+                // Never ran: keep the first planned slot instead of pushing it back every tick
+                (None, Some(planned)) => planned,
+                (None, None) => now_local.with_timezone(&Utc) + chrono::Duration::seconds(*every_seconds as i64),
+            };
+            
+            // A next time in the past is due now; keep it so lateness can be measured
+            Some(next)
+        }
+    }
+}
+
+/// Expand a trigger into its occurrences between `from` and `until` (at most `limit`)
+pub fn expand_occurrences(
+    trigger: &Trigger,
+    state: &TaskState,
+    from: DateTime<Local>,
+    until: DateTime<Local>,
+    limit: usize,
+) -> Vec<DateTime<Utc>> {
+    let mut occurrences = Vec::new();
+    
+    match trigger {
+        Trigger::OnLogin { .. } | Trigger::Webhook { .. } => {
+            // Only fires at app startup or on request, nothing to forecast
+        }
+        
+        Trigger::OncePerDay { enabled, earliest_time_local, days_of_week } => {
+            if !enabled {
+                return occurrences;
+            }
+            
+            let earliest = earliest_time_local.as_ref()
+                .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
+                .unwrap_or(NaiveTime::MIN);
+            let today = from.format("%Y-%m-%d").to_string();
             
-            // Add jitter if specified
-            let next = if let Some(jitter) = jitter_seconds {
-                let jitter_offset = rand_jitter(*jitter);
-                next + chrono::Duration::seconds(jitter_offset as i64)
-            } else {
-                next
+            let mut date = from.date_naive();
+            while date <= until.date_naive() && occurrences.len() < limit {
+                let already_ran = date == from.date_naive()
+                    && state.last_run_date_local.as_ref() == Some(&today);
+                
+                if day_allowed(days_of_week, date.weekday()) && !already_ran {
+                    if let Some(t) = Local.from_local_datetime(&date.and_time(earliest)).latest() {
+                        // Runs as soon as possible once the earliest time has passed
+                        let t = t.max(from);
+                        if t <= until {
+                            occurrences.push(t.with_timezone(&Utc));
+                        }
+                    }
+                }
+                
+                date = match date.succ_opt() {
+                    Some(d) => d,
+                    None => break,
+                };
+            }
+        }
+        
+        Trigger::DailyAt { enabled, time_local, days_of_week } => {
+            if !enabled {
+                return occurrences;
+            }
+            
+            let target_time = match NaiveTime::parse_from_str(time_local, "%H:%M") {
+                Ok(t) => t,
+                Err(_) => return occurrences,
             };
             
-            // If next is in the past, schedule for now
-            if next <= now_local.with_timezone(&Utc) {
-                Some(now_local.with_timezone(&Utc))
-            } else {
-                Some(next)
+            let mut date = from.date_naive();
+            while date <= until.date_naive() && occurrences.len() < limit {
+                if day_allowed(days_of_week, date.weekday()) {
+                    if let Some(t) = Local.from_local_datetime(&date.and_time(target_time)).latest() {
+                        if t >= from && t <= until {
+                            occurrences.push(t.with_timezone(&Utc));
+                        }
+                    }
+                }
+                
+                date = match date.succ_opt() {
+                    Some(d) => d,
+                    None => break,
+                };
+            }
+        }
+        
+        Trigger::Interval { every_seconds, .. } => {
+            let mut next = match compute_next_run(trigger, from, state) {
+                Some(t) => t,
+                None => return occurrences,
+            };
+            let until_utc = until.with_timezone(&Utc);
+            let step = chrono::Duration::seconds(*every_seconds as i64);
+            
+            // An overdue run happens right away, followed by the regular cadence
+            if next < from.with_timezone(&Utc) {
+                next = from.with_timezone(&Utc);
+            }
+            
+            while next <= until_utc && occurrences.len() < limit {
+                occurrences.push(next);
+                next += step;
             }
         }
     }
+    
+    occurrences
+}
+
+/// If `now_local` falls into the quiet-hours window (which may wrap past midnight),
+/// return when the window ends
+pub fn quiet_hours_end(start: &str, end: &str, now_local: DateTime<Local>) -> Option<DateTime<Utc>> {
+    let start = NaiveTime::parse_from_str(start, "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(end, "%H:%M").ok()?;
+    let now = now_local.time();
+    let today = now_local.date_naive();
+    
+    let end_date = if start < end {
+        if now < start || now >= end {
+            return None;
+        }
+        today
+    } else if start > end {
+        if now >= start {
+            today.succ_opt()?
+        } else if now < end {
+            today
+        } else {
+            return None;
+        }
+    } else {
+        return None; // Empty window
+    };
+    
+    Local.from_local_datetime(&end_date.and_time(end))
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Effective runtime limit of a run starting at `now_local`: the task's max runtime,
+/// shortened so the run ends by its must-finish-by time
+pub fn runtime_limit(task: &Task, now_local: DateTime<Local>) -> Option<u32> {
+    let until_deadline = task.must_finish_by_local.as_ref()
+        .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M").ok())
+        .and_then(|deadline| {
+            let mut date = now_local.date_naive();
+            if now_local.time() >= deadline {
+                date = date.succ_opt()?;
+            }
+            Local.from_local_datetime(&date.and_time(deadline)).earliest()
+        })
+        .map(|deadline| (deadline - now_local).num_seconds().max(1) as u32);
+    
+    match (task.max_runtime_seconds, until_deadline) {
+        (Some(max), Some(deadline)) => Some(max.min(deadline)),
+        (max, deadline) => max.or(deadline),
+    }
+}
+
+/// Check a day-of-week restriction (no restriction allows every day)
+fn day_allowed(days_of_week: &Option<Vec<String>>, wd: Weekday) -> bool {
+    match days_of_week {
+        Some(days) => {
+            let weekday = weekday_to_string(wd);
+            days.iter().any(|d| d.eq_ignore_ascii_case(&weekday))
+        }
+        None => true,
+    }
 }
 
 fn weekday_to_string(wd: Weekday) -> String {
@@ -129,12 +296,17 @@ fn weekday_to_string(wd: Weekday) -> String {
     }
 }
 
-fn rand_jitter(max: u32) -> u32 {
-    // Simple pseudo-random based on current time
-    (std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .subsec_nanos() % max) as u32
+/// Draw a random jitter offset in seconds for the given spread
+fn jitter_offset(jitter: u32, mode: JitterMode) -> i64 {
+    if jitter == 0 {
+        return 0;
+    }
+    let jitter = jitter as i64;
+    let mut rng = rand::thread_rng();
+    match mode {
+        JitterMode::Delay => rng.gen_range(0..=jitter),
+        JitterMode::Spread => rng.gen_range(-jitter..=jitter),
+    }
 }
 
 /// Check if a task should be skipped due to misfire policy
@@ -151,3 +323,121 @@ pub fn check_misfire(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn daily_at(time: &str) -> Trigger {
+        Trigger::DailyAt {
+            enabled: true,
+            time_local: time.to_string(),
+            days_of_week: None,
+        }
+    }
+    
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+    
+    #[test]
+    fn test_daily_at_never_ran_plans_future_slot() {
+        let now = local(2024, 1, 10, 15, 0);
+        let state = TaskState::default();
+        let next = compute_next_run(&daily_at("09:00"), now, &state).unwrap();
+        assert_eq!(next, local(2024, 1, 11, 9, 0).with_timezone(&Utc));
+    }
+    
+    #[test]
+    fn test_daily_at_missed_slot_is_due() {
+        let now = local(2024, 1, 10, 9, 20);
+        let state = TaskState {
+            last_run_at_utc: Some(local(2024, 1, 9, 9, 0).with_timezone(&Utc)),
+            ..Default::default()
+        };
+        let next = compute_next_run(&daily_at("09:00"), now, &state).unwrap();
+        assert_eq!(next, local(2024, 1, 10, 9, 0).with_timezone(&Utc));
+        assert!(next <= now.with_timezone(&Utc));
+    }
+    
+    #[test]
+    fn test_expand_daily_at_respects_days() {
+        let trigger = Trigger::DailyAt {
+            enabled: true,
+            time_local: "09:00".to_string(),
+            days_of_week: Some(vec!["Mon".to_string(), "Wed".to_string()]),
+        };
+        // 2024-01-08 is a Monday
+        let from = local(2024, 1, 8, 10, 0);
+        let until = local(2024, 1, 15, 23, 59);
+        let runs = expand_occurrences(&trigger, &TaskState::default(), from, until, 100);
+        assert_eq!(runs, vec![
+            local(2024, 1, 10, 9, 0).with_timezone(&Utc),
+            local(2024, 1, 15, 9, 0).with_timezone(&Utc),
+        ]);
+    }
+    
+    #[test]
+    fn test_quiet_hours_wrap_past_midnight() {
+        let late = local(2024, 1, 10, 23, 30);
+        assert_eq!(
+            quiet_hours_end("22:00", "07:00", late),
+            Some(local(2024, 1, 11, 7, 0).with_timezone(&Utc))
+        );
+        let early = local(2024, 1, 11, 2, 0);
+        assert_eq!(
+            quiet_hours_end("22:00", "07:00", early),
+            Some(local(2024, 1, 11, 7, 0).with_timezone(&Utc))
+        );
+        assert_eq!(quiet_hours_end("22:00", "07:00", local(2024, 1, 11, 12, 0)), None);
+        assert_eq!(quiet_hours_end("12:00", "13:00", local(2024, 1, 11, 13, 0)), None);
+    }
+    
+    #[test]
+    fn test_interval_keeps_planned_jittered_run() {
+        let trigger = Trigger::Interval {
+            enabled: true,
+            every_seconds: 3600,
+            jitter_seconds: Some(600),
+            jitter_mode: JitterMode::Spread,
+        };
+        let last = local(2024, 1, 10, 9, 0).with_timezone(&Utc);
+        let now = local(2024, 1, 10, 9, 30);
+        
+        let next = compute_next_run(&trigger, now, &TaskState {
+            last_run_at_utc: Some(last),
+            ..Default::default()
+        }).unwrap();
+        let offset = (next - last).num_seconds() - 3600;
+        assert!((-600..=600).contains(&offset));
+        
+        let replanned = compute_next_run(&trigger, now, &TaskState {
+            last_run_at_utc: Some(last),
+            next_run_at_utc: Some(next),
+            ..Default::default()
+        });
+        assert_eq!(replanned, Some(next));
+    }
+    
+    #[test]
+    fn test_runtime_limit_stops_at_deadline() {
+        let task = Task {
+            max_runtime_seconds: Some(3600),
+            must_finish_by_local: Some("08:45".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(runtime_limit(&task, local(2024, 1, 10, 8, 30)), Some(15 * 60));
+        assert_eq!(runtime_limit(&task, local(2024, 1, 10, 6, 0)), Some(3600));
+    }
+    
+    #[test]
+    fn test_daily_at_planned_slot_becomes_due() {
+        let planned = local(2024, 1, 11, 9, 0).with_timezone(&Utc);
+        let state = TaskState {
+            next_run_at_utc: Some(planned),
+            ..Default::default()
+        };
+        let next = compute_next_run(&daily_at("09:00"), local(2024, 1, 11, 9, 1), &state);
+        assert_eq!(next, Some(planned));
+    }
+}
@@ -1,22 +1,166 @@
 //! Scheduler Runner - Background task scheduler
 
 use crate::conditions::evaluate_conditions;
-use crate::executor::{execute_task, ExecutionResult};
+use crate::executor::{execute_task_streamed, ExecutionResult, ExecutorError, OutputStream};
+use crate::metrics::ENGINE_METRICS;
 use crate::models::*;
-use crate::scheduler::compute_next_run;
+use crate::scheduler::{check_misfire, compute_next_run, quiet_hours_end, runtime_limit};
 use crate::storage::Database;
-use chrono::{Local, Utc};
-use std::collections::HashSet;
+use chrono::{DateTime, Local, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::process::Child;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex, Notify};
+
+/// Longest the loop sleeps without re-checking (clock changes, day rollover)
+const MAX_SLEEP_SECS: i64 = 60;
+/// Retry interval while a run is due but could not start (conditions)
+const RETRY_SLEEP_SECS: i64 = 5;
+/// How often old run logs are purged
+const LOG_CLEANUP_INTERVAL_SECS: i64 = 60 * 60;
+/// Trigger type recorded for runs started by hand
+const MANUAL_TRIGGER: &str = "Manual";
+/// Trigger type recorded for runs started through the webhook server
+const WEBHOOK_TRIGGER: &str = "Webhook";
+/// How often buffered run logs are written to the database
+const LOG_FLUSH_INTERVAL_SECS: u64 = 5;
+/// How often runs in progress are checked while waiting for them to finish
+const DRAIN_POLL_MS: u64 = 250;
+/// Wait before restarting the loop after it panicked, so a panic on every tick doesn't spin
+const RESTART_DELAY_SECS: u64 = 5;
+/// Output lines of a run in progress kept for subscribers that join late
+const RECENT_OUTPUT_LINES: usize = 500;
+
+/// Frontend events emitted during a run's lifecycle
+pub const EVENT_TASK_STARTED: &str = "task_started";
+pub const EVENT_TASK_FINISHED: &str = "task_finished";
+pub const EVENT_TASK_SKIPPED: &str = "task_skipped";
+pub const EVENT_QUEUE_CHANGED: &str = "queue_changed";
+pub const EVENT_PAUSE_CHANGED: &str = "scheduler_pause_changed";
+pub const EVENT_NEXT_RUN_CHANGED: &str = "next_run_changed";
+pub const EVENT_SCHEDULER_RESTARTED: &str = "scheduler_restarted";
+
+/// Payload of the scheduler_pause_changed event
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PauseChanged {
+    pub paused: bool,
+    /// Automatic resume time of a timed pause
+    pub paused_until_utc: Option<DateTime<Utc>>,
+}
+
+/// Payload of the next_run_changed event: the soonest planned run of any task, None if nothing is planned
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NextRun {
+    pub task_id: String,
+    pub task_name: String,
+    pub next_run_at_utc: DateTime<Utc>,
+}
+
+/// Payload of the queue_changed event
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QueueChanged {
+    pub running_task_ids: Vec<String>,
+    pub queued_task_ids: Vec<String>,
+}
+
+/// A line of output from a run in progress, sent to the run's subscribers
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RunOutputLine {
+    pub run_id: String,
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+/// Output of a run in progress: its latest lines and the channels watching it
+#[derive(Default)]
+struct RunOutput {
+    recent: VecDeque<RunOutputLine>,
+    subscribers: Vec<Channel<RunOutputLine>>,
+}
+
+/// A run currently in progress
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RunningTask {
+    pub task_id: String,
+    pub task_name: String,
+    pub run_id: String,
+    pub started_at_utc: DateTime<Utc>,
+    /// Time since the run started, as of the status request
+    pub elapsed_ms: i64,
+}
+
+/// A run waiting in the executor pool for a free slot
+struct PendingRun {
+    task: Task,
+    overrides: RunOverrides,
+    /// Trigger state key of a scheduled run, whose occurrence is recorded when it ends
+    trigger_key: Option<String>,
+    /// Its started log, timed from when it was queued until it starts
+    log: RunLog,
+}
+
+/// Snapshot of what the scheduler engine is doing
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SchedulerStatus {
+    pub paused: bool,
+    pub paused_until_utc: Option<DateTime<Utc>>,
+    pub running: Vec<RunningTask>,
+    /// Due tasks waiting for a free slot (max parallel runs reached)
+    pub queued_task_ids: Vec<String>,
+    pub last_tick_at_utc: Option<DateTime<Utc>>,
+    pub next_wake_at_utc: Option<DateTime<Utc>>,
+}
+
+/// Records how long a task took to plan once dropped, leaving out time spent running it
+struct EvaluationTimer<'a> {
+    task: &'a Task,
+    started: Instant,
+    running: Duration,
+}
+
+impl Drop for EvaluationTimer<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed().saturating_sub(self.running);
+        ENGINE_METRICS.record_task(&self.task.id, &self.task.name, elapsed);
+    }
+}
 
 /// Scheduler state
 pub struct SchedulerRunner {
     db: Arc<Database>,
     paused: Arc<AtomicBool>,
-    running_tasks: Arc<Mutex<HashSet<String>>>,
+    /// Cleared while exiting, after which no run of any kind is started
+    accepting: Arc<AtomicBool>,
+    /// Resume deadline of a timed pause
+    paused_until: Arc<Mutex<Option<DateTime<Utc>>>>,
+    running_tasks: Arc<Mutex<HashMap<String, RunningTask>>>,
+    /// Started log of each run in progress, by run id
+    active_runs: Arc<Mutex<HashMap<String, RunLog>>>,
+    /// Output of each run in progress, by run id. Fed from the executor's blocking thread.
+    run_output: Arc<std::sync::Mutex<HashMap<String, RunOutput>>>,
+    /// Runs waiting for a free slot, oldest first
+    pending_runs: Arc<Mutex<VecDeque<PendingRun>>>,
+    last_tick_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    next_wake_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    last_log_cleanup_at: Arc<Mutex<Option<DateTime<Utc>>>>,
+    /// Simulated task state while in dry-run mode, so the real state stays untouched
+    dry_run_states: Arc<Mutex<HashMap<String, TaskState>>>,
+    /// Occurrence each trigger ("task id:trigger key") last logged a condition skip for,
+    /// so a run held back by its conditions is logged once while it keeps being retried
+    condition_skips: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
     max_parallel: u8,
+    /// Used to emit run events to the frontend
+    app: Option<AppHandle>,
+    /// Wakes the loop early when tasks or settings change
+    wake: Arc<Notify>,
+    /// Running in the background engine, where no desktop is available
+    background: bool,
+    /// Soonest planned run as last announced
+    next_run: Arc<Mutex<Option<NextRun>>>,
 }
 
 impl SchedulerRunner {
@@ -24,250 +168,1197 @@ impl SchedulerRunner {
         Self {
             db,
             paused: Arc::new(AtomicBool::new(false)),
-            running_tasks: Arc::new(Mutex::new(HashSet::new())),
+            accepting: Arc::new(AtomicBool::new(true)),
+            paused_until: Arc::new(Mutex::new(None)),
+            running_tasks: Arc::new(Mutex::new(HashMap::new())),
+            active_runs: Arc::new(Mutex::new(HashMap::new())),
+            run_output: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            pending_runs: Arc::new(Mutex::new(VecDeque::new())),
+            last_tick_at: Arc::new(Mutex::new(None)),
+            next_wake_at: Arc::new(Mutex::new(None)),
+            last_log_cleanup_at: Arc::new(Mutex::new(None)),
+            dry_run_states: Arc::new(Mutex::new(HashMap::new())),
+            condition_skips: Arc::new(Mutex::new(HashMap::new())),
             max_parallel,
+            app: None,
+            wake: Arc::new(Notify::new()),
+            background: false,
+            next_run: Arc::new(Mutex::new(None)),
+        }
+    }
+    
+    /// Run as the background engine: tasks that need a desktop are held back
+    /// until the app takes over in the user's session
+    pub fn in_background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+    
+    /// Emit run lifecycle events to the frontend through this app handle
+    pub fn with_app_handle(mut self, app: AppHandle) -> Self {
+        self.app = Some(app);
+        self
+    }
+    
+    /// Emit an event to the frontend, if an app handle was injected
+    fn emit<S: serde::Serialize + Clone>(&self, event: &str, payload: S) {
+        emit_event(&self.app, event, payload);
+    }
+    
+    /// Tell the frontend which tasks are currently running
+    async fn emit_queue_changed(&self) {
+        let running_task_ids = self.running_tasks.lock().await.keys().cloned().collect();
+        let queued_task_ids = self.queued_task_ids().await;
+        self.emit(EVENT_QUEUE_CHANGED, QueueChanged { running_task_ids, queued_task_ids });
+    }
+    
+    /// Tasks with a run waiting for a slot, in queue order
+    async fn queued_task_ids(&self) -> Vec<String> {
+        self.pending_runs.lock().await.iter().map(|run| run.task.id.clone()).collect()
+    }
+    
+    /// Current engine status: pause state, running runs, queue and timing
+    pub async fn status(&self) -> SchedulerStatus {
+        let now = Utc::now();
+        let mut running: Vec<RunningTask> = self.running_tasks.lock().await.values()
+            .map(|r| RunningTask { elapsed_ms: (now - r.started_at_utc).num_milliseconds(), ..r.clone() })
+            .collect();
+        running.sort_by_key(|r| r.started_at_utc);
+        
+        SchedulerStatus {
+            paused: self.is_paused(),
+            paused_until_utc: *self.paused_until.lock().await,
+            running,
+            queued_task_ids: self.queued_task_ids().await,
+            last_tick_at_utc: *self.last_tick_at.lock().await,
+            next_wake_at_utc: *self.next_wake_at.lock().await,
         }
     }
     
-    /// Pause the scheduler
-    pub fn pause(&self) {
+    /// Pause the scheduler until resumed; the pause survives restarts
+    pub async fn pause(&self) {
         self.paused.store(true, Ordering::SeqCst);
+        self.save_paused(true).await;
+        // An open-ended pause replaces any timed one
+        self.set_paused_until(None).await;
+        self.wake.notify_one();
         tracing::info!("Scheduler paused");
     }
     
-    /// Resume the scheduler
-    pub fn resume(&self) {
+    /// Pause the scheduler until the given time, then resume automatically
+    pub async fn pause_until(&self, until: DateTime<Utc>) {
+        self.paused.store(true, Ordering::SeqCst);
+        self.save_paused(false).await;
+        self.set_paused_until(Some(until)).await;
+        self.wake.notify_one();
+        tracing::info!("Scheduler paused until {}", until);
+    }
+    
+    /// Resume the scheduler, ending any pause
+    pub async fn resume(&self) {
         self.paused.store(false, Ordering::SeqCst);
+        self.save_paused(false).await;
+        self.set_paused_until(None).await;
+        self.wake.notify_one();
         tracing::info!("Scheduler resumed");
     }
     
+    /// Remember an open-ended pause for the next start
+    async fn save_paused(&self, paused: bool) {
+        if let Err(e) = self.db.run(move |db| db.set_scheduler_paused(paused)).await {
+            tracing::error!("Failed to save scheduler pause: {}", e);
+        }
+    }
+    
+    /// Store (or clear) the timed pause deadline and tell the frontend and tray
+    async fn set_paused_until(&self, until: Option<DateTime<Utc>>) {
+        *self.paused_until.lock().await = until;
+        if let Err(e) = self.db.run(move |db| db.set_scheduler_paused_until(until)).await {
+            tracing::error!("Failed to save scheduler pause: {}", e);
+        }
+        self.emit(EVENT_PAUSE_CHANGED, PauseChanged {
+            paused: self.is_paused(),
+            paused_until_utc: until,
+        });
+    }
+    
+    /// Start no more runs in this session, scheduled or requested, e.g. while exiting.
+    /// Unlike `pause`, nothing is saved or announced.
+    pub fn stop_starting(&self) {
+        self.accepting.store(false, Ordering::SeqCst);
+        self.paused.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+    
+    /// Wait until no run is in progress, or at most `timeout_secs` if given.
+    /// Returns whether every run ended.
+    pub async fn wait_idle(&self, timeout_secs: Option<u64>) -> bool {
+        let deadline = timeout_secs.map(|secs| tokio::time::Instant::now() + tokio::time::Duration::from_secs(secs));
+        while !self.running_tasks.lock().await.is_empty() {
+            if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                return false;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(DRAIN_POLL_MS)).await;
+        }
+        true
+    }
+    
+    /// Log the runs still in progress as interrupted and record that in their task state,
+    /// so exiting leaves none looking as if it never ended. Returns how many there were.
+    pub async fn interrupt_running(&self) -> usize {
+        // Queued runs never started, there is nothing to log for them
+        self.pending_runs.lock().await.clear();
+        let interrupted: Vec<RunLog> = self.active_runs.lock().await.drain().map(|(_, log)| log).collect();
+        if interrupted.is_empty() {
+            return 0;
+        }
+        self.running_tasks.lock().await.clear();
+        self.emit_queue_changed().await;
+        
+        let error = Message::from(MessageId::RunInterrupted);
+        let finished_at = Utc::now();
+        for started in interrupted.iter().cloned() {
+            tracing::warn!("Run of task {} interrupted by exiting", started.task_name);
+            let task_id = started.task_id.clone();
+            let log = RunLog {
+                finished_at_utc: Some(finished_at),
+                status: RunStatus::Failed,
+                error_message: Some(error.to_string()),
+                error: Some(error.clone()),
+                duration_ms: Some((finished_at - started.started_at_utc).num_milliseconds()),
+                ..started
+            };
+            self.db.queue_log(&log);
+            self.emit(EVENT_TASK_FINISHED, log);
+            
+            self.record_run_result(&task_id, finished_at, RunResult::Failed, Some(error.to_string())).await;
+        }
+        interrupted.len()
+    }
+    
     /// Check if scheduler is paused
     pub fn is_paused(&self) -> bool {
         self.paused.load(Ordering::SeqCst)
     }
     
-    /// Toggle pause state
-    pub fn toggle_pause(&self) -> bool {
-        let was_paused = self.paused.fetch_xor(true, Ordering::SeqCst);
-        let is_now_paused = !was_paused;
-        tracing::info!("Scheduler {}", if is_now_paused { "paused" } else { "resumed" });
-        is_now_paused
+    /// Toggle pause state, returns whether the scheduler is now paused
+    pub async fn toggle_pause(&self) -> bool {
+        if self.is_paused() {
+            self.resume().await;
+            false
+        } else {
+            self.pause().await;
+            true
+        }
+    }
+    
+    /// Wake the loop so it re-plans right away (call after tasks or settings change)
+    pub fn wake(&self) {
+        self.wake.notify_one();
     }
     
     /// Run the scheduler loop
-    pub async fn run(&self) {
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+    pub async fn run(self: &Arc<Self>) {
+        // A timed pause survives restarts
+        match self.db.run(|db| db.get_scheduler_paused_until()).await {
+            Ok(Some(until)) if until > Utc::now() => self.pause_until(until).await,
+            Ok(Some(_)) => self.set_paused_until(None).await,
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to load scheduler pause: {}", e),
+        }
+        match self.db.run(|db| db.get_scheduler_paused()).await {
+            Ok(true) => self.pause().await,
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to load scheduler pause: {}", e),
+        }
+        
+        if let Err(e) = self.recover_missed_runs().await {
+            tracing::error!("Missed-run recovery failed: {}", e);
+        }
         
         loop {
-            interval.tick().await;
+            self.cleanup_logs_if_due().await;
             
-            if self.is_paused() {
-                continue;
+            // A timed pause ends on its own; any other resume drops the deadline
+            let paused_until = *self.paused_until.lock().await;
+            if let Some(until) = paused_until {
+                if !self.is_paused() || until <= Utc::now() {
+                    self.paused.store(false, Ordering::SeqCst);
+                    self.set_paused_until(None).await;
+                    tracing::info!("Scheduler resumed after timed pause");
+                }
             }
             
-            if let Err(e) = self.tick().await {
-                tracing::error!("Scheduler tick error: {}", e);
+            let next_wake = if self.is_paused() {
+                *self.paused_until.lock().await
+            } else {
+                match self.tick().await {
+                    Ok(next_wake) => next_wake,
+                    Err(e) => {
+                        tracing::error!("Scheduler tick error: {}", e);
+                        None
+                    }
+                }
+            };
+            
+            // Sleep until the soonest planned run, bounded so we still re-check regularly
+            let now = Utc::now();
+            let secs = match next_wake {
+                Some(t) if t <= now => RETRY_SLEEP_SECS,
+                Some(t) => ((t - now).num_milliseconds() + 999) / 1000,
+                None => MAX_SLEEP_SECS,
+            };
+            let secs = secs.clamp(1, MAX_SLEEP_SECS);
+            *self.next_wake_at.lock().await = Some(now + chrono::Duration::seconds(secs));
+            let sleep = tokio::time::Duration::from_secs(secs as u64);
+            
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = self.wake.notified() => {
+                    tracing::debug!("Scheduler woken early");
+                }
+            }
+        }
+    }
+    
+    /// Run the scheduler loop, restarting it if it panics instead of leaving schedules dead
+    pub async fn run_supervised(self: Arc<Self>) {
+        loop {
+            let runner = Arc::clone(&self);
+            let task = tokio::spawn(async move { runner.run().await });
+            // Aborting this job (e.g. on a profile switch) has to stop the loop too
+            let _abort = AbortOnDrop(task.abort_handle());
+            match task.await {
+                Err(e) if e.is_panic() => {
+                    tracing::error!("Scheduler loop panicked, restarting in {}s", RESTART_DELAY_SECS);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(RESTART_DELAY_SECS)).await;
+                    self.emit(EVENT_SCHEDULER_RESTARTED, ());
+                }
+                _ => return,
             }
         }
     }
     
-    /// Single tick of the scheduler
-    async fn tick(&self) -> Result<(), String> {
-        let tasks = self.db.get_all_tasks().map_err(|e| e.to_string())?;
+    /// Purge run logs past the retention period, at most once per cleanup interval
+    async fn cleanup_logs_if_due(&self) {
+        let now = Utc::now();
+        {
+            let mut last = self.last_log_cleanup_at.lock().await;
+            if last.is_some_and(|t| now - t < chrono::Duration::seconds(LOG_CLEANUP_INTERVAL_SECS)) {
+                return;
+            }
+            *last = Some(now);
+        }
+        
+        if let Err(e) = self.db.run(purge_old_logs).await {
+            tracing::error!("Log cleanup failed: {}", e);
+        }
+    }
+    
+    /// Single tick of the scheduler, returns the soonest time something needs attention
+    async fn tick(self: &Arc<Self>) -> Result<Option<DateTime<Utc>>, String> {
+        let tick_started = Instant::now();
+        let mut running_time = Duration::ZERO;
+        let (tasks, settings) = self.load_tasks_and_settings().await?;
+        let task_count = tasks.len();
         let now_local = Local::now();
         let now_utc = Utc::now();
+        *self.last_tick_at.lock().await = Some(now_utc);
+        
+        // Forget simulated runs once dry-run is switched off
+        if !settings.dry_run {
+            self.dry_run_states.lock().await.clear();
+        }
+        
+        let mut next_wake: Option<DateTime<Utc>> = None;
+        let mut plan_wake = |t: DateTime<Utc>| {
+            next_wake = Some(next_wake.map_or(t, |w| w.min(t)));
+        };
+        let mut soonest: Option<NextRun> = None;
         
         for task in tasks {
+            let mut timer = EvaluationTimer { task: &task, started: Instant::now(), running: Duration::ZERO };
             if !task.enabled {
+                self.save_next_runs(&task.id, &[], settings.dry_run).await;
+                continue;
+            }
+            
+            // Suspended tasks resume on their own once the date passes
+            if let Some(until) = task.paused_until_utc.filter(|until| *until > now_utc) {
+                plan_wake(until);
                 continue;
             }
             
             // Get task state
-            let state = self.get_task_state(&task.id);
+            let state = self.get_task_state(&task.id, settings.dry_run).await;
+            
+            // Hold back due runs while the task is snoozed
+            if let Some(until) = state.snoozed_until_utc.filter(|until| *until > now_utc) {
+                plan_wake(until);
+                continue;
+            }
+            
+            // Quiet hours hold back (or drop) runs of non-exempt tasks
+            let quiet_until = if settings.quiet_hours_enabled && !task.exempt_from_quiet_hours {
+                quiet_hours_end(&settings.quiet_hours_start, &settings.quiet_hours_end, now_local)
+            } else {
+                None
+            };
+            
+            // Runs the engine can't show stay due for the app
+            let held = self.background && crate::background::needs_desktop(&task);
             
             // Check each trigger
-            for trigger in &task.triggers {
-                if let Some(next_run) = compute_next_run(trigger, now_local, &state) {
+            let mut ran = false;
+            for (index, trigger) in task.triggers.iter().enumerate() {
+                let key = trigger.state_key(index);
+                if let Some(next_run) = compute_next_run(trigger, now_local, &state.for_trigger(&key)) {
                     if next_run <= now_utc {
+                        // A delayed tick can see the same occurrence due twice
+                        if state.slot_consumed(&key, next_run) {
+                            tracing::debug!("Task {} already handled its run at {}", task.name, next_run);
+                            continue;
+                        }
+                        
+                        if quiet_until.is_some() {
+                            if settings.quiet_hours_action == QuietHoursAction::Skip {
+                                self.skip_for_quiet_hours(&task, trigger, &key, next_run, settings.dry_run).await;
+                                ran = true;
+                            }
+                            continue;
+                        }
+                        
+                        if held {
+                            tracing::debug!("Task {} needs a desktop, holding it for the app", task.name);
+                            continue;
+                        }
+                        
                         // Task is due!
-                        self.execute_task_if_ready(&task, trigger, &state).await?;
+                        let run_started = Instant::now();
+                        if settings.dry_run {
+                            self.dry_run_task(&task, trigger, &key, next_run).await;
+                        } else {
+                            self.queue_task_if_ready(&task, trigger, &key, next_run).await;
+                        }
+                        timer.running += run_started.elapsed();
+                        running_time += run_started.elapsed();
+                        ran = true;
                     }
                 }
             }
+            
+            // Persist the next occurrence so the UI and tray can show it
+            let state = if ran {
+                self.get_task_state(&task.id, settings.dry_run).await
+            } else {
+                state
+            };
+            let planned: Vec<(String, Option<DateTime<Utc>>)> = task.triggers.iter()
+                .enumerate()
+                .map(|(index, trigger)| {
+                    let key = trigger.state_key(index);
+                    let next_run = compute_next_run(trigger, Local::now(), &state.for_trigger(&key));
+                    (key, next_run)
+                })
+                .collect();
+            let next_run = self.save_next_runs(&task.id, &planned, settings.dry_run).await;
+            if let Some(next_run) = next_run.filter(|t| soonest.as_ref().is_none_or(|s| *t < s.next_run_at_utc)) {
+                soonest = Some(NextRun { task_id: task.id.clone(), task_name: task.name.clone(), next_run_at_utc: next_run });
+            }
+            if let Some(next_run) = next_run.filter(|t| !held || *t > now_utc) {
+                // Deferred runs wait for the end of quiet hours
+                plan_wake(match quiet_until {
+                    Some(end) if next_run < end => end,
+                    _ => next_run,
+                });
+            }
         }
         
-        Ok(())
+        
+        // Tell the tray when the soonest run changes
+        let mut announced = self.next_run.lock().await;
+        if *announced != soonest {
+            *announced = soonest.clone();
+            self.emit(EVENT_NEXT_RUN_CHANGED, soonest);
+        }
+        drop(announced);
+        
+        ENGINE_METRICS.record_tick(tick_started.elapsed().saturating_sub(running_time), task_count);
+        
+        Ok(next_wake)
     }
     
-    /// Get task state from database
-    fn get_task_state(&self, task_id: &str) -> TaskState {
-        // TODO: Actually fetch from database
-        TaskState {
-            task_id: task_id.to_string(),
-            last_run_date_local: None,
-            last_run_at_utc: None,
-            last_result: None,
-            last_error: None,
-            next_run_at_utc: None,
+    /// Store the planned next run of each trigger and the earliest of them for the task
+    /// (in the simulated state while in dry-run mode). Returns the task's next run.
+    async fn save_next_runs(
+        &self,
+        task_id: &str,
+        planned: &[(String, Option<DateTime<Utc>>)],
+        dry_run: bool,
+    ) -> Option<DateTime<Utc>> {
+        let mut state = self.get_task_state(task_id, dry_run).await;
+        let before = state.clone();
+        
+        for (key, next_run) in planned {
+            state.trigger_states.entry(key.clone()).or_default().next_run_utc = *next_run;
+        }
+        state.next_run_at_utc = planned.iter().filter_map(|(_, next_run)| *next_run).min();
+        let next_run = state.next_run_at_utc;
+        
+        if state == before {
+            return next_run;
         }
+        
+        if dry_run {
+            self.dry_run_states.lock().await.insert(task_id.to_string(), state);
+        } else if let Err(e) = self.save_task_state(state).await {
+            tracing::error!("Failed to save next run for task {}: {}", task_id, e);
+        }
+        next_run
     }
     
-    /// Execute task if all conditions are met
-    async fn execute_task_if_ready(
-        &self,
-        task: &Task,
-        trigger: &Trigger,
-        _state: &TaskState,
-    ) -> Result<(), String> {
-        // Check if already running (singleton)
-        if task.singleton {
-            let running = self.running_tasks.lock().await;
-            if running.contains(&task.id) {
-                tracing::info!("Task {} already running (singleton), skipping", task.name);
-                self.log_skip(&task, trigger, SkipReason::Singleton);
-                return Ok(());
+    /// Everything a tick works from, read off the async runtime
+    async fn load_tasks_and_settings(&self) -> Result<(Vec<Task>, Settings), String> {
+        self.db.run(|db| Ok((db.get_all_tasks()?, db.get_settings()?)))
+            .await
+            .map_err(|e: rusqlite::Error| e.to_string())
+    }
+    
+    /// Get task state from database (or the simulated state in dry-run mode)
+    async fn get_task_state(&self, task_id: &str, dry_run: bool) -> TaskState {
+        if dry_run {
+            if let Some(state) = self.dry_run_states.lock().await.get(task_id) {
+                return state.clone();
             }
         }
         
-        // Check max parallel
-        {
-            let running = self.running_tasks.lock().await;
-            if running.len() >= self.max_parallel as usize {
-                tracing::info!("Max parallel tasks reached, queuing {}", task.name);
-                return Ok(());
+        let id = task_id.to_string();
+        match self.db.run(move |db| db.get_task_state_or_default(&id)).await {
+            Ok(state) => state,
+            Err(e) => {
+                tracing::error!("Failed to load state for task {}: {}", task_id, e);
+                TaskState {
+                    task_id: task_id.to_string(),
+                    ..Default::default()
+                }
             }
         }
-        
-        // Check conditions
-        match evaluate_conditions(&task.conditions) {
+    }
+    
+    /// Evaluate conditions and log what would have happened, without executing
+    async fn dry_run_task(&self, task: &Task, trigger: &Trigger, trigger_key: &str, scheduled: DateTime<Utc>) {
+        match evaluate_conditions(&task.conditions).await {
             Ok(true) => {}
             Ok(false) => {
-                tracing::info!("Conditions not met for task {}", task.name);
-                self.log_skip(&task, trigger, SkipReason::ConditionFail);
-                return Ok(());
+                self.log_condition_skip(task, trigger, trigger_key, scheduled).await;
+                return;
             }
             Err(e) => {
-                tracing::error!("Error evaluating conditions: {}", e);
-                return Err(e);
+                tracing::error!("[dry-run] Error evaluating conditions: {}", e);
+                return;
             }
         }
         
-        // Execute!
-        tracing::info!("Executing task: {}", task.name);
+        tracing::info!("[dry-run] Would execute task: {}", task.name);
+        
+        let now = Utc::now();
+        let log = RunLog {
+            run_id: uuid::Uuid::new_v4().to_string(),
+            task_id: task.id.clone(),
+            task_name: task.name.clone(),
+            trigger_type: format!("{:?}", trigger),
+            scheduled_time_utc: Some(scheduled),
+            started_at_utc: now,
+            finished_at_utc: Some(now),
+            status: RunStatus::DryRun,
+            skip_reason: None,
+            exit_code: None,
+            error_message: None,
+            error: None,
+            output: None,
+            overrides: None,
+            duration_ms: None,
+        };
+        
+        self.db.queue_log(&log);
+        self.emit(EVENT_TASK_FINISHED, log);
         
-        // Mark as running
+        // Record the simulated run so the trigger isn't considered due again
+        self.record_fired(task, trigger_key, scheduled, RunResult::Success, None, true).await;
+    }
+    
+    /// Apply each task's misfire policy to runs missed while the app was not running.
+    /// Runs within the policy are left due and picked up by the first tick.
+    async fn recover_missed_runs(&self) -> Result<(), String> {
+        let (tasks, settings) = self.load_tasks_and_settings().await?;
+        let now_local = Local::now();
+        let now_utc = Utc::now();
+        
+        for task in tasks.iter().filter(|t| t.enabled) {
+            let mut state = self.get_task_state(&task.id, settings.dry_run).await;
+            
+            for (index, trigger) in task.triggers.iter().enumerate() {
+                let key = trigger.state_key(index);
+                let scheduled = match compute_next_run(trigger, now_local, &state.for_trigger(&key)) {
+                    Some(t) if t <= now_utc => t,
+                    _ => continue,
+                };
+                
+                if check_misfire(&task.misfire_policy, scheduled, now_utc) {
+                    tracing::info!("Task {} missed its run at {} by too much, skipping", task.name, scheduled);
+                    self.log_skip(task, trigger, scheduled, SkipReason::MisfireSkip);
+                    state = self.record_fired(task, &key, scheduled, RunResult::Skipped, None, settings.dry_run).await;
+                } else {
+                    tracing::info!("Task {} missed its run at {}, catching up", task.name, scheduled);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Drop a run that fell into quiet hours, consuming the occurrence
+    async fn skip_for_quiet_hours(
+        &self,
+        task: &Task,
+        trigger: &Trigger,
+        trigger_key: &str,
+        scheduled: DateTime<Utc>,
+        dry_run: bool,
+    ) {
+        tracing::info!("Task {} skipped during quiet hours", task.name);
+        self.log_skip(task, trigger, scheduled, SkipReason::QuietHours);
+        self.record_fired(task, trigger_key, scheduled, RunResult::Skipped, None, dry_run).await;
+    }
+    
+    /// Record that a trigger fired (or its occurrence was consumed) so it isn't due again
+    async fn record_fired(
+        &self,
+        task: &Task,
+        trigger_key: &str,
+        scheduled: DateTime<Utc>,
+        last_result: RunResult,
+        last_error: Option<String>,
+        dry_run: bool,
+    ) -> TaskState {
+        let now = Utc::now();
+        let mut state = self.get_task_state(&task.id, dry_run).await;
+        state.last_run_date_local = Some(Local::now().format("%Y-%m-%d").to_string());
+        state.last_run_at_utc = Some(now);
+        state.last_result = Some(last_result);
+        state.last_error = last_error;
+        state.snoozed_until_utc = None;
+        let trigger_state = state.trigger_states.entry(trigger_key.to_string()).or_default();
+        trigger_state.last_fired_utc = Some(now);
+        trigger_state.consumed_slot_utc = Some(scheduled);
+        
+        if dry_run {
+            self.dry_run_states.lock().await.insert(task.id.clone(), state.clone());
+        } else if let Err(e) = self.save_task_state(state.clone()).await {
+            tracing::error!("Failed to save state for task {}: {}", task.name, e);
+        }
+        state
+    }
+    
+    /// Write a task's state on the blocking pool
+    async fn save_task_state(&self, state: TaskState) -> Result<(), String> {
+        self.db.run(move |db| db.update_task_state(&state)).await.map_err(|e| e.to_string())
+    }
+    
+    /// Record the result of a run no trigger fired (started by hand, by a webhook or
+    /// cut off by exiting) in its task's state
+    async fn record_run_result(&self, task_id: &str, finished_at: DateTime<Utc>, last_result: RunResult, last_error: Option<String>) {
+        let id = task_id.to_string();
+        let saved = self.db.run(move |db| {
+            let mut state = db.get_task_state_or_default(&id)?;
+            state.last_run_date_local = Some(Local::now().format("%Y-%m-%d").to_string());
+            state.last_run_at_utc = Some(finished_at);
+            state.last_result = Some(last_result);
+            state.last_error = last_error;
+            db.update_task_state(&state)
+        }).await;
+        if let Err(e) = saved {
+            tracing::error!("Failed to save state for task {}: {}", task_id, e);
+        }
+    }
+    
+    /// Mark an occurrence of a trigger as handled, before its run has a result
+    async fn claim_slot(&self, task: &Task, trigger_key: &str, scheduled: DateTime<Utc>) {
+        let mut state = self.get_task_state(&task.id, false).await;
+        let trigger_state = state.trigger_states.entry(trigger_key.to_string()).or_default();
+        trigger_state.last_fired_utc = Some(Utc::now());
+        trigger_state.consumed_slot_utc = Some(scheduled);
+        if let Err(e) = self.save_task_state(state).await {
+            tracing::error!("Failed to save state for task {}: {}", task.name, e);
+        }
+    }
+    
+    /// Hand a due run to the executor pool if all conditions are met
+    async fn queue_task_if_ready(
+        self: &Arc<Self>,
+        task: &Task,
+        trigger: &Trigger,
+        trigger_key: &str,
+        scheduled: DateTime<Utc>,
+    ) {
+        // Check conditions; one that can't be checked counts as not met for this task only
+        match evaluate_conditions(&task.conditions).await {
+            Ok(true) => {}
+            Ok(false) => {
+                self.log_condition_skip(task, trigger, trigger_key, scheduled).await;
+                return;
+            }
+            Err(e) => {
+                tracing::error!("Error evaluating conditions for task {}: {}", task.name, e);
+                self.log_condition_skip(task, trigger, trigger_key, scheduled).await;
+                return;
+            }
+        }
+        
+        // Consume the occurrence first, so later ticks don't queue it again while it waits or runs
+        self.claim_slot(task, trigger_key, scheduled).await;
+        let run = PendingRun {
+            task: task.clone(),
+            overrides: RunOverrides::default(),
+            trigger_key: Some(trigger_key.to_string()),
+            log: new_run_log(task, format!("{:?}", trigger), Some(scheduled), &RunOverrides::default()),
+        };
+        if self.enqueue(run).await.is_err() {
+            tracing::info!("Task {} already running (singleton), skipping", task.name);
+            self.log_skip(task, trigger, scheduled, SkipReason::Singleton);
+        }
+    }
+    
+    /// Queue a manual run in the executor pool and return its run id right away.
+    /// Progress is reported through the task_started/task_finished events and run_status.
+    pub async fn run_now(self: &Arc<Self>, task: Task, overrides: RunOverrides) -> Result<String, String> {
+        self.start_run(task, overrides, MANUAL_TRIGGER).await
+    }
+    
+    /// Queue a run requested through a webhook trigger, like a manual run but logged as such
+    pub async fn run_from_webhook(self: &Arc<Self>, task: Task) -> Result<String, String> {
+        self.start_run(task, RunOverrides::default(), WEBHOOK_TRIGGER).await
+    }
+    
+    async fn start_run(self: &Arc<Self>, task: Task, overrides: RunOverrides, trigger_type: &str) -> Result<String, String> {
+        if !self.accepting.load(Ordering::SeqCst) {
+            return Err(format!("Not starting task '{}', the app is exiting", task.name));
+        }
+        let log = new_run_log(&task, trigger_type.to_string(), None, &overrides);
+        let run_id = log.run_id.clone();
+        tracing::info!("Queuing run of task {}", task.name);
+        self.enqueue(PendingRun { task, overrides, trigger_key: None, log }).await?;
+        Ok(run_id)
+    }
+    
+    /// Add a run to the executor pool and start it if a slot is free.
+    /// Fails if the task is a singleton with a run already queued or in progress.
+    async fn enqueue(self: &Arc<Self>, run: PendingRun) -> Result<(), String> {
         {
-            let mut running = self.running_tasks.lock().await;
-            running.insert(task.id.clone());
+            let running = self.running_tasks.lock().await;
+            let mut pending = self.pending_runs.lock().await;
+            let busy = running.contains_key(&run.task.id) || pending.iter().any(|r| r.task.id == run.task.id);
+            if run.task.singleton && busy {
+                return Err(format!("Task '{}' is already running", run.task.name));
+            }
+            pending.push_back(run);
         }
+        self.emit_queue_changed().await;
+        self.dispatch().await;
+        Ok(())
+    }
+    
+    /// Start queued runs, oldest first, while fewer than `max_parallel` are running.
+    /// A run waits while its task already has one in progress.
+    async fn dispatch(self: &Arc<Self>) {
+        loop {
+            if !self.accepting.load(Ordering::SeqCst) {
+                return;
+            }
+            let started_at = Utc::now();
+            let run = {
+                let mut running = self.running_tasks.lock().await;
+                if running.len() >= (self.max_parallel as usize).max(1) {
+                    return;
+                }
+                let mut pending = self.pending_runs.lock().await;
+                let Some(index) = pending.iter().position(|r| !running.contains_key(&r.task.id)) else { return };
+                let Some(mut run) = pending.remove(index) else { return };
+                running.insert(run.task.id.clone(), RunningTask {
+                    task_id: run.task.id.clone(),
+                    task_name: run.task.name.clone(),
+                    run_id: run.log.run_id.clone(),
+                    started_at_utc: started_at,
+                    elapsed_ms: 0,
+                });
+                ENGINE_METRICS.queue_wait.record((started_at - run.log.started_at_utc).to_std().unwrap_or_default());
+                run.log.started_at_utc = started_at;
+                run
+            };
+            self.emit_queue_changed().await;
+            
+            tracing::info!("Executing task: {}", run.task.name);
+            self.active_runs.lock().await.insert(run.log.run_id.clone(), run.log.clone());
+            self.open_output(&run.log.run_id);
+            self.emit(EVENT_TASK_STARTED, run.log.clone());
+            let runner = Arc::clone(self);
+            tokio::spawn(async move { runner.execute(run).await });
+        }
+    }
+    
+    /// Start whatever a freed slot lets through, without waiting for it
+    fn dispatch_soon(self: &Arc<Self>) {
+        let runner = Arc::clone(self);
+        tokio::spawn(async move { runner.dispatch().await });
+    }
+    
+    /// Carry out a started run, then log it and record it in the task's state
+    async fn execute(self: Arc<Self>, run: PendingRun) {
+        let PendingRun { task, overrides, trigger_key, log: started_log } = run;
         
         // Apply start delay
-        if task.start_delay_seconds > 0 {
-            tokio::time::sleep(tokio::time::Duration::from_secs(
-                task.start_delay_seconds as u64,
-            ))
-            .await;
+        if trigger_key.is_some() && task.start_delay_seconds > 0 {
+            tokio::time::sleep(tokio::time::Duration::from_secs(task.start_delay_seconds as u64)).await;
         }
         
-        // Run the task
-        let result = execute_task(task);
+        // Cut short by its max runtime or must-finish-by time
+        let max_runtime = runtime_limit(&task, Local::now());
+        let run_task = Task { max_runtime_seconds: max_runtime, ..task.clone() };
+        let on_output = self.output_sink(&started_log.run_id);
+        // The executor blocks while waiting for the process, keep it off the async workers
+        let mut result = tokio::task::spawn_blocking(move || execute_task_streamed(&run_task, &overrides, &on_output))
+            .await
+            .unwrap_or_else(|e| Err(ExecutorError::OpenFailed(e.to_string())));
+        self.close_output(&started_log.run_id);
+        let child = result.as_mut().ok().and_then(|r| r.child.take());
         
-        // Mark as not running
-        {
-            let mut running = self.running_tasks.lock().await;
-            running.remove(&task.id);
-        }
+        self.running_tasks.lock().await.remove(&task.id);
+        self.emit_queue_changed().await;
+        self.dispatch_soon();
         
-        // Log result
-        self.log_execution(task, trigger, &result);
+        let (status, error, exit_code, output) = run_outcome(&result);
+        let error_message = error.as_ref().map(Message::to_string);
+        let finished_at = Utc::now();
+        let started_at = started_log.started_at_utc;
+        let scheduled = started_log.scheduled_time_utc;
+        let log = RunLog {
+            finished_at_utc: Some(finished_at),
+            status: status.clone(),
+            exit_code,
+            error_message: error_message.clone(),
+            error,
+            output,
+            duration_ms: Some((finished_at - started_at).num_milliseconds()),
+            ..started_log
+        };
         
-        // Update task state
-        self.update_task_state(task, &result);
+        // A launched process we didn't wait for still has to end within the max runtime
+        if let (Some(child), Some(max_runtime)) = (child, max_runtime) {
+            self.watch_max_runtime(log.clone(), child, max_runtime);
+        }
+        {
+            // Exiting may have logged the run as interrupted already
+            let mut active_runs = self.active_runs.lock().await;
+            if active_runs.remove(&log.run_id).is_none() {
+                return;
+            }
+            self.db.queue_log(&log);
+        }
+        self.emit(EVENT_TASK_FINISHED, log);
         
+        let last_result = if status == RunStatus::Success { RunResult::Success } else { RunResult::Failed };
+        if let (Some(trigger_key), Some(scheduled)) = (trigger_key, scheduled) {
+            self.record_fired(&task, &trigger_key, scheduled, last_result, error_message, false).await;
+            return;
+        }
+        self.record_run_result(&task.id, finished_at, last_result, error_message).await;
+    }
+    
+    /// The log of a run: its started entry while queued or in progress, the final one afterwards
+    pub async fn run_status(&self, run_id: &str) -> Result<Option<RunLog>, String> {
+        if let Some(run) = self.pending_runs.lock().await.iter().find(|run| run.log.run_id == run_id) {
+            return Ok(Some(run.log.clone()));
+        }
+        if let Some(log) = self.active_runs.lock().await.get(run_id) {
+            return Ok(Some(log.clone()));
+        }
+        let run_id = run_id.to_string();
+        self.db.run(move |db| db.get_log(&run_id)).await.map_err(|e| e.to_string())
+    }
+    
+    /// Send a run's output lines to `channel` as they arrive, starting with the latest ones so far
+    pub fn subscribe_output(&self, run_id: &str, channel: Channel<RunOutputLine>) -> Result<(), String> {
+        let mut outputs = self.run_output.lock().unwrap_or_else(|e| e.into_inner());
+        let output = outputs.get_mut(run_id).ok_or_else(|| format!("Run {} is not in progress", run_id))?;
+        for line in &output.recent {
+            channel.send(line.clone()).map_err(|e| e.to_string())?;
+        }
+        output.subscribers.push(channel);
         Ok(())
     }
     
+    /// Stop sending a run's output to the channel with this id
+    pub fn unsubscribe_output(&self, run_id: &str, channel_id: u32) {
+        let mut outputs = self.run_output.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(output) = outputs.get_mut(run_id) {
+            output.subscribers.retain(|channel| channel.id() != channel_id);
+        }
+    }
+    
+    /// Start collecting output for a run that just started
+    fn open_output(&self, run_id: &str) {
+        self.run_output.lock().unwrap_or_else(|e| e.into_inner()).insert(run_id.to_string(), RunOutput::default());
+    }
+    
+    /// Drop a finished run's output; the full text ends up in its log
+    fn close_output(&self, run_id: &str) {
+        self.run_output.lock().unwrap_or_else(|e| e.into_inner()).remove(run_id);
+    }
+    
+    /// Passes a run's output lines on to its subscribers, for the executor
+    fn output_sink(&self, run_id: &str) -> impl Fn(OutputStream, &str) + Send + Sync + 'static {
+        let run_output = Arc::clone(&self.run_output);
+        let run_id = run_id.to_string();
+        move |stream, line| {
+            let mut outputs = run_output.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(output) = outputs.get_mut(&run_id) else { return };
+            let line = RunOutputLine { run_id: run_id.clone(), stream, line: line.to_string() };
+            // A channel whose window went away stops accepting lines
+            output.subscribers.retain(|channel| channel.send(line.clone()).is_ok());
+            if output.recent.len() == RECENT_OUTPUT_LINES {
+                output.recent.pop_front();
+            }
+            output.recent.push_back(line);
+        }
+    }
+    
+    /// Kill a launched process once it exceeds the task's max runtime, and turn the
+    /// run's finished log into a timeout
+    fn watch_max_runtime(&self, log: RunLog, mut child: Child, max_runtime: u32) {
+        let db = self.db.clone();
+        let app = self.app.clone();
+        
+        tokio::spawn(async move {
+            let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(max_runtime as u64);
+            
+            loop {
+                match child.try_wait() {
+                    Ok(None) if tokio::time::Instant::now() < deadline => {
+                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                    }
+                    Ok(None) => break,
+                    // Exited on its own (or can't be observed anymore)
+                    Ok(Some(_)) | Err(_) => return,
+                }
+            }
+            
+            tracing::warn!("Task {} exceeded max runtime of {} seconds, killing process", log.task_name, max_runtime);
+            let _ = tokio::task::spawn_blocking(move || {
+                let _ = child.kill();
+                child.wait()
+            })
+            .await;
+            
+            // Same run id, so the run's log is replaced rather than joined by a second one
+            let error = ExecutorError::Timeout(max_runtime).message();
+            let finished_at = Utc::now();
+            let log = RunLog {
+                finished_at_utc: Some(finished_at),
+                status: RunStatus::Failed,
+                error_message: Some(error.to_string()),
+                error: Some(error.clone()),
+                duration_ms: Some((finished_at - log.started_at_utc).num_milliseconds()),
+                ..log
+            };
+            db.queue_log(&log);
+            let task_id = log.task_id.clone();
+            emit_event(&app, EVENT_TASK_FINISHED, log);
+            
+            let saved = db.run(move |db| {
+                let mut state = db.get_task_state_or_default(&task_id)?;
+                state.last_result = Some(RunResult::Failed);
+                state.last_error = Some(error.to_string());
+                db.update_task_state(&state)
+            }).await;
+            if let Err(e) = saved {
+                tracing::error!("Failed to save the timeout in the task state: {}", e);
+            }
+        });
+    }
+    
+    /// Log that a due run's conditions aren't met, once per occurrence. The run stays due
+    /// and is retried quietly until they are.
+    async fn log_condition_skip(&self, task: &Task, trigger: &Trigger, trigger_key: &str, scheduled: DateTime<Utc>) {
+        let key = format!("{}:{}", task.id, trigger_key);
+        if self.condition_skips.lock().await.insert(key, scheduled) == Some(scheduled) {
+            return;
+        }
+        tracing::info!("Conditions not met for task {}", task.name);
+        self.log_skip(task, trigger, scheduled, SkipReason::ConditionFail);
+    }
+    
     /// Log a skipped execution
-    fn log_skip(&self, task: &Task, trigger: &Trigger, reason: SkipReason) {
+    fn log_skip(&self, task: &Task, trigger: &Trigger, scheduled: DateTime<Utc>, reason: SkipReason) {
         let log = RunLog {
             run_id: uuid::Uuid::new_v4().to_string(),
             task_id: task.id.clone(),
             task_name: task.name.clone(),
             trigger_type: format!("{:?}", trigger),
-            scheduled_time_utc: Some(Utc::now()),
+            scheduled_time_utc: Some(scheduled),
             started_at_utc: Utc::now(),
             finished_at_utc: Some(Utc::now()),
             status: RunStatus::Skipped,
             skip_reason: Some(reason),
             exit_code: None,
             error_message: None,
+            error: None,
             output: None,
+            overrides: None,
+            duration_ms: None,
         };
         
-        if let Err(e) = self.db.insert_log(&log) {
-            tracing::error!("Failed to insert log: {}", e);
+        self.db.queue_log(&log);
+        self.emit(EVENT_TASK_SKIPPED, log);
+    }
+}
+
+/// Started log of a new run, timed from now
+fn new_run_log(task: &Task, trigger_type: String, scheduled: Option<DateTime<Utc>>, overrides: &RunOverrides) -> RunLog {
+    RunLog {
+        run_id: uuid::Uuid::new_v4().to_string(),
+        task_id: task.id.clone(),
+        task_name: task.name.clone(),
+        trigger_type,
+        scheduled_time_utc: scheduled,
+        started_at_utc: Utc::now(),
+        finished_at_utc: None,
+        status: RunStatus::Started,
+        skip_reason: None,
+        exit_code: None,
+        error_message: None,
+        error: None,
+        output: None,
+        overrides: (*overrides != RunOverrides::default()).then(|| overrides.clone()),
+        duration_ms: None,
+    }
+}
+
+/// Status, error, exit code and output to log for an execution result
+fn run_outcome(
+    result: &Result<ExecutionResult, ExecutorError>,
+) -> (RunStatus, Option<Message>, Option<i32>, Option<String>) {
+    match result {
+        Ok(r) if r.success => (RunStatus::Success, None, r.exit_code, r.output.clone()),
+        Ok(r) => (RunStatus::Failed, r.error.clone(), r.exit_code, r.output.clone()),
+        Err(e) => (RunStatus::Failed, Some(e.message()), None, None),
+    }
+}
+
+/// Delete run logs older than each task's log_retention_days override, or the global
+/// log_retention_days setting (0 keeps everything). Returns how many rows were removed.
+pub fn purge_old_logs(db: &Database) -> Result<usize, String> {
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let removed = db.purge_expired_logs(settings.log_retention_days).map_err(|e| e.to_string())?;
+    if removed > 0 {
+        tracing::info!("Removed {} expired run logs", removed);
+    }
+    Ok(removed)
+}
+
+/// Background job: write buffered run logs in one transaction every few seconds
+pub async fn log_flush_loop(db: Arc<Database>) {
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(LOG_FLUSH_INTERVAL_SECS)).await;
+        if let Err(e) = db.run(|db| db.flush_logs()).await {
+            tracing::error!("Failed to write run logs: {}", e);
+        }
+    }
+}
+
+/// Aborts a spawned task when dropped
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Emit an event to the frontend, if an app handle is available
+fn emit_event<S: serde::Serialize + Clone>(app: &Option<AppHandle>, event: &str, payload: S) {
+    if let Some(app) = app {
+        if let Err(e) = app.emit(event, payload) {
+            tracing::warn!("Failed to emit {} event: {}", event, e);
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
     
-    /// Log an execution result
-    fn log_execution(
-        &self,
-        task: &Task,
-        trigger: &Trigger,
-        result: &Result<ExecutionResult, crate::executor::ExecutorError>,
-    ) {
-        let (status, error_message, exit_code, output) = match result {
-            Ok(r) => {
-                if r.success {
-                    (RunStatus::Success, None, r.exit_code, r.output.clone())
-                } else {
-                    (RunStatus::Failed, r.error_message.clone(), r.exit_code, r.output.clone())
-                }
-            }
-            Err(e) => (RunStatus::Failed, Some(e.to_string()), None, None),
-        };
+    #[tokio::test]
+    async fn test_interrupt_running_logs_runs_in_progress() {
+        let db = Arc::new(Database::open(Path::new(":memory:")).unwrap());
+        db.insert_task(&Task { id: "task".to_string(), name: "Backup".to_string(), ..Task::default() }).unwrap();
+        let runner = SchedulerRunner::new(db.clone(), 1);
+        let started_at = Utc::now();
+        runner.running_tasks.lock().await.insert("task".to_string(), RunningTask {
+            task_id: "task".to_string(),
+            task_name: "Backup".to_string(),
+            run_id: "run".to_string(),
+            started_at_utc: started_at,
+            elapsed_ms: 0,
+        });
+        runner.active_runs.lock().await.insert("run".to_string(), RunLog {
+            run_id: "run".to_string(),
+            task_id: "task".to_string(),
+            task_name: "Backup".to_string(),
+            trigger_type: MANUAL_TRIGGER.to_string(),
+            scheduled_time_utc: None,
+            started_at_utc: started_at,
+            finished_at_utc: None,
+            status: RunStatus::Started,
+            skip_reason: None,
+            exit_code: None,
+            error_message: None,
+            error: None,
+            output: None,
+            overrides: None,
+            duration_ms: None,
+        });
         
-        let log = RunLog {
-            run_id: uuid::Uuid::new_v4().to_string(),
-            task_id: task.id.clone(),
-            task_name: task.name.clone(),
-            trigger_type: format!("{:?}", trigger),
-            scheduled_time_utc: Some(Utc::now()),
+        assert!(!runner.wait_idle(Some(0)).await);
+        assert_eq!(runner.interrupt_running().await, 1);
+        assert!(runner.wait_idle(Some(0)).await);
+        assert_eq!(runner.interrupt_running().await, 0);
+        
+        db.flush_logs().unwrap();
+        let log = db.get_log("run").unwrap().unwrap();
+        assert_eq!(log.status, RunStatus::Failed);
+        assert_eq!(log.error, Some(Message::from(MessageId::RunInterrupted)));
+        assert!(log.finished_at_utc.is_some());
+        let state = db.get_task_state_or_default("task").unwrap();
+        assert_eq!(state.last_result, Some(RunResult::Failed));
+    }
+    
+    #[tokio::test]
+    async fn test_no_run_starts_after_stop_starting() {
+        let db = Arc::new(Database::open(Path::new(":memory:")).unwrap());
+        let task = Task { id: "task".to_string(), name: "Backup".to_string(), ..Task::default() };
+        db.insert_task(&task).unwrap();
+        let runner = Arc::new(SchedulerRunner::new(db.clone(), 1));
+        
+        runner.stop_starting();
+        assert!(runner.run_now(task.clone(), RunOverrides::default()).await.is_err());
+        assert!(runner.run_from_webhook(task).await.is_err());
+        assert!(runner.running_tasks.lock().await.is_empty());
+        assert!(runner.active_runs.lock().await.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_condition_skip_logged_once_per_occurrence() {
+        let db = Arc::new(Database::open(Path::new(":memory:")).unwrap());
+        let task = Task { id: "task".to_string(), name: "Backup".to_string(), ..Task::default() };
+        let trigger = Trigger::Interval { enabled: true, every_seconds: 60, jitter_seconds: None, jitter_mode: JitterMode::default() };
+        let runner = SchedulerRunner::new(db.clone(), 1);
+        let first = Utc::now();
+        
+        runner.log_condition_skip(&task, &trigger, "t0", first).await;
+        runner.log_condition_skip(&task, &trigger, "t0", first).await;
+        runner.log_condition_skip(&task, &trigger, "t0", first + chrono::Duration::seconds(60)).await;
+        
+        db.flush_logs().unwrap();
+        let logs = db.get_logs_for_task("task", 10, 0).unwrap();
+        assert_eq!(logs.len(), 2);
+        assert!(logs.iter().all(|log| matches!(log.skip_reason, Some(SkipReason::ConditionFail))));
+    }
+    
+    #[tokio::test]
+    async fn test_manual_run_waits_for_a_free_slot() {
+        let db = Arc::new(Database::open(Path::new(":memory:")).unwrap());
+        let task = Task { id: "task".to_string(), name: "Backup".to_string(), ..Task::default() };
+        db.insert_task(&task).unwrap();
+        let runner = Arc::new(SchedulerRunner::new(db.clone(), 1));
+        runner.running_tasks.lock().await.insert("other".to_string(), RunningTask {
+            task_id: "other".to_string(),
+            task_name: "Other".to_string(),
+            run_id: "other-run".to_string(),
             started_at_utc: Utc::now(),
-            finished_at_utc: Some(Utc::now()),
-            status,
-            skip_reason: None,
-            exit_code,
-            error_message,
-            output,
-        };
+            elapsed_ms: 0,
+        });
         
-        if let Err(e) = self.db.insert_log(&log) {
-            tracing::error!("Failed to insert log: {}", e);
-        }
+        let run_id = runner.run_now(task, RunOverrides::default()).await.unwrap();
+        let status = runner.status().await;
+        assert_eq!(status.queued_task_ids, ["task"]);
+        assert_eq!(status.running.len(), 1);
+        assert_eq!(runner.run_status(&run_id).await.unwrap().map(|log| log.status), Some(RunStatus::Started));
+        
+        runner.running_tasks.lock().await.remove("other");
+        runner.dispatch().await;
+        assert!(runner.status().await.queued_task_ids.is_empty());
+        assert!(runner.wait_idle(Some(10)).await);
     }
     
-    /// Update task state after execution
-    fn update_task_state(
-        &self,
-        task: &Task,
-        result: &Result<ExecutionResult, crate::executor::ExecutorError>,
-    ) {
-        let now_local = Local::now();
-        let last_result = match result {
-            Ok(r) if r.success => RunResult::Success,
-            _ => RunResult::Failed,
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_manual_run_over_max_runtime_times_out() {
+        let db = Arc::new(Database::open(Path::new(":memory:")).unwrap());
+        let task = Task {
+            id: "task".to_string(),
+            name: "Sleep".to_string(),
+            target_type: TargetType::Exe,
+            path_or_url: "/bin/sleep".to_string(),
+            args: Some("30".to_string()),
+            wait_policy: WaitPolicy::DontWait,
+            max_runtime_seconds: Some(1),
+            ..Task::default()
         };
+        db.insert_task(&task).unwrap();
+        let runner = Arc::new(SchedulerRunner::new(db.clone(), 1));
         
-        let _state = TaskState {
-            task_id: task.id.clone(),
-            last_run_date_local: Some(now_local.format("%Y-%m-%d").to_string()),
-            last_run_at_utc: Some(Utc::now()),
-            last_result: Some(last_result),
-            last_error: result.as_ref().err().map(|e| e.to_string()),
-            next_run_at_utc: None, // Will be computed next tick
+        runner.run_now(task, RunOverrides::default()).await.unwrap();
+        assert!(runner.wait_idle(Some(10)).await);
+        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+        
+        db.flush_logs().unwrap();
+        let logs = db.get_logs_for_task("task", 10, 0).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].status, RunStatus::Failed);
+        assert_eq!(logs[0].error.as_ref().map(|e| e.id), Some(MessageId::Timeout));
+    }
+    
+    #[test]
+    fn test_run_output_reaches_subscribers() {
+        let runner = SchedulerRunner::new(Arc::new(Database::open(Path::new(":memory:")).unwrap()), 1);
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let subscribe = || {
+            let received = received.clone();
+            Channel::new(move |body| {
+                if let tauri::ipc::InvokeResponseBody::Json(json) = body {
+                    let line: serde_json::Value = serde_json::from_str(&json).unwrap();
+                    received.lock().unwrap().push(line["line"].as_str().unwrap().to_string());
+                }
+                Ok(())
+            })
         };
+        assert!(runner.subscribe_output("run", subscribe()).is_err());
+        
+        runner.open_output("run");
+        let on_output = runner.output_sink("run");
+        on_output(OutputStream::Stdout, "first");
+        // A late subscriber catches up on the lines so far
+        let channel = subscribe();
+        let channel_id = channel.id();
+        runner.subscribe_output("run", channel).unwrap();
+        on_output(OutputStream::Stderr, "second");
+        runner.unsubscribe_output("run", channel_id);
+        on_output(OutputStream::Stdout, "third");
+        assert_eq!(*received.lock().unwrap(), ["first", "second"]);
         
-        // TODO: Save state to database
+        runner.close_output("run");
+        on_output(OutputStream::Stdout, "after");
+        assert!(runner.subscribe_output("run", subscribe()).is_err());
     }
 }
@@ -0,0 +1,239 @@
+//! Secrets module - Encrypt secret task values at rest with Windows DPAPI
+
+use crate::models::{Settings, Task};
+
+/// Prefix marking a value that is already encrypted
+const SEALED_PREFIX: &str = "dpapi:";
+
+/// What secret values look like in API responses
+pub const SECRET_MASK: &str = "********";
+
+/// Whether a stored value is encrypted
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// Encrypt a value for the current Windows user (already encrypted or empty values pass through)
+pub fn seal(value: &str) -> Result<String, String> {
+    if value.is_empty() || is_sealed(value) {
+        return Ok(value.to_string());
+    }
+    let encrypted = protect(value.as_bytes())?;
+    Ok(format!("{}{}", SEALED_PREFIX, to_hex(&encrypted)))
+}
+
+/// Decrypt a stored value (plain values from before encryption pass through)
+pub fn unseal(value: &str) -> Result<String, String> {
+    let Some(hex) = value.strip_prefix(SEALED_PREFIX) else {
+        return Ok(value.to_string());
+    };
+    let bytes = from_hex(hex).ok_or("Corrupt encrypted value")?;
+    let plain = unprotect(&bytes)?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
+
+/// Apply `f` to every secret value of a task
+fn map_secrets(task: &Task, mut f: impl FnMut(&str) -> Result<String, String>) -> Result<Task, String> {
+    let mut task = task.clone();
+    if task.args_secret {
+        task.args = task.args.as_deref().map(&mut f).transpose()?;
+    }
+    for var in task.env.iter_mut().filter(|v| v.secret) {
+        var.value = f(&var.value)?;
+    }
+    Ok(task)
+}
+
+/// The task as it should be stored, with secret values encrypted
+pub fn seal_task(task: &Task) -> Result<Task, String> {
+    map_secrets(task, seal)
+}
+
+/// The task with secret values decrypted; only for handing to the executor
+pub fn reveal_task(task: &Task) -> Result<Task, String> {
+    map_secrets(task, unseal)
+}
+
+/// The task with secret values hidden, for API responses
+pub fn mask_task(task: Task) -> Task {
+    map_secrets(&task, |_| Ok(SECRET_MASK.to_string())).unwrap_or(task)
+}
+
+/// Where the client sent a secret back still masked, keep the stored value
+pub fn keep_masked(task: &mut Task, stored: &Task) {
+    if task.args_secret && task.args.as_deref() == Some(SECRET_MASK) {
+        task.args = stored.args.clone();
+    }
+    for var in task.env.iter_mut().filter(|v| v.secret && v.value == SECRET_MASK) {
+        if let Some(old) = stored.env.iter().find(|old| old.name == var.name) {
+            var.value = old.value.clone();
+        }
+    }
+}
+
+/// Whether a secret value can't be used here: still masked, or encrypted on another PC
+fn is_unusable(value: &str) -> bool {
+    value == SECRET_MASK || (is_sealed(value) && unseal(value).is_err())
+}
+
+/// Drop secret values that can't be used here, having no stored value to fall back on.
+/// Returns whether there were any.
+pub fn clear_unusable(task: &mut Task) -> bool {
+    let mut cleared = false;
+    if task.args_secret && task.args.as_deref().is_some_and(is_unusable) {
+        task.args = None;
+        cleared = true;
+    }
+    for var in task.env.iter_mut().filter(|v| v.secret && is_unusable(&v.value)) {
+        var.value.clear();
+        cleared = true;
+    }
+    cleared
+}
+
+/// Apply `f` to every secret value in the settings
+fn map_settings_secrets(settings: &Settings, mut f: impl FnMut(&str) -> Result<String, String>) -> Result<Settings, String> {
+    let mut settings = settings.clone();
+    for notifier in &mut settings.chat_notifiers {
+        let secret = notifier.service.secret_mut();
+        *secret = f(secret)?;
+    }
+    if !settings.email.password.is_empty() {
+        settings.email.password = f(&settings.email.password)?;
+    }
+    if !settings.mqtt.password.is_empty() {
+        settings.mqtt.password = f(&settings.mqtt.password)?;
+    }
+    Ok(settings)
+}
+
+/// The settings as they should be stored, with credentials encrypted
+pub fn seal_settings(settings: &Settings) -> Result<Settings, String> {
+    map_settings_secrets(settings, seal)
+}
+
+/// The settings with credentials decrypted; only for sending notifications
+pub fn reveal_settings(settings: &Settings) -> Result<Settings, String> {
+    map_settings_secrets(settings, unseal)
+}
+
+/// The settings with credentials hidden, for API responses
+pub fn mask_settings(settings: Settings) -> Settings {
+    map_settings_secrets(&settings, |_| Ok(SECRET_MASK.to_string())).unwrap_or(settings)
+}
+
+/// Where the client sent a credential back still masked, keep the stored value
+pub fn keep_masked_settings(settings: &mut Settings, stored: &Settings) {
+    for notifier in &mut settings.chat_notifiers {
+        if notifier.service.secret() != SECRET_MASK {
+            continue;
+        }
+        if let Some(old) = stored.chat_notifiers.iter().find(|old| old.id == notifier.id) {
+            *notifier.service.secret_mut() = old.service.secret().to_string();
+        }
+    }
+    if settings.email.password == SECRET_MASK {
+        settings.email.password = stored.email.password.clone();
+    }
+    if settings.mqtt.password == SECRET_MASK {
+        settings.mqtt.password = stored.mqtt.password.clone();
+    }
+}
+
+/// Drop credentials that can't be used here, having no stored value to fall back on.
+/// Returns whether there were any.
+pub fn clear_unusable_settings(settings: &mut Settings) -> bool {
+    let mut cleared = false;
+    for notifier in &mut settings.chat_notifiers {
+        let secret = notifier.service.secret_mut();
+        if is_unusable(secret) {
+            secret.clear();
+            cleared = true;
+        }
+    }
+    for password in [&mut settings.email.password, &mut settings.mqtt.password] {
+        if is_unusable(password) {
+            password.clear();
+            cleared = true;
+        }
+    }
+    cleared
+}
+
+/// Plain secret values of a revealed task
+pub fn secret_values(task: &Task) -> Vec<String> {
+    let mut values = Vec::new();
+    map_secrets(task, |value| {
+        if !value.is_empty() {
+            values.push(value.to_string());
+        }
+        Ok(value.to_string())
+    }).ok();
+    values
+}
+
+/// Replace any secret value appearing in `text` with the mask
+pub fn redact(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| text.replace(secret.as_str(), SECRET_MASK))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(windows)]
+fn protect(data: &[u8]) -> Result<Vec<u8>, String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Security::Cryptography::{CryptProtectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB};
+
+    let input = CRYPT_INTEGER_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        CryptProtectData(&input, PCWSTR::null(), None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+            .map_err(|e| format!("Failed to encrypt secret: {}", e))?;
+        Ok(take_blob(output))
+    }
+}
+
+#[cfg(windows)]
+fn unprotect(data: &[u8]) -> Result<Vec<u8>, String> {
+    use windows::Win32::Security::Cryptography::{CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB};
+
+    let input = CRYPT_INTEGER_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+    let mut output = CRYPT_INTEGER_BLOB::default();
+    unsafe {
+        CryptUnprotectData(&input, None, None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+            .map_err(|e| format!("Failed to decrypt secret: {}", e))?;
+        Ok(take_blob(output))
+    }
+}
+
+/// Copy out a blob allocated by DPAPI and free it
+#[cfg(windows)]
+unsafe fn take_blob(blob: windows::Win32::Security::Cryptography::CRYPT_INTEGER_BLOB) -> Vec<u8> {
+    use windows::Win32::Foundation::{LocalFree, HLOCAL};
+
+    let bytes = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+    let _ = LocalFree(HLOCAL(blob.pbData as _));
+    bytes
+}
+
+#[cfg(not(windows))]
+fn protect(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Secrets are only supported on Windows".to_string())
+}
+
+#[cfg(not(windows))]
+fn unprotect(_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("Secrets are only supported on Windows".to_string())
+}
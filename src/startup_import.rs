@@ -0,0 +1,137 @@
+//! Startup import module - Turn Startup folder shortcuts into on-login tasks
+
+use crate::models::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Startup folder under %APPDATA% (shell:startup)
+const USER_STARTUP: &str = r"Microsoft\Windows\Start Menu\Programs\Startup";
+
+/// Startup folder under %ProgramData% (shell:common startup)
+const COMMON_STARTUP: &str = r"Microsoft\Windows\Start Menu\Programs\StartUp";
+
+/// A shortcut found in a Startup folder
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupShortcut {
+    pub path: String,
+    pub name: String,
+    /// In the all-users folder; moving it needs administrator rights
+    pub all_users: bool,
+    /// Resolved program or file, None if the shortcut points somewhere we cannot launch directly
+    pub target: Option<String>,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    pub minimized: bool,
+}
+
+/// Result of an import
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StartupImportSummary {
+    pub imported: u32,
+    /// Shortcuts that already have a matching on-login task
+    pub skipped: u32,
+    /// Originals moved out of the Startup folder
+    pub disabled: u32,
+    /// One message per shortcut that could not be imported or disabled
+    pub errors: Vec<String>,
+}
+
+/// Startup folders that exist, with whether each is the all-users one
+pub fn startup_folders() -> Vec<(PathBuf, bool)> {
+    [("APPDATA", USER_STARTUP, false), ("ProgramData", COMMON_STARTUP, true)]
+        .into_iter()
+        .filter_map(|(var, sub, all_users)| {
+            let dir = PathBuf::from(std::env::var_os(var)?).join(sub);
+            dir.is_dir().then_some((dir, all_users))
+        })
+        .collect()
+}
+
+/// Every .lnk in the Startup folders
+pub fn scan() -> Vec<StartupShortcut> {
+    let mut shortcuts = Vec::new();
+    for (dir, all_users) in startup_folders() {
+        let Ok(entries) = std::fs::read_dir(&dir) else { continue };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let is_link = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("lnk"));
+            if is_link && path.is_file() {
+                shortcuts.push(read(&path, all_users));
+            }
+        }
+    }
+    shortcuts.sort_by_key(|s| s.name.to_lowercase());
+    shortcuts
+}
+
+fn read(path: &Path, all_users: bool) -> StartupShortcut {
+    let info = std::fs::read(path).ok().and_then(|bytes| crate::inspect::read_shortcut(&bytes)).unwrap_or_default();
+    // Fall back to the relative path for shortcuts without a local base path
+    let folder = path.parent().unwrap_or(Path::new(""));
+    let target = info.target.or_else(|| {
+        let resolved = folder.join(info.relative_path?);
+        resolved.exists().then(|| resolved.to_string_lossy().into_owned())
+    });
+
+    StartupShortcut {
+        path: path.to_string_lossy().into_owned(),
+        name: path.file_stem().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        all_users,
+        target,
+        arguments: info.arguments,
+        working_dir: info.working_dir,
+        minimized: info.minimized,
+    }
+}
+
+/// On-login task doing what the shortcut did
+pub fn shortcut_task(shortcut: &StartupShortcut) -> Option<Task> {
+    let target = shortcut.target.clone()?;
+    let target_type = crate::inspect::inspect_target(&target).target_type;
+
+    Some(Task {
+        name: shortcut.name.clone(),
+        description: Some(format!("Imported from {}", shortcut.path)),
+        tags: vec!["startup".to_string()],
+        target_type,
+        path_or_url: target,
+        args: shortcut.arguments.clone(),
+        working_dir: shortcut.working_dir.clone(),
+        run_window_style: if shortcut.minimized { RunWindowStyle::Minimized } else { RunWindowStyle::Normal },
+        triggers: vec![Trigger::OnLogin { enabled: true, delay_seconds: 0 }],
+        ..Default::default()
+    })
+}
+
+/// Whether an existing task already launches the shortcut's target on login
+pub fn is_imported(shortcut: &StartupShortcut, tasks: &[Task]) -> bool {
+    let Some(target) = &shortcut.target else { return false };
+    tasks.iter().any(|task| {
+        task.path_or_url.eq_ignore_ascii_case(target)
+            && task.args == shortcut.arguments
+            && task.triggers.iter().any(|t| matches!(t, Trigger::OnLogin { .. }))
+    })
+}
+
+/// Move a shortcut out of its Startup folder into `backup_dir`, so Windows no longer runs it.
+/// Returns where it was moved; moving it back restores it.
+pub fn disable(shortcut: &StartupShortcut, backup_dir: &Path) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(backup_dir)?;
+    let source = Path::new(&shortcut.path);
+    let file_name = source.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut destination = backup_dir.join(&file_name);
+    let mut n = 1;
+    while destination.exists() {
+        n += 1;
+        destination = backup_dir.join(format!("{} ({}).lnk", shortcut.name, n));
+    }
+
+    if std::fs::rename(source, &destination).is_err() {
+        // Different volume: copy, then remove
+        std::fs::copy(source, &destination)?;
+        if let Err(e) = std::fs::remove_file(source) {
+            let _ = std::fs::remove_file(&destination);
+            return Err(e);
+        }
+    }
+    Ok(destination)
+}
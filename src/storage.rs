@@ -1,26 +1,417 @@
 //! Storage module - SQLite database operations
 
 use crate::models::*;
-use rusqlite::{Connection, OptionalExtension, params, Result};
-use std::path::Path;
-use std::sync::Mutex;
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension, Row, params, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const TASK_COLUMNS: &str =
+    "id, enabled, name, description, target_type, path_or_url, args, working_dir,
+     stdin_input, start_delay_seconds, run_window_style, wait_policy, singleton, priority,
+     max_retries, retry_backoff_seconds, success_exit_codes, misfire_policy,
+     if_running_action, triggers, conditions, created_at_utc, updated_at_utc, paused_until_utc,
+     max_runtime_seconds, exempt_from_quiet_hours, must_finish_by_local, managed_by_file, tags, group_id,
+     deleted_at_utc, env, args_secret, log_retention_days, notify_policy,
+     webhooks, chat_alerts";
+
+const STATE_COLUMNS: &str =
+    "task_id, last_run_date_local, last_run_at_utc, last_result, last_error, next_run_at_utc, snoozed_until_utc,
+     trigger_states";
+
+const LOG_COLUMNS: &str =
+    "run_id, task_id, task_name, trigger_type, scheduled_time_utc, started_at_utc, finished_at_utc,
+     status, skip_reason, exit_code, error_message, output, overrides, duration_ms, error";
+
+/// WHERE clause matching a `LogFilter`, bound with `log_filter_params`
+const LOG_FILTER_WHERE: &str =
+    "(?1 IS NULL OR task_id = ?1) AND (?2 IS NULL OR status = ?2)
+     AND (?3 IS NULL OR started_at_utc >= ?3) AND (?4 IS NULL OR started_at_utc < ?4)";
+
+/// A schema change, applied once and recorded in schema_migrations
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+/// Ordered schema changes; append new steps with the next version number
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add run_logs.output",
+        up: |conn| add_column(conn, "run_logs", "output", "TEXT"),
+    },
+    Migration {
+        version: 2,
+        description: "add task_state.snoozed_until_utc",
+        up: |conn| add_column(conn, "task_state", "snoozed_until_utc", "TEXT"),
+    },
+    Migration {
+        version: 3,
+        description: "add tasks.paused_until_utc",
+        up: |conn| add_column(conn, "tasks", "paused_until_utc", "TEXT"),
+    },
+    Migration {
+        version: 4,
+        description: "add tasks.max_runtime_seconds",
+        up: |conn| add_column(conn, "tasks", "max_runtime_seconds", "INTEGER"),
+    },
+    Migration {
+        version: 5,
+        description: "add tasks.exempt_from_quiet_hours",
+        up: |conn| add_column(conn, "tasks", "exempt_from_quiet_hours", "INTEGER NOT NULL DEFAULT 0"),
+    },
+    Migration {
+        version: 6,
+        description: "add task_state.trigger_states",
+        up: |conn| add_column(conn, "task_state", "trigger_states", "TEXT"),
+    },
+    Migration {
+        version: 7,
+        description: "add run_logs.overrides",
+        up: |conn| add_column(conn, "run_logs", "overrides", "TEXT"),
+    },
+    Migration {
+        version: 8,
+        description: "add tasks.must_finish_by_local",
+        up: |conn| add_column(conn, "tasks", "must_finish_by_local", "TEXT"),
+    },
+    Migration {
+        version: 9,
+        description: "add tasks.managed_by_file",
+        up: |conn| add_column(conn, "tasks", "managed_by_file", "INTEGER NOT NULL DEFAULT 0"),
+    },
+    Migration {
+        version: 10,
+        description: "add run_logs_fts full-text index",
+        up: |conn| conn.execute_batch(RUN_LOGS_FTS_SQL),
+    },
+    Migration {
+        version: 11,
+        description: "add tasks.tags",
+        up: |conn| add_column(conn, "tasks", "tags", "TEXT NOT NULL DEFAULT '[]'"),
+    },
+    Migration {
+        version: 12,
+        description: "add task_groups and tasks.group_id",
+        up: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS task_groups (
+                    id TEXT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    sort_order INTEGER NOT NULL DEFAULT 0,
+                    created_at_utc TEXT NOT NULL
+                );"
+            )?;
+            add_column(conn, "tasks", "group_id", "TEXT")
+        },
+    },
+    Migration {
+        version: 13,
+        description: "add task_templates",
+        up: |conn| conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS task_templates (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                description TEXT,
+                task TEXT NOT NULL,
+                created_at_utc TEXT NOT NULL
+            );"
+        ),
+    },
+    Migration {
+        version: 14,
+        description: "add tasks.deleted_at_utc",
+        up: |conn| add_column(conn, "tasks", "deleted_at_utc", "TEXT"),
+    },
+    Migration {
+        version: 15,
+        description: "add task_history",
+        up: |conn| conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS task_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id TEXT NOT NULL,
+                changed_at_utc TEXT NOT NULL,
+                change TEXT NOT NULL,
+                diff TEXT NOT NULL,
+                snapshot TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_task_history_task ON task_history(task_id, id);"
+        ),
+    },
+    Migration {
+        version: 16,
+        description: "add tasks.env and tasks.args_secret",
+        up: |conn| {
+            add_column(conn, "tasks", "env", "TEXT NOT NULL DEFAULT '[]'")?;
+            add_column(conn, "tasks", "args_secret", "INTEGER NOT NULL DEFAULT 0")
+        },
+    },
+    Migration {
+        version: 17,
+        description: "add run_logs.duration_ms",
+        up: |conn| {
+            add_column(conn, "run_logs", "duration_ms", "INTEGER")?;
+            // Backfill finished runs from their timestamps
+            conn.execute(
+                "UPDATE run_logs
+                 SET duration_ms = CAST((julianday(finished_at_utc) - julianday(started_at_utc)) * 86400000 AS INTEGER)
+                 WHERE duration_ms IS NULL AND finished_at_utc IS NOT NULL
+                   AND status IN ('\"success\"', '\"failed\"')",
+                [],
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 18,
+        description: "add tasks.log_retention_days",
+        up: |conn| add_column(conn, "tasks", "log_retention_days", "INTEGER"),
+    },
+    Migration {
+        version: 19,
+        description: "add tasks.notify_policy",
+        up: |conn| add_column(conn, "tasks", "notify_policy", "TEXT NOT NULL DEFAULT '\"default\"'"),
+    },
+    Migration {
+        version: 20,
+        description: "add tasks.webhooks and webhook_deliveries",
+        up: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    run_id TEXT NOT NULL,
+                    task_id TEXT NOT NULL,
+                    url TEXT NOT NULL,
+                    attempt INTEGER NOT NULL,
+                    attempted_at_utc TEXT NOT NULL,
+                    status_code INTEGER,
+                    error TEXT,
+                    delivered INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_run ON webhook_deliveries(run_id);"
+            )?;
+            add_column(conn, "tasks", "webhooks", "TEXT NOT NULL DEFAULT '[]'")
+        },
+    },
+    Migration {
+        version: 21,
+        description: "add tasks.chat_alerts",
+        up: |conn| add_column(conn, "tasks", "chat_alerts", "TEXT NOT NULL DEFAULT '[]'"),
+    },
+    Migration {
+        version: 22,
+        description: "add email_digest_queue",
+        up: |conn| conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS email_digest_queue (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                queued_at_utc TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                body TEXT NOT NULL
+            );"
+        ),
+    },
+    Migration {
+        version: 23,
+        description: "add sync_state",
+        up: |conn| conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sync_state (
+                task_id TEXT PRIMARY KEY,
+                local_version TEXT NOT NULL,
+                remote_version TEXT NOT NULL
+            );"
+        ),
+    },
+    Migration {
+        version: 24,
+        description: "add run_logs.error",
+        up: |conn| add_column(conn, "run_logs", "error", "TEXT"),
+    },
+];
+
+/// Settings key of the timed scheduler pause
+const SCHEDULER_PAUSED_UNTIL_KEY: &str = "scheduler_paused_until_utc";
+/// Settings key of the open-ended scheduler pause
+const SCHEDULER_PAUSED_KEY: &str = "scheduler_paused";
+
+/// Full-text index over run logs, kept in sync by triggers
+const RUN_LOGS_FTS_SQL: &str = r#"
+    CREATE VIRTUAL TABLE IF NOT EXISTS run_logs_fts USING fts5(
+        task_name, error_message, output,
+        content='run_logs', content_rowid='rowid'
+    );
+
+    CREATE TRIGGER IF NOT EXISTS run_logs_fts_insert AFTER INSERT ON run_logs BEGIN
+        INSERT INTO run_logs_fts(rowid, task_name, error_message, output)
+        VALUES (new.rowid, new.task_name, new.error_message, new.output);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS run_logs_fts_delete AFTER DELETE ON run_logs BEGIN
+        INSERT INTO run_logs_fts(run_logs_fts, rowid, task_name, error_message, output)
+        VALUES ('delete', old.rowid, old.task_name, old.error_message, old.output);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS run_logs_fts_update AFTER UPDATE ON run_logs BEGIN
+        INSERT INTO run_logs_fts(run_logs_fts, rowid, task_name, error_message, output)
+        VALUES ('delete', old.rowid, old.task_name, old.error_message, old.output);
+        INSERT INTO run_logs_fts(rowid, task_name, error_message, output)
+        VALUES (new.rowid, new.task_name, new.error_message, new.output);
+    END;
+
+    INSERT INTO run_logs_fts(run_logs_fts) VALUES ('rebuild');
+"#;
+
+/// How long a statement waits on a lock held by another connection
+const BUSY_TIMEOUT_SECS: u64 = 5;
+
+/// Connection pragmas: WAL so readers don't block the scheduler's writes,
+/// a busy timeout instead of failing with "database is locked", and FK enforcement
+fn configure(conn: &Connection) -> Result<()> {
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    conn.busy_timeout(std::time::Duration::from_secs(BUSY_TIMEOUT_SECS))
+}
+
+/// Whether an error means the file itself is damaged or not a database
+fn is_corruption(e: &rusqlite::Error) -> bool {
+    matches!(
+        e.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseCorrupt | rusqlite::ErrorCode::NotADatabase)
+    )
+}
+
+/// Rename a corrupt database (with its WAL files) to "<name>.corrupt-<timestamp>.db"
+fn move_aside(path: &Path) -> std::io::Result<PathBuf> {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("database");
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let target = path.with_file_name(format!("{}.corrupt-{}.db", stem, stamp));
+    std::fs::rename(path, &target)?;
+    for suffix in ["-wal", "-shm"] {
+        let side = PathBuf::from(format!("{}{}", path.display(), suffix));
+        if side.exists() {
+            std::fs::rename(&side, format!("{}{}", target.display(), suffix))?;
+        }
+    }
+    Ok(target)
+}
+
+/// Add a column unless it is already there (databases from before versioned
+/// migrations may have it from an ad-hoc ALTER)
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .exists([column])?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+/// Database work run through `Database::run` panicked
+#[derive(Debug)]
+pub struct QueryPanicked(String);
+
+impl std::fmt::Display for QueryPanicked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Database task failed: {}", self.0)
+    }
+}
+
+impl From<QueryPanicked> for rusqlite::Error {
+    fn from(e: QueryPanicked) -> Self {
+        rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_INTERNAL), Some(e.to_string()))
+    }
+}
+
+impl From<QueryPanicked> for String {
+    fn from(e: QueryPanicked) -> Self {
+        e.to_string()
+    }
+}
 
 pub struct Database {
     conn: Mutex<Connection>,
+    /// Scheduler run logs waiting to be written in one transaction
+    pending_logs: Mutex<Vec<RunLog>>,
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_logs() {
+            tracing::error!("Failed to write pending run logs: {}", e);
+        }
+    }
 }
 
 impl Database {
     /// Open or create database at path
     pub fn open(path: &Path) -> Result<Self> {
         let conn = Connection::open(path)?;
-        let db = Self { conn: Mutex::new(conn) };
+        configure(&conn)?;
+        let db = Self { conn: Mutex::new(conn), pending_logs: Mutex::new(Vec::new()) };
         db.run_migrations()?;
         Ok(db)
     }
 
-    /// Run database migrations
-    fn run_migrations(&self) -> Result<()> {
+    /// Open an existing database without ever writing to it: no migrations and no
+    /// journal mode change, so logs can be browsed from a read-only snapshot
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.busy_timeout(std::time::Duration::from_secs(BUSY_TIMEOUT_SECS))?;
+        Ok(Self { conn: Mutex::new(conn), pending_logs: Mutex::new(Vec::new()) })
+    }
+
+    /// Open the database, checking its integrity first. A corrupt file is renamed
+    /// aside and replaced by a fresh one; returns where the corrupt file went, if so.
+    pub fn open_or_recover(path: &Path) -> Result<(Self, Option<PathBuf>)> {
+        let problem = match Self::open(path) {
+            Ok(db) => match db.integrity_check() {
+                Ok(problems) if problems.is_empty() => return Ok((db, None)),
+                Ok(problems) => problems.join("; "),
+                Err(e) if is_corruption(&e) => e.to_string(),
+                Err(e) => return Err(e),
+            },
+            Err(e) if is_corruption(&e) => e.to_string(),
+            Err(e) => return Err(e),
+        };
+        
+        tracing::error!("Database {:?} is corrupt, starting a new one: {}", path, problem);
+        let moved_to = move_aside(path).map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        Ok((Self::open(path)?, Some(moved_to)))
+    }
+
+    /// Problems reported by `PRAGMA integrity_check` (empty when the database is sound)
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
         let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let problems = rows.collect::<Result<Vec<_>>>()?;
+        Ok(problems.into_iter().filter(|p| p != "ok").collect())
+    }
+
+    /// Run blocking database work on the runtime's blocking pool, so callers on
+    /// async tasks (commands, the scheduler loop) don't stall other work.
+    /// A panic in `f` comes back as an error instead of taking the caller down.
+    pub async fn run<T, E, F>(self: &Arc<Self>, f: F) -> std::result::Result<T, E>
+    where
+        F: FnOnce(&Database) -> std::result::Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: From<QueryPanicked> + Send + 'static,
+    {
+        let db = Arc::clone(self);
+        let started = std::time::Instant::now();
+        let result = tokio::task::spawn_blocking(move || f(&db))
+            .await
+            .unwrap_or_else(|e| Err(QueryPanicked(e.to_string()).into()));
+        crate::metrics::ENGINE_METRICS.db_query.record(started.elapsed());
+        result
+    }
+
+    /// Create the base schema, then apply pending versioned migrations in order
+    fn run_migrations(&self) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
         conn.execute_batch(r#"
             CREATE TABLE IF NOT EXISTS tasks (
                 id TEXT PRIMARY KEY,
@@ -79,10 +470,30 @@ impl Database {
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             );
+
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at_utc TEXT NOT NULL
+            );
         "#)?;
         
-        // Migration: add stdin_input column if not exists
-        let _ = conn.execute("ALTER TABLE tasks ADD COLUMN stdin_input TEXT", []);
+        let current: u32 = conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+            [],
+            |row| row.get(0),
+        )?;
+        
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            let tx = conn.transaction()?;
+            (migration.up)(&tx)?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, description, applied_at_utc) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.description, chrono::Utc::now().to_rfc3339()],
+            )?;
+            tx.commit()?;
+            tracing::info!("Applied migration {}: {}", migration.version, migration.description);
+        }
         
         Ok(())
     }
@@ -91,54 +502,66 @@ impl Database {
 
     pub fn get_all_tasks(&self) -> Result<Vec<Task>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, enabled, name, description, target_type, path_or_url, args, working_dir,
-                    stdin_input, start_delay_seconds, run_window_style, wait_policy, singleton, priority,
-                    max_retries, retry_backoff_seconds, success_exit_codes, misfire_policy,
-                    if_running_action, triggers, conditions, created_at_utc, updated_at_utc
-             FROM tasks ORDER BY name"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM tasks WHERE deleted_at_utc IS NULL ORDER BY name",
+            TASK_COLUMNS
+        ))?;
         
-        let tasks = stmt.query_map([], |row| {
-            Ok(Task {
-                id: row.get(0)?,
-                enabled: row.get::<_, i32>(1)? != 0,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                target_type: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or(TargetType::File),
-                path_or_url: row.get(5)?,
-                args: row.get(6)?,
-                working_dir: row.get(7)?,
-                stdin_input: row.get(8)?,
-                start_delay_seconds: row.get::<_, i32>(9)? as u32,
-                run_window_style: serde_json::from_str(&row.get::<_, String>(10)?).unwrap_or_default(),
-                wait_policy: serde_json::from_str(&row.get::<_, String>(11)?).unwrap_or_default(),
-                singleton: row.get::<_, i32>(12)? != 0,
-                priority: row.get(13)?,
-                max_retries: row.get::<_, i32>(14)? as u8,
-                retry_backoff_seconds: row.get::<_, i32>(15)? as u32,
-                success_exit_codes: row.get::<_, Option<String>>(16)?
-                    .and_then(|s| serde_json::from_str(&s).ok()),
-                misfire_policy: serde_json::from_str(&row.get::<_, String>(17)?).unwrap_or_default(),
-                if_running_action: serde_json::from_str(&row.get::<_, String>(18)?).unwrap_or_default(),
-                triggers: serde_json::from_str(&row.get::<_, String>(19)?).unwrap_or_default(),
-                conditions: serde_json::from_str(&row.get::<_, String>(20)?).unwrap_or_default(),
-                created_at_utc: row.get::<_, String>(21)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
-                updated_at_utc: row.get::<_, String>(22)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
-            })
-        })?.collect::<Result<Vec<_>>>()?;
+        let tasks = stmt.query_map([], task_from_row)?.collect::<Result<Vec<_>>>()?;
+        
+        Ok(tasks)
+    }
+
+    /// Tasks whose name, description, path or (non-secret) arguments contain `query`,
+    /// ignoring case; names starting with it come first, then other name matches
+    pub fn search_tasks(&self, query: &str) -> Result<Vec<Task>> {
+        let escaped = query.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let (pattern, prefix) = (format!("%{}%", escaped), format!("{}%", escaped));
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM tasks
+             WHERE deleted_at_utc IS NULL
+               AND (name LIKE ?1 ESCAPE '\\' OR description LIKE ?1 ESCAPE '\\'
+                    OR path_or_url LIKE ?1 ESCAPE '\\' OR (args_secret = 0 AND args LIKE ?1 ESCAPE '\\'))
+             ORDER BY CASE WHEN name LIKE ?2 ESCAPE '\\' THEN 0 WHEN name LIKE ?1 ESCAPE '\\' THEN 1 ELSE 2 END, name",
+            TASK_COLUMNS
+        ))?;
+        
+        let tasks = stmt.query_map([pattern, prefix], task_from_row)?.collect::<Result<Vec<_>>>()?;
+        
+        Ok(tasks)
+    }
+
+    pub fn get_task(&self, id: &str) -> Result<Option<Task>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM tasks WHERE id = ?1 AND deleted_at_utc IS NULL",
+            TASK_COLUMNS
+        ))?;
+        stmt.query_row([id], task_from_row).optional()
+    }
+
+    /// Tasks in the trash, most recently deleted first
+    pub fn get_deleted_tasks(&self) -> Result<Vec<Task>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM tasks WHERE deleted_at_utc IS NOT NULL ORDER BY deleted_at_utc DESC",
+            TASK_COLUMNS
+        ))?;
+        
+        let tasks = stmt.query_map([], task_from_row)?.collect::<Result<Vec<_>>>()?;
         
         Ok(tasks)
     }
 
     pub fn insert_task(&self, task: &Task) -> Result<()> {
+        let task = &sealed(task)?;
         let conn = self.conn.lock().unwrap();
+        // A new task with the id of a trashed one replaces it
+        conn.execute("DELETE FROM tasks WHERE id = ?1 AND deleted_at_utc IS NOT NULL", params![task.id])?;
         conn.execute(
-            "INSERT INTO tasks (id, enabled, name, description, target_type, path_or_url, args, working_dir,
-                stdin_input, start_delay_seconds, run_window_style, wait_policy, singleton, priority,
-                max_retries, retry_backoff_seconds, success_exit_codes, misfire_policy,
-                if_running_action, triggers, conditions, created_at_utc, updated_at_utc)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
+            &format!("INSERT INTO tasks ({})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37)", TASK_COLUMNS),
             params![
                 task.id,
                 task.enabled as i32,
@@ -163,18 +586,39 @@ impl Database {
                 serde_json::to_string(&task.conditions).unwrap(),
                 task.created_at_utc.to_rfc3339(),
                 task.updated_at_utc.to_rfc3339(),
+                task.paused_until_utc.map(|t| t.to_rfc3339()),
+                task.max_runtime_seconds,
+                task.exempt_from_quiet_hours as i32,
+                task.must_finish_by_local,
+                task.managed_by_file as i32,
+                serde_json::to_string(&task.tags).unwrap(),
+                task.group_id,
+                task.deleted_at_utc.map(|t| t.to_rfc3339()),
+                serde_json::to_string(&task.env).unwrap(),
+                task.args_secret as i32,
+                task.log_retention_days,
+                serde_json::to_string(&task.notify_policy).unwrap(),
+                serde_json::to_string(&task.webhooks).unwrap(),
+                serde_json::to_string(&task.chat_alerts).unwrap(),
             ]
         )?;
+        record_change(&conn, &task.id, TaskChange::Created, None)?;
         Ok(())
     }
 
     pub fn update_task(&self, task: &Task) -> Result<()> {
+        let task = &sealed(task)?;
         let conn = self.conn.lock().unwrap();
+        let before = load_task(&conn, &task.id)?;
         conn.execute(
             "UPDATE tasks SET enabled=?2, name=?3, description=?4, target_type=?5, path_or_url=?6,
                 args=?7, working_dir=?8, stdin_input=?9, start_delay_seconds=?10, run_window_style=?11, wait_policy=?12,
                 singleton=?13, priority=?14, max_retries=?15, retry_backoff_seconds=?16, success_exit_codes=?17,
-                misfire_policy=?18, if_running_action=?19, triggers=?20, conditions=?21, updated_at_utc=?22
+                misfire_policy=?18, if_running_action=?19, triggers=?20, conditions=?21, updated_at_utc=?22,
+                paused_until_utc=?23, max_runtime_seconds=?24,
+                exempt_from_quiet_hours=?25, must_finish_by_local=?26, managed_by_file=?27,
+                tags=?28, group_id=?29, env=?30, args_secret=?31, log_retention_days=?32,
+                notify_policy=?33, webhooks=?34, chat_alerts=?35
              WHERE id=?1",
             params![
                 task.id,
@@ -199,195 +643,1258 @@ impl Database {
                 serde_json::to_string(&task.triggers).unwrap(),
                 serde_json::to_string(&task.conditions).unwrap(),
                 chrono::Utc::now().to_rfc3339(),
+                task.paused_until_utc.map(|t| t.to_rfc3339()),
+                task.max_runtime_seconds,
+                task.exempt_from_quiet_hours as i32,
+                task.must_finish_by_local,
+                task.managed_by_file as i32,
+                serde_json::to_string(&task.tags).unwrap(),
+                task.group_id,
+                serde_json::to_string(&task.env).unwrap(),
+                task.args_secret as i32,
+                task.log_retention_days,
+                serde_json::to_string(&task.notify_policy).unwrap(),
+                serde_json::to_string(&task.webhooks).unwrap(),
+                serde_json::to_string(&task.chat_alerts).unwrap(),
             ]
         )?;
+        record_change(&conn, &task.id, TaskChange::Updated, before.as_ref())?;
         Ok(())
     }
 
+    /// Import tasks, resolving id collisions with existing tasks as requested.
+    /// Secrets the export masked come from the existing task where there is one; those
+    /// and any encrypted on another PC are otherwise cleared and the task listed in `missing_secrets`.
+    pub fn import_tasks(&self, tasks: &[Task], on_collision: ImportCollision) -> Result<ImportSummary> {
+        let mut summary = ImportSummary::default();
+        
+        for task in tasks {
+            let stored = self.get_task(&task.id)?;
+            if stored.is_some() && on_collision == ImportCollision::Skip {
+                summary.skipped += 1;
+                continue;
+            }
+            
+            let mut task = task.clone();
+            if let Some(stored) = &stored {
+                crate::secrets::keep_masked(&mut task, stored);
+            }
+            if crate::secrets::clear_unusable(&mut task) {
+                summary.missing_secrets.push(task.name.clone());
+            }
+            
+            match (stored, on_collision) {
+                (None, _) => {
+                    self.insert_task(&task)?;
+                    summary.created += 1;
+                }
+                (Some(_), ImportCollision::Overwrite) => {
+                    self.update_task(&task)?;
+                    summary.overwritten += 1;
+                }
+                (Some(_), _) => {
+                    self.insert_task(&Task {
+                        id: uuid::Uuid::new_v4().to_string(),
+                        ..task
+                    })?;
+                    summary.duplicated += 1;
+                }
+            }
+        }
+        
+        Ok(summary)
+    }
+
+    /// Move a task to the trash; its run history stays
     pub fn delete_task(&self, id: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        conn.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
-        conn.execute("DELETE FROM task_state WHERE task_id = ?1", params![id])?;
+        let before = load_task(&conn, id)?;
+        let deleted = conn.execute(
+            "UPDATE tasks SET deleted_at_utc = ?2 WHERE id = ?1 AND deleted_at_utc IS NULL",
+            params![id, chrono::Utc::now().to_rfc3339()],
+        )?;
+        if deleted > 0 {
+            record_change(&conn, id, TaskChange::Deleted, before.as_ref())?;
+        }
         Ok(())
     }
 
-    // === Run Logs ===
+    /// Apply a patch to several tasks in one transaction. Missing, trashed and
+    /// file-managed tasks are left alone. Returns how many tasks changed.
+    pub fn bulk_update_tasks(&self, ids: &[String], patch: &TaskPatch) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let now = chrono::Utc::now().to_rfc3339();
+        let mut changed = 0;
+        
+        for id in ids {
+            let Some(before) = load_task(&tx, id)? else { continue };
+            if before.deleted_at_utc.is_some() || before.managed_by_file {
+                continue;
+            }
+            if patch.delete {
+                tx.execute("UPDATE tasks SET deleted_at_utc = ?2 WHERE id = ?1", params![id, now])?;
+                record_change(&tx, id, TaskChange::Deleted, Some(&before))?;
+                changed += 1;
+            } else if let Some(enabled) = patch.enabled.filter(|e| *e != before.enabled) {
+                tx.execute(
+                    "UPDATE tasks SET enabled = ?2, updated_at_utc = ?3 WHERE id = ?1",
+                    params![id, enabled as i32, now],
+                )?;
+                record_change(&tx, id, TaskChange::Updated, Some(&before))?;
+                changed += 1;
+            }
+        }
+        
+        tx.commit()?;
+        Ok(changed)
+    }
 
-    pub fn get_logs(&self, limit: u32) -> Result<Vec<RunLog>> {
+    /// Take a task out of the trash. It comes back editable, even if a task file
+    /// used to manage it.
+    pub fn restore_task(&self, id: &str) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
-        // Check if output column exists, if not add it (simple migration)
-        let _ = conn.execute("ALTER TABLE run_logs ADD COLUMN output TEXT", []);
+        let before = load_task(&conn, id)?;
+        let restored = conn.execute(
+            "UPDATE tasks SET deleted_at_utc = NULL, managed_by_file = 0 WHERE id = ?1 AND deleted_at_utc IS NOT NULL",
+            params![id],
+        )?;
+        if restored > 0 {
+            record_change(&conn, id, TaskChange::Restored, before.as_ref())?;
+        }
+        Ok(restored > 0)
+    }
+
+    /// Permanently remove trashed tasks (run logs are kept), returns how many
+    pub fn purge_trash(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM task_state WHERE task_id IN (SELECT id FROM tasks WHERE deleted_at_utc IS NOT NULL)",
+            [],
+        )?;
+        conn.execute("DELETE FROM tasks WHERE deleted_at_utc IS NOT NULL", [])
+    }
+
+    /// File a task under a group (None takes it out of any group)
+    pub fn set_task_group(&self, task_id: &str, group_id: Option<&str>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let before = load_task(&conn, task_id)?;
+        conn.execute("UPDATE tasks SET group_id = ?2 WHERE id = ?1", params![task_id, group_id])?;
+        record_change(&conn, task_id, TaskChange::Updated, before.as_ref())?;
+        Ok(())
+    }
+
+    // === Task History ===
+
+    /// Recorded changes to a task, newest first
+    pub fn get_task_history(&self, task_id: &str) -> Result<Vec<TaskHistoryEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, changed_at_utc, change, diff, snapshot FROM task_history
+             WHERE task_id = ?1 ORDER BY id DESC"
+        )?;
+        
+        let entries = stmt.query_map([task_id], history_from_row)?
+            .filter_map(|e| e.transpose())
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(entries)
+    }
+
+    pub fn get_history_entry(&self, id: i64) -> Result<Option<TaskHistoryEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, changed_at_utc, change, diff, snapshot FROM task_history WHERE id = ?1"
+        )?;
+        
+        let entry = stmt.query_row([id], history_from_row).optional()?;
+        
+        Ok(entry.flatten())
+    }
+
+    // === Task Groups ===
 
+    pub fn get_groups(&self) -> Result<Vec<TaskGroup>> {
+        let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT run_id, task_id, task_name, trigger_type, scheduled_time_utc,
-                    started_at_utc, finished_at_utc, status, skip_reason, exit_code, error_message, output
-             FROM run_logs ORDER BY started_at_utc DESC LIMIT ?1"
+            "SELECT id, name, sort_order, created_at_utc FROM task_groups ORDER BY sort_order, name"
         )?;
         
-        let logs = stmt.query_map([limit], |row| {
-            Ok(RunLog {
-                run_id: row.get(0)?,
-                task_id: row.get(1)?,
-                task_name: row.get(2)?,
-                trigger_type: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
-                scheduled_time_utc: row.get::<_, Option<String>>(4)?
-                    .and_then(|s| s.parse().ok()),
-                started_at_utc: row.get::<_, String>(5)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
-                finished_at_utc: row.get::<_, Option<String>>(6)?
-                    .and_then(|s| s.parse().ok()),
-                status: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or(RunStatus::Failed),
-                skip_reason: row.get::<_, Option<String>>(8)?
-                    .and_then(|s| serde_json::from_str(&s).ok()),
-                exit_code: row.get(9)?,
-                error_message: row.get(10)?,
-                output: row.get(11)?,
+        let groups = stmt.query_map([], |row| {
+            Ok(TaskGroup {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                sort_order: row.get(2)?,
+                created_at_utc: row.get::<_, String>(3)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
             })
         })?.collect::<Result<Vec<_>>>()?;
         
+        Ok(groups)
+    }
+
+    pub fn insert_group(&self, group: &TaskGroup) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO task_groups (id, name, sort_order, created_at_utc) VALUES (?1, ?2, ?3, ?4)",
+            params![group.id, group.name, group.sort_order, group.created_at_utc.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    pub fn update_group(&self, group: &TaskGroup) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE task_groups SET name = ?2, sort_order = ?3 WHERE id = ?1",
+            params![group.id, group.name, group.sort_order],
+        )?;
+        Ok(())
+    }
+
+    /// Delete a group; its tasks stay, just ungrouped
+    pub fn delete_group(&self, id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE tasks SET group_id = NULL WHERE group_id = ?1", params![id])?;
+        conn.execute("DELETE FROM task_groups WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    // === Task Templates ===
+
+    pub fn get_templates(&self) -> Result<Vec<TaskTemplate>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, task, created_at_utc FROM task_templates ORDER BY name"
+        )?;
+        
+        let templates = stmt.query_map([], template_from_row)?
+            .filter_map(|t| t.transpose())
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(templates)
+    }
+
+    pub fn get_template(&self, id: &str) -> Result<Option<TaskTemplate>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, task, created_at_utc FROM task_templates WHERE id = ?1"
+        )?;
+        
+        let template = stmt.query_row([id], template_from_row).optional()?;
+        
+        Ok(template.flatten())
+    }
+
+    pub fn insert_template(&self, template: &TaskTemplate) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO task_templates (id, name, description, task, created_at_utc) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                template.id,
+                template.name,
+                template.description,
+                serde_json::to_string(&template.task).unwrap(),
+                template.created_at_utc.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_template(&self, id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM task_templates WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Hand file-managed tasks back to the UI (when the task file is turned off)
+    pub fn release_file_managed_tasks(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE tasks SET managed_by_file = 0 WHERE managed_by_file != 0", [])
+    }
+
+    // === Run Logs ===
+
+    pub fn get_logs(&self, limit: u32) -> Result<Vec<RunLog>> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM run_logs ORDER BY started_at_utc DESC LIMIT ?1",
+            LOG_COLUMNS
+        ))?;
+        
+        let logs = stmt.query_map([limit], log_from_row)?.collect::<Result<Vec<_>>>()?;
+        
         Ok(logs)
     }
 
     pub fn insert_log(&self, log: &RunLog) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        write_log(&conn, log)
+    }
+
+    /// Buffer a run log; it is written by the next `flush_logs` (or read that needs it)
+    pub fn queue_log(&self, log: &RunLog) {
+        self.pending_logs.lock().unwrap().push(log.clone());
+    }
+
+    /// Write buffered run logs in a single transaction, returns how many were written
+    pub fn flush_logs(&self) -> Result<usize> {
+        let pending = std::mem::take(&mut *self.pending_logs.lock().unwrap());
+        if pending.is_empty() {
+            return Ok(0);
+        }
+        
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut written = 0;
+        for log in &pending {
+            match write_log(&tx, log) {
+                Ok(()) => written += 1,
+                Err(e) => tracing::error!("Failed to insert log {}: {}", log.run_id, e),
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    /// Pass each log matching the filter to `f`, oldest first, one row at a time.
+    /// Returns how many logs were visited.
+    pub fn for_each_log<E>(&self, filter: &LogFilter, mut f: impl FnMut(RunLog) -> std::result::Result<(), E>) -> std::result::Result<usize, E>
+    where
+        E: From<rusqlite::Error>,
+    {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM run_logs WHERE {} ORDER BY started_at_utc",
+            LOG_COLUMNS, LOG_FILTER_WHERE
+        ))?;
+        let mut rows = stmt.query(log_filter_params(filter))?;
+        
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            f(log_from_row(row)?)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// How many logs match the filter
+    pub fn count_logs(&self, filter: &LogFilter) -> Result<usize> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM run_logs WHERE {}", LOG_FILTER_WHERE),
+            log_filter_params(filter),
+            |row| row.get(0),
+        )
+    }
+
+    /// Delete the logs matching the filter, returns how many were removed
+    pub fn delete_logs(&self, filter: &LogFilter) -> Result<usize> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(&format!("DELETE FROM run_logs WHERE {}", LOG_FILTER_WHERE), log_filter_params(filter))
+    }
+
+    pub fn get_log(&self, run_id: &str) -> Result<Option<RunLog>> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM run_logs WHERE run_id = ?1", LOG_COLUMNS))?;
+        stmt.query_row([run_id], log_from_row).optional()
+    }
+
+    /// A page of one task's logs, newest first
+    pub fn get_logs_for_task(&self, task_id: &str, limit: u32, offset: u32) -> Result<Vec<RunLog>> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM run_logs WHERE task_id = ?1 ORDER BY started_at_utc DESC LIMIT ?2 OFFSET ?3",
+            LOG_COLUMNS
+        ))?;
+        
+        let logs = stmt.query_map(params![task_id, limit, offset], log_from_row)?
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(logs)
+    }
+
+    /// Logs of one task that started at or after `since`
+    pub fn get_logs_for_task_since(&self, task_id: &str, since: chrono::DateTime<chrono::Utc>) -> Result<Vec<RunLog>> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM run_logs WHERE task_id = ?1 AND started_at_utc >= ?2 ORDER BY started_at_utc DESC",
+            LOG_COLUMNS
+        ))?;
+        
+        let logs = stmt.query_map(params![task_id, since.to_rfc3339()], log_from_row)?
+            .collect::<Result<Vec<_>>>()?;
+        
+        Ok(logs)
+    }
+
+    /// Success rate, failures and durations of a task over the last `days` days
+    pub fn get_task_stats(&self, task_id: &str, days: u32) -> Result<TaskStats> {
+        let since = chrono::Utc::now() - chrono::Duration::days(days as i64);
+        let logs = self.get_logs_for_task_since(task_id, since)?;
+        Ok(TaskStats::from_logs(task_id, days, &logs))
+    }
+
+    /// Full-text search over task name, error message and output, newest first
+    pub fn search_logs(&self, query: &str, limit: u32) -> Result<Vec<RunLog>> {
+        let match_query = fts_query(query);
+        if match_query.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM run_logs
+             WHERE rowid IN (SELECT rowid FROM run_logs_fts WHERE run_logs_fts MATCH ?1)
+             ORDER BY started_at_utc DESC LIMIT ?2",
+            LOG_COLUMNS
+        ))?;
+        
+        let logs = stmt.query_map(params![match_query, limit], log_from_row)?.collect::<Result<Vec<_>>>()?;
+        
+        Ok(logs)
+    }
+
+    /// How many of the task's most recent finished runs failed in a row (skips don't count)
+    pub fn consecutive_failures(&self, task_id: &str) -> Result<u32> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare_cached(
+            "SELECT status FROM run_logs
+             WHERE task_id = ?1 AND status IN ('\"success\"', '\"failed\"')
+             ORDER BY started_at_utc DESC"
+        )?;
+        let mut rows = stmt.query([task_id])?;
+        let mut count = 0;
+        while let Some(row) = rows.next()? {
+            if row.get::<_, String>(0)? != "\"failed\"" {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Delete run logs older than their task's retention, or `default_days` for tasks
+    /// without an override (0 keeps everything)
+    pub fn purge_expired_logs(&self, default_days: u32) -> Result<usize> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM run_logs WHERE run_id IN (
+                SELECT l.run_id FROM run_logs l LEFT JOIN tasks t ON t.id = l.task_id
+                WHERE COALESCE(t.log_retention_days, ?1) > 0
+                  AND julianday(l.started_at_utc) < julianday('now') - COALESCE(t.log_retention_days, ?1)
+             )",
+            [default_days],
+        )?;
+        // Delivery attempts go with their run
+        conn.execute(
+            "DELETE FROM webhook_deliveries WHERE run_id NOT IN (SELECT run_id FROM run_logs)",
+            [],
+        )?;
+        Ok(removed)
+    }
+
+    // === Webhook Deliveries ===
+
+    pub fn insert_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO run_logs (run_id, task_id, task_name, trigger_type, scheduled_time_utc,
-                started_at_utc, finished_at_utc, status, skip_reason, exit_code, error_message, output)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            "INSERT INTO webhook_deliveries
+                (run_id, task_id, url, attempt, attempted_at_utc, status_code, error, delivered)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
-                log.run_id,
-                log.task_id,
-                log.task_name,
-                log.trigger_type,
-                log.scheduled_time_utc.map(|t| t.to_rfc3339()),
-                log.started_at_utc.to_rfc3339(),
-                log.finished_at_utc.map(|t| t.to_rfc3339()),
-                serde_json::to_string(&log.status).unwrap(),
-                log.skip_reason.as_ref().map(|r| serde_json::to_string(r).unwrap()),
-                log.exit_code,
-                log.error_message,
-                log.output,
-            ]
+                delivery.run_id,
+                delivery.task_id,
+                delivery.url,
+                delivery.attempt,
+                delivery.attempted_at_utc.to_rfc3339(),
+                delivery.status_code,
+                delivery.error,
+                delivery.delivered as i32,
+            ],
         )?;
         Ok(())
     }
 
-    // === Settings ===
+    /// Most recent delivery attempts, optionally for one task
+    pub fn get_webhook_deliveries(&self, task_id: Option<&str>, limit: u32) -> Result<Vec<WebhookDelivery>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, run_id, task_id, url, attempt, attempted_at_utc, status_code, error, delivered
+             FROM webhook_deliveries
+             WHERE ?1 IS NULL OR task_id = ?1
+             ORDER BY id DESC LIMIT ?2"
+        )?;
+        
+        let deliveries = stmt.query_map(params![task_id, limit], |row| {
+            Ok(WebhookDelivery {
+                id: row.get(0)?,
+                run_id: row.get(1)?,
+                task_id: row.get(2)?,
+                url: row.get(3)?,
+                attempt: row.get(4)?,
+                attempted_at_utc: row.get::<_, String>(5)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+                status_code: row.get(6)?,
+                error: row.get(7)?,
+                delivered: row.get::<_, i32>(8)? != 0,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+        
+        Ok(deliveries)
+    }
 
-    pub fn get_settings(&self) -> Result<Settings> {
+    // === Email Digest ===
+
+    pub fn queue_email_alert(&self, subject: &str, body: &str) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
-        let rows = stmt.query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-        })?;
+        conn.execute(
+            "INSERT INTO email_digest_queue (queued_at_utc, subject, body) VALUES (?1, ?2, ?3)",
+            params![chrono::Utc::now().to_rfc3339(), subject, body],
+        )?;
+        Ok(())
+    }
+
+    /// Alerts waiting for the next digest, oldest first
+    pub fn get_queued_email_alerts(&self) -> Result<Vec<QueuedEmailAlert>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, queued_at_utc, subject, body FROM email_digest_queue ORDER BY id")?;
+        let alerts = stmt.query_map([], |row| {
+            Ok(QueuedEmailAlert {
+                id: row.get(0)?,
+                queued_at_utc: row.get::<_, String>(1)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+                subject: row.get(2)?,
+                body: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+        Ok(alerts)
+    }
+
+    /// Drop sent alerts, up to and including `last_id`
+    pub fn delete_queued_email_alerts(&self, last_id: i64) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM email_digest_queue WHERE id <= ?1", [last_id])
+    }
+
+    // === Task Sync ===
+
+    /// Per task, the local and shared-file versions as of the last sync
+    pub fn get_sync_versions(&self) -> Result<HashMap<String, (String, String)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT task_id, local_version, remote_version FROM sync_state")?;
+        let versions = stmt.query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(versions)
+    }
+
+    pub fn set_sync_versions(&self, task_id: &str, local_version: &str, remote_version: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_state (task_id, local_version, remote_version) VALUES (?1, ?2, ?3)",
+            params![task_id, local_version, remote_version],
+        )?;
+        Ok(())
+    }
 
-        let mut settings = Settings::default();
-        for row in rows {
-            let (key, value) = row?;
-            match key.as_str() {
-                "start_with_windows" => settings.start_with_windows = value == "true",
-                "start_minimized_to_tray" => settings.start_minimized_to_tray = value == "true",
-                "show_notifications" => settings.show_notifications = value == "true",
-                "timezone_id" => settings.timezone_id = value,
-                "log_retention_days" => settings.log_retention_days = value.parse().unwrap_or(30),
-                "max_parallel_runs" => settings.max_parallel_runs = value.parse().unwrap_or(3),
-                _ => {}
+    // === Settings ===
+
+    /// Settings are stored one row per field, keyed by the field's serde name, so a
+    /// new field on `Settings` (with a default) needs no changes here
+    pub fn get_settings(&self) -> Result<Settings> {
+        let stored = self.get_setting_values()?;
+        let defaults = serde_json::to_value(Settings::default()).unwrap();
+        let mut settings = defaults.clone();
+        
+        for (key, default) in defaults.as_object().into_iter().flatten() {
+            let Some(raw) = stored.get(key) else { continue };
+            let value = setting_value(raw, default);
+            // A value that no longer fits the field's type keeps the default
+            let mut candidate = defaults.clone();
+            candidate[key] = value.clone();
+            if serde_json::from_value::<Settings>(candidate).is_ok() {
+                settings[key] = value;
+            } else {
+                tracing::warn!("Ignoring invalid stored value for setting {}", key);
             }
         }
-        Ok(settings)
+        
+        Ok(serde_json::from_value(settings).unwrap_or_default())
     }
 
     pub fn save_settings(&self, settings: &Settings) -> Result<()> {
+        let fields = serde_json::to_value(settings).unwrap();
+        
         let conn = self.conn.lock().unwrap();
-        let pairs = [
-            ("start_with_windows", settings.start_with_windows.to_string()),
-            ("start_minimized_to_tray", settings.start_minimized_to_tray.to_string()),
-            ("show_notifications", settings.show_notifications.to_string()),
-            ("timezone_id", settings.timezone_id.clone()),
-            ("log_retention_days", settings.log_retention_days.to_string()),
-            ("max_parallel_runs", settings.max_parallel_runs.to_string()),
-        ];
-
-        for (key, value) in pairs {
+        for (key, value) in fields.as_object().into_iter().flatten() {
             conn.execute(
                 "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-                params![key, value]
+                params![key, value.to_string()]
             )?;
         }
         Ok(())
     }
 
+    /// A single stored setting outside of `Settings` (None if unset or unreadable)
+    pub fn get_setting<T: serde::de::DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let conn = self.conn.lock().unwrap();
+        let raw: Option<String> = conn.query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            [key],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(raw.and_then(|raw| serde_json::from_value(setting_value(&raw, &serde_json::Value::Null)).ok()))
+    }
+
+    /// Store a single setting; None removes it
+    pub fn set_setting<T: serde::Serialize>(&self, key: &str, value: Option<&T>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        match value {
+            Some(value) => conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                params![key, serde_json::to_string(value).unwrap()],
+            )?,
+            None => conn.execute("DELETE FROM settings WHERE key = ?1", [key])?,
+        };
+        Ok(())
+    }
+
+    fn get_setting_values(&self) -> Result<HashMap<String, String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.collect()
+    }
+
+    // === Backup ===
+
+    /// Copy the live database to `path` with SQLite's online backup API
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        self.flush_logs()?;
+        let conn = self.conn.lock().unwrap();
+        conn.backup(DatabaseName::Main, path, None)
+    }
+
+    /// Replace the live database with the backup at `path`, then bring its schema up to date
+    pub fn restore_from(&self, path: &Path) -> Result<()> {
+        // Opening a missing file would create an empty database and wipe everything
+        let src = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let has_tasks = src
+            .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'tasks'")?
+            .exists([])?;
+        drop(src);
+        if !has_tasks {
+            return Err(rusqlite::Error::InvalidPath(path.to_path_buf()));
+        }
+        
+        {
+            let mut conn = self.conn.lock().unwrap();
+            conn.restore(DatabaseName::Main, path, None::<fn(rusqlite::backup::Progress)>)?;
+        }
+        self.run_migrations()
+    }
+
+    // === Maintenance ===
+
+    /// Reclaim free pages, refresh planner statistics and merge the log search index
+    pub fn compact(&self) -> Result<CompactSummary> {
+        let conn = self.conn.lock().unwrap();
+        let size_before_bytes = database_size(&conn)?;
+        
+        conn.execute_batch(
+            "INSERT INTO run_logs_fts(run_logs_fts) VALUES ('optimize');
+             ANALYZE;
+             VACUUM;"
+        )?;
+        // Fold the WAL back in so the file on disk actually shrinks
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        
+        let size_after_bytes = database_size(&conn)?;
+        Ok(CompactSummary {
+            size_before_bytes,
+            size_after_bytes,
+            freed_bytes: size_before_bytes.saturating_sub(size_after_bytes),
+        })
+    }
+
+    // === Scheduler ===
+
+    /// End of a timed scheduler pause, if one is active
+    pub fn get_scheduler_paused_until(&self) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        self.get_setting(SCHEDULER_PAUSED_UNTIL_KEY)
+    }
+
+    pub fn set_scheduler_paused_until(&self, until: Option<chrono::DateTime<chrono::Utc>>) -> Result<()> {
+        self.set_setting(SCHEDULER_PAUSED_UNTIL_KEY, until.as_ref())
+    }
+
+    pub fn get_scheduler_paused(&self) -> Result<bool> {
+        Ok(self.get_setting(SCHEDULER_PAUSED_KEY)?.unwrap_or(false))
+    }
+
+    pub fn set_scheduler_paused(&self, paused: bool) -> Result<()> {
+        self.set_setting(SCHEDULER_PAUSED_KEY, paused.then_some(&true))
+    }
+
     // === Task State ===
 
     pub fn get_task_states(&self) -> Result<Vec<TaskState>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT task_id, last_run_date_local, last_run_at_utc, last_result, last_error, next_run_at_utc
-             FROM task_state"
-        )?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM task_state", STATE_COLUMNS))?;
         
-        let states = stmt.query_map([], |row| {
-            Ok(TaskState {
-                task_id: row.get(0)?,
-                last_run_date_local: row.get(1)?,
-                last_run_at_utc: row.get::<_, Option<String>>(2)?
-                    .and_then(|s| s.parse().ok()),
-                last_result: row.get::<_, Option<String>>(3)?
-                    .and_then(|s| serde_json::from_str(&s).ok()),
-                last_error: row.get(4)?,
-                next_run_at_utc: row.get::<_, Option<String>>(5)?
-                    .and_then(|s| s.parse().ok()),
-            })
-        })?.collect::<Result<Vec<_>>>()?;
+        let states = stmt.query_map([], state_from_row)?.collect::<Result<Vec<_>>>()?;
         
         Ok(states)
     }
 
+    pub fn get_task_state(&self, task_id: &str) -> Result<Option<TaskState>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM task_state WHERE task_id = ?1", STATE_COLUMNS))?;
+        
+        let state = stmt.query_row([task_id], state_from_row).optional()?;
+        
+        Ok(state)
+    }
+
+    /// Load the stored state for a task, or a blank state if it has never run
+    pub fn get_task_state_or_default(&self, task_id: &str) -> Result<TaskState> {
+        Ok(self.get_task_state(task_id)?.unwrap_or_else(|| TaskState {
+            task_id: task_id.to_string(),
+            ..Default::default()
+        }))
+    }
+
     pub fn update_task_state(&self, state: &TaskState) -> Result<()> {
         let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "INSERT OR REPLACE INTO task_state (task_id, last_run_date_local, last_run_at_utc, last_result, last_error, next_run_at_utc)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                state.task_id,
-                state.last_run_date_local,
-                state.last_run_at_utc.map(|t| t.to_rfc3339()),
-                state.last_result.as_ref().map(|r| serde_json::to_string(r).unwrap()),
-                state.last_error,
-                state.next_run_at_utc.map(|t| t.to_rfc3339()),
-            ]
-        )?;
+        conn.prepare_cached(
+            &format!("INSERT OR REPLACE INTO task_state ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)", STATE_COLUMNS),
+        )?.execute(params![
+            state.task_id,
+            state.last_run_date_local,
+            state.last_run_at_utc.map(|t| t.to_rfc3339()),
+            state.last_result.as_ref().map(|r| serde_json::to_string(r).unwrap()),
+            state.last_error,
+            state.next_run_at_utc.map(|t| t.to_rfc3339()),
+            state.snoozed_until_utc.map(|t| t.to_rfc3339()),
+            serde_json::to_string(&state.trigger_states).unwrap(),
+        ])?;
         Ok(())
     }
 
     /// Get the last log entry for a specific task
     pub fn get_last_run_for_task(&self, task_id: &str) -> Result<Option<RunLog>> {
+        self.flush_logs()?;
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT run_id, task_id, task_name, trigger_type, scheduled_time_utc,
-                    started_at_utc, finished_at_utc, status, skip_reason, exit_code, error_message, output
-             FROM run_logs WHERE task_id = ?1 ORDER BY started_at_utc DESC LIMIT 1"
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM run_logs WHERE task_id = ?1 ORDER BY started_at_utc DESC LIMIT 1",
+            LOG_COLUMNS
+        ))?;
         
-        let log = stmt.query_row([task_id], |row| {
-            Ok(RunLog {
-                run_id: row.get(0)?,
-                task_id: row.get(1)?,
-                task_name: row.get(2)?,
-                trigger_type: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
-                scheduled_time_utc: row.get::<_, Option<String>>(4)?
-                    .and_then(|s| s.parse().ok()),
-                started_at_utc: row.get::<_, String>(5)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
-                finished_at_utc: row.get::<_, Option<String>>(6)?
-                    .and_then(|s| s.parse().ok()),
-                status: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or(RunStatus::Failed),
-                skip_reason: row.get::<_, Option<String>>(8)?
-                    .and_then(|s| serde_json::from_str(&s).ok()),
-                exit_code: row.get(9)?,
-                error_message: row.get(10)?,
-                output: row.get(11)?,
-            })
-        }).optional()?;
+        let log = stmt.query_row([task_id], log_from_row).optional()?;
         
         Ok(log)
     }
 }
+
+fn task_from_row(row: &Row) -> Result<Task> {
+    Ok(Task {
+        id: row.get(0)?,
+        enabled: row.get::<_, i32>(1)? != 0,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        target_type: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or(TargetType::File),
+        path_or_url: row.get(5)?,
+        args: row.get(6)?,
+        working_dir: row.get(7)?,
+        stdin_input: row.get(8)?,
+        start_delay_seconds: row.get::<_, i32>(9)? as u32,
+        run_window_style: serde_json::from_str(&row.get::<_, String>(10)?).unwrap_or_default(),
+        wait_policy: serde_json::from_str(&row.get::<_, String>(11)?).unwrap_or_default(),
+        singleton: row.get::<_, i32>(12)? != 0,
+        priority: row.get(13)?,
+        max_retries: row.get::<_, i32>(14)? as u8,
+        retry_backoff_seconds: row.get::<_, i32>(15)? as u32,
+        success_exit_codes: row.get::<_, Option<String>>(16)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
+        misfire_policy: serde_json::from_str(&row.get::<_, String>(17)?).unwrap_or_default(),
+        if_running_action: serde_json::from_str(&row.get::<_, String>(18)?).unwrap_or_default(),
+        triggers: serde_json::from_str(&row.get::<_, String>(19)?).unwrap_or_default(),
+        conditions: serde_json::from_str(&row.get::<_, String>(20)?).unwrap_or_default(),
+        created_at_utc: row.get::<_, String>(21)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+        updated_at_utc: row.get::<_, String>(22)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+        paused_until_utc: row.get::<_, Option<String>>(23)?
+            .and_then(|s| s.parse().ok()),
+        max_runtime_seconds: row.get(24)?,
+        exempt_from_quiet_hours: row.get::<_, i32>(25)? != 0,
+        must_finish_by_local: row.get(26)?,
+        managed_by_file: row.get::<_, i32>(27)? != 0,
+        tags: serde_json::from_str(&row.get::<_, String>(28)?).unwrap_or_default(),
+        group_id: row.get(29)?,
+        deleted_at_utc: row.get::<_, Option<String>>(30)?
+            .and_then(|s| s.parse().ok()),
+        env: serde_json::from_str(&row.get::<_, String>(31)?).unwrap_or_default(),
+        args_secret: row.get::<_, i32>(32)? != 0,
+        log_retention_days: row.get(33)?,
+        notify_policy: serde_json::from_str(&row.get::<_, String>(34)?).unwrap_or_default(),
+        webhooks: serde_json::from_str(&row.get::<_, String>(35)?).unwrap_or_default(),
+        chat_alerts: serde_json::from_str(&row.get::<_, String>(36)?).unwrap_or_default(),
+    })
+}
+
+/// The task with its secret values encrypted, ready to be written
+fn sealed(task: &Task) -> Result<Task> {
+    crate::secrets::seal_task(task)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))
+}
+
+/// Load a task whether or not it is in the trash
+fn load_task(conn: &Connection, id: &str) -> Result<Option<Task>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM tasks WHERE id = ?1", TASK_COLUMNS))?;
+    stmt.query_row([id], task_from_row).optional()
+}
+
+/// Append the task's current version to its history. Updates that changed
+/// nothing but the timestamp aren't recorded.
+fn record_change(conn: &Connection, task_id: &str, change: TaskChange, before: Option<&Task>) -> Result<()> {
+    let Some(after) = load_task(conn, task_id)? else {
+        return Ok(());
+    };
+    let diff = task_diff(before, &after);
+    if change == TaskChange::Updated && diff.is_empty() {
+        return Ok(());
+    }
+    
+    conn.execute(
+        "INSERT INTO task_history (task_id, changed_at_utc, change, diff, snapshot) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            task_id,
+            chrono::Utc::now().to_rfc3339(),
+            serde_json::to_string(&change).unwrap(),
+            serde_json::Value::Object(diff).to_string(),
+            serde_json::to_string(&after).unwrap(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Read a history row; None if its snapshot no longer deserializes
+fn history_from_row(row: &Row) -> Result<Option<TaskHistoryEntry>> {
+    let snapshot = match serde_json::from_str(&row.get::<_, String>(5)?) {
+        Ok(task) => task,
+        Err(e) => {
+            tracing::warn!("Skipping unreadable history entry {}: {}", row.get::<_, i64>(0)?, e);
+            return Ok(None);
+        }
+    };
+    
+    Ok(Some(TaskHistoryEntry {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        changed_at_utc: row.get::<_, String>(2)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+        change: serde_json::from_str(&row.get::<_, String>(3)?).unwrap_or(TaskChange::Updated),
+        diff: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
+        snapshot,
+    }))
+}
+
+/// Size of the main database file in bytes
+fn database_size(conn: &Connection) -> Result<u64> {
+    let page_count: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+    let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+    Ok(page_count * page_size)
+}
+
+/// Parse a stored setting. Values are JSON; older rows hold bare strings
+/// ("system", "22:00", a path), and an empty string meant "unset".
+fn setting_value(raw: &str, default: &serde_json::Value) -> serde_json::Value {
+    if raw.is_empty() && default.is_null() {
+        return serde_json::Value::Null;
+    }
+    match serde_json::from_str::<serde_json::Value>(raw) {
+        // A bare "30" is a number, unless the field holds text
+        Ok(value) if value.is_string() || !default.is_string() => value,
+        _ => serde_json::Value::String(raw.to_string()),
+    }
+}
+
+fn state_from_row(row: &Row) -> Result<TaskState> {
+    Ok(TaskState {
+        task_id: row.get(0)?,
+        last_run_date_local: row.get(1)?,
+        last_run_at_utc: row.get::<_, Option<String>>(2)?
+            .and_then(|s| s.parse().ok()),
+        last_result: row.get::<_, Option<String>>(3)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
+        last_error: row.get(4)?,
+        next_run_at_utc: row.get::<_, Option<String>>(5)?
+            .and_then(|s| s.parse().ok()),
+        snoozed_until_utc: row.get::<_, Option<String>>(6)?
+            .and_then(|s| s.parse().ok()),
+        trigger_states: row.get::<_, Option<String>>(7)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+    })
+}
+
+/// Read a template row; None if its stored task no longer deserializes
+fn template_from_row(row: &Row) -> Result<Option<TaskTemplate>> {
+    let task = match serde_json::from_str(&row.get::<_, String>(3)?) {
+        Ok(task) => task,
+        Err(e) => {
+            tracing::warn!("Skipping unreadable template {}: {}", row.get::<_, String>(0)?, e);
+            return Ok(None);
+        }
+    };
+    
+    Ok(Some(TaskTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        task,
+        created_at_utc: row.get::<_, String>(4)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+    }))
+}
+
+/// Turn free text into an FTS5 query matching all of its words, so characters
+/// like `-` or `:` in an error message aren't parsed as query syntax
+fn fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Values for the placeholders of LOG_FILTER_WHERE
+fn log_filter_params(filter: &LogFilter) -> [Option<String>; 4] {
+    [
+        filter.task_id.clone(),
+        filter.status.as_ref().map(|s| serde_json::to_string(s).unwrap()),
+        filter.since_utc.map(|t| t.to_rfc3339()),
+        filter.until_utc.map(|t| t.to_rfc3339()),
+    ]
+}
+
+/// Insert one run log row, or replace the outcome of a run logged before (e.g. a launched
+/// process later killed for running too long)
+fn write_log(conn: &Connection, log: &RunLog) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO run_logs (run_id, task_id, task_name, trigger_type, scheduled_time_utc,
+            started_at_utc, finished_at_utc, status, skip_reason, exit_code, error_message, output, overrides, duration_ms, error)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+         ON CONFLICT(run_id) DO UPDATE SET
+            finished_at_utc = excluded.finished_at_utc,
+            status = excluded.status,
+            skip_reason = excluded.skip_reason,
+            exit_code = excluded.exit_code,
+            error_message = excluded.error_message,
+            output = excluded.output,
+            duration_ms = excluded.duration_ms,
+            error = excluded.error",
+    )?.execute(params![
+        log.run_id,
+        log.task_id,
+        log.task_name,
+        log.trigger_type,
+        log.scheduled_time_utc.map(|t| t.to_rfc3339()),
+        log.started_at_utc.to_rfc3339(),
+        log.finished_at_utc.map(|t| t.to_rfc3339()),
+        serde_json::to_string(&log.status).unwrap(),
+        log.skip_reason.as_ref().map(|r| serde_json::to_string(r).unwrap()),
+        log.exit_code,
+        log.error_message,
+        log.output,
+        log.overrides.as_ref().map(|o| serde_json::to_string(o).unwrap()),
+        log.duration_ms,
+        log.error.as_ref().map(|e| serde_json::to_string(e).unwrap()),
+    ])?;
+    Ok(())
+}
+
+fn log_from_row(row: &Row) -> Result<RunLog> {
+    Ok(RunLog {
+        run_id: row.get(0)?,
+        task_id: row.get(1)?,
+        task_name: row.get(2)?,
+        trigger_type: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+        scheduled_time_utc: row.get::<_, Option<String>>(4)?
+            .and_then(|s| s.parse().ok()),
+        started_at_utc: row.get::<_, String>(5)?.parse().unwrap_or_else(|_| chrono::Utc::now()),
+        finished_at_utc: row.get::<_, Option<String>>(6)?
+            .and_then(|s| s.parse().ok()),
+        status: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or(RunStatus::Failed),
+        skip_reason: row.get::<_, Option<String>>(8)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
+        exit_code: row.get(9)?,
+        error_message: row.get(10)?,
+        output: row.get(11)?,
+        overrides: row.get::<_, Option<String>>(12)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
+        duration_ms: row.get(13)?,
+        error: row.get::<_, Option<String>>(14)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_migrations_upgrade_legacy_database() {
+        let path = std::env::temp_dir().join(format!("routine-runner-{}.db", uuid::Uuid::new_v4()));
+        {
+            // A database from before versioned migrations, with one ad-hoc column already added
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE run_logs (run_id TEXT PRIMARY KEY, task_id TEXT NOT NULL, task_name TEXT NOT NULL,
+                    trigger_type TEXT, scheduled_time_utc TEXT, started_at_utc TEXT NOT NULL, finished_at_utc TEXT,
+                    status TEXT NOT NULL, skip_reason TEXT, exit_code INTEGER, error_message TEXT, output TEXT);"
+            ).unwrap();
+        }
+        
+        let db = Database::open(&path).unwrap();
+        assert!(db.get_logs(10).unwrap().is_empty());
+        assert!(db.get_all_tasks().unwrap().is_empty());
+        drop(db);
+        
+        // Reopening applies nothing new
+        let db = Database::open(&path).unwrap();
+        let version: u32 = db.conn.lock().unwrap()
+            .query_row("SELECT MAX(version) FROM schema_migrations", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.last().unwrap().version);
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+    
+    #[test]
+    fn test_settings_read_legacy_values() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        {
+            // Rows as written by the old per-key code: bare strings, "" for None
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(
+                "INSERT INTO settings (key, value) VALUES
+                    ('timezone_id', 'Asia/Ho_Chi_Minh'), ('log_retention_days', '14'),
+                    ('quiet_hours_start', '23:30'), ('quiet_hours_action', '\"skip\"'),
+                    ('task_file_path', ''), ('max_parallel_runs', 'lots');"
+            ).unwrap();
+        }
+        
+        let settings = db.get_settings().unwrap();
+        assert_eq!(settings.quiet_hours_action, QuietHoursAction::Skip);
+        assert_eq!(settings.timezone_id, "Asia/Ho_Chi_Minh");
+        assert_eq!(settings.log_retention_days, 14);
+        assert_eq!(settings.quiet_hours_start, "23:30");
+        assert_eq!(settings.task_file_path, None);
+        assert_eq!(settings.max_parallel_runs, Settings::default().max_parallel_runs);
+        
+        db.save_settings(&Settings { task_file_path: Some("C:\\tasks.yaml".to_string()), ..settings }).unwrap();
+        let settings = db.get_settings().unwrap();
+        assert_eq!(settings.timezone_id, "Asia/Ho_Chi_Minh");
+        assert_eq!(settings.task_file_path.as_deref(), Some("C:\\tasks.yaml"));
+    }
+    
+    #[test]
+    fn test_read_only_open_serves_logs() {
+        let path = std::env::temp_dir().join(format!("routine-runner-{}.db", uuid::Uuid::new_v4()));
+        drop(Database::open(&path).unwrap());
+        
+        let db = Database::open_read_only(&path).unwrap();
+        assert!(db.get_logs(10).unwrap().is_empty());
+        assert!(db.get_all_tasks().unwrap().is_empty());
+        assert!(db.insert_task(&Task::default()).is_err());
+        drop(db);
+        let _ = std::fs::remove_file(&path);
+    }
+    
+    #[test]
+    fn test_search_logs_matches_output() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let log = RunLog {
+            run_id: "run-1".to_string(),
+            task_id: "task-1".to_string(),
+            task_name: "Backup photos".to_string(),
+            trigger_type: "Manual".to_string(),
+            scheduled_time_utc: None,
+            started_at_utc: chrono::Utc::now(),
+            finished_at_utc: None,
+            status: RunStatus::Failed,
+            skip_reason: None,
+            exit_code: Some(2),
+            error_message: None,
+            error: None,
+            output: Some("rsync: connection-refused on host nas".to_string()),
+            overrides: None,
+            duration_ms: Some(1200),
+        };
+        db.insert_log(&log).unwrap();
+        
+        assert_eq!(db.search_logs("connection-refused", 10).unwrap().len(), 1);
+        assert_eq!(db.search_logs("photos", 10).unwrap().len(), 1);
+        assert!(db.search_logs("timeout", 10).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_task_history_records_changes() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let task = Task { id: "task-1".to_string(), name: "Backup".to_string(), ..Task::default() };
+        db.insert_task(&task).unwrap();
+        db.update_task(&Task { name: "Nightly backup".to_string(), ..task.clone() }).unwrap();
+        // Saving without changes adds nothing
+        db.update_task(&Task { name: "Nightly backup".to_string(), ..task.clone() }).unwrap();
+        db.delete_task(&task.id).unwrap();
+        
+        let history = db.get_task_history(&task.id).unwrap();
+        let changes: Vec<TaskChange> = history.iter().map(|e| e.change).collect();
+        assert_eq!(changes, vec![TaskChange::Deleted, TaskChange::Updated, TaskChange::Created]);
+        assert_eq!(history[1].diff["name"]["old"], "Backup");
+        assert_eq!(history[1].diff["name"]["new"], "Nightly backup");
+        assert_eq!(history[1].snapshot.name, "Nightly backup");
+    }
+    
+    #[test]
+    fn test_purge_honors_task_retention() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let keep = Task { id: "nightly".to_string(), log_retention_days: Some(365), ..Task::default() };
+        let short = Task { id: "pinger".to_string(), log_retention_days: Some(2), ..Task::default() };
+        db.insert_task(&keep).unwrap();
+        db.insert_task(&short).unwrap();
+        
+        let ten_days_ago = chrono::Utc::now() - chrono::Duration::days(10);
+        for (run_id, task_id) in [("a", "nightly"), ("b", "pinger"), ("c", "other")] {
+            db.queue_log(&RunLog {
+                run_id: run_id.to_string(),
+                task_id: task_id.to_string(),
+                task_name: task_id.to_string(),
+                trigger_type: "Manual".to_string(),
+                scheduled_time_utc: None,
+                started_at_utc: ten_days_ago,
+                finished_at_utc: None,
+                status: RunStatus::Success,
+                skip_reason: None,
+                exit_code: Some(0),
+                error_message: None,
+                error: None,
+                output: None,
+                overrides: None,
+                duration_ms: None,
+            });
+        }
+        
+        // Buffered logs are written before the purge reads them; the global default (30 days) keeps "other"; only the pinger's 2 days expire
+        assert_eq!(db.purge_expired_logs(30).unwrap(), 1);
+        // 0 keeps logs of tasks without an override; nightly keeps its own year
+        assert_eq!(db.purge_expired_logs(0).unwrap(), 0);
+        assert_eq!(db.purge_expired_logs(5).unwrap(), 1);
+        assert_eq!(db.get_logs(10).unwrap()[0].task_id, "nightly");
+    }
+    
+    #[test]
+    fn test_corrupt_database_is_moved_aside() {
+        let path = std::env::temp_dir().join(format!("routine-runner-{}.db", uuid::Uuid::new_v4()));
+        std::fs::write(&path, vec![0x42u8; 8192]).unwrap();
+        
+        let (db, moved_to) = Database::open_or_recover(&path).unwrap();
+        let moved_to = moved_to.expect("corrupt file moved aside");
+        assert!(moved_to.exists());
+        assert!(db.integrity_check().unwrap().is_empty());
+        db.insert_task(&Task::default()).unwrap();
+        drop(db);
+        
+        let (_, moved_again) = Database::open_or_recover(&path).unwrap();
+        assert!(moved_again.is_none());
+        for file in [path.clone(), moved_to] {
+            let _ = std::fs::remove_file(&file);
+            let _ = std::fs::remove_file(format!("{}-wal", file.display()));
+            let _ = std::fs::remove_file(format!("{}-shm", file.display()));
+        }
+    }
+    
+    #[test]
+    fn test_search_tasks_matches_fields() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        db.insert_task(&Task {
+            name: "Timesheet".to_string(),
+            path_or_url: "C:\\Tools\\report_100%.exe".to_string(),
+            ..Task::default()
+        }).unwrap();
+        db.insert_task(&Task { name: "Backup".to_string(), args: Some("--dest nas".to_string()), ..Task::default() }).unwrap();
+        
+        assert_eq!(db.search_tasks("TIMESHEET").unwrap().len(), 1);
+        assert_eq!(db.search_tasks("tools\\report_").unwrap().len(), 1);
+        assert_eq!(db.search_tasks("100%").unwrap().len(), 1);
+        assert_eq!(db.search_tasks("nas").unwrap()[0].name, "Backup");
+        assert_eq!(db.search_tasks("_").unwrap().len(), 1);
+    }
+    
+    #[test]
+    fn test_search_tasks_ranks_name_matches_first() {
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        for (name, args) in [("Archive", Some("--backup")), ("Nightly backup", None), ("Backup photos", None)] {
+            db.insert_task(&Task { name: name.to_string(), args: args.map(String::from), ..Task::default() }).unwrap();
+        }
+        
+        let names: Vec<String> = db.search_tasks("backup").unwrap().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["Backup photos", "Nightly backup", "Archive"]);
+    }
+    
+    #[test]
+    fn test_import_clears_secrets_left_out_of_export() {
+        let task = Task {
+            id: "task".to_string(),
+            name: "Deploy".to_string(),
+            args: Some("--token abc".to_string()),
+            args_secret: true,
+            env: vec![
+                EnvVar { name: "API_KEY".to_string(), value: "abc".to_string(), secret: true },
+                EnvVar { name: "MODE".to_string(), value: "prod".to_string(), secret: false },
+            ],
+            ..Task::default()
+        };
+        let bundle = ExportBundle {
+            format_version: ExportBundle::FORMAT_VERSION,
+            exported_at_utc: chrono::Utc::now(),
+            tasks: vec![crate::secrets::mask_task(task)],
+            settings: None,
+        };
+        let bundle: ExportBundle = serde_json::from_str(&serde_json::to_string(&bundle).unwrap()).unwrap();
+        
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let summary = db.import_tasks(&bundle.tasks, ImportCollision::Skip).unwrap();
+        assert_eq!(summary.created, 1);
+        assert_eq!(summary.missing_secrets, ["Deploy"]);
+        
+        let imported = db.get_task("task").unwrap().unwrap();
+        assert_eq!(imported.args, None);
+        assert_eq!(imported.env[0].value, "");
+        assert_eq!(imported.env[1].value, "prod");
+    }
+    
+    #[test]
+    fn test_import_clears_secrets_sealed_elsewhere() {
+        let task = Task {
+            id: "task".to_string(),
+            name: "Deploy".to_string(),
+            args: Some("dpapi:0badc0de".to_string()),
+            args_secret: true,
+            ..Task::default()
+        };
+        
+        let db = Database::open(Path::new(":memory:")).unwrap();
+        let summary = db.import_tasks(&[task], ImportCollision::Skip).unwrap();
+        assert_eq!(summary.missing_secrets, ["Deploy"]);
+        assert_eq!(db.get_task("task").unwrap().unwrap().args, None);
+    }
+}
@@ -0,0 +1,204 @@
+//! Task file module - Declarative tasks from a YAML/TOML file, reconciled into the database
+
+use crate::models::*;
+use crate::storage::Database;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TaskFileError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Invalid task file: {0}")]
+    Invalid(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+impl From<crate::storage::QueryPanicked> for TaskFileError {
+    fn from(e: crate::storage::QueryPanicked) -> Self {
+        TaskFileError::Database(e.into())
+    }
+}
+
+/// What a reconcile changed in the database
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ReconcileSummary {
+    pub created: u32,
+    pub updated: u32,
+    pub removed: u32,
+    pub unchanged: u32,
+}
+
+/// Top-level layout of the file: `tasks:` (YAML) or `[[tasks]]` (TOML)
+#[derive(serde::Deserialize)]
+struct TaskFileContent {
+    #[serde(default)]
+    tasks: Vec<serde_json::Value>,
+}
+
+/// Parse the task file. Each entry needs an `id` (the stable key used to match it
+/// against the database) and a `name`; omitted fields take the usual task defaults.
+pub fn parse_task_file(path: &Path) -> Result<Vec<Task>, TaskFileError> {
+    let text = std::fs::read_to_string(path)?;
+    let extension = path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let content: TaskFileContent = match extension.as_str() {
+        "yaml" | "yml" => serde_yaml::from_str(&text)?,
+        "toml" => toml::from_str(&text)?,
+        _ => return Err(TaskFileError::Invalid("expected a .yaml, .yml or .toml file".to_string())),
+    };
+
+    let mut ids = HashSet::new();
+    let mut tasks = Vec::new();
+    for entry in content.tasks {
+        let task = task_from_entry(entry)?;
+        if !ids.insert(task.id.clone()) {
+            return Err(TaskFileError::Invalid(format!("duplicate task id '{}'", task.id)));
+        }
+        tasks.push(task);
+    }
+
+    Ok(tasks)
+}
+
+/// Build a task from a file entry, filling in defaults for omitted fields
+fn task_from_entry(entry: serde_json::Value) -> Result<Task, TaskFileError> {
+    let serde_json::Value::Object(fields) = entry else {
+        return Err(TaskFileError::Invalid("each task must be a table/mapping".to_string()));
+    };
+    if !fields.get("id").is_some_and(|v| v.is_string()) {
+        return Err(TaskFileError::Invalid("every task needs a string 'id'".to_string()));
+    }
+
+    let mut merged = serde_json::to_value(Task::default()).expect("task serializes");
+    if let serde_json::Value::Object(defaults) = &mut merged {
+        defaults.extend(fields);
+    }
+
+    let mut task: Task = serde_json::from_value(merged)
+        .map_err(|e| TaskFileError::Invalid(e.to_string()))?;
+    task.managed_by_file = true;
+    Ok(task)
+}
+
+/// Make the database match the file: create/update its tasks and remove
+/// file-managed tasks that are no longer listed
+pub fn reconcile(db: &Database, tasks: &[Task]) -> Result<ReconcileSummary, TaskFileError> {
+    let mut summary = ReconcileSummary::default();
+    let existing = db.get_all_tasks()?;
+
+    for task in tasks {
+        match existing.iter().find(|t| t.id == task.id) {
+            Some(current) => {
+                // Timestamps and runtime-only fields aren't part of the file
+                let task = Task {
+                    created_at_utc: current.created_at_utc,
+                    updated_at_utc: current.updated_at_utc,
+                    paused_until_utc: current.paused_until_utc,
+                    group_id: current.group_id.clone(),
+                    ..task.clone()
+                };
+                if serde_json::to_value(&task).ok() == serde_json::to_value(current).ok() {
+                    summary.unchanged += 1;
+                } else {
+                    db.update_task(&task)?;
+                    summary.updated += 1;
+                }
+            }
+            None => {
+                db.insert_task(task)?;
+                summary.created += 1;
+            }
+        }
+    }
+
+    let listed: HashSet<&str> = tasks.iter().map(|t| t.id.as_str()).collect();
+    for task in existing.iter().filter(|t| t.managed_by_file && !listed.contains(t.id.as_str())) {
+        db.delete_task(&task.id)?;
+        summary.removed += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Parse and reconcile the file in one go
+pub fn sync_task_file(db: &Database, path: &Path) -> Result<ReconcileSummary, TaskFileError> {
+    let tasks = parse_task_file(path)?;
+    let summary = reconcile(db, &tasks)?;
+    tracing::info!("Synced task file {:?}: {:?}", path, summary);
+    Ok(summary)
+}
+
+/// How often the watcher checks the file for changes
+const POLL_SECS: u64 = 2;
+
+/// Watch the configured task file and re-sync whenever it (or the setting) changes.
+/// `on_change` runs after every sync that touched the database.
+pub async fn watch(db: Arc<Database>, on_change: impl Fn()) {
+    let mut last_seen: Option<(String, Option<SystemTime>)> = None;
+
+    loop {
+        let path = db.get_settings().ok().and_then(|s| s.task_file_path);
+        let seen = path.map(|p| {
+            let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok();
+            (p, modified)
+        });
+
+        if seen != last_seen {
+            if let Some((path, _)) = &seen {
+                let file = path.clone();
+                match db.run(move |db| sync_task_file(db, Path::new(&file))).await {
+                    Ok(summary) if summary.created + summary.updated + summary.removed > 0 => on_change(),
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("Failed to sync task file {}: {}", path, e),
+                }
+            }
+            last_seen = seen;
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(POLL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_entry_fills_defaults() {
+        let content: TaskFileContent = serde_yaml::from_str(r#"
+tasks:
+  - id: timesheet
+    name: Open Timesheet
+    target_type: url
+    path_or_url: https://example.com/timesheet
+    triggers:
+      - type: daily_at
+        enabled: true
+        time_local: "09:00"
+        days_of_week: null
+"#).unwrap();
+
+        let task = task_from_entry(content.tasks.into_iter().next().unwrap()).unwrap();
+        assert_eq!(task.id, "timesheet");
+        assert!(task.managed_by_file);
+        assert!(task.enabled);
+        assert_eq!(task.triggers.len(), 1);
+        assert!(task_from_entry(serde_json::json!({ "name": "no id" })).is_err());
+    }
+}
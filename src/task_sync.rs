@@ -0,0 +1,225 @@
+//! Task sync module - Share one task set between machines through a file in a synced folder
+//!
+//! Each side remembers the versions (`updated_at_utc`, or the trash time) it last agreed on.
+//! A task changed on one side only is copied to the other; changed on both is a conflict,
+//! resolved in favour of the newer change. Secret values stay on each machine.
+
+use crate::models::*;
+use crate::storage::Database;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Name of the shared file inside the sync folder
+pub const SYNC_FILE: &str = "routine-runner-tasks.json";
+
+/// How often the shared file is synced
+const POLL_SECS: u64 = 30;
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid sync file: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+impl From<crate::storage::QueryPanicked> for SyncError {
+    fn from(e: crate::storage::QueryPanicked) -> Self {
+        SyncError::Database(e.into())
+    }
+}
+
+/// What a sync changed
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SyncSummary {
+    /// Local changes written to the shared file
+    pub pushed: u32,
+    /// Changes from the shared file applied locally
+    pub pulled: u32,
+    /// Names of tasks changed on both sides; the newer change was kept
+    pub conflicts: Vec<String>,
+}
+
+/// When a task last changed, counting a move to the trash
+fn version(task: &Task) -> DateTime<Utc> {
+    task.deleted_at_utc.map_or(task.updated_at_utc, |deleted| deleted.max(task.updated_at_utc))
+}
+
+fn version_key(task: Option<&Task>) -> String {
+    task.map(|t| version(t).to_rfc3339()).unwrap_or_default()
+}
+
+/// The task as written to the shared file
+fn shared_copy(task: &Task) -> Task {
+    crate::secrets::mask_task(task.clone())
+}
+
+/// Whether both sides hold the same definition, apart from timestamps and local-only fields
+fn same_definition(local: &Task, remote: &Task) -> bool {
+    if local.deleted_at_utc.is_some() != remote.deleted_at_utc.is_some() {
+        return false;
+    }
+    let local = Task {
+        created_at_utc: remote.created_at_utc,
+        updated_at_utc: remote.updated_at_utc,
+        group_id: remote.group_id.clone(),
+        deleted_at_utc: remote.deleted_at_utc,
+        ..shared_copy(local)
+    };
+    serde_json::to_value(&local).ok() == serde_json::to_value(remote).ok()
+}
+
+/// Round-trip the task set through the shared file in `folder`
+pub fn sync_folder(db: &Database, folder: &Path) -> Result<SyncSummary, SyncError> {
+    let path = folder.join(SYNC_FILE);
+    let mut remote: BTreeMap<String, Task> = match std::fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str::<ExportBundle>(&text)?
+            .tasks
+            .into_iter()
+            .map(|t| (t.id.clone(), t))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    // Tasks from the task file have their own source of truth
+    let mut local = HashMap::new();
+    let mut file_managed = HashSet::new();
+    for task in db.get_all_tasks()?.into_iter().chain(db.get_deleted_tasks()?) {
+        if task.managed_by_file {
+            file_managed.insert(task.id.clone());
+        } else {
+            local.insert(task.id.clone(), task);
+        }
+    }
+    let base = db.get_sync_versions()?;
+
+    let mut summary = SyncSummary::default();
+    let mut agreed = Vec::new();
+    let mut file_changed = false;
+    let ids: BTreeSet<String> = local.keys().chain(remote.keys()).cloned().collect();
+    for id in ids.into_iter().filter(|id| !file_managed.contains(id)) {
+        let (local_task, remote_task) = (local.get(&id), remote.get(&id));
+        let (local_key, remote_key) = (version_key(local_task), version_key(remote_task));
+        let (base_local, base_remote) = base.get(&id).cloned().unwrap_or_default();
+        let local_changed = local_key != base_local;
+        let remote_changed = remote_key != base_remote;
+        if !local_changed && !remote_changed {
+            continue;
+        }
+
+        let push = match (local_task, remote_task) {
+            (Some(_), None) => true,
+            // Purged here after it was synced: leave it gone
+            (None, Some(_)) if !base_local.is_empty() => {
+                agreed.push((id, local_key, remote_key));
+                continue;
+            }
+            (None, Some(_)) => false,
+            (Some(l), Some(r)) if same_definition(l, r) => {
+                agreed.push((id, local_key, remote_key));
+                continue;
+            }
+            (Some(l), Some(r)) => match (local_changed, remote_changed) {
+                (true, false) => true,
+                (false, true) => false,
+                _ => {
+                    tracing::warn!("Task {} changed here and in the sync file; keeping the newer change", l.name);
+                    summary.conflicts.push(l.name.clone());
+                    version(l) >= version(r)
+                }
+            },
+            (None, None) => continue,
+        };
+
+        if push {
+            let task = local_task.map(shared_copy).expect("pushed task exists locally");
+            remote.insert(id.clone(), task);
+            file_changed = true;
+            summary.pushed += 1;
+            agreed.push((id, local_key.clone(), local_key));
+        } else {
+            let task = remote_task.expect("pulled task exists in the file");
+            apply_remote(db, task, local_task)?;
+            summary.pulled += 1;
+            let applied = db.get_task(&id)?
+                .or_else(|| db.get_deleted_tasks().ok()?.into_iter().find(|t| t.id == id));
+            agreed.push((id, version_key(applied.as_ref()), remote_key));
+        }
+    }
+
+    // Record the agreed versions only once the file holds the pushed changes
+    if file_changed {
+        let bundle = ExportBundle {
+            format_version: ExportBundle::FORMAT_VERSION,
+            exported_at_utc: Utc::now(),
+            tasks: remote.into_values().collect(),
+            settings: None,
+        };
+        let temp = folder.join(format!("{}.tmp", SYNC_FILE));
+        std::fs::write(&temp, serde_json::to_string_pretty(&bundle)?)?;
+        std::fs::rename(&temp, &path)?;
+    }
+    for (id, local_version, remote_version) in agreed {
+        db.set_sync_versions(&id, &local_version, &remote_version)?;
+    }
+
+    Ok(summary)
+}
+
+/// Make the local task match the shared file's version, keeping local secrets and group
+fn apply_remote(db: &Database, remote: &Task, local: Option<&Task>) -> Result<(), SyncError> {
+    let mut task = Task { managed_by_file: false, group_id: None, deleted_at_utc: None, ..remote.clone() };
+    if let Some(local) = local {
+        crate::secrets::keep_masked(&mut task, local);
+        task.group_id = local.group_id.clone();
+    }
+    crate::secrets::clear_unusable(&mut task);
+
+    match (local, remote.deleted_at_utc.is_some()) {
+        (None, true) => {}
+        (None, false) => db.insert_task(&task)?,
+        (Some(local), true) => {
+            if local.deleted_at_utc.is_none() {
+                db.delete_task(&task.id)?;
+            }
+        }
+        (Some(local), false) => {
+            if local.deleted_at_utc.is_some() {
+                db.restore_task(&task.id)?;
+            }
+            db.update_task(&task)?;
+        }
+    }
+    Ok(())
+}
+
+/// Sync with the configured folder every little while. `on_change` runs after
+/// every sync that changed local tasks.
+pub async fn sync_loop(db: Arc<Database>, on_change: impl Fn()) {
+    loop {
+        let folder = db.get_settings().ok().and_then(|s| s.sync_folder).filter(|f| !f.trim().is_empty());
+        if let Some(folder) = folder {
+            let dir = folder.clone();
+            match db.run(move |db| sync_folder(db, Path::new(&dir))).await {
+                Ok(summary) => {
+                    if summary.pushed + summary.pulled > 0 {
+                        tracing::info!("Synced tasks with {}: {:?}", folder, summary);
+                    }
+                    if summary.pulled > 0 {
+                        on_change();
+                    }
+                }
+                Err(e) => tracing::error!("Failed to sync tasks with {}: {}", folder, e),
+            }
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(POLL_SECS)).await;
+    }
+}
@@ -0,0 +1,238 @@
+//! Updater module - Find new releases and install them, on request or at a quiet time
+//!
+//! The manifest follows Tauri's latest.json:
+//! `{ "version": "1.2.0", "notes": "...", "platforms": { "windows-x86_64": { "url": "...", "signature": "..." } } }`.
+//! The signature is a minisign signature of the installer, as made by `tauri signer sign`. It is
+//! checked against the public key built into the app, since the manifest itself is not signed.
+//! The installer runs silently and starts the app again when it is done.
+
+use crate::scheduler_runner::SchedulerRunner;
+use crate::storage::Database;
+use chrono::{DateTime, Local, NaiveTime, Utc};
+use base64::Engine;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Settings key recording when updates were last checked for
+const LAST_CHECK_KEY: &str = "last_update_check_utc";
+
+/// Hours between automatic checks
+const CHECK_INTERVAL_HOURS: i64 = 24;
+
+/// How often the job wakes to check or install
+const LOOP_INTERVAL_SECS: u64 = 15 * 60;
+
+/// How long after `install_at` an automatic install may still start
+const INSTALL_WINDOW_MINUTES: i64 = 60;
+
+/// Public key that release installers are signed with (the base64 key file from
+/// `tauri signer generate`), set when building a release
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("ROUTINE_RUNNER_UPDATE_PUBKEY");
+
+/// Release manifest
+#[derive(Debug, serde::Deserialize)]
+struct Manifest {
+    version: String,
+    #[serde(default)]
+    notes: Option<String>,
+    #[serde(default)]
+    platforms: HashMap<String, PlatformRelease>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct PlatformRelease {
+    url: String,
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+/// Result of a check
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UpdateInfo {
+    pub current_version: String,
+    pub latest_version: String,
+    /// The latest version is newer and has an installer for this machine
+    pub available: bool,
+    pub notes: Option<String>,
+    #[serde(skip)]
+    installer_url: Option<String>,
+    #[serde(skip)]
+    installer_signature: Option<String>,
+}
+
+/// Version components, ignoring a leading "v" and any pre-release or build suffix
+fn version_parts(version: &str) -> Vec<u64> {
+    version.trim().trim_start_matches('v')
+        .split(['-', '+']).next().unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Whether `latest` is a newer version than `current`
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    let (mut latest, mut current) = (version_parts(latest), version_parts(current));
+    let len = latest.len().max(current.len());
+    latest.resize(len, 0);
+    current.resize(len, 0);
+    latest > current
+}
+
+/// Fetch the manifest and compare it with the running version. Blocks until done.
+pub fn check(manifest_url: &str) -> Result<UpdateInfo, String> {
+    let body = crate::http::get(manifest_url).map_err(|e| format!("Failed to fetch the update manifest: {}", e))?;
+    let manifest: Manifest = serde_json::from_str(&body).map_err(|e| format!("Invalid update manifest: {}", e))?;
+    let current_version = env!("CARGO_PKG_VERSION").to_string();
+    let release = manifest.platforms.get(&format!("windows-{}", std::env::consts::ARCH));
+
+    Ok(UpdateInfo {
+        available: release.is_some() && is_newer(&manifest.version, &current_version),
+        current_version,
+        latest_version: manifest.version,
+        notes: manifest.notes,
+        installer_url: release.map(|r| r.url.clone()),
+        installer_signature: release.and_then(|r| r.signature.clone()),
+    })
+}
+
+/// Check and remember when, so the daily check counts from here
+pub fn check_and_record(db: &Database, manifest_url: &str) -> Result<UpdateInfo, String> {
+    let info = check(manifest_url)?;
+    db.set_setting(LAST_CHECK_KEY, Some(&Utc::now())).map_err(|e| e.to_string())?;
+    if info.available {
+        tracing::info!("Routine Runner {} is available (running {})", info.latest_version, info.current_version);
+    }
+    Ok(info)
+}
+
+/// Download the installer, verify it and start it. The app has to exit right after,
+/// so the installer can replace it. Blocks until the installer has started.
+pub fn install(info: &UpdateInfo) -> Result<(), String> {
+    if !info.available {
+        return Err("No update available".to_string());
+    }
+    let url = info.installer_url.as_deref().ok_or("No installer for this machine")?;
+    let public_key = UPDATE_PUBLIC_KEY.ok_or("This build has no key to verify updates with")?;
+    let signature = info.installer_signature.as_deref().ok_or("The update manifest has no signature for the installer")?;
+
+    let path = std::env::temp_dir().join(format!("routine-runner-{}-setup.exe", info.latest_version));
+    crate::http::download(url, &path).map_err(|e| format!("Failed to download the update: {}", e))?;
+    let verified = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|installer| verify_signature(&installer, signature, public_key));
+    if let Err(e) = verified {
+        let _ = std::fs::remove_file(&path);
+        return Err(format!("The downloaded update failed verification: {}", e));
+    }
+
+    tracing::info!("Installing Routine Runner {}", info.latest_version);
+    // /S installs silently, /R starts the app again afterwards
+    std::process::Command::new(&path)
+        .args(["/S", "/R"])
+        .spawn()
+        .map_err(|e| format!("Failed to start the installer: {}", e))?;
+    Ok(())
+}
+
+/// Check a minisign signature of `data` against a public key, both base64 encoded
+/// the way `tauri signer` writes them
+fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> Result<(), String> {
+    let decode = |value: &str| {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(value.trim()).map_err(|e| e.to_string())?;
+        String::from_utf8(bytes).map_err(|e| e.to_string())
+    };
+    let public_key = minisign_verify::PublicKey::decode(&decode(public_key)?)
+        .map_err(|e| format!("Invalid update public key: {}", e))?;
+    let signature = minisign_verify::Signature::decode(&decode(signature)?)
+        .map_err(|e| format!("Invalid signature: {}", e))?;
+    public_key.verify(data, &signature, false).map_err(|e| format!("Bad signature: {}", e))
+}
+
+/// Whether it is between `install_at` and the end of the install window
+fn in_install_window(install_at: &str, now: DateTime<Local>) -> bool {
+    let Ok(start) = NaiveTime::parse_from_str(install_at, "%H:%M") else { return false };
+    let minutes = (now.time() - start).num_minutes().rem_euclid(24 * 60);
+    minutes < INSTALL_WINDOW_MINUTES
+}
+
+/// Background job: check daily while enabled, and install at the quiet time if allowed.
+/// `exit` is called once the installer has started.
+pub async fn update_loop(db: Arc<Database>, scheduler: Arc<SchedulerRunner>, exit: impl Fn() + Send + 'static) {
+    let mut available: Option<UpdateInfo> = None;
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(LOOP_INTERVAL_SECS)).await;
+
+        let settings = match db.get_settings() {
+            Ok(settings) => settings.updates,
+            Err(e) => {
+                tracing::error!("Failed to read settings for updates: {}", e);
+                continue;
+            }
+        };
+        if !settings.check_enabled {
+            continue;
+        }
+
+        let last: Option<DateTime<Utc>> = db.get_setting(LAST_CHECK_KEY).unwrap_or_default();
+        if last.is_none_or(|last| Utc::now() - last >= chrono::Duration::hours(CHECK_INTERVAL_HOURS)) {
+            let url = settings.manifest_url.clone();
+            match db.run(move |db| check_and_record(db, &url)).await {
+                Ok(info) => available = info.available.then_some(info),
+                Err(e) => tracing::warn!("Update check failed: {}", e),
+            }
+        }
+
+        let Some(info) = available.as_ref() else { continue };
+        if !settings.auto_install || !in_install_window(&settings.install_at, Local::now()) {
+            continue;
+        }
+        if !scheduler.status().await.running.is_empty() {
+            tracing::info!("Postponing update to {}: tasks are running", info.latest_version);
+            continue;
+        }
+        let info = info.clone();
+        match tokio::task::spawn_blocking(move || install(&info)).await {
+            Ok(Ok(())) => return exit(),
+            Ok(Err(e)) => tracing::error!("Automatic update failed: {}", e),
+            Err(e) => tracing::error!("Automatic update failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.0"));
+        assert!(is_newer("v1.0.1", "1.0.0"));
+        assert!(is_newer("1.10.0", "1.9.3"));
+        assert!(is_newer("1.0.0.1", "1.0.0"));
+        assert!(!is_newer("1.0", "1.0.0"));
+        assert!(!is_newer("1.0.0-beta", "1.0.0"));
+        assert!(!is_newer("0.9.9", "1.0.0"));
+    }
+
+    #[test]
+    fn test_in_install_window_wraps_midnight() {
+        let at = |h, m| Local.with_ymd_and_hms(2026, 3, 1, h, m, 0).unwrap();
+        assert!(in_install_window("03:00", at(3, 0)));
+        assert!(in_install_window("03:00", at(3, 59)));
+        assert!(!in_install_window("03:00", at(4, 0)));
+        assert!(!in_install_window("03:00", at(2, 59)));
+        assert!(in_install_window("23:30", at(0, 15)));
+        assert!(!in_install_window("bad", at(3, 0)));
+    }
+
+    #[test]
+    fn test_verify_signature_rejects_garbage() {
+        let encode = |text: &str| base64::engine::general_purpose::STANDARD.encode(text);
+        let public_key = encode("untrusted comment: minisign public key\nRWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n");
+        assert!(verify_signature(b"setup", "not base64!", &public_key).is_err());
+        assert!(verify_signature(b"setup", &encode("untrusted comment: nope\nAAAA\n"), &public_key).is_err());
+        assert!(verify_signature(b"setup", &public_key, "not base64!").is_err());
+    }
+}
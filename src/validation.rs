@@ -0,0 +1,208 @@
+//! Validation module - Check a task for problems before it is saved
+
+use crate::models::*;
+use chrono::NaiveTime;
+use serde::Serialize;
+use std::path::Path;
+
+/// Day names accepted in days_of_week
+const DAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Shortest webhook token accepted, so it can't be guessed
+const MIN_WEBHOOK_TOKEN_CHARS: usize = 16;
+
+/// How serious a validation finding is
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationLevel {
+    /// The task will not work as configured
+    Error,
+    /// The task will run, but probably not as intended
+    Warning,
+}
+
+/// One problem found in a task
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskWarning {
+    pub level: ValidationLevel,
+    /// Field the problem is in, e.g. "path_or_url" or "triggers[1].time_local"
+    pub field: String,
+    pub message: String,
+}
+
+/// Collects findings while checking a task
+#[derive(Default)]
+struct Findings(Vec<TaskWarning>);
+
+impl Findings {
+    fn error(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.push(ValidationLevel::Error, field.into(), message.into());
+    }
+
+    fn warning(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.push(ValidationLevel::Warning, field.into(), message.into());
+    }
+
+    fn push(&mut self, level: ValidationLevel, field: String, message: String) {
+        self.0.push(TaskWarning { level, field, message });
+    }
+}
+
+/// Check a task's target, arguments, triggers and conditions. An empty list means
+/// nothing looks wrong.
+pub fn validate_task(task: &Task) -> Vec<TaskWarning> {
+    let mut findings = Findings::default();
+
+    if task.name.trim().is_empty() {
+        findings.error("name", "Name is empty");
+    }
+    check_target(task, &mut findings);
+    check_args(task, &mut findings);
+    check_time(&mut findings, "must_finish_by_local", task.must_finish_by_local.as_deref());
+    if task.success_exit_codes.as_ref().is_some_and(|codes| codes.is_empty()) {
+        findings.warning("success_exit_codes", "No exit code counts as success, so every run will fail");
+    }
+
+    for (i, trigger) in task.triggers.iter().enumerate() {
+        check_trigger(trigger, &format!("triggers[{}]", i), &mut findings);
+    }
+    if task.enabled && !task.triggers.iter().any(Trigger::is_enabled) {
+        findings.warning("triggers", "No enabled trigger; the task only runs when started by hand");
+    }
+
+    for (i, condition) in task.conditions.iter().enumerate() {
+        check_condition(task, condition, &format!("conditions[{}]", i), &mut findings);
+    }
+
+    findings.0
+}
+
+fn check_target(task: &Task, findings: &mut Findings) {
+    let target = task.path_or_url.trim();
+    if target.is_empty() {
+        findings.error("path_or_url", "Path or URL is empty");
+        return;
+    }
+
+    if task.target_type == TargetType::Url {
+        if !target.contains("://") {
+            findings.warning("path_or_url", "URL has no scheme (e.g. https://)");
+        }
+        return;
+    }
+
+    let path = Path::new(target);
+    // A bare program name like "notepad.exe" is looked up on PATH at run time
+    let is_bare_name = task.target_type == TargetType::Exe && path.components().count() == 1;
+    if is_bare_name {
+        return;
+    }
+    if !path.exists() {
+        findings.error("path_or_url", format!("'{}' does not exist", target));
+    } else if task.target_type == TargetType::Folder && !path.is_dir() {
+        findings.error("path_or_url", format!("'{}' is not a folder", target));
+    } else if task.target_type != TargetType::Folder && path.is_dir() {
+        findings.error("path_or_url", format!("'{}' is a folder", target));
+    }
+
+    if let Some(dir) = task.working_dir.as_deref().filter(|d| !d.trim().is_empty()) {
+        if !Path::new(dir).is_dir() {
+            findings.warning("working_dir", format!("Working directory '{}' does not exist", dir));
+        }
+    }
+}
+
+fn check_args(task: &Task, findings: &mut Findings) {
+    let Some(args) = task.args.as_deref() else { return };
+    if task.target_type != TargetType::Exe && !args.trim().is_empty() {
+        findings.warning("args", "Arguments are only passed to exe targets");
+    }
+    // Masked secrets can't be inspected
+    if task.args_secret && args == crate::secrets::SECRET_MASK {
+        return;
+    }
+    if !args.matches('"').count().is_multiple_of(2) {
+        findings.error("args", "Unbalanced quote in arguments");
+    }
+}
+
+fn check_trigger(trigger: &Trigger, field: &str, findings: &mut Findings) {
+    match trigger {
+        Trigger::OnLogin { .. } => {}
+        Trigger::OncePerDay { earliest_time_local, days_of_week, .. } => {
+            check_time(findings, &format!("{}.earliest_time_local", field), earliest_time_local.as_deref());
+            check_days(findings, &format!("{}.days_of_week", field), days_of_week);
+        }
+        Trigger::DailyAt { time_local, days_of_week, .. } => {
+            check_time(findings, &format!("{}.time_local", field), Some(time_local));
+            check_days(findings, &format!("{}.days_of_week", field), days_of_week);
+        }
+        Trigger::Interval { every_seconds, jitter_seconds, .. } => {
+            if *every_seconds == 0 {
+                findings.error(format!("{}.every_seconds", field), "Interval must be at least 1 second");
+            } else if jitter_seconds.is_some_and(|j| j >= *every_seconds) {
+                findings.warning(
+                    format!("{}.jitter_seconds", field),
+                    "Jitter is as long as the interval, so runs may bunch up or be skipped",
+                );
+            }
+        }
+        Trigger::Webhook { token, .. } => {
+            if !is_strong_webhook_token(token) {
+                findings.error(
+                    format!("{}.token", field),
+                    format!("Token must be at least {} characters", MIN_WEBHOOK_TOKEN_CHARS),
+                );
+            }
+        }
+    }
+}
+
+/// Whether a webhook token is long enough to be accepted
+pub fn is_strong_webhook_token(token: &str) -> bool {
+    token.chars().count() >= MIN_WEBHOOK_TOKEN_CHARS
+}
+
+/// Refuse to save a task with a webhook trigger whose token is missing or too short,
+/// since anyone who can reach the server could fire it
+pub fn check_webhook_tokens(task: &Task) -> Result<(), String> {
+    let weak = task.triggers.iter().any(|trigger| matches!(trigger, Trigger::Webhook { token, .. } if !is_strong_webhook_token(token)));
+    if weak {
+        return Err(format!("Webhook tokens must be at least {} characters", MIN_WEBHOOK_TOKEN_CHARS));
+    }
+    Ok(())
+}
+
+/// A "HH:MM" local time, when set
+fn check_time(findings: &mut Findings, field: &str, time: Option<&str>) {
+    if let Some(time) = time {
+        if NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+            findings.error(field, format!("'{}' is not a time in HH:MM format", time));
+        }
+    }
+}
+
+fn check_days(findings: &mut Findings, field: &str, days: &Option<Vec<String>>) {
+    let Some(days) = days else { return };
+    if days.is_empty() {
+        findings.error(field, "No day of the week is selected, so the trigger never fires");
+    }
+    for day in days.iter().filter(|d| !DAY_NAMES.iter().any(|name| name.eq_ignore_ascii_case(d))) {
+        findings.error(field, format!("'{}' is not a day of the week (use {})", day, DAY_NAMES.join(", ")));
+    }
+}
+
+fn check_condition(task: &Task, condition: &Condition, field: &str, findings: &mut Findings) {
+    match condition {
+        Condition::ProcessNotRunning { process_name } if process_name.trim().is_empty() => {
+            findings.error(field, "Process name is empty");
+        }
+        Condition::IdleForSeconds { seconds: 0 } => {
+            findings.warning(field, "Idle for 0 seconds is always true");
+        }
+        Condition::OnlyIfPathExists if task.target_type == TargetType::Url => {
+            findings.warning(field, "URL targets have no path to check");
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,253 @@
+//! Webhook server module - Let other systems fire tasks with `POST /tasks/<id>/run`
+//!
+//! The caller proves it may run the task with `Authorization: Bearer <token>`, matching one of
+//! the task's enabled webhook triggers. Runs per trigger and failed attempts per client are
+//! limited, so a leaked URL can't hammer a task and tokens can't be guessed.
+
+use crate::models::*;
+use crate::scheduler_runner::SchedulerRunner;
+use crate::storage::Database;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+
+/// How often the settings are checked for the server being switched on, off or moved
+const SETTINGS_CHECK_SECS: u64 = 15;
+
+/// Requests larger than this are not read further
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// How long a client gets to send its request before the connection is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connections handled at once; more are closed right away
+const MAX_CONNECTIONS: usize = 32;
+
+/// Window the limits below count over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Runs one webhook trigger may start per window
+const RUNS_PER_WINDOW: usize = 6;
+
+/// Rejected tokens one client may send per window before it is turned away
+const FAILED_AUTH_PER_WINDOW: usize = 10;
+
+/// Recent requests, for rate limiting
+#[derive(Default)]
+struct Limits {
+    /// Run times per "task id:trigger index"
+    runs: HashMap<String, VecDeque<Instant>>,
+    /// Rejected token times per client address
+    failures: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+/// Drop entries older than the window and report whether another one fits
+fn has_room(times: &mut VecDeque<Instant>, limit: usize, now: Instant) -> bool {
+    while times.front().is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW) {
+        times.pop_front();
+    }
+    times.len() < limit
+}
+
+/// Compare without stopping at the first difference, so timing doesn't reveal the token
+fn tokens_match(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Listen while the server is enabled in settings, rebinding when the address or port changes
+pub async fn serve_loop(db: Arc<Database>, scheduler: Arc<SchedulerRunner>) {
+    let wanted_address = |db: &Database| match db.get_settings() {
+        Ok(settings) => settings.webhook_server_enabled
+            .then_some((settings.webhook_server_address, settings.webhook_server_port)),
+        Err(e) => {
+            tracing::error!("Failed to read settings for the webhook server: {}", e);
+            None
+        }
+    };
+    let limits = Arc::new(Mutex::new(Limits::default()));
+
+    loop {
+        let Some((address, port)) = wanted_address(&db) else {
+            tokio::time::sleep(tokio::time::Duration::from_secs(SETTINGS_CHECK_SECS)).await;
+            continue;
+        };
+        let listener = match TcpListener::bind((address.as_str(), port)).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Failed to start the webhook server on {}:{}: {}", address, port, e);
+                tokio::time::sleep(tokio::time::Duration::from_secs(SETTINGS_CHECK_SECS)).await;
+                continue;
+            }
+        };
+        tracing::info!("Accepting webhook triggers on http://{}:{}", address, port);
+
+        let current = Some((address.clone(), port));
+        let settings_changed = async {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(SETTINGS_CHECK_SECS)).await;
+                if wanted_address(&db) != current {
+                    break;
+                }
+            }
+        };
+        tokio::select! {
+            _ = accept_loop(&listener, &db, &scheduler, &limits) => {}
+            _ = settings_changed => tracing::info!("Stopped the webhook server on {}:{}", address, port),
+        }
+    }
+}
+
+async fn accept_loop(
+    listener: &TcpListener,
+    db: &Arc<Database>,
+    scheduler: &Arc<SchedulerRunner>,
+    limits: &Arc<Mutex<Limits>>,
+) {
+    let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let Ok(permit) = connections.clone().try_acquire_owned() else {
+                    tracing::warn!("Too many webhook connections, dropping one from {}", peer.ip());
+                    continue;
+                };
+                let (db, scheduler, limits) = (db.clone(), scheduler.clone(), limits.clone());
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    if let Err(e) = handle_request(stream, peer.ip(), &db, &scheduler, &limits).await {
+                        tracing::debug!("Webhook request failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                tracing::warn!("Failed to accept webhook connection: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+async fn handle_request(
+    mut stream: TcpStream,
+    client: IpAddr,
+    db: &Arc<Database>,
+    scheduler: &Arc<SchedulerRunner>,
+    limits: &Mutex<Limits>,
+) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read_head = async {
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_REQUEST_BYTES {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::time::timeout(READ_TIMEOUT, read_head)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "Request not received in time"))??;
+
+    let head = String::from_utf8_lossy(&head).into_owned();
+    let (status, body) = respond(&head, client, db, scheduler, limits).await;
+    let retry_after = if status.starts_with("429") {
+        format!("Retry-After: {}\r\n", RATE_WINDOW.as_secs())
+    } else {
+        String::new()
+    };
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        retry_after,
+        body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Status line and JSON body for a request
+async fn respond(
+    head: &str,
+    client: IpAddr,
+    db: &Arc<Database>,
+    scheduler: &Arc<SchedulerRunner>,
+    limits: &Mutex<Limits>,
+) -> (&'static str, serde_json::Value) {
+    let error = |message: &str| serde_json::json!({ "error": message });
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (method, path) = (request_line.next().unwrap_or_default(), request_line.next().unwrap_or_default());
+
+    let parts: Vec<&str> = path.split('?').next().unwrap_or_default().split('/').filter(|p| !p.is_empty()).collect();
+    let task_id = match parts.as_slice() {
+        ["tasks", id, "run"] => id.to_string(),
+        _ => return ("404 Not Found", error("Not found")),
+    };
+    if method != "POST" {
+        return ("405 Method Not Allowed", error("Use POST"));
+    }
+
+    let now = Instant::now();
+    if !has_room(limits.lock().unwrap().failures.entry(client).or_default(), FAILED_AUTH_PER_WINDOW, now) {
+        return ("429 Too Many Requests", error("Too many rejected tokens"));
+    }
+    let token = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("authorization"))
+        .and_then(|(_, value)| value.trim().strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .unwrap_or_default();
+
+    let id = task_id.clone();
+    let task = match db.run(move |db| db.get_task(&id)).await {
+        Ok(task) => task.filter(|t| t.enabled),
+        Err(e) => {
+            tracing::error!("Failed to load task {} for a webhook: {}", task_id, e);
+            return ("500 Internal Server Error", error("Failed to load task"));
+        }
+    };
+    // Unknown tasks and wrong tokens look the same, so task ids can't be probed
+    let trigger_index = task.as_ref().and_then(|task| {
+        task.triggers.iter().position(|trigger| match trigger {
+            // A missing or short token would let anyone in, however it got saved
+            Trigger::Webhook { enabled: true, token: expected } => {
+                crate::validation::is_strong_webhook_token(expected) && tokens_match(expected, &token)
+            }
+            _ => false,
+        })
+    });
+    let (Some(task), Some(index)) = (task, trigger_index) else {
+        limits.lock().unwrap().failures.entry(client).or_default().push_back(now);
+        tracing::warn!("Rejected webhook for task {} from {}: bad token", task_id, client);
+        return ("401 Unauthorized", error("Unknown task or wrong token"));
+    };
+
+    {
+        let mut limits = limits.lock().unwrap();
+        let runs = limits.runs.entry(format!("{}:{}", task.id, index)).or_default();
+        if !has_room(runs, RUNS_PER_WINDOW, now) {
+            tracing::warn!("Rate limited webhook for task {} from {}", task.name, client);
+            return ("429 Too Many Requests", error("Too many runs, try again later"));
+        }
+        runs.push_back(now);
+    }
+    if scheduler.is_paused() {
+        return ("503 Service Unavailable", error("Scheduler is paused"));
+    }
+
+    tracing::info!("Webhook from {} fired task {}", client, task.name);
+    match scheduler.run_from_webhook(task).await {
+        Ok(run_id) => ("202 Accepted", serde_json::json!({ "run_id": run_id })),
+        Err(e) => ("409 Conflict", error(&e)),
+    }
+}
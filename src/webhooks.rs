@@ -0,0 +1,85 @@
+//! Webhooks module - POST run results to configured URLs, with retries and a delivery log
+
+use crate::models::*;
+use crate::storage::Database;
+use chrono::Utc;
+use std::sync::Arc;
+
+/// Wait before each retry; a delivery is tried once more than there are entries
+const RETRY_DELAYS_SECS: [u64; 3] = [10, 60, 300];
+
+/// Webhooks that want this run's result: the global ones, then the task's own.
+/// A URL listed twice is only sent once.
+pub fn webhooks_for<'a>(log: &RunLog, task: Option<&'a Task>, settings: &'a Settings) -> Vec<&'a Webhook> {
+    let wanted = |hook: &&Webhook| match log.status {
+        RunStatus::Failed => hook.on_failure,
+        RunStatus::Success => hook.on_success,
+        _ => false,
+    };
+    let mut hooks: Vec<&Webhook> = Vec::new();
+    for hook in settings.webhooks.iter().chain(task.into_iter().flat_map(|t| &t.webhooks)).filter(wanted) {
+        if !hooks.iter().any(|h| h.url == hook.url) {
+            hooks.push(hook);
+        }
+    }
+    hooks
+}
+
+/// JSON body describing a finished run. Output is left out, it can be large or sensitive.
+pub fn payload(log: &RunLog) -> String {
+    serde_json::json!({
+        "event": if log.status == RunStatus::Success { "run_succeeded" } else { "run_failed" },
+        "run_id": log.run_id,
+        "task_id": log.task_id,
+        "task_name": log.task_name,
+        "trigger_type": log.trigger_type,
+        "status": log.status,
+        "exit_code": log.exit_code,
+        "error_message": log.error_message,
+        "scheduled_time_utc": log.scheduled_time_utc,
+        "started_at_utc": log.started_at_utc,
+        "finished_at_utc": log.finished_at_utc,
+        "duration_ms": log.duration_ms,
+    })
+    .to_string()
+}
+
+/// POST `body` to `url` until it is accepted (2xx) or the retries run out,
+/// recording each attempt. Returns whether it was delivered.
+pub async fn deliver(db: Arc<Database>, run_id: String, task_id: String, url: String, body: String) -> bool {
+    for attempt in 1..=RETRY_DELAYS_SECS.len() as u32 + 1 {
+        let (request_url, request_body) = (url.clone(), body.clone());
+        let result = tokio::task::spawn_blocking(move || crate::http::post_json(&request_url, &request_body))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+        let delivered = matches!(result, Ok(code) if (200..300).contains(&code));
+
+        let delivery = WebhookDelivery {
+            id: 0,
+            run_id: run_id.clone(),
+            task_id: task_id.clone(),
+            url: url.clone(),
+            attempt,
+            attempted_at_utc: Utc::now(),
+            status_code: result.as_ref().ok().copied(),
+            error: match &result {
+                Ok(_) if delivered => None,
+                Ok(code) => Some(format!("HTTP {}", code)),
+                Err(e) => Some(e.clone()),
+            },
+            delivered,
+        };
+        if let Err(e) = db.run(move |db| db.insert_webhook_delivery(&delivery)).await {
+            tracing::warn!("Failed to record webhook delivery: {}", e);
+        }
+
+        if delivered {
+            return true;
+        }
+        let Some(delay) = RETRY_DELAYS_SECS.get(attempt as usize - 1) else { break };
+        tracing::warn!("Webhook {} attempt {} failed, retrying in {}s", url, attempt, delay);
+        tokio::time::sleep(tokio::time::Duration::from_secs(*delay)).await;
+    }
+    tracing::error!("Giving up on webhook {} for run {}", url, run_id);
+    false
+}